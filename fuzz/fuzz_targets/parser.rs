@@ -0,0 +1,21 @@
+#![no_main]
+
+use std::io;
+
+use libfuzzer_sys::fuzz_target;
+use rlox::analysis;
+use rlox::formatter;
+use rlox::loxerr;
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+
+// Parses whatever the scanner made of the input, errors and all, then
+// resolves and formats it as the language server would.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        loxerr::set_error_output(Some(Box::new(io::sink())));
+        let tokens = Scanner::new(source).scan_tokens().clone();
+        Parser::new(&tokens).parse();
+        formatter::format(&analysis::analyze(source).stmts);
+    }
+});
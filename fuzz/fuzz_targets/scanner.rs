@@ -0,0 +1,14 @@
+#![no_main]
+
+use std::io;
+
+use libfuzzer_sys::fuzz_target;
+use rlox::loxerr;
+use rlox::scanner::Scanner;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        loxerr::set_error_output(Some(Box::new(io::sink())));
+        Scanner::new(source).scan_tokens();
+    }
+});
@@ -0,0 +1,149 @@
+use std::io;
+use std::time::Instant;
+
+use crate::interpreter::Interpreter;
+use crate::lox::Timings;
+use crate::loxerr::{self, Diagnostic, Phase};
+use crate::stmt::Stmt;
+use crate::token::Span;
+use crate::{parser, resolver, scanner};
+
+// What the front end made of a script without running it, for tooling such
+// as the language server.
+pub struct Analysis {
+    // As far as the parser got, so with errors it can be missing statements.
+    pub stmts: Vec<Stmt>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub declarations: Vec<Declaration>,
+    // Where each use of a variable was declared, see
+    // `Resolver::definitions`. Empty unless the script parsed.
+    pub definitions: Vec<(Span, Span)>,
+}
+
+impl Analysis {
+    // Where the variable used at `offset` was declared.
+    pub fn definition(&self, offset: usize) -> Option<Span> {
+        self.definitions
+            .iter()
+            .find(|(span, _)| span.start <= offset && offset <= span.end)
+            .map(|(_, declaration)| *declaration)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeclarationKind {
+    Function,
+    Class,
+    Method,
+}
+
+// A function, class or method, with what's declared in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    pub kind: DeclarationKind,
+    // Of its name.
+    pub span: Span,
+    pub children: Vec<Declaration>,
+}
+
+// Scans, parses and resolves `source` with a fresh interpreter, collecting
+// what was reported rather than writing it out.
+pub fn analyze(source: &str) -> Analysis {
+    let mut interpreter = Interpreter::new();
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let (front_end, reported) =
+        loxerr::collect(|| front_end(&mut interpreter, source, &mut Timings::default(), true));
+    loxerr::set_error_output(previous);
+    Analysis {
+        declarations: declarations(&front_end.stmts),
+        stmts: front_end.stmts,
+        diagnostics: reported.diagnostics,
+        definitions: front_end.definitions,
+    }
+}
+
+pub(crate) struct FrontEnd {
+    pub stmts: Vec<Stmt>,
+    // The phase that reported errors, if any did. Later phases didn't run.
+    pub failed: Option<Phase>,
+    pub definitions: Vec<(Span, Span)>,
+}
+
+// Scans, parses and resolves `source`, which is all that runs before the
+// program does.
+pub(crate) fn front_end(interpreter: &mut Interpreter, source: &str, timings: &mut Timings, record_definitions: bool) -> FrontEnd {
+    let start = Instant::now();
+    let mut scanner = scanner::Scanner::new(source);
+    let tokens = scanner.scan_tokens();
+    timings.scan = start.elapsed();
+    timings.tokens = tokens.len() - 1;
+
+    let start = Instant::now();
+    let mut parser = parser::Parser::new(tokens);
+    let stmts = parser.parse();
+    timings.parse = start.elapsed();
+    timings.statements = stmts.len();
+
+    let parse_error = parser.has_error;
+    let failed = if scanner.has_error {
+        Some(Phase::Scan)
+    } else if parse_error {
+        Some(Phase::Parse)
+    } else {
+        None
+    };
+    if failed.is_some() {
+        return FrontEnd { stmts, failed, definitions: Vec::new() };
+    }
+
+    let start = Instant::now();
+    let mut resolver = resolver::Resolver::new(interpreter);
+    if record_definitions {
+        resolver.record_definitions();
+    }
+    resolver.resolve(&stmts);
+    timings.resolve = start.elapsed();
+    let failed = if resolver.has_error { Some(Phase::Resolve) } else { None };
+    let definitions = resolver.definitions();
+    FrontEnd { stmts, failed, definitions }
+}
+
+fn declarations(stmts: &[Stmt]) -> Vec<Declaration> {
+    let mut found = Vec::new();
+    for stmt in stmts {
+        declare(stmt, DeclarationKind::Function, &mut found);
+    }
+    found
+}
+
+// Adds what `stmt` declares to `found`. Functions are of `kind`, which is
+// `Method` in a class body.
+fn declare(stmt: &Stmt, kind: DeclarationKind, found: &mut Vec<Declaration>) {
+    match stmt {
+        Stmt::Function(name, _, body) => found.push(Declaration {
+            name: name.lexeme.to_string(),
+            kind,
+            span: name.span,
+            children: declarations(body),
+        }),
+        Stmt::Class(name, .., methods) => {
+            let mut children = Vec::new();
+            for method in methods {
+                declare(method, DeclarationKind::Method, &mut children);
+            }
+            found.push(Declaration { name: name.lexeme.to_string(), kind: DeclarationKind::Class, span: name.span, children });
+        }
+        Stmt::Block(stmts) => found.extend(declarations(stmts)),
+        Stmt::If(_, _, then_branch, else_branch) => {
+            declare(then_branch, DeclarationKind::Function, found);
+            if let Some(else_branch) = else_branch {
+                declare(else_branch, DeclarationKind::Function, found);
+            }
+        }
+        Stmt::While(_, _, body) | Stmt::ForIn(_, _, _, body) | Stmt::For(_, _, _, _, body) => {
+            declare(body, DeclarationKind::Function, found)
+        }
+        _ => (),
+    }
+}
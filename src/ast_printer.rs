@@ -1,10 +1,107 @@
 use crate::expr::Expr;
+use crate::repl;
+use crate::stmt::Stmt;
+use crate::token::Token;
 
+// Trees as s-expressions, as in the book: `(* (- 123) (group 45.67))`.
+// Strings are quoted so they can be told from names.
 pub fn print(expr: &Expr) -> String {
     match expr {
-        Expr::Binary(left, op, right) => format!("({} {} {})", op.lexeme, print(&left), print(&right)),
-        Expr::Unary(op, right) => format!("({} {})", op.lexeme, print(&right)),
-        Expr::Literal(l) => format!("({:?})", l),
-        Expr::Grouping(expr) => format!("(group {})", print(&expr)),
+        Expr::Array(elements) => parenthesize("array", elements.iter().map(|element| print(element))),
+        Expr::Assign(_, name, value) => parenthesize("=", [name.lexeme.to_string(), print(value)]),
+        Expr::AssignArray(targets, _, value) => {
+            let targets = parenthesize("array", targets.iter().map(|target| print(target)));
+            parenthesize("=", [targets, print(value)])
+        }
+        Expr::Binary(left, op, right) | Expr::Logical(left, op, right) | Expr::Range(left, op, right) => {
+            parenthesize(&op.lexeme, [print(left), print(right)])
+        }
+        Expr::Call(callee, _, arguments) => {
+            parenthesize("call", std::iter::once(print(callee)).chain(arguments.iter().map(|argument| print(argument))))
+        }
+        Expr::Get(_, object, name) => parenthesize(".", [print(object), name.lexeme.to_string()]),
+        Expr::Grouping(expr) => parenthesize("group", [print(expr)]),
+        Expr::Index(object, _, index) => parenthesize("[]", [print(object), print(index)]),
+        Expr::Is(value, _, class) => parenthesize("is", [print(value), print(class)]),
+        Expr::Literal(value) => repl::show(value),
+        Expr::Set(object, name, value) => parenthesize("=", [print(object), name.lexeme.to_string(), print(value)]),
+        Expr::SetIndex(object, _, index, value) => parenthesize("[]=", [print(object), print(index), print(value)]),
+        Expr::Super(_, _, method) => parenthesize("super", [method.lexeme.to_string()]),
+        Expr::This(..) => "this".to_owned(),
+        Expr::Tuple(elements) => parenthesize("tuple", elements.iter().map(|element| print(element))),
+        Expr::Unary(op, right) => parenthesize(&op.lexeme, [print(right)]),
+        Expr::Variable(_, name) => name.lexeme.to_string(),
     }
 }
+
+pub fn print_stmt(stmt: &Stmt) -> String {
+    match stmt {
+        // What's left of a statement that didn't parse.
+        Stmt::Null => "(error)".to_owned(),
+        Stmt::Block(stmts) => parenthesize("block", stmts.iter().map(print_stmt)),
+        Stmt::Class(name, superclass, mixins, interfaces, methods) => {
+            let mut parts = vec![name.lexeme.to_string()];
+            if let Some(superclass) = superclass {
+                parts.push("<".to_owned());
+                parts.push(print(superclass));
+            }
+            for (word, names) in [("with", mixins), ("implements", interfaces)] {
+                if !names.is_empty() {
+                    parts.push(parenthesize(word, names.iter().map(|name| print(name))));
+                }
+            }
+            parts.extend(methods.iter().map(print_stmt));
+            parenthesize("class", parts)
+        }
+        Stmt::Expression(_, expr) => parenthesize(";", [print(expr)]),
+        Stmt::For(_, initializer, condition, increment, body) => {
+            // `_` for the parts left out.
+            let initializer = initializer.as_ref().map_or("_".to_owned(), |initializer| print_stmt(initializer));
+            let condition = condition.as_ref().map_or("_".to_owned(), |condition| print(condition));
+            let increment = increment.as_ref().map_or("_".to_owned(), |increment| print(increment));
+            parenthesize("for", [initializer, condition, increment, print_stmt(body)])
+        }
+        Stmt::ForIn(_, name, iterable, body) => {
+            parenthesize("for-in", [name.lexeme.to_string(), print(iterable), print_stmt(body)])
+        }
+        Stmt::Function(name, params, body) => {
+            let parts = std::iter::once(format!("{}{}", name.lexeme, parameters(params)));
+            parenthesize("fun", parts.chain(body.iter().map(print_stmt)))
+        }
+        Stmt::If(_, condition, then_branch, None) => parenthesize("if", [print(condition), print_stmt(then_branch)]),
+        Stmt::If(_, condition, then_branch, Some(else_branch)) => {
+            parenthesize("if-else", [print(condition), print_stmt(then_branch), print_stmt(else_branch)])
+        }
+        Stmt::Import(_, name) => parenthesize("import", [format!("{:?}", name)]),
+        Stmt::Interface(name, methods) => {
+            let methods = methods.iter().map(|(name, params)| format!("{}{}", name.lexeme, parameters(params)));
+            parenthesize("interface", std::iter::once(name.lexeme.to_string()).chain(methods))
+        }
+        Stmt::Print(_, expr) => parenthesize("print", [print(expr)]),
+        Stmt::Return(_, None) => "(return)".to_owned(),
+        Stmt::Return(_, Some(value)) => parenthesize("return", [print(value)]),
+        Stmt::Var(name, None) => parenthesize("var", [name.lexeme.to_string()]),
+        Stmt::Var(name, Some(init)) => parenthesize("var", [name.lexeme.to_string(), "=".to_owned(), print(init)]),
+        Stmt::VarArray(names, value) => {
+            let names = parenthesize("array", names.iter().map(|name| name.lexeme.to_string()));
+            parenthesize("var", [names, "=".to_owned(), print(value)])
+        }
+        Stmt::While(_, condition, body) => parenthesize("while", [print(condition), print_stmt(body)]),
+    }
+}
+
+// `(a b)` for parameters `a` and `b`.
+fn parameters(params: &[Token]) -> String {
+    let names: Vec<String> = params.iter().map(|param| param.lexeme.to_string()).collect();
+    format!("({})", names.join(" "))
+}
+
+fn parenthesize(name: &str, parts: impl IntoIterator<Item = String>) -> String {
+    let mut out = format!("({}", name);
+    for part in parts {
+        out.push(' ');
+        out.push_str(&part);
+    }
+    out.push(')');
+    out
+}
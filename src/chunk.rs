@@ -0,0 +1,114 @@
+use std::fmt;
+
+use crate::expr::Value;
+use crate::symbol::Symbol;
+use crate::token::Token;
+use crate::vm::VmFunction;
+
+// Instructions for the bytecode VM. Operands are stored inline: indexes into
+// the chunk's constants or names, stack slots relative to the current call
+// frame, and absolute jump targets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Constant(usize),
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal(usize),
+    SetLocal(usize),
+    GetGlobal(usize),
+    DefineGlobal(usize),
+    SetGlobal(usize),
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    FloorDivide,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Not,
+    Negate,
+    BitNot,
+    Print,
+    // Pushes a new function value for one of the chunk's functions.
+    Function(usize),
+    // Uses one unit of fuel, see `Interpreter::set_fuel`.
+    Tick,
+    Jump(usize),
+    // Jumps when the value on top of the stack is falsey, leaving it there.
+    JumpIfFalse(usize),
+    Call(usize),
+    // A call whose result is returned right away. Calls to bytecode functions
+    // reuse the current frame.
+    TailCall(usize),
+    Return,
+    // A `return` outside any function, ending the script with the value on
+    // top of the stack as its status.
+    Exit,
+}
+
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    // The source line of every instruction, for runtime errors.
+    pub lines: Vec<usize>,
+    pub constants: Vec<Value>,
+    // Global variable names, one per use so that errors point at the right
+    // line.
+    pub names: Vec<Token>,
+    // The locals in scope at each use of a global, to suggest when it's
+    // undefined.
+    pub scopes: Vec<Vec<Symbol>>,
+    pub functions: Vec<VmFunction>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: Op, line: usize) -> usize {
+        self.code.push(op);
+        self.lines.push(line);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub fn add_name(&mut self, name: &Token, scope: Vec<Symbol>) -> usize {
+        self.names.push(name.clone());
+        self.scopes.push(scope);
+        self.names.len() - 1
+    }
+
+    pub fn add_function(&mut self, function: VmFunction) -> usize {
+        self.functions.push(function);
+        self.functions.len() - 1
+    }
+}
+
+// One instruction per line, for debugging the compiler.
+impl fmt::Display for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, op) in self.code.iter().enumerate() {
+            write!(f, "{:04} {:4} {:?}", idx, self.lines[idx], op)?;
+            match op {
+                Op::Constant(constant) => writeln!(f, " ; {}", self.constants[*constant])?,
+                Op::GetGlobal(name) | Op::DefineGlobal(name) | Op::SetGlobal(name) => {
+                    writeln!(f, " ; {}", self.names[*name].lexeme)?
+                }
+                Op::Function(function) => writeln!(f, " ; {}", self.functions[*function])?,
+                _ => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,27 +1,102 @@
-use std::cell::RefCell;
+use std::any::Any;
 use std::collections::HashMap;
 use std::fmt;
-use std::rc::Rc;
 
 use crate::instance::LoxInstance;
+use crate::interface::LoxInterface;
 use crate::loxcallables::{LoxCallable, Function};
 use crate::interpreter::Interpreter;
 use crate::loxerr::RuntimeException;
+use crate::shared::{AnyBox, Rc, RefCell};
+use crate::spelling;
 use crate::expr::Value;
+use crate::symbol::Symbol;
+use crate::token::Token;
 
 #[derive(Clone)]
 pub struct LoxClass {
     pub name: String,
-    superclass: Option<Rc<LoxClass>>,
-    methods: Rc<HashMap<String, Function>>, //Rc to derive Clone.
+    pub(crate) line: usize,
+    pub(crate) superclass: Option<Rc<LoxClass>>,
+    pub(crate) methods: Rc<HashMap<Symbol, Function>>, //Rc to derive Clone.
+    // Static fields, set through the class itself and shared by its clones.
+    // They aren't inherited and instances don't see them.
+    pub(crate) fields: Rc<RefCell<HashMap<Symbol, Value>>>,
+    pub(crate) native: Option<Rc<NativeClass>>,
+    // The interfaces it declares, not the ones its superclass does.
+    pub(crate) interfaces: Vec<Rc<LoxInterface>>,
 }
 
 impl LoxClass {
-    pub fn new(name: String, superclass: Option<Rc<LoxClass>>, methods: &Rc<HashMap<String, Function>>) -> Self {
-        LoxClass {name, superclass, methods: Rc::clone(methods)}
+    pub fn new(name: String, line: usize, superclass: Option<Rc<LoxClass>>, methods: &Rc<HashMap<Symbol, Function>>) -> Self {
+        LoxClass {name, line, superclass, methods: Rc::clone(methods), fields: Rc::new(RefCell::new(HashMap::new())), native: None, interfaces: Vec::new()}
     }
 
-    pub fn find_method(&self, name: &str) -> Option<&Function> {
+    pub fn native(native: NativeClass) -> Self {
+        let mut class = LoxClass::new(native.name.to_string(), 0, None, &Rc::new(HashMap::new()));
+        class.native = Some(Rc::new(native));
+        class
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.native.is_some()
+    }
+
+    // Whether this is `other` or a subclass of it. Mixins don't count. Clones
+    // of a class share its methods, so those tell classes apart.
+    pub fn is_subclass_of(&self, other: &LoxClass) -> bool {
+        Rc::ptr_eq(&self.methods, &other.methods)
+            || self.superclass.as_ref().is_some_and(|superclass| superclass.is_subclass_of(other))
+    }
+
+    // Whether this class or a superclass declares `interface`.
+    pub fn implements(&self, interface: &Rc<LoxInterface>) -> bool {
+        self.interfaces.iter().any(|declared| Rc::ptr_eq(declared, interface))
+            || self.superclass.as_ref().is_some_and(|superclass| superclass.implements(interface))
+    }
+
+    // The first way the class fails to implement `interface`, if any.
+    pub fn conformance_error(&self, interface: &LoxInterface) -> Option<String> {
+        for (name, arity) in &interface.methods {
+            match self.find_method(name) {
+                None => {
+                    return Some(format!(
+                        "Class '{}' doesn't implement '{}' from interface '{}'.",
+                        self.name, name, interface.name
+                    ))
+                }
+                Some(method) if method.arity() != *arity => {
+                    return Some(format!(
+                        "Method '{}' of class '{}' must take {} parameters to implement interface '{}'.",
+                        name, self.name, arity, interface.name
+                    ))
+                }
+                Some(_) => (),
+            }
+        }
+        None
+    }
+
+    // Classes have no static methods, so only fields are looked up.
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeException> {
+        let fields = self.fields.borrow();
+        match fields.get(&name.lexeme) {
+            Some(value) => Ok(value.clone()),
+            None => {
+                let names: Vec<Symbol> = fields.keys().cloned().collect();
+                Err(RuntimeException::RuntimeError {
+                    token: name.clone(),
+                    error: format!("Undefined property '{}'.{}", name.lexeme, spelling::did_you_mean(&name.lexeme, &names)),
+                })
+            }
+        }
+    }
+
+    pub fn set(&self, name: &Token, value: Value) {
+        self.fields.borrow_mut().insert(name.lexeme.clone(), value);
+    }
+
+    pub fn find_method(&self, name: &Symbol) -> Option<&Function> {
         if self.methods.contains_key(name) {
             return self.methods.get(name);
         }
@@ -32,6 +107,52 @@ impl LoxClass {
 
         None
     }
+
+    // Every method instances of the class can call, overrides replacing
+    // what they override.
+    pub fn all_methods(&self) -> HashMap<Symbol, Function> {
+        let mut methods = match &self.superclass {
+            Some(superclass) => superclass.all_methods(),
+            None => HashMap::new(),
+        };
+        methods.extend(self.methods.iter().map(|(name, method)| (name.clone(), method.clone())));
+        methods
+    }
+
+    pub fn has_method(&self, name: &Symbol) -> bool {
+        self.find_method(name).is_some() || self.native.as_ref().is_some_and(|native| native.methods.contains_key(name))
+    }
+
+    // Its own methods and inherited ones, each name once, sorted.
+    pub fn method_names(&self) -> Vec<Symbol> {
+        let mut names: Vec<Symbol> = self.methods.keys().cloned().collect();
+        if let Some(native) = &self.native {
+            names.extend(native.methods.keys().cloned());
+        }
+        if let Some(superclass) = &self.superclass {
+            names.extend(superclass.method_names());
+        }
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        names.dedup();
+        names
+    }
+
+    fn construct(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
+        interpreter.allocate(std::mem::size_of::<LoxInstance>())?;
+        if let Some(native) = &self.native {
+            let state = (native.constructor)(interpreter, args)?;
+            let instance = LoxInstance::with_state(self.clone(), state);
+            return Ok(Value::Instance(Rc::new(RefCell::new(instance))));
+        }
+
+        let instance = LoxInstance::new(self.clone());
+        let instance = Value::Instance(Rc::new(RefCell::new(instance)));
+
+        if let Some(init) = self.find_method(&Symbol::init()) {
+            init.bind(instance.clone()).call(interpreter, args)?;
+        }
+        Ok(instance)
+    }
 }
 
 impl fmt::Display for LoxClass {
@@ -42,20 +163,94 @@ impl fmt::Display for LoxClass {
 
 impl LoxCallable for LoxClass {
     fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
-        let instance = LoxInstance::new(self.clone());
-        let instance = Value::Instance(Rc::new(RefCell::new(instance)));
-
-        if let Some(init) = self.find_method("init") {
-            init.bind(instance.clone()).call(interpreter, args)?;
+        if !interpreter.profiling() {
+            return self.construct(interpreter, args);
         }
-        Ok(instance)
+        let name = Symbol::intern(&self.name);
+        interpreter.profile_enter(&name, self.line);
+        let result = self.construct(interpreter, args);
+        interpreter.profile_exit(&name, self.line);
+        result
     }
 
     fn arity(&self) -> usize {
-        if let Some(init) = self.find_method("init") {
+        if let Some(native) = &self.native {
+            return native.arity;
+        }
+        if let Some(init) = self.find_method(&Symbol::init()) {
             init.arity()
         } else {
             0
         }
     }
-}
\ No newline at end of file
+}
+pub type Constructor = fn(&mut Interpreter, &[Value]) -> Result<AnyBox, RuntimeException>;
+pub type MethodBody = fn(&mut Interpreter, &mut dyn Any, &[Value]) -> Result<Value, RuntimeException>;
+
+// A class implemented by the host, see `Interpreter::define_native_class`.
+// The constructor builds the Rust state each instance carries, and methods
+// get that state mutably borrowed, so a method that calls back into the same
+// instance panics. Lox classes can't inherit from native ones.
+pub struct NativeClass {
+    name: Symbol,
+    arity: usize,
+    constructor: Constructor,
+    methods: HashMap<Symbol, NativeMethod>,
+}
+
+impl NativeClass {
+    pub fn new(name: &str, arity: usize, constructor: Constructor) -> Self {
+        NativeClass { name: Symbol::intern(name), arity, constructor, methods: HashMap::new() }
+    }
+
+    pub fn method(mut self, name: &str, arity: usize, body: MethodBody) -> Self {
+        let name = Symbol::intern(name);
+        self.methods.insert(name.clone(), NativeMethod { name, arity, body });
+        self
+    }
+
+    pub fn name(&self) -> &Symbol {
+        &self.name
+    }
+
+    pub(crate) fn find_method(&self, name: &Symbol) -> Option<&NativeMethod> {
+        self.methods.get(name)
+    }
+}
+
+#[derive(Clone)]
+pub struct NativeMethod {
+    name: Symbol,
+    arity: usize,
+    body: MethodBody,
+}
+
+impl NativeMethod {
+    pub(crate) fn bind(&self, state: &Rc<RefCell<AnyBox>>) -> BoundNativeMethod {
+        BoundNativeMethod { method: self.clone(), state: Rc::clone(state) }
+    }
+}
+
+pub struct BoundNativeMethod {
+    method: NativeMethod,
+    state: Rc<RefCell<AnyBox>>,
+}
+
+impl LoxCallable for BoundNativeMethod {
+    fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
+        interpreter.profile_enter(&self.method.name, 0);
+        let result = (self.method.body)(interpreter, self.state.borrow_mut().as_mut(), args);
+        interpreter.profile_exit(&self.method.name, 0);
+        result
+    }
+
+    fn arity(&self) -> usize {
+        self.method.arity
+    }
+}
+
+impl fmt::Display for BoundNativeMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.method.name)
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::UNIX_EPOCH;
+
+use crate::shared::Shareable;
+
+// Where `clock()` gets the time, see `Interpreter::set_time_source`.
+pub trait TimeSource: Shareable {
+    // Milliseconds since some fixed point, the Unix epoch for the real clock.
+    fn now_millis(&mut self) -> f64;
+}
+
+// The real clock, which interpreters start with.
+pub struct SystemTime;
+
+impl TimeSource for SystemTime {
+    fn now_millis(&mut self) -> f64 {
+        std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Could not retrieve time.")
+            .as_millis() as f64
+    }
+}
+
+// A clock that starts at 0 and moves `step` milliseconds every time it's
+// read, so programs that time themselves print the same thing every run.
+pub struct MockTime {
+    now: f64,
+    step: f64,
+}
+
+impl MockTime {
+    pub fn new(step: f64) -> Self {
+        MockTime { now: 0.0, step }
+    }
+}
+
+impl TimeSource for MockTime {
+    fn now_millis(&mut self) -> f64 {
+        let now = self.now;
+        self.now += self.step;
+        now
+    }
+}
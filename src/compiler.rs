@@ -0,0 +1,397 @@
+use crate::chunk::{Chunk, Op};
+use crate::expr::{Expr, Value};
+use crate::shared::Rc;
+use crate::stmt::Stmt;
+use crate::symbol::Symbol;
+use crate::token::{Token, TokenType};
+use crate::vm::VmFunction;
+
+// A construct the VM can't run yet. The whole program then runs on the
+// tree-walker instead.
+#[derive(Debug)]
+pub struct Unsupported(pub &'static str);
+
+type Result<T> = std::result::Result<T, Unsupported>;
+
+// Compiles a resolved program into the function the VM runs as its script.
+// Classes and closures over the locals of an enclosing function aren't
+// supported.
+pub fn compile(stmts: &[Stmt]) -> Result<VmFunction> {
+    let mut compiler = Compiler::new(None, 0);
+    for stmt in stmts {
+        compiler.stmt(stmt)?;
+    }
+    let line = compiler.last_line();
+    compiler.chunk.write(Op::Nil, line);
+    compiler.chunk.write(Op::Return, line);
+    Ok(VmFunction::new(Symbol::intern("script"), 0, compiler.chunk))
+}
+
+struct Local {
+    name: Symbol,
+    depth: usize,
+}
+
+struct Compiler<'a> {
+    enclosing: Option<&'a Compiler<'a>>,
+    chunk: Chunk,
+    // Locals live on the stack in declaration order. Slot 0 holds the
+    // function being called.
+    locals: Vec<Local>,
+    scope_depth: usize,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(enclosing: Option<&'a Compiler<'a>>, scope_depth: usize) -> Self {
+        let callee = Local { name: Symbol::intern(""), depth: 0 };
+        Compiler { enclosing, chunk: Chunk::default(), locals: vec![callee], scope_depth }
+    }
+
+    fn emit(&mut self, op: Op, line: usize) -> usize {
+        self.chunk.write(op, line)
+    }
+
+    // For instructions that can't fail, where the line doesn't matter.
+    fn last_line(&self) -> usize {
+        self.chunk.lines.last().copied().unwrap_or(1)
+    }
+
+    // Points the jump at `at` to the next instruction.
+    fn patch(&mut self, at: usize) {
+        let target = self.chunk.code.len();
+        self.chunk.code[at] = match self.chunk.code[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            _ => unreachable!(),
+        };
+    }
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.scope_depth -= 1;
+        while self.locals.last().is_some_and(|local| local.depth > self.scope_depth) {
+            self.locals.pop();
+            self.emit(Op::Pop, self.last_line());
+        }
+    }
+
+    fn resolve_local(&self, name: &Symbol) -> Option<usize> {
+        self.locals.iter().rposition(|local| local.name == *name)
+    }
+
+    // The names of the locals in scope, other than the function itself.
+    fn scope(&self) -> Vec<Symbol> {
+        self.locals.iter().skip(1).map(|local| local.name.clone()).collect()
+    }
+
+    fn variable(&mut self, name: &Token) -> Result<Option<usize>> {
+        if let Some(slot) = self.resolve_local(&name.lexeme) {
+            return Ok(Some(slot));
+        }
+        let mut enclosing = self.enclosing;
+        while let Some(compiler) = enclosing {
+            if compiler.resolve_local(&name.lexeme).is_some() {
+                return Err(Unsupported("closures"));
+            }
+            enclosing = compiler.enclosing;
+        }
+        Ok(None)
+    }
+
+    // Globals are defined by name, locals by pushing them.
+    fn define(&mut self, name: &Token) {
+        if self.scope_depth == 0 {
+            let name_idx = self.chunk.add_name(name, self.scope());
+            self.emit(Op::DefineGlobal(name_idx), name.line);
+        } else {
+            self.locals.push(Local { name: name.lexeme.clone(), depth: self.scope_depth });
+        }
+    }
+
+    fn function(&self, name: &Token, params: &[Token], body: &[Stmt]) -> Result<VmFunction> {
+        let mut compiler = Compiler::new(Some(self), 1);
+        for param in params {
+            compiler.locals.push(Local { name: param.lexeme.clone(), depth: 1 });
+        }
+        for stmt in body {
+            compiler.stmt(stmt)?;
+        }
+        compiler.emit(Op::Nil, name.line);
+        compiler.emit(Op::Return, name.line);
+        Ok(VmFunction::new(name.lexeme.clone(), params.len(), compiler.chunk))
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<()> {
+        match stmt {
+            Stmt::Null => (),
+            Stmt::Block(stmts) => {
+                self.begin_scope();
+                for stmt in stmts {
+                    self.stmt(stmt)?;
+                }
+                self.end_scope();
+            }
+            Stmt::Class(..) | Stmt::Interface(..) => return Err(Unsupported("classes")),
+            Stmt::Expression(_, expr) => {
+                let line = self.expr(expr)?;
+                self.emit(Op::Pop, line);
+            }
+            Stmt::For(_, initializer, condition, increment, body) => {
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.stmt(initializer)?;
+                }
+                let start = self.chunk.code.len();
+                let exit = match condition {
+                    Some(condition) => {
+                        let line = self.expr(condition)?;
+                        let exit = self.emit(Op::JumpIfFalse(0), line);
+                        self.emit(Op::Pop, line);
+                        Some((exit, line))
+                    }
+                    None => None,
+                };
+                self.emit(Op::Tick, self.last_line());
+                self.stmt(body)?;
+                if let Some(increment) = increment {
+                    let line = self.expr(increment)?;
+                    self.emit(Op::Pop, line);
+                }
+                self.emit(Op::Jump(start), self.last_line());
+                if let Some((exit, line)) = exit {
+                    self.patch(exit);
+                    self.emit(Op::Pop, line);
+                }
+                self.end_scope();
+            }
+            Stmt::Function(name, params, body) => {
+                // A local function is in scope in its own body, so referring
+                // to itself makes it a closure.
+                let local = self.scope_depth > 0;
+                if local {
+                    self.define(name);
+                }
+                let function = self.function(name, params, body)?;
+                let function_idx = self.chunk.add_function(function);
+                self.emit(Op::Function(function_idx), name.line);
+                if !local {
+                    self.define(name);
+                }
+            }
+            Stmt::If(_, condition, then_branch, else_branch) => {
+                let line = self.expr(condition)?;
+                let else_jump = self.emit(Op::JumpIfFalse(0), line);
+                self.emit(Op::Pop, line);
+                self.stmt(then_branch)?;
+                let end_jump = self.emit(Op::Jump(0), line);
+                self.patch(else_jump);
+                self.emit(Op::Pop, line);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch)?;
+                }
+                self.patch(end_jump);
+            }
+            Stmt::Print(keyword, expr) => {
+                self.expr(expr)?;
+                self.emit(Op::Print, keyword.line);
+            }
+            Stmt::VarArray(..) | Stmt::ForIn(..) => return Err(Unsupported("arrays")),
+            Stmt::Import(..) => return Err(Unsupported("imports")),
+            Stmt::Return(keyword, value) if self.enclosing.is_none() => {
+                match value.as_deref() {
+                    Some(value) => {
+                        self.expr(value)?;
+                    }
+                    None => {
+                        self.emit(Op::Nil, keyword.line);
+                    }
+                }
+                self.emit(Op::Exit, keyword.line);
+            }
+            Stmt::Return(keyword, value) => match value.as_deref() {
+                Some(Expr::Call(callee, paren, args)) => {
+                    self.call(callee, args)?;
+                    self.emit(Op::TailCall(args.len()), paren.line);
+                }
+                Some(value) => {
+                    self.expr(value)?;
+                    self.emit(Op::Return, keyword.line);
+                }
+                None => {
+                    self.emit(Op::Nil, keyword.line);
+                    self.emit(Op::Return, keyword.line);
+                }
+            },
+            Stmt::Var(name, initializer) => {
+                match initializer {
+                    Some(initializer) => {
+                        self.expr(initializer)?;
+                    }
+                    None => {
+                        self.emit(Op::Nil, name.line);
+                    }
+                }
+                self.define(name);
+            }
+            Stmt::While(_, condition, body) => {
+                let start = self.chunk.code.len();
+                let line = self.expr(condition)?;
+                let exit = self.emit(Op::JumpIfFalse(0), line);
+                self.emit(Op::Pop, line);
+                self.emit(Op::Tick, line);
+                self.stmt(body)?;
+                self.emit(Op::Jump(start), line);
+                self.patch(exit);
+                self.emit(Op::Pop, line);
+            }
+        }
+        Ok(())
+    }
+
+    // Pushes the callee and then the arguments.
+    fn call(&mut self, callee: &Expr, args: &[Rc<Expr>]) -> Result<()> {
+        self.expr(callee)?;
+        for arg in args {
+            self.expr(arg)?;
+        }
+        Ok(())
+    }
+
+    // Returns the line of the expression, for the instructions that use its
+    // value.
+    fn expr(&mut self, expr: &Expr) -> Result<usize> {
+        let line = match expr {
+            Expr::Assign(_, name, value) => {
+                self.expr(value)?;
+                match self.variable(name)? {
+                    Some(slot) => self.emit(Op::SetLocal(slot), name.line),
+                    None => {
+                        let name_idx = self.chunk.add_name(name, self.scope());
+                        self.emit(Op::SetGlobal(name_idx), name.line)
+                    }
+                };
+                name.line
+            }
+            Expr::Binary(left, op, right) => {
+                self.expr(left)?;
+                self.expr(right)?;
+                match op.token_type {
+                    TokenType::EqualEqual => {
+                        self.emit(Op::Equal, op.line);
+                    }
+                    TokenType::BangEqual => {
+                        self.emit(Op::Equal, op.line);
+                        self.emit(Op::Not, op.line);
+                    }
+                    TokenType::Greater => {
+                        self.emit(Op::Greater, op.line);
+                    }
+                    TokenType::GreaterEqual => {
+                        self.emit(Op::GreaterEqual, op.line);
+                    }
+                    TokenType::Less => {
+                        self.emit(Op::Less, op.line);
+                    }
+                    TokenType::LessEqual => {
+                        self.emit(Op::LessEqual, op.line);
+                    }
+                    TokenType::Plus => {
+                        self.emit(Op::Add, op.line);
+                    }
+                    TokenType::Minus => {
+                        self.emit(Op::Subtract, op.line);
+                    }
+                    TokenType::Star => {
+                        self.emit(Op::Multiply, op.line);
+                    }
+                    TokenType::Slash => {
+                        self.emit(Op::Divide, op.line);
+                    }
+                    TokenType::Div => {
+                        self.emit(Op::FloorDivide, op.line);
+                    }
+                    TokenType::Ampersand => {
+                        self.emit(Op::BitAnd, op.line);
+                    }
+                    TokenType::Pipe => {
+                        self.emit(Op::BitOr, op.line);
+                    }
+                    TokenType::Caret => {
+                        self.emit(Op::BitXor, op.line);
+                    }
+                    TokenType::LessLess => {
+                        self.emit(Op::ShiftLeft, op.line);
+                    }
+                    TokenType::GreaterGreater => {
+                        self.emit(Op::ShiftRight, op.line);
+                    }
+                    _ => unreachable!(),
+                }
+                op.line
+            }
+            Expr::Call(callee, paren, args) => {
+                self.call(callee, args)?;
+                self.emit(Op::Call(args.len()), paren.line);
+                paren.line
+            }
+            Expr::Array(..) | Expr::AssignArray(..) | Expr::Index(..) | Expr::SetIndex(..) | Expr::Tuple(..) | Expr::Range(..) => {
+                return Err(Unsupported("arrays"))
+            }
+            Expr::Get(..) | Expr::Set(..) | Expr::This(..) | Expr::Super(..) | Expr::Is(..) => {
+                return Err(Unsupported("classes"))
+            }
+            Expr::Grouping(expr) => self.expr(expr)?,
+            Expr::Literal(value) => {
+                let op = match value {
+                    Value::Nil => Op::Nil,
+                    Value::Bool(true) => Op::True,
+                    Value::Bool(false) => Op::False,
+                    _ => Op::Constant(self.chunk.add_constant(value.clone())),
+                };
+                // Literals don't carry a line.
+                let line = self.last_line();
+                self.emit(op, line);
+                line
+            }
+            Expr::Logical(left, op, right) => {
+                self.expr(left)?;
+                let end_jump = if op.token_type == TokenType::Or {
+                    let else_jump = self.emit(Op::JumpIfFalse(0), op.line);
+                    let end_jump = self.emit(Op::Jump(0), op.line);
+                    self.patch(else_jump);
+                    end_jump
+                } else {
+                    self.emit(Op::JumpIfFalse(0), op.line)
+                };
+                self.emit(Op::Pop, op.line);
+                self.expr(right)?;
+                self.patch(end_jump);
+                op.line
+            }
+            Expr::Unary(op, right) => {
+                self.expr(right)?;
+                match op.token_type {
+                    TokenType::Bang => self.emit(Op::Not, op.line),
+                    TokenType::Minus => self.emit(Op::Negate, op.line),
+                    TokenType::Tilde => self.emit(Op::BitNot, op.line),
+                    _ => unreachable!(),
+                };
+                op.line
+            }
+            Expr::Variable(_, name) => {
+                match self.variable(name)? {
+                    Some(slot) => self.emit(Op::GetLocal(slot), name.line),
+                    None => {
+                        let name_idx = self.chunk.add_name(name, self.scope());
+                        self.emit(Op::GetGlobal(name_idx), name.line)
+                    }
+                };
+                name.line
+            }
+        };
+        Ok(line)
+    }
+}
@@ -0,0 +1,271 @@
+//! Conversions between Lox values and Rust types, for hosts embedding the
+//! interpreter. Numbers, booleans, strings and nil convert directly:
+//!
+//! ```
+//! use std::convert::TryFrom;
+//!
+//! use rlox::expr::Value;
+//!
+//! assert_eq!(f64::try_from(Value::from(1.5)).unwrap(), 1.5);
+//! assert_eq!(Option::<String>::try_from(Value::Nil).unwrap(), None);
+//! let err = bool::try_from(Value::from("yes")).unwrap_err();
+//! assert_eq!(err.to_string(), "Expected a boolean, but got string.");
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::expr::Value;
+use crate::shared::{Rc, RefCell};
+
+// Why a value couldn't be converted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError(pub String);
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+fn mismatch(expected: &str, value: &Value) -> ConversionError {
+    ConversionError(format!("Expected {}, but got {}.", expected, value.type_name()))
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.into())
+    }
+}
+
+// A new array, which Lox code can change without affecting the host.
+impl From<Vec<Value>> for Value {
+    fn from(elements: Vec<Value>) -> Self {
+        Value::Array(Rc::new(RefCell::new(elements)))
+    }
+}
+
+// None is nil.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Nil, Into::into)
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(n) => Ok(n),
+            _ => Err(mismatch("a number", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ConversionError;
+
+    // Only `true` and `false`, not whatever Lox considers truthy.
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            _ => Err(mismatch("a boolean", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(s) => Ok(s.to_string()),
+            _ => Err(mismatch("a string", &value)),
+        }
+    }
+}
+
+// The elements of an array or a tuple. An array is copied, so changing the
+// vector doesn't change the array.
+impl TryFrom<Value> for Vec<Value> {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Array(array) => Ok(array.borrow().clone()),
+            Value::Tuple(elements) => Ok(elements.to_vec()),
+            _ => Err(mismatch("an array", &value)),
+        }
+    }
+}
+
+// Nil is None, anything else has to convert to `T`.
+macro_rules! try_from_optional {
+    ($($t:ty),*) => {$(
+        impl TryFrom<Value> for Option<$t> {
+            type Error = ConversionError;
+
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                match value {
+                    Value::Nil => Ok(None),
+                    value => <$t>::try_from(value).map(Some),
+                }
+            }
+        }
+    )*};
+}
+
+try_from_optional!(f64, bool, String, Vec<Value>);
+
+#[cfg(feature = "json")]
+mod json {
+    //! With the `json` feature, values also convert to and from
+    //! `serde_json::Value`, say to pass a host's configuration to a script
+    //! and read back what it computed:
+    //!
+    //! ```
+    //! use std::convert::TryFrom;
+    //!
+    //! use rlox::expr::Value;
+    //! use rlox::interpreter::Interpreter;
+    //! use rlox::lox;
+    //! use rlox::symbol::Symbol;
+    //!
+    //! let config = serde_json::json!({"width": 3, "height": 4, "label": "box"});
+    //! let mut interpreter = Interpreter::new();
+    //! interpreter.globals.borrow_mut().define(&Symbol::intern("config"), Value::from(config));
+    //!
+    //! let (result, _) = lox::run(
+    //!     &mut interpreter,
+    //!     r#"var result = Map();
+    //!        result["area"] = config["width"] * config["height"];
+    //!        result["labels"] = [config["label"], nil];"#,
+    //! );
+    //! assert!(result.is_ok());
+    //!
+    //! let result = interpreter.variable("result").unwrap();
+    //! let json = serde_json::Value::try_from(&result).unwrap();
+    //! assert_eq!(json, serde_json::json!({"area": 12, "labels": ["box", null]}));
+    //! ```
+
+    use std::convert::TryFrom;
+
+    use super::ConversionError;
+    use crate::expr::Value;
+    use crate::map::{Key, LoxMap};
+    use crate::shared::{Rc, RefCell};
+
+    // As `jsonParse` reads it: objects become maps, arrays become arrays and
+    // null becomes nil.
+    impl From<serde_json::Value> for Value {
+        fn from(json: serde_json::Value) -> Self {
+            match json {
+                serde_json::Value::Null => Value::Nil,
+                serde_json::Value::Bool(b) => Value::Bool(b),
+                serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+                serde_json::Value::String(s) => Value::String(s.into()),
+                serde_json::Value::Array(elements) => {
+                    let elements = elements.into_iter().map(Value::from).collect();
+                    Value::Array(Rc::new(RefCell::new(elements)))
+                }
+                serde_json::Value::Object(entries) => {
+                    let mut map = LoxMap::new();
+                    for (key, value) in entries {
+                        let key: Rc<str> = key.into();
+                        map.insert(Key::String(Rc::clone(&key)), Value::String(key), value.into());
+                    }
+                    Value::Map(Rc::new(RefCell::new(map)))
+                }
+            }
+        }
+    }
+
+    // As `jsonStringify` writes it, except that instances are an error as
+    // there's no interpreter to call their `toJson()` method. So are
+    // functions, classes, interfaces, sets, ranges, maps with keys that aren't
+    // strings, numbers that aren't finite and values that contain themselves.
+    impl TryFrom<&Value> for serde_json::Value {
+        type Error = ConversionError;
+
+        fn try_from(value: &Value) -> Result<Self, Self::Error> {
+            to_json(value, &mut Vec::new())
+        }
+    }
+
+    impl TryFrom<Value> for serde_json::Value {
+        type Error = ConversionError;
+
+        fn try_from(value: Value) -> Result<Self, Self::Error> {
+            serde_json::Value::try_from(&value)
+        }
+    }
+
+    fn unsupported(value: &Value) -> ConversionError {
+        ConversionError(format!("Can't convert {} to JSON.", value.type_name()))
+    }
+
+    // `open` holds the arrays and maps being converted, to catch cycles.
+    fn to_json(value: &Value, open: &mut Vec<usize>) -> Result<serde_json::Value, ConversionError> {
+        let json = match value {
+            Value::Nil => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            // Whole numbers are written without a fraction, as `print` does.
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 => (*n as i64).into(),
+            Value::Number(n) => serde_json::Number::from_f64(*n).map(Into::into).ok_or_else(|| unsupported(value))?,
+            Value::String(s) => serde_json::Value::String(s.to_string()),
+            Value::Array(array) => {
+                enter(Rc::as_ptr(array) as usize, open)?;
+                let elements = array.borrow().iter().map(|element| to_json(element, open)).collect::<Result<_, _>>()?;
+                open.pop();
+                serde_json::Value::Array(elements)
+            }
+            Value::Tuple(elements) => {
+                serde_json::Value::Array(elements.iter().map(|element| to_json(element, open)).collect::<Result<_, _>>()?)
+            }
+            Value::Map(map) => {
+                enter(Rc::as_ptr(map) as usize, open)?;
+                let mut object = serde_json::Map::new();
+                for (key, value) in map.borrow().entries() {
+                    let name = match key {
+                        Value::String(name) => name,
+                        _ => return Err(ConversionError(format!("Can't convert map key {} to JSON, keys must be strings.", key))),
+                    };
+                    object.insert(name.to_string(), to_json(&value, open)?);
+                }
+                open.pop();
+                serde_json::Value::Object(object)
+            }
+            _ => return Err(unsupported(value)),
+        };
+        Ok(json)
+    }
+
+    fn enter(id: usize, open: &mut Vec<usize>) -> Result<(), ConversionError> {
+        if open.contains(&id) {
+            return Err(ConversionError("Can't convert a value that contains itself to JSON.".to_owned()));
+        }
+        open.push(id);
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use crate::expr::Value;
+use crate::instance::LoxInstance;
+use crate::map::{Key, LoxMap};
+use crate::shared::{Rc, RefCell};
+
+// What `copy()` returns: instances, arrays, maps, sets and tuples are copied
+// all the way down, so changing the copy never changes the original.
+// Functions, classes and interfaces are shared, as are the values that can't
+// change anyway. A value reached twice is copied once, so the copy has the
+// same shape as the original, cycles included. Instances of native classes
+// can't be copied, as their state is opaque. Copies of frozen instances
+// aren't frozen.
+pub fn deep_copy(value: &Value) -> Result<Value, String> {
+    copy(value, &mut HashMap::new())
+}
+
+// `copies` holds what every instance, array and map copied so far became,
+// by the address of what was copied.
+fn copy(value: &Value, copies: &mut HashMap<usize, Value>) -> Result<Value, String> {
+    let copied = match value {
+        Value::Instance(instance) => {
+            let instance = instance.borrow();
+            let id = Rc::as_ptr(&instance.fields) as usize;
+            if let Some(copied) = copies.get(&id) {
+                return Ok(copied.clone());
+            }
+            if instance.state.is_some() {
+                return Err(format!("Can't copy an instance of the native class {}.", instance.class.name));
+            }
+            let copied = Rc::new(RefCell::new(LoxInstance::new(instance.class.clone())));
+            copies.insert(id, Value::Instance(Rc::clone(&copied)));
+            let fields: Vec<_> = instance.fields.borrow().iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+            for (name, value) in fields {
+                let value = copy(&value, copies)?;
+                copied.borrow_mut().set_field(name, value);
+            }
+            Value::Instance(copied)
+        }
+        Value::Array(array) => {
+            let id = Rc::as_ptr(array) as usize;
+            if let Some(copied) = copies.get(&id) {
+                return Ok(copied.clone());
+            }
+            let copied = Rc::new(RefCell::new(Vec::new()));
+            copies.insert(id, Value::Array(Rc::clone(&copied)));
+            let elements = array.borrow().clone();
+            for element in &elements {
+                let element = copy(element, copies)?;
+                copied.borrow_mut().push(element);
+            }
+            Value::Array(copied)
+        }
+        Value::Map(map) => {
+            let id = Rc::as_ptr(map) as usize;
+            if let Some(copied) = copies.get(&id) {
+                return Ok(copied.clone());
+            }
+            let copied = Rc::new(RefCell::new(LoxMap::new()));
+            copies.insert(id, Value::Map(Rc::clone(&copied)));
+            let entries = map.borrow().entries();
+            for (key, value) in entries {
+                let value = copy(&value, copies)?;
+                // Keys are strings, numbers, booleans or nil, which don't
+                // need copying.
+                copied.borrow_mut().insert(Key::new(&key).unwrap(), key, value);
+            }
+            Value::Map(copied)
+        }
+        // Set elements are keys too.
+        Value::Set(set) => Value::Set(Rc::new(RefCell::new(set.borrow().clone()))),
+        // Tuples can't change, but what they hold can.
+        Value::Tuple(elements) => {
+            let elements = elements.iter().map(|element| copy(element, copies)).collect::<Result<_, _>>()?;
+            Value::Tuple(Rc::new(elements))
+        }
+        _ => value.clone(),
+    };
+    Ok(copied)
+}
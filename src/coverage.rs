@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::stmt::Stmt;
+
+// How many times a statement starting on each line ran.
+pub type Hits = BTreeMap<usize, u64>;
+
+// The lines coverage reports on: those a statement the interpreter runs
+// starts on. Method declarations aren't run themselves, their bodies are.
+pub fn statement_lines(stmts: &[Stmt]) -> BTreeSet<usize> {
+    let mut lines = BTreeSet::new();
+    for stmt in stmts {
+        collect(stmt, &mut lines);
+    }
+    lines
+}
+
+fn collect(stmt: &Stmt, lines: &mut BTreeSet<usize>) {
+    if let Some(line) = stmt.line() {
+        lines.insert(line);
+    }
+    match stmt {
+        Stmt::Block(stmts) => {
+            for stmt in stmts {
+                collect(stmt, lines);
+            }
+        }
+        Stmt::Class(_, _, _, _, methods) => {
+            for method in methods {
+                if let Stmt::Function(_, _, body) = method {
+                    for stmt in body.iter() {
+                        collect(stmt, lines);
+                    }
+                }
+            }
+        }
+        Stmt::For(_, initializer, _, _, body) => {
+            if let Some(initializer) = initializer {
+                collect(initializer, lines);
+            }
+            collect(body, lines);
+        }
+        Stmt::Function(_, _, body) => {
+            for stmt in body.iter() {
+                collect(stmt, lines);
+            }
+        }
+        Stmt::If(_, _, then_branch, else_branch) => {
+            collect(then_branch, lines);
+            if let Some(else_branch) = else_branch {
+                collect(else_branch, lines);
+            }
+        }
+        Stmt::ForIn(_, _, _, body) | Stmt::While(_, _, body) => collect(body, lines),
+        _ => (),
+    }
+}
+
+fn covered(lines: &BTreeSet<usize>, hits: &Hits) -> usize {
+    lines.iter().filter(|line| hits.contains_key(line)).count()
+}
+
+// A summary line followed by the source with gcov-style counts: how often
+// each line ran, `#####` for lines that never did, and `-` for lines without
+// statements.
+pub fn report(name: &str, source: &str, lines: &BTreeSet<usize>, hits: &Hits) -> String {
+    let mut out = String::new();
+    let covered = covered(lines, hits);
+    let percent = if lines.is_empty() { 100.0 } else { 100.0 * covered as f64 / lines.len() as f64 };
+    writeln!(out, "{}: {} of {} lines covered ({:.1}%)", name, covered, lines.len(), percent).unwrap();
+
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let count = match hits.get(&line) {
+            Some(count) => count.to_string(),
+            None if lines.contains(&line) => "#####".to_owned(),
+            None => "-".to_owned(),
+        };
+        writeln!(out, "{:>9}:{:>5}:{}", count, line, text).unwrap();
+    }
+    out
+}
+
+// The same counts in the LCOV tracefile format.
+pub fn lcov(name: &str, lines: &BTreeSet<usize>, hits: &Hits) -> String {
+    let mut out = String::new();
+    writeln!(out, "TN:").unwrap();
+    writeln!(out, "SF:{}", name).unwrap();
+    for line in lines {
+        writeln!(out, "DA:{},{}", line, hits.get(line).unwrap_or(&0)).unwrap();
+    }
+    writeln!(out, "LF:{}", lines.len()).unwrap();
+    writeln!(out, "LH:{}", covered(lines, hits)).unwrap();
+    writeln!(out, "end_of_record").unwrap();
+    out
+}
@@ -0,0 +1,64 @@
+use std::io::{self, Write};
+
+use crate::formatter;
+use crate::interpreter::Interpreter;
+use crate::shared::Shareable;
+use crate::stmt::Stmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    // Pause again before the next statement.
+    Step,
+    // Run until the next `breakpoint()`.
+    Continue,
+}
+
+// Gets control before a statement runs, once `breakpoint()` was called or a
+// previous pause asked to step.
+pub trait Debugger: Shareable {
+    fn pause(&mut self, interpreter: &Interpreter, stmt: &Stmt) -> Command;
+}
+
+// A line-oriented debugger on stdin. It writes to stderr, so the program's
+// own output stays apart.
+pub struct Prompt;
+
+const HELP: &str = "Commands: step, continue, print <name>, locals, stack.";
+
+impl Debugger for Prompt {
+    fn pause(&mut self, interpreter: &Interpreter, stmt: &Stmt) -> Command {
+        eprintln!("[line {}] {}", stmt.line().unwrap_or(0), formatter::header(stmt));
+        loop {
+            eprint!("(debug) ");
+            io::stderr().flush().expect("Could not write prompt.");
+            let mut input = String::new();
+            // Carry on when stdin is closed.
+            if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+                return Command::Continue;
+            }
+
+            let words: Vec<&str> = input.split_whitespace().collect();
+            match words.as_slice() {
+                ["s"] | ["step"] => return Command::Step,
+                ["c"] | ["continue"] => return Command::Continue,
+                ["p", name] | ["print", name] => match interpreter.variable(name) {
+                    Some(value) => eprintln!("{} = {}", name, value),
+                    None => eprintln!("Undefined variable '{}'.", name),
+                },
+                ["locals"] => {
+                    for (name, value) in interpreter.local_variables() {
+                        eprintln!("{} = {}", name, value);
+                    }
+                }
+                ["bt"] | ["stack"] => {
+                    for frame in interpreter.call_stack().iter().rev() {
+                        eprintln!("{} called from line {}", frame.callee, frame.line);
+                    }
+                    eprintln!("script");
+                }
+                [] => (),
+                _ => eprintln!("{}", HELP),
+            }
+        }
+    }
+}
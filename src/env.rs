@@ -1,62 +1,110 @@
 use std::collections::HashMap;
-use std::cell::RefCell;
-use std::rc::Rc;
 
+use crate::shared::{Rc, RefCell};
 use crate::token::Token;
 use crate::expr::Value;
+use crate::symbol::Symbol;
 use crate::loxerr::RuntimeException;
+use crate::spelling;
 
+// Globals are looked up by name. Every other environment stores its variables
+// in declaration order, which is the slot the resolver assigned to them, so
+// resolved accesses are plain indexing.
 pub struct Environment {
-    values: HashMap<String, Value>,
-    enclosing: Option<Rc<RefCell<Environment>>>,
+    // Each global has a cell of its own that the interpreter keeps for the
+    // expressions using it, so only the first use looks up its name.
+    // Redefining a global changes what the cell holds.
+    pub(crate) values: HashMap<Symbol, Rc<RefCell<Value>>>,
+    pub(crate) slots: Vec<(Symbol, Value)>,
+    pub(crate) enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
-        Environment{values: HashMap::new(), enclosing: None}
+        Environment{values: HashMap::new(), slots: Vec::new(), enclosing: None}
     }
 
     pub fn encloser(encloser: &Rc<RefCell<Environment>>) -> Self {
-        Environment { values: HashMap::new(), enclosing: Some(Rc::clone(encloser)) }
+        Environment { values: HashMap::new(), slots: Vec::new(), enclosing: Some(Rc::clone(encloser)) }
     }
 
-    pub fn define(&mut self, name: &str, val: Value) {
-        self.values.insert(name.to_owned(), val);
+    // A new environment in the same place holding the same variables.
+    pub(crate) fn copy(&self) -> Self {
+        Environment { values: self.values.clone(), slots: self.slots.clone(), enclosing: self.enclosing.clone() }
+    }
+
+    pub fn define(&mut self, name: &Symbol, val: Value) {
+        if self.enclosing.is_none() {
+            match self.values.get(name) {
+                Some(cell) => *cell.borrow_mut() = val,
+                None => {
+                    self.values.insert(name.clone(), Rc::new(RefCell::new(val)));
+                }
+            }
+        } else {
+            self.slots.push((name.clone(), val));
+        }
     }
 
     pub fn assign(&mut self, t: &Token, val: Value) ->Result<(), RuntimeException> {
-        if self.values.contains_key(&t.lexeme) {
-            self.values.insert(t.lexeme.clone(), val);
+        if let Some(cell) = self.values.get(&t.lexeme) {
+            *cell.borrow_mut() = val;
             Ok(())
         } else if let Some(enclosed) = &self.enclosing {
             enclosed.borrow_mut().assign(t, val)
         } else {
-            Err(RuntimeException::RuntimeError { 
-                token: t.clone(), 
-                error: format!("Undefined variable '{}'.", t.lexeme)})
+            Err(self.undefined(t, &[]))
         }
     }
 
-    pub fn assign_at(&mut self, dist:usize, t: &Token, val: Value) {
+    pub fn assign_at(&mut self, dist: usize, slot: usize, val: Value) {
         if dist == 0 {
-            self.values.insert(t.lexeme.clone(), val);
+            self.slots[slot].1 = val;
         } else {
             let env = self.ancestor(dist);
-            env.borrow_mut().values.insert(t.lexeme.clone(), val);
+            env.borrow_mut().slots[slot].1 = val;
         }
-        
     }
 
     pub fn get(&self, name: &Token) -> Result<Value, RuntimeException> {
-        if let Some(v) = self.values.get(&name.lexeme) {
-            Ok(v.clone())
+        if let Some(cell) = self.values.get(&name.lexeme) {
+            Ok(cell.borrow().clone())
         } else if let Some(enclosed) = &self.enclosing {
             enclosed.borrow().get(name)
         } else {
-            Err(RuntimeException::RuntimeError{
-                token:name.clone(), 
-                error: format!("Undefined variable '{}'.", name.lexeme)
-            })
+            Err(self.undefined(name, &[]))
+        }
+    }
+
+    pub(crate) fn cell(&self, name: &Symbol) -> Option<Rc<RefCell<Value>>> {
+        self.values.get(name).cloned()
+    }
+
+    // Every name defined here or in an enclosing environment.
+    pub(crate) fn names(&self) -> Vec<Symbol> {
+        let mut names: Vec<Symbol> = self.values.keys().cloned().collect();
+        names.extend(self.slots.iter().map(|(name, _)| name.clone()));
+        if let Some(enclosing) = &self.enclosing {
+            names.extend(enclosing.borrow().names());
+        }
+        names
+    }
+
+    // The error for a name that isn't defined, suggesting one that is when
+    // it looks like a typo. `locals` are names visible where the error
+    // happened that this environment doesn't hold.
+    pub(crate) fn undefined(&self, name: &Token, locals: &[Symbol]) -> RuntimeException {
+        let mut candidates = self.names();
+        candidates.extend_from_slice(locals);
+        RuntimeException::RuntimeError {
+            token: name.clone(),
+            error: format!("Undefined variable '{}'.{}", name.lexeme, spelling::did_you_mean(&name.lexeme, &candidates)),
         }
     }
 
@@ -69,15 +117,14 @@ impl Environment {
         env
     }
 
-    pub fn get_at(&self, dist: usize, name: &Token) -> Result<Value, RuntimeException> {
-        let val = if dist == 0 {
-            self.values.get(&name.lexeme).unwrap().clone()
+    pub fn get_at(&self, dist: usize, slot: usize) -> Value {
+        if dist == 0 {
+            self.slots[slot].1.clone()
         } else {
             let env = self.ancestor(dist);
-            Rc::clone(&env).borrow().values.get(&name.lexeme).unwrap().clone()
-        };
-        
-        Ok(val)
+            let val = env.borrow().slots[slot].1.clone();
+            val
+        }
     }
 
-}
\ No newline at end of file
+}
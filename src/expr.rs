@@ -1,79 +1,284 @@
-use std::cell::RefCell;
 use std::fmt;
-use std::rc::Rc;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use serde::{Deserialize, Serialize};
 
 use crate::class::LoxClass;
+use crate::shared::{Rc, RefCell, Weak};
 use crate::token::Token;
 use crate::loxcallables::LoxCallable;
 use crate::instance::LoxInstance;
+use crate::interface::LoxInterface;
+use crate::map::LoxMap;
+use crate::set::LoxSet;
+
+static NEXT_EXPR_ID: AtomicU32 = AtomicU32::new(0);
+
+// Identifies an expression node, and holds what its variable turned out to
+// refer to, so that it lives exactly as long as the tree does. Ids are unique
+// across every parse in the process, so nodes from earlier REPL lines never
+// collide with later ones, and they survive cloning or rebuilding the tree as
+// long as the id is carried over. They are left out of the serialized tree,
+// and a deserialized tree gets fresh ones.
+pub struct ExprId {
+    id: u32,
+    binding: RefCell<Option<Binding>>,
+}
+
+// What a variable, `this` or `super` refers to: a slot the resolver found in
+// an enclosing scope, or the cell of the global it turned out to be. The cell
+// is held weakly, since the global may well be a function whose body holds
+// this node.
+#[derive(Clone)]
+pub(crate) enum Binding {
+    Local(usize, usize),
+    Global(Weak<RefCell<Value>>),
+}
+
+impl ExprId {
+    pub fn fresh() -> ExprId {
+        ExprId { id: NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed), binding: RefCell::new(None) }
+    }
+
+    pub(crate) fn binding(&self) -> Option<Binding> {
+        self.binding.borrow().clone()
+    }
+
+    pub(crate) fn bind(&self, binding: Binding) {
+        *self.binding.borrow_mut() = Some(binding);
+    }
+}
+
+impl Default for ExprId {
+    fn default() -> Self {
+        ExprId::fresh()
+    }
+}
+
+impl Clone for ExprId {
+    fn clone(&self) -> Self {
+        ExprId { id: self.id, binding: RefCell::new(self.binding()) }
+    }
+}
+
+impl PartialEq for ExprId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for ExprId {}
+
+impl Hash for ExprId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl fmt::Debug for ExprId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ExprId").field(&self.id).finish()
+    }
+}
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Expr {
-    Assign(Token, Rc<Expr>),
+    Array(Vec<Rc<Expr>>),
+    Assign(#[serde(skip)] ExprId, Token, Rc<Expr>),
+    // `[a, b] = value`: the variables assigned, the `=` and the value.
+    AssignArray(Vec<Rc<Expr>>, Token, Rc<Expr>),
     Binary(Rc<Expr>, Token, Rc<Expr>),
     Call(Rc<Expr>, Token, Vec<Rc<Expr>>),
-    Get(Rc<Expr>, Token),
+    Get(#[serde(skip)] ExprId, Rc<Expr>, Token),
     Grouping(Rc<Expr>),
+    // `object[index]`, with the `[`.
+    Index(Rc<Expr>, Token, Rc<Expr>),
+    // `value is ClassOrInterface`, with the `is` token.
+    Is(Rc<Expr>, Token, Rc<Expr>),
     Literal(Value),
     Logical(Rc<Expr>, Token, Rc<Expr>),
+    // `start..end`, with the `..`.
+    Range(Rc<Expr>, Token, Rc<Expr>),
     Set(Rc<Expr>, Token, Rc<Expr>),
-    Super(Token, Token),
-    This(Token),
+    // `object[index] = value`.
+    SetIndex(Rc<Expr>, Token, Rc<Expr>, Rc<Expr>),
+    Super(#[serde(skip)] ExprId, Token, Token),
+    This(#[serde(skip)] ExprId, Token),
+    Tuple(Vec<Rc<Expr>>),
     Unary(Token, Rc<Expr>),
-    Variable(Token),
+    Variable(#[serde(skip)] ExprId, Token),
 }
 
-#[derive(Clone)]
+// Only literals are part of a parsed tree, so the other variants can't be
+// serialized.
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Value {
     Bool(bool),
     Number(f64),
     Nil,
-    String(String),
+    String(Rc<str>),
+    #[serde(skip)]
     Callable(Rc<dyn LoxCallable>),
+    #[serde(skip)]
     Class(Rc<LoxClass>),
+    #[serde(skip)]
     Instance(Rc<RefCell<LoxInstance>>),
+    #[serde(skip)]
+    Array(Rc<RefCell<Vec<Value>>>),
+    #[serde(skip)]
+    Interface(Rc<LoxInterface>),
+    #[serde(skip)]
+    Map(Rc<RefCell<LoxMap>>),
+    #[serde(skip)]
+    Set(Rc<RefCell<LoxSet>>),
+    #[serde(skip)]
+    Tuple(Rc<Vec<Value>>),
+    // From the first number up to, but not including, the second.
+    #[serde(skip)]
+    Range(f64, f64),
 }
 
 impl Value {
-    pub fn eq(&self, other: &Self) -> bool {
+    // What `type()` returns for the value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Nil => "nil",
+            Value::String(_) => "string",
+            Value::Callable(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::Array(_) => "array",
+            Value::Interface(_) => "interface",
+            Value::Map(_) => "map",
+            Value::Set(_) => "set",
+            Value::Tuple(_) => "tuple",
+            Value::Range(..) => "range",
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Value::Bool(l), Value::Bool(r)) => l == r,
+            // Numbers follow IEEE 754: NaN is unequal to everything, itself
+            // included, and 0 == -0.
             (Value::Number(l), Value::Number(r)) => l == r,
             (Value::Nil, Value::Nil) => true,
-            (Value::String(l), Value::String(r)) => l == r,
-            (Value::Class(l), Value::Class(r)) => {
-                l.to_string() == r.to_string()
-            }
-            (Value::Callable(l), Value::Callable(r)) => l.to_string() == r.to_string(),
+            (Value::String(l), Value::String(r)) => Rc::ptr_eq(l, r) || l == r,
+            // Classes and functions compare by identity. Every property access
+            // binds a fresh method, so `obj.method == obj.method` is false, while
+            // a bound method stored in a variable is equal to itself (as in jlox).
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Callable(l), Value::Callable(r)) => Rc::ptr_eq(l, r),
+            // An instance is only equal to itself.
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            (Value::Array(l), Value::Array(r)) => Rc::ptr_eq(l, r),
+            (Value::Interface(l), Value::Interface(r)) => Rc::ptr_eq(l, r),
+            (Value::Map(l), Value::Map(r)) => Rc::ptr_eq(l, r),
+            (Value::Set(l), Value::Set(r)) => *l.borrow() == *r.borrow(),
+            // Tuples can't change, so they compare by their elements.
+            (Value::Tuple(l), Value::Tuple(r)) => l == r,
+            (Value::Range(l_start, l_end), Value::Range(r_start, r_end)) => l_start == r_start && l_end == r_end,
             _ => false
         }
     }
 }
 
+// Formats a number the way jlox does: Java's `Double.toString` with a trailing
+// ".0" stripped. Java switches to scientific notation outside [1e-3, 1e7).
+pub fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_owned();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "Infinity" } else { "-Infinity" }.to_owned();
+    }
+
+    let abs = n.abs();
+    if abs == 0.0 || (1e-3..1e7).contains(&abs) {
+        return format!("{}", n);
+    }
+
+    let sci = format!("{:e}", n);
+    let (mantissa, exponent) = sci.split_at(sci.find('e').unwrap());
+    if mantissa.contains('.') {
+        format!("{}E{}", mantissa, &exponent[1..])
+    } else {
+        format!("{}.0E{}", mantissa, &exponent[1..])
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Value::Bool(b) => write!(f, "{}", b),
-            Value::Number(n) => write!(f, "{}", n),
-            Value::String(s) => write!(f, "{}", s),
-            Value::Nil => write!(f, "nil"),
-            Value::Callable(c) => write!(f, "{}", c),
-            Value::Class(c) =>  write!(f, "{}", c),
-            Value::Instance(i) => write!(f, "{}", (**i).borrow())
+        write_value(self, f, &mut Vec::new())
+    }
+}
+
+// `open` holds the arrays and maps being written, so that one inside itself
+// shows as `[...]` or `{...}` instead of going on forever.
+fn write_value(value: &Value, f: &mut fmt::Formatter, open: &mut Vec<usize>) -> fmt::Result {
+    match value {
+        Value::Bool(b) => write!(f, "{}", b),
+        Value::Number(n) => write!(f, "{}", number_to_string(*n)),
+        Value::String(s) => write!(f, "{}", s),
+        Value::Nil => write!(f, "nil"),
+        Value::Callable(c) => write!(f, "{}", c),
+        Value::Class(c) =>  write!(f, "{}", c),
+        Value::Interface(i) => write!(f, "{}", i),
+        Value::Map(map) => {
+            let id = Rc::as_ptr(map) as *const u8 as usize;
+            if open.contains(&id) {
+                return write!(f, "{{...}}");
+            }
+            open.push(id);
+            write!(f, "{{")?;
+            let entries = map.borrow().entries();
+            for (idx, (key, value)) in entries.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                write_value(key, f, open)?;
+                write!(f, ": ")?;
+                write_value(value, f, open)?;
+            }
+            open.pop();
+            write!(f, "}}")
+        }
+        Value::Set(set) => write!(f, "{}", set.borrow()),
+        Value::Range(start, end) => write!(f, "{}..{}", number_to_string(*start), number_to_string(*end)),
+        Value::Tuple(elements) => write_elements("(", elements, ")", f, open),
+        Value::Instance(i) => write!(f, "{}", (**i).borrow()),
+        Value::Array(elements) => {
+            let id = Rc::as_ptr(elements) as *const u8 as usize;
+            if open.contains(&id) {
+                return write!(f, "[...]");
+            }
+            open.push(id);
+            let copy = elements.borrow().clone();
+            write_elements("[", &copy, "]", f, open)?;
+            open.pop();
+            Ok(())
         }
     }
 }
 
+fn write_elements(start: &str, elements: &[Value], end: &str, f: &mut fmt::Formatter, open: &mut Vec<usize>) -> fmt::Result {
+    write!(f, "{}", start)?;
+    for (idx, element) in elements.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
+        }
+        write_value(element, f, open)?;
+    }
+    write!(f, "{}", end)
+}
+
 impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Value::Bool(b) => write!(f, "{}", b),
-            Value::Number(n) => write!(f, "{}", n),
-            Value::String(s) => write!(f, "{}", s),
-            Value::Nil => write!(f, "nil"),
-            Value::Callable(c) => write!(f, "{}", c),
-            Value::Class(c) =>  write!(f, "{}", c),
-            Value::Instance(i) => write!(f, "{}", (**i).borrow())
-        }
+        fmt::Display::fmt(self, f)
     }
 }
@@ -0,0 +1,532 @@
+use std::mem;
+use std::ops::Range;
+
+use crate::expr::{Expr, Value};
+use crate::scanner::Scanner;
+use crate::shared::Rc;
+use crate::stmt::Stmt;
+use crate::token::Token;
+
+const INDENT: &str = "  ";
+
+// Prints a parsed program back as Lox source in a canonical layout: two
+// space indentation, one statement per line, `{` on the line that opens the
+// block and a blank line around function and class declarations. The output
+// parses back to the same tree. Comments aren't part of the tree, so they
+// are lost, see `format_with_comments`.
+pub fn format(stmts: &[Stmt]) -> String {
+    let mut formatter = Formatter { out: String::new(), depth: 0 };
+    formatter.stmts(stmts);
+    formatter.out
+}
+
+// The first line `format` prints for a statement, without the brace that
+// opens a block.
+pub fn header(stmt: &Stmt) -> String {
+    let mut formatter = Formatter { out: String::new(), depth: 0 };
+    formatter.stmt(stmt);
+    let line = formatter.out.lines().next().unwrap_or("");
+    line.strip_suffix(" {").unwrap_or(line).to_owned()
+}
+
+// Like `format`, but keeps the comments in `source`, which `stmts` were
+// parsed from. The formatted program has the same tokens as the source, less
+// the empty statements, so each comment stays between the same two tokens. A
+// comment with code before it on its line goes at the end of the line its
+// previous token ends up on, and any other on a line of its own before the
+// line of its next token.
+pub fn format_with_comments(stmts: &[Stmt], source: &str) -> String {
+    let formatted = format(stmts);
+    let (tokens, comments) = scan(source);
+    if comments.is_empty() {
+        return formatted;
+    }
+    let (formatted_tokens, _) = scan(&formatted);
+
+    // Where each source token went, None for the `;` of an empty statement.
+    let mut next = 0;
+    let placed: Vec<Option<&Token>> = tokens
+        .iter()
+        .map(|token| {
+            let same = formatted_tokens
+                .get(next)
+                .is_some_and(|other| mem::discriminant(&other.token_type) == mem::discriminant(&token.token_type));
+            if same {
+                next += 1;
+            }
+            same.then(|| &formatted_tokens[next - 1])
+        })
+        .collect();
+
+    let lines: Vec<&str> = formatted.lines().collect();
+    let line_starts: Vec<usize> = lines.iter().map(|line| line.as_ptr() as usize - formatted.as_ptr() as usize).collect();
+    // The line an offset is on. A string literal can span lines, and nothing
+    // goes between its lines, so an offset inside one counts as on the line
+    // the string starts or ends on.
+    let inside = |offset: usize| formatted_tokens.iter().find(|token| token.span.start < offset && offset < token.span.end);
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset).saturating_sub(1);
+    let first_line = |offset: usize| {
+        let mut line = line_of(offset);
+        while let Some(string) = line_starts.get(line).and_then(|&start| inside(start)) {
+            line = line_of(string.span.start);
+        }
+        line
+    };
+    let last_line = |offset: usize| {
+        let mut line = line_of(offset);
+        while let Some(string) = inside(line_starts[line] + lines[line].len()) {
+            line = line_of(string.span.end);
+        }
+        line
+    };
+
+    // The comments on a line of their own before each line, the last entry
+    // being for the end, with whether a blank line followed them, and the
+    // comment ending each line.
+    let mut own_lines: Vec<Vec<(&str, bool)>> = vec![Vec::new(); lines.len() + 1];
+    let mut ending: Vec<Option<&str>> = vec![None; lines.len()];
+    for comment in &comments {
+        let text = source[comment.clone()].trim_end();
+        let after = tokens.partition_point(|token| token.span.end <= comment.start);
+        let line_start = source[..comment.start].rfind('\n').map_or(0, |idx| idx + 1);
+        let previous = placed[..after].iter().rev().find_map(|token| *token);
+        match previous {
+            Some(previous) if !source[line_start..comment.start].trim().is_empty() => {
+                let line = last_line(previous.span.end - 1);
+                match ending[line] {
+                    None => ending[line] = Some(text),
+                    Some(_) => own_lines[line + 1].push((text, false)),
+                }
+            }
+            _ => {
+                let line = match placed[after..].iter().find_map(|token| *token) {
+                    Some(next) if next.span.start < formatted.len() => first_line(next.span.start),
+                    _ => lines.len(),
+                };
+                let blank_after = source[comment.end..].lines().nth(1).is_some_and(|line| line.trim().is_empty());
+                own_lines[line].push((text, blank_after && line < lines.len()));
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for (idx, own) in own_lines.iter().enumerate() {
+        // Comments before a `}` belong to the block it closes.
+        let indent = match lines.get(idx) {
+            Some(line) => {
+                let code = line.trim_start();
+                let indent = &line[..line.len() - code.len()];
+                if code.starts_with('}') {
+                    format!("{}{}", indent, INDENT)
+                } else {
+                    indent.to_owned()
+                }
+            }
+            None => String::new(),
+        };
+        for &(comment, blank_after) in own {
+            out.push_str(&indent);
+            out.push_str(comment);
+            out.push('\n');
+            if blank_after {
+                out.push('\n');
+            }
+        }
+        if let Some(line) = lines.get(idx) {
+            out.push_str(line);
+            if let Some(comment) = ending[idx] {
+                out.push(' ');
+                out.push_str(comment);
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// The tokens of a program and where its comments are.
+fn scan(source: &str) -> (Vec<Token>, Vec<Range<usize>>) {
+    let mut scanner = Scanner::new(source);
+    scanner.report_errors = false;
+    let tokens = scanner.scan_tokens().clone();
+    (tokens, scanner.comments)
+}
+
+struct Formatter {
+    out: String,
+    depth: usize,
+}
+
+fn is_declaration(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Function(..) | Stmt::Class(..) | Stmt::Interface(..))
+}
+
+impl Formatter {
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    // One statement per line, each at the current depth.
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        let stmts: Vec<&Stmt> = stmts.iter().filter(|stmt| !matches!(stmt, Stmt::Null)).collect();
+        for (idx, stmt) in stmts.iter().enumerate() {
+            if idx > 0 && (is_declaration(stmt) || is_declaration(stmts[idx - 1])) {
+                self.out.push('\n');
+            }
+            self.indent();
+            self.stmt(stmt);
+            self.out.push('\n');
+        }
+    }
+
+    // `{`, the statements one level deeper, and `}` at the current depth.
+    fn block(&mut self, stmts: &[Stmt]) {
+        if stmts.iter().all(|stmt| matches!(stmt, Stmt::Null)) {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{\n");
+        self.depth += 1;
+        self.stmts(stmts);
+        self.depth -= 1;
+        self.indent();
+        self.out.push('}');
+    }
+
+    // The body of an `if`, `while` or `for`. Blocks open on the same line and
+    // single statements follow on it.
+    fn body(&mut self, body: &Stmt) {
+        self.out.push(' ');
+        self.stmt(body);
+    }
+
+    fn function(&mut self, name: &Token, params: &[Token], body: &[Stmt]) {
+        self.signature(name, params);
+        self.out.push(' ');
+        self.block(body);
+    }
+
+    fn signature(&mut self, name: &Token, params: &[Token]) {
+        self.out.push_str(&name.lexeme);
+        self.out.push('(');
+        for (idx, param) in params.iter().enumerate() {
+            if idx > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&param.lexeme);
+        }
+        self.out.push(')');
+    }
+
+    // ` with A, B` and the like, nothing for no names.
+    fn names(&mut self, word: &str, names: &[Rc<Expr>]) {
+        for (idx, name) in names.iter().enumerate() {
+            if idx == 0 {
+                self.out.push(' ');
+                self.out.push_str(word);
+                self.out.push(' ');
+            } else {
+                self.out.push_str(", ");
+            }
+            self.expr(name);
+        }
+    }
+
+    // Writes a statement starting at the current position. Lines after the
+    // first are indented to the current depth, and there's no newline at the
+    // end.
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Null => (),
+            Stmt::Block(stmts) => self.block(stmts),
+            Stmt::Class(name, superclass, mixins, interfaces, methods) => {
+                self.out.push_str("class ");
+                self.out.push_str(&name.lexeme);
+                if let Some(superclass) = superclass {
+                    self.out.push_str(" < ");
+                    self.expr(superclass);
+                }
+                self.names("with", mixins);
+                self.names("implements", interfaces);
+                if methods.is_empty() {
+                    self.out.push_str(" {}");
+                    return;
+                }
+
+                self.out.push_str(" {\n");
+                self.depth += 1;
+                for (idx, method) in methods.iter().enumerate() {
+                    if let Stmt::Function(name, params, body) = method {
+                        if idx > 0 {
+                            self.out.push('\n');
+                        }
+                        self.indent();
+                        self.function(name, params, body);
+                        self.out.push('\n');
+                    }
+                }
+                self.depth -= 1;
+                self.indent();
+                self.out.push('}');
+            }
+            Stmt::Expression(_, expr) => {
+                self.expr(expr);
+                self.out.push(';');
+            }
+            Stmt::For(_, initializer, condition, increment, body) => {
+                self.out.push_str("for (");
+                match initializer {
+                    Some(initializer) => self.stmt(initializer),
+                    None => self.out.push(';'),
+                }
+                if let Some(condition) = condition {
+                    self.out.push(' ');
+                    self.expr(condition);
+                }
+                self.out.push(';');
+                if let Some(increment) = increment {
+                    self.out.push(' ');
+                    self.expr(increment);
+                }
+                self.out.push(')');
+                self.body(body);
+            }
+            Stmt::ForIn(_, name, iterable, body) => {
+                self.out.push_str("for (var ");
+                self.out.push_str(&name.lexeme);
+                self.out.push_str(" in ");
+                self.expr(iterable);
+                self.out.push(')');
+                self.body(body);
+            }
+            Stmt::Function(name, params, body) => {
+                self.out.push_str("fun ");
+                self.function(name, params, body);
+            }
+            Stmt::Interface(name, methods) => {
+                self.out.push_str("interface ");
+                self.out.push_str(&name.lexeme);
+                if methods.is_empty() {
+                    self.out.push_str(" {}");
+                    return;
+                }
+
+                self.out.push_str(" {\n");
+                self.depth += 1;
+                for (name, params) in methods {
+                    self.indent();
+                    self.signature(name, params);
+                    self.out.push_str(";\n");
+                }
+                self.depth -= 1;
+                self.indent();
+                self.out.push('}');
+            }
+            Stmt::If(_, condition, then_branch, else_branch) => {
+                self.out.push_str("if (");
+                self.expr(condition);
+                self.out.push(')');
+                self.body(then_branch);
+                // `else` stays on the line the `then` branch ends on, which
+                // also keeps `else if` chains flat.
+                if let Some(else_branch) = else_branch {
+                    self.out.push_str(" else");
+                    self.body(else_branch);
+                }
+            }
+            Stmt::Print(_, expr) => {
+                self.out.push_str("print ");
+                self.expr(expr);
+                self.out.push(';');
+            }
+            Stmt::Return(_, value) => {
+                self.out.push_str("return");
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr(value);
+                }
+                self.out.push(';');
+            }
+            Stmt::Var(name, initializer) => {
+                self.out.push_str("var ");
+                self.out.push_str(&name.lexeme);
+                if let Some(initializer) = initializer {
+                    self.out.push_str(" = ");
+                    self.expr(initializer);
+                }
+                self.out.push(';');
+            }
+            Stmt::Import(_, name) => {
+                self.out.push_str("import ");
+                self.literal(&Value::String(Rc::clone(name)));
+                self.out.push(';');
+            }
+            Stmt::VarArray(names, value) => {
+                self.out.push_str("var [");
+                for (idx, name) in names.iter().enumerate() {
+                    if idx > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(&name.lexeme);
+                }
+                self.out.push_str("] = ");
+                self.expr(value);
+                self.out.push(';');
+            }
+            Stmt::While(_, condition, body) => {
+                self.out.push_str("while (");
+                self.expr(condition);
+                self.out.push(')');
+                self.body(body);
+            }
+        }
+    }
+
+    // The parser keeps parentheses as `Grouping` nodes, so printing every
+    // node the way it was written reproduces the same tree.
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Array(elements) => self.array(elements),
+            Expr::AssignArray(targets, _, value) => {
+                self.array(targets);
+                self.out.push_str(" = ");
+                self.expr(value);
+            }
+            Expr::Assign(_, name, value) => {
+                self.out.push_str(&name.lexeme);
+                self.out.push_str(" = ");
+                self.expr(value);
+            }
+            Expr::Binary(left, op, right) | Expr::Logical(left, op, right) => {
+                self.expr(left);
+                self.out.push(' ');
+                self.out.push_str(&op.lexeme);
+                self.out.push(' ');
+                self.expr(right);
+            }
+            Expr::Call(callee, _, args) => {
+                self.expr(callee);
+                self.out.push('(');
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(arg);
+                }
+                self.out.push(')');
+            }
+            Expr::Get(_, object, name) => self.property(object, name),
+            Expr::Is(value, _, class) => {
+                self.expr(value);
+                self.out.push_str(" is ");
+                self.expr(class);
+            }
+            Expr::Index(object, _, index) => {
+                self.expr(object);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            }
+            Expr::SetIndex(object, _, index, value) => {
+                self.expr(object);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push_str("] = ");
+                self.expr(value);
+            }
+            Expr::Tuple(elements) => {
+                self.out.push('(');
+                for (idx, element) in elements.iter().enumerate() {
+                    if idx > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(element);
+                }
+                self.out.push(')');
+            }
+            Expr::Range(start, _, end) => {
+                self.expr(start);
+                self.out.push_str("..");
+                self.expr(end);
+            }
+            Expr::Grouping(expr) => {
+                self.out.push('(');
+                self.expr(expr);
+                self.out.push(')');
+            }
+            Expr::Literal(value) => self.literal(value),
+            Expr::Set(object, name, value) => {
+                self.property(object, name);
+                self.out.push_str(" = ");
+                self.expr(value);
+            }
+            Expr::Super(_, _, method) => {
+                self.out.push_str("super.");
+                self.out.push_str(&method.lexeme);
+            }
+            Expr::This(..) => self.out.push_str("this"),
+            Expr::Unary(op, right) => {
+                self.out.push_str(&op.lexeme);
+                self.expr(right);
+            }
+            Expr::Variable(_, name) => self.out.push_str(&name.lexeme),
+        }
+    }
+
+    // `object.name`. Right after a whole number, `._` would be read as part
+    // of the number, so a space goes before the dot.
+    fn property(&mut self, object: &Expr, name: &Token) {
+        self.expr(object);
+        if let Expr::Literal(Value::Number(n)) = object {
+            if (n.is_infinite() || n.fract() == 0.0) && name.lexeme.starts_with('_') {
+                self.out.push(' ');
+            }
+        }
+        self.out.push('.');
+        self.out.push_str(&name.lexeme);
+    }
+
+    fn array(&mut self, elements: &[Rc<Expr>]) {
+        self.out.push('[');
+        for (idx, element) in elements.iter().enumerate() {
+            if idx > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(element);
+        }
+        self.out.push(']');
+    }
+
+    fn literal(&mut self, value: &Value) {
+        match value {
+            // Unlike `Value`'s Display, this never uses an exponent, which
+            // Lox can't read.
+            // Literals too long for a double are infinite, and the shortest
+            // one stands for all of them.
+            Value::Number(n) if n.is_infinite() => {
+                self.out.push('1');
+                self.out.push_str(&"0".repeat(309));
+            }
+            Value::Number(n) => self.out.push_str(&format!("{}", n)),
+            // Double quotes unless the string holds some. Newlines and other
+            // control characters stay as they are.
+            Value::String(s) => {
+                let quote = if s.contains('"') && !s.contains('\'') { '\'' } else { '"' };
+                self.out.push(quote);
+                for c in s.chars() {
+                    if c == '\\' || c == quote {
+                        self.out.push('\\');
+                    }
+                    self.out.push(c);
+                }
+                self.out.push(quote);
+            }
+            Value::Bool(_) | Value::Nil => self.out.push_str(&value.to_string()),
+            Value::Callable(_) | Value::Class(_) | Value::Instance(_) | Value::Array(_) | Value::Interface(_) | Value::Map(_) | Value::Set(_) | Value::Tuple(_) | Value::Range(..) => {
+                unreachable!("only literals appear in a parsed tree")
+            }
+        }
+    }
+}
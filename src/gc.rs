@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+use std::thread::{self, ThreadId};
+
+use crate::class::LoxClass;
+use crate::env::Environment;
+use crate::expr::Value;
+use crate::instance::LoxInstance;
+use crate::loxcallables::{Function, LoxCallable};
+use crate::map::LoxMap;
+use crate::shared::{Rc, RefCell, Weak};
+use crate::symbol::Symbol;
+
+// Closures keep their defining environment alive and environments keep the
+// closures defined in them alive, so reference counting alone leaks every
+// closure that outlives its call. All environments are registered here and,
+// once enough of them pile up, scanned for groups that are only kept alive by
+// each other.
+//
+// The scan is a trial deletion: for every object reachable from a registered
+// environment, references coming from other scanned objects are subtracted
+// from its strong count. Whatever is left over is held from outside (the
+// interpreter, values on the Rust stack, the host), so that object is alive,
+// as is everything reachable from it. The rest is garbage, and emptying the
+// garbage environments, field maps, arrays and maps breaks its cycles. Because it only
+// relies on reference counts, a collection is safe at any point.
+//
+// Every interpreter registers its environments in a heap of its own, which
+// becomes its thread's current heap when it runs. With the `sync` feature an
+// interpreter can move to another thread, and a heap is only ever scanned on
+// the thread that last ran its interpreter.
+
+const MIN_THRESHOLD: usize = 1024;
+
+pub struct Heap {
+    environments: Vec<Weak<RefCell<Environment>>>,
+    threshold: usize,
+    owner: ThreadId,
+}
+
+fn new_heap() -> Rc<RefCell<Heap>> {
+    Rc::new(RefCell::new(Heap { environments: Vec::new(), threshold: MIN_THRESHOLD, owner: thread_id() }))
+}
+
+// An interpreter's heap. The cycles the interpreter leaves behind would never
+// be scanned again, so dropping this collects them, which only works once
+// everything else the interpreter held is gone.
+pub struct OwnedHeap(Rc<RefCell<Heap>>);
+
+impl OwnedHeap {
+    pub fn new() -> Self {
+        OwnedHeap(new_heap())
+    }
+
+    pub fn tracked(&self) -> usize {
+        self.0.borrow().tracked()
+    }
+}
+
+impl Default for OwnedHeap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for OwnedHeap {
+    fn drop(&mut self) {
+        collect_heap(&self.0);
+    }
+}
+
+impl Heap {
+    // How many of its environments are still alive.
+    pub fn tracked(&self) -> usize {
+        self.environments.iter().filter(|env| env.strong_count() > 0).count()
+    }
+}
+
+thread_local! {
+    static THREAD: ThreadId = thread::current().id();
+    static CURRENT: std::cell::RefCell<Rc<RefCell<Heap>>> = std::cell::RefCell::new(new_heap());
+    static ALLOCATED: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+fn thread_id() -> ThreadId {
+    THREAD.with(|id| *id)
+}
+
+// Makes `heap` the one environments made on this thread go to. Waits for a
+// collection of it on another thread to finish.
+pub fn install(heap: &OwnedHeap) {
+    heap.0.borrow_mut().owner = thread_id();
+    CURRENT.with(|current| *current.borrow_mut() = Rc::clone(&heap.0));
+}
+
+// The current heap, or a new one if the current one's interpreter has since
+// run on another thread.
+fn current() -> Rc<RefCell<Heap>> {
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        if current.borrow().owner != thread_id() {
+            *current = new_heap();
+        }
+        Rc::clone(&current)
+    })
+}
+
+pub fn track(env: Environment) -> Rc<RefCell<Environment>> {
+    ALLOCATED.with(|allocated| allocated.set(allocated.get() + 1));
+    let env = Rc::new(RefCell::new(env));
+    let heap = current();
+    let (tracked, threshold) = {
+        let mut heap = heap.borrow_mut();
+        heap.environments.push(Rc::downgrade(&env));
+        (heap.environments.len(), heap.threshold)
+    };
+
+    if tracked >= threshold {
+        collect_heap(&heap);
+        let mut heap = heap.borrow_mut();
+        heap.threshold = MIN_THRESHOLD.max(2 * heap.environments.len());
+    }
+    env
+}
+
+// Frees environments of the current heap only reachable through cycles and
+// returns how many there were.
+pub fn collect() -> usize {
+    collect_heap(&current())
+}
+
+fn collect_heap(heap: &Rc<RefCell<Heap>>) -> usize {
+    // Held until the end, so the interpreter can't start running elsewhere.
+    let mut heap = heap.borrow_mut();
+    heap.environments.retain(|env| env.strong_count() > 0);
+    let envs = heap.environments.iter().filter_map(Weak::upgrade).collect::<Vec<_>>();
+
+    let mut collector = Collector::default();
+    for env in envs {
+        // Upgrading the weak reference added one strong reference of our own.
+        let strong = Rc::strong_count(&env) - 1;
+        collector.add(Node::Env(env), strong);
+    }
+    collector.scan();
+    let freed = collector.sweep();
+
+    heap.environments.retain(|env| env.strong_count() > 0);
+    freed
+}
+
+// How many environments this thread has made, freed or not.
+pub fn allocated_environments() -> u64 {
+    ALLOCATED.with(std::cell::Cell::get)
+}
+
+// How many environments of the current heap are alive.
+pub fn tracked_environments() -> usize {
+    current().borrow().tracked()
+}
+
+#[derive(Clone)]
+enum Node {
+    Env(Rc<RefCell<Environment>>),
+    Callable(Rc<dyn LoxCallable>),
+    Class(Rc<LoxClass>),
+    Methods(Rc<HashMap<Symbol, Function>>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    Fields(Rc<RefCell<HashMap<Symbol, Value>>>),
+    Array(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<LoxMap>>),
+    Tuple(Rc<Vec<Value>>),
+}
+
+enum Edge<'a> {
+    Env(&'a Rc<RefCell<Environment>>),
+    Callable(&'a Rc<dyn LoxCallable>),
+    Class(&'a Rc<LoxClass>),
+    Methods(&'a Rc<HashMap<Symbol, Function>>),
+    Instance(&'a Rc<RefCell<LoxInstance>>),
+    Fields(&'a Rc<RefCell<HashMap<Symbol, Value>>>),
+    Array(&'a Rc<RefCell<Vec<Value>>>),
+    Map(&'a Rc<RefCell<LoxMap>>),
+    Tuple(&'a Rc<Vec<Value>>),
+}
+
+impl Edge<'_> {
+    fn key(&self) -> usize {
+        match self {
+            Edge::Env(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Edge::Callable(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Edge::Class(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Edge::Methods(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Edge::Instance(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Edge::Fields(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Edge::Array(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Edge::Map(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Edge::Tuple(rc) => Rc::as_ptr(rc) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Edge::Env(rc) => Rc::strong_count(rc),
+            Edge::Callable(rc) => Rc::strong_count(rc),
+            Edge::Class(rc) => Rc::strong_count(rc),
+            Edge::Methods(rc) => Rc::strong_count(rc),
+            Edge::Instance(rc) => Rc::strong_count(rc),
+            Edge::Fields(rc) => Rc::strong_count(rc),
+            Edge::Array(rc) => Rc::strong_count(rc),
+            Edge::Map(rc) => Rc::strong_count(rc),
+            Edge::Tuple(rc) => Rc::strong_count(rc),
+        }
+    }
+
+    fn to_node(&self) -> Node {
+        match self {
+            Edge::Env(rc) => Node::Env(Rc::clone(rc)),
+            Edge::Callable(rc) => Node::Callable(Rc::clone(rc)),
+            Edge::Class(rc) => Node::Class(Rc::clone(rc)),
+            Edge::Methods(rc) => Node::Methods(Rc::clone(rc)),
+            Edge::Instance(rc) => Node::Instance(Rc::clone(rc)),
+            Edge::Fields(rc) => Node::Fields(Rc::clone(rc)),
+            Edge::Array(rc) => Node::Array(Rc::clone(rc)),
+            Edge::Map(rc) => Node::Map(Rc::clone(rc)),
+            Edge::Tuple(rc) => Node::Tuple(Rc::clone(rc)),
+        }
+    }
+}
+
+fn trace_value(value: &Value, visit: &mut dyn FnMut(Edge)) {
+    match value {
+        Value::Callable(callable) => visit(Edge::Callable(callable)),
+        Value::Class(class) => visit(Edge::Class(class)),
+        Value::Instance(instance) => visit(Edge::Instance(instance)),
+        Value::Array(array) => visit(Edge::Array(array)),
+        Value::Map(map) => visit(Edge::Map(map)),
+        Value::Tuple(tuple) => visit(Edge::Tuple(tuple)),
+        _ => (),
+    }
+}
+
+fn trace_class(class: &LoxClass, visit: &mut dyn FnMut(Edge)) {
+    if let Some(superclass) = &class.superclass {
+        visit(Edge::Class(superclass));
+    }
+    visit(Edge::Methods(&class.methods));
+    visit(Edge::Fields(&class.fields));
+}
+
+impl Node {
+    // Reports every strong reference held by this object. Returns false when
+    // the object is borrowed and can't be inspected.
+    fn trace(&self, visit: &mut dyn FnMut(Edge)) -> bool {
+        match self {
+            Node::Env(env) => {
+                let env = match env.try_borrow() {
+                    Ok(env) => env,
+                    Err(_) => return false,
+                };
+                if let Some(enclosing) = &env.enclosing {
+                    visit(Edge::Env(enclosing));
+                }
+                for cell in env.values.values() {
+                    match cell.try_borrow() {
+                        Ok(value) => trace_value(&value, visit),
+                        Err(_) => return false,
+                    }
+                }
+                for (_, value) in &env.slots {
+                    trace_value(value, visit);
+                }
+            }
+            Node::Callable(callable) => {
+                if let Some(closure) = callable.closure() {
+                    visit(Edge::Env(closure));
+                }
+            }
+            Node::Class(class) => trace_class(class, visit),
+            Node::Methods(methods) => {
+                for method in methods.values() {
+                    if let Some(closure) = method.closure() {
+                        visit(Edge::Env(closure));
+                    }
+                }
+            }
+            Node::Instance(instance) => {
+                let instance = match instance.try_borrow() {
+                    Ok(instance) => instance,
+                    Err(_) => return false,
+                };
+                trace_class(&instance.class, visit);
+                visit(Edge::Fields(&instance.fields));
+            }
+            Node::Fields(fields) => {
+                let fields = match fields.try_borrow() {
+                    Ok(fields) => fields,
+                    Err(_) => return false,
+                };
+                for value in fields.values() {
+                    trace_value(value, visit);
+                }
+            }
+            Node::Array(array) => {
+                let array = match array.try_borrow() {
+                    Ok(array) => array,
+                    Err(_) => return false,
+                };
+                for value in array.iter() {
+                    trace_value(value, visit);
+                }
+            }
+            // Keys are never references, only the values need following.
+            Node::Map(map) => {
+                let map = match map.try_borrow() {
+                    Ok(map) => map,
+                    Err(_) => return false,
+                };
+                for value in map.values() {
+                    trace_value(value, visit);
+                }
+            }
+            // Tuples can't be emptied, but every cycle through one also goes
+            // through something that can.
+            Node::Tuple(tuple) => {
+                for value in tuple.iter() {
+                    trace_value(value, visit);
+                }
+            }
+        }
+        true
+    }
+}
+
+struct Entry {
+    node: Node,
+    strong: usize,
+    internal: usize,
+    traced: bool,
+    edges: Vec<usize>,
+    alive: bool,
+}
+
+#[derive(Default)]
+struct Collector {
+    entries: Vec<Entry>,
+    index: HashMap<usize, usize>,
+}
+
+impl Collector {
+    fn add(&mut self, node: Node, strong: usize) -> usize {
+        let key = match &node {
+            Node::Env(rc) => Edge::Env(rc).key(),
+            Node::Callable(rc) => Edge::Callable(rc).key(),
+            Node::Class(rc) => Edge::Class(rc).key(),
+            Node::Methods(rc) => Edge::Methods(rc).key(),
+            Node::Instance(rc) => Edge::Instance(rc).key(),
+            Node::Fields(rc) => Edge::Fields(rc).key(),
+            Node::Array(rc) => Edge::Array(rc).key(),
+            Node::Map(rc) => Edge::Map(rc).key(),
+            Node::Tuple(rc) => Edge::Tuple(rc).key(),
+        };
+        if let Some(&idx) = self.index.get(&key) {
+            return idx;
+        }
+
+        self.entries.push(Entry {
+            node,
+            strong,
+            internal: 0,
+            traced: false,
+            edges: Vec::new(),
+            alive: false,
+        });
+        self.index.insert(key, self.entries.len() - 1);
+        self.entries.len() - 1
+    }
+
+    fn reach(&mut self, edge: Edge) -> usize {
+        let idx = match self.index.get(&edge.key()) {
+            Some(&idx) => idx,
+            None => {
+                // The count has to be read before the node is cloned into the table.
+                let strong = edge.strong_count();
+                self.add(edge.to_node(), strong)
+            }
+        };
+        self.entries[idx].internal += 1;
+        idx
+    }
+
+    fn scan(&mut self) {
+        let mut idx = 0;
+        while idx < self.entries.len() {
+            let node = self.entries[idx].node.clone();
+            let mut edges = Vec::new();
+            let traced = node.trace(&mut |edge| edges.push(self.reach(edge)));
+            self.entries[idx].traced = traced;
+            self.entries[idx].edges = edges;
+            idx += 1;
+        }
+    }
+
+    fn sweep(self) -> usize {
+        let mut entries = self.entries;
+
+        let mut stack: Vec<usize> = (0..entries.len())
+            .filter(|&idx| !entries[idx].traced || entries[idx].strong > entries[idx].internal)
+            .collect();
+        while let Some(idx) = stack.pop() {
+            if entries[idx].alive {
+                continue;
+            }
+            entries[idx].alive = true;
+            stack.extend(entries[idx].edges.iter().copied());
+        }
+
+        // Dropping the contents may free further objects, so it happens only
+        // after every borrow taken here has been released.
+        let mut graveyard = Vec::new();
+        let mut cells = Vec::new();
+        let mut slots = Vec::new();
+        let mut enclosings = Vec::new();
+        let mut arrays = Vec::new();
+        let mut maps = Vec::new();
+        let mut freed = 0;
+        for entry in entries.iter().filter(|entry| !entry.alive) {
+            match &entry.node {
+                Node::Env(env) => {
+                    if let Ok(mut env) = env.try_borrow_mut() {
+                        cells.push(std::mem::take(&mut env.values));
+                        slots.push(std::mem::take(&mut env.slots));
+                        enclosings.push(env.enclosing.take());
+                        freed += 1;
+                    }
+                }
+                Node::Fields(fields) => {
+                    if let Ok(mut fields) = fields.try_borrow_mut() {
+                        graveyard.push(std::mem::take(&mut *fields));
+                    }
+                }
+                Node::Array(array) => {
+                    if let Ok(mut array) = array.try_borrow_mut() {
+                        arrays.push(std::mem::take(&mut *array));
+                    }
+                }
+                Node::Map(map) => {
+                    if let Ok(mut map) = map.try_borrow_mut() {
+                        maps.push(map.take());
+                    }
+                }
+                _ => (),
+            }
+        }
+        drop(entries);
+        drop(graveyard);
+        drop(cells);
+        drop(slots);
+        drop(enclosings);
+        drop(arrays);
+        drop(maps);
+        freed
+    }
+}
@@ -0,0 +1,187 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::Helper;
+
+use crate::scanner::Scanner;
+use crate::token::Token;
+use crate::token::TokenType::{self, *};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Keyword,
+    String,
+    Number,
+    Comment,
+    // Whatever the scanner couldn't make sense of.
+    Error,
+    // The bracket matching the one at the cursor.
+    MatchingBracket,
+}
+
+impl Style {
+    fn ansi(self) -> &'static str {
+        match self {
+            Style::Keyword => "\x1b[35m",
+            Style::String => "\x1b[32m",
+            Style::Number => "\x1b[33m",
+            Style::Comment => "\x1b[90m",
+            Style::Error => "\x1b[31m",
+            Style::MatchingBracket => "\x1b[2m",
+        }
+    }
+}
+
+fn token_style(token_type: &TokenType) -> Option<Style> {
+    match token_type {
+        And | Class | Else | False | Fun | For | If | Nil | Or | Print | Return | Super | This | True | Var
+        | While => Some(Style::Keyword),
+        StringLiteral(_) => Some(Style::String),
+        Number(_) => Some(Style::Number),
+        _ => None,
+    }
+}
+
+// The styled parts of `line` in order, with the cursor at byte `cursor`.
+// Text in no span is left as it is. An error colors the text it covers even
+// when it's inside a token, such as a bad escape in a string.
+pub fn spans(line: &str, cursor: usize) -> Vec<(Range<usize>, Style)> {
+    let mut scanner = Scanner::new(line);
+    scanner.report_errors = false;
+    let tokens = scanner.scan_tokens().clone();
+
+    let mut styles = vec![None; line.len()];
+    let mut set = |range: &Range<usize>, style: Style| {
+        for byte in &mut styles[range.clone()] {
+            *byte = Some(style);
+        }
+    };
+    for (token, span) in tokens.iter().zip(&scanner.spans) {
+        if let Some(style) = token_style(&token.token_type) {
+            set(span, style);
+        }
+    }
+    for comment in &scanner.comments {
+        set(comment, Style::Comment);
+    }
+    if let Some(bracket) = matching_bracket(&tokens, &scanner.spans, cursor) {
+        set(&scanner.spans[bracket], Style::MatchingBracket);
+    }
+    for error in &scanner.errors {
+        set(error, Style::Error);
+    }
+
+    let mut spans: Vec<(Range<usize>, Style)> = Vec::new();
+    for (idx, style) in styles.into_iter().enumerate() {
+        let style = match style {
+            Some(style) => style,
+            None => continue,
+        };
+        match spans.last_mut() {
+            Some((range, last)) if range.end == idx && *last == style => range.end += 1,
+            _ => spans.push((idx..idx + 1, style)),
+        }
+    }
+    spans
+}
+
+// The index of the token matching the bracket just before the cursor, or
+// else the one under it. Only brackets of the same kind count.
+fn matching_bracket(tokens: &[Token], spans: &[Range<usize>], cursor: usize) -> Option<usize> {
+    let at = |offset: usize| spans.iter().position(|span| span.start == offset && span.len() == 1);
+    let candidates = cursor.checked_sub(1).and_then(at).into_iter().chain(at(cursor));
+    for idx in candidates {
+        let found = match tokens[idx].token_type {
+            LeftParen => find_close(tokens, idx, LeftParen, RightParen),
+            LeftBrace => find_close(tokens, idx, LeftBrace, RightBrace),
+            LeftBracket => find_close(tokens, idx, LeftBracket, RightBracket),
+            RightParen => find_open(tokens, idx, LeftParen, RightParen),
+            RightBrace => find_open(tokens, idx, LeftBrace, RightBrace),
+            RightBracket => find_open(tokens, idx, LeftBracket, RightBracket),
+            _ => continue,
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+fn find_close(tokens: &[Token], open: usize, left: TokenType, right: TokenType) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, token) in tokens.iter().enumerate().skip(open) {
+        if token.token_type == left {
+            depth += 1;
+        } else if token.token_type == right {
+            depth -= 1;
+            if depth == 0 {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+fn find_open(tokens: &[Token], close: usize, left: TokenType, right: TokenType) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, token) in tokens[..=close].iter().enumerate().rev() {
+        if token.token_type == right {
+            depth += 1;
+        } else if token.token_type == left {
+            depth -= 1;
+            if depth == 0 {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+// `line` with the ANSI escapes for its spans.
+pub fn paint(line: &str, spans: &[(Range<usize>, Style)]) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut last = 0;
+    for (range, style) in spans {
+        out.push_str(&line[last..range.start]);
+        out.push_str(style.ansi());
+        out.push_str(&line[range.clone()]);
+        out.push_str("\x1b[0m");
+        last = range.end;
+    }
+    out.push_str(&line[last..]);
+    out
+}
+
+// Colors the prompt's line as it's typed.
+pub struct LoxHelper;
+
+impl Highlighter for LoxHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let spans = spans(line, pos);
+        if spans.is_empty() {
+            Cow::Borrowed(line)
+        } else {
+            Cow::Owned(paint(line, &spans))
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Completer for LoxHelper {
+    type Candidate = String;
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
@@ -1,13 +1,21 @@
 use std::{collections::HashMap, fmt::Display};
-use std::rc::Rc;
-use std::cell::RefCell;
 
-use crate::{class::LoxClass, expr::Value, loxerr::RuntimeException, token::Token};
+use crate::shared::{AnyBox, Cell, Rc, RefCell};
+use crate::{class::LoxClass, expr::Value, loxerr::RuntimeException, spelling, symbol::Symbol, token::Token};
+
+// How many fields and methods an undefined property error lists at most.
+const MAX_LISTED: usize = 10;
+
+fn list(names: &[Symbol]) -> String {
+    names.iter().map(Symbol::as_str).collect::<Vec<_>>().join(", ")
+}
 
-#[derive(Clone)]
 pub struct LoxInstance {
     pub class: LoxClass,
-    fields: Rc<RefCell<HashMap<String, Value>>>,
+    pub(crate) fields: Rc<RefCell<HashMap<Symbol, Value>>>,
+    // What the constructor of a native class built.
+    pub(crate) state: Option<Rc<RefCell<AnyBox>>>,
+    frozen: Cell<bool>,
 }
 
 impl LoxInstance {
@@ -15,26 +23,109 @@ impl LoxInstance {
         LoxInstance {
             class,
             fields: Rc::new(RefCell::new(HashMap::new())),
+            state: None,
+            frozen: Cell::new(false),
+        }
+    }
+
+    pub fn with_state(class: LoxClass, state: AnyBox) -> LoxInstance {
+        let mut instance = LoxInstance::new(class);
+        instance.state = Some(Rc::new(RefCell::new(state)));
+        instance
+    }
+
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, RuntimeException> {
+        LoxInstance::lookup(instance, &name.lexeme).ok_or_else(|| RuntimeException::RuntimeError {
+            token: name.clone(),
+            error: instance.borrow().undefined_property(&name.lexeme),
+        })
+    }
+
+    // Suggests a field or method with a similar name and, when there are
+    // only a few, lists them all.
+    pub(crate) fn undefined_property(&self, name: &Symbol) -> String {
+        let fields = self.field_names();
+        let methods = self.class.method_names();
+        let mut candidates = fields.clone();
+        candidates.extend(methods.iter().cloned());
+        let mut msg = format!("Undefined property '{}'.{}", name, spelling::did_you_mean(name, &candidates));
+        if !candidates.is_empty() && candidates.len() <= MAX_LISTED {
+            let mut available = Vec::new();
+            if !fields.is_empty() {
+                available.push(format!("fields [{}]", list(&fields)));
+            }
+            if !methods.is_empty() {
+                available.push(format!("methods [{}]", list(&methods)));
+            }
+            msg.push_str(&format!(" Available on {} instance: {}.", self.class.name, available.join(", ")));
+        }
+        msg
+    }
+
+    pub(crate) fn field(&self, name: &Symbol) -> Option<Value> {
+        self.fields.borrow().get(name).cloned()
+    }
+
+    // A field, or else a method bound to this instance.
+    pub fn lookup(instance: &Rc<RefCell<LoxInstance>>, name: &Symbol) -> Option<Value> {
+        let receiver = instance.borrow();
+        if let Some(value) = receiver.fields.borrow().get(name) {
+            return Some(value.clone());
+        }
+        if let Some(method) = receiver.class.find_method(name) {
+            let val = Value::Instance(Rc::clone(instance));
+            return Some(Value::Callable(Rc::new(method.bind(val))));
         }
+        let method = receiver.class.native.as_ref()?.find_method(name)?;
+        Some(Value::Callable(Rc::new(method.bind(receiver.state.as_ref()?))))
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) -> Result<(), RuntimeException> {
+        self.check_unfrozen(&name.lexeme).map_err(|error| RuntimeException::RuntimeError {
+            token: name.clone(),
+            error,
+        })?;
+        self.set_field(name.lexeme.clone(), value);
+        Ok(())
+    }
+
+    pub fn set_field(&mut self, name: Symbol, value: Value) {
+        self.fields.borrow_mut().insert(name, value);
+    }
+
+    // Freezing is shallow: the values of the fields can still change if they
+    // are instances, arrays or maps themselves.
+    pub fn freeze(&self) {
+        self.frozen.set(true);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.get()
     }
 
-    pub fn get(&self, name: &Token) -> Result<Value, RuntimeException> {
-        if self.fields.borrow().contains_key(&name.lexeme) {
-            Ok(self.fields.borrow().get(&name.lexeme).unwrap().clone())
-        } else if let Some(method) = self.class.find_method(&name.lexeme) {
-            let val = Value::Instance(Rc::new(RefCell::new(self.clone())));
-            let fun = method.bind(val);
-            Ok(Value::Callable(Rc::new(fun)))
-        } else {
-            Err(RuntimeException::RuntimeError {
-                token: name.clone(),
-                error: format!("Undefined property '{}'.", name.lexeme),
-            })
+    // Fails if `name` is about to be set or removed on a frozen instance.
+    pub(crate) fn check_unfrozen(&self, name: &Symbol) -> Result<(), String> {
+        if self.is_frozen() {
+            return Err(format!("Cannot modify field '{}' of frozen instance of {}.", name, self.class.name));
         }
+        Ok(())
+    }
+
+    // Only looks at fields, not methods.
+    pub fn contains(&self, name: &Symbol) -> bool {
+        self.fields.borrow().contains_key(name)
+    }
+
+    // Sorted, so the order doesn't depend on hashing.
+    pub fn field_names(&self) -> Vec<Symbol> {
+        let mut names: Vec<Symbol> = self.fields.borrow().keys().cloned().collect();
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        names
     }
 
-    pub fn set(&mut self, name: &Token, value: Value) {
-        self.fields.borrow_mut().insert(name.lexeme.clone(), value);
+    // Returns whether there was such a field.
+    pub fn remove(&self, name: &Symbol) -> bool {
+        self.fields.borrow_mut().remove(name).is_some()
     }
 }
 
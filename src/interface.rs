@@ -0,0 +1,22 @@
+use std::fmt;
+
+use crate::symbol::Symbol;
+
+// The methods a class declaring the interface must have, with their arities.
+// Conformance is checked once, when the class is defined.
+pub struct LoxInterface {
+    pub name: String,
+    pub(crate) methods: Vec<(Symbol, usize)>,
+}
+
+impl LoxInterface {
+    pub fn new(name: String, methods: Vec<(Symbol, usize)>) -> Self {
+        LoxInterface { name, methods }
+    }
+}
+
+impl fmt::Display for LoxInterface {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
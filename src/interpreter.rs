@@ -1,19 +1,44 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::class;
+use crate::class::{self, NativeClass};
+use crate::clock::{SystemTime, TimeSource};
+use crate::instance::LoxInstance;
+use crate::interface::LoxInterface;
+use crate::coverage::Hits;
+use crate::debugger::{Command, Debugger};
 use crate::env::Environment;
-use crate::expr::{Expr, Value};
+use crate::formatter;
+use crate::gc;
+use crate::expr::{number_to_string, Binding, Expr, ExprId, Value};
 use crate::loxcallables::LoxCallable;
-use crate::loxcallables::{self, Native};
-use crate::loxerr::RuntimeException;
-use crate::stmt::Stmt;
+use crate::loxcallables;
+use crate::lox::{self, Timings};
+use crate::loxerr::{self, RuntimeException, Warnings};
+use crate::map::Key;
+use crate::module::NativeModule;
+use crate::natives::{self, Group, Profile};
+use crate::rng::Rng;
+use crate::shared::{Output, Rc, RefCell};
+use crate::spelling;
+use crate::stack;
+use crate::profile::{FunctionProfile, Profiler};
+use crate::stmt::{self, Stmt};
+use crate::symbol::Symbol;
 use crate::token::{Token, TokenType};
 use TokenType::*;
 use Value::*;
 
+// What one element of an array, tuple or map takes, as the memory limit
+// counts it.
+pub(crate) const VALUE_SIZE: usize = std::mem::size_of::<Value>();
+
 pub fn is_truthy(value: &Value) -> bool {
     match value {
         Value::Nil => false,
@@ -22,6 +47,28 @@ pub fn is_truthy(value: &Value) -> bool {
     }
 }
 
+// A script returns nil or a whole number from 0 to 255, as exit statuses go.
+fn exit_status(keyword: &Token, value: &Value) -> Result<i32, RuntimeException> {
+    match value {
+        Value::Nil => Ok(0),
+        Value::Number(n) if n.fract() == 0.0 && (0.0..=255.0).contains(n) => Ok(*n as i32),
+        _ => Err(gen_err(
+            keyword,
+            &format!("Exit status must be a whole number from 0 to 255, but got {}.", describe(value)),
+        )),
+    }
+}
+
+// The status a script ends with, given how running it went. A `return` at
+// its top level ends it early with the status it returns.
+pub(crate) fn script_status(result: Result<(), RuntimeException>) -> Result<i32, RuntimeException> {
+    match result {
+        Ok(()) => Ok(0),
+        Err(RuntimeException::Exit(keyword, value)) => exit_status(&keyword, &value),
+        Err(e) => Err(e),
+    }
+}
+
 fn gen_err(token: &Token, msg: &str) -> RuntimeException {
     RuntimeException::RuntimeError {
         token: token.clone(),
@@ -29,60 +76,1006 @@ fn gen_err(token: &Token, msg: &str) -> RuntimeException {
     }
 }
 
-fn err_numeric_operand(token: &Token) -> Result<Value, RuntimeException> {
-    Err(gen_err(token, "Operands must be numbers."))
+// One element per name, from the start of the array or tuple. Elements
+// left over are ignored.
+fn destructure(value: &Value, names: &[&Token], token: &Token) -> Result<Vec<Value>, RuntimeException> {
+    let (kind, elements) = match value {
+        Value::Array(elements) => ("array", elements.borrow().iter().take(names.len()).cloned().collect()),
+        Value::Tuple(elements) => ("tuple", elements.iter().take(names.len()).cloned().collect::<Vec<_>>()),
+        _ => return Err(gen_err(token, "Can only destructure arrays and tuples.")),
+    };
+    if let Some(missing) = names.get(elements.len()) {
+        let msg = format!(
+            "No element at index {} for '{}', the {} has length {}.",
+            elements.len(),
+            missing.lexeme,
+            kind,
+            elements.len()
+        );
+        return Err(gen_err(missing, &msg));
+    }
+    Ok(elements)
+}
+
+fn map_key(index: &Value, bracket: &Token) -> Result<Key, RuntimeException> {
+    Key::new(index).ok_or_else(|| gen_err(bracket, "Map keys must be strings, numbers, booleans or nil."))
+}
+
+// Checks `index` is a whole number within a sequence of `len` elements.
+fn element_index(index: &Value, len: usize, bracket: &Token) -> Result<usize, RuntimeException> {
+    match index {
+        Value::Number(n) if n.fract() == 0.0 => {
+            if *n >= 0.0 && *n < len as f64 {
+                Ok(*n as usize)
+            } else {
+                let msg = format!("Index {} is out of bounds for length {}.", number_to_string(*n), len);
+                Err(gen_err(bracket, &msg))
+            }
+        }
+        _ => Err(gen_err(bracket, "Index must be an integer.")),
+    }
+}
+
+enum Subscript {
+    Element(usize),
+    Slice(usize, usize),
 }
 
-fn err_numstr_operand(token: &Token) -> Result<Value, RuntimeException> {
-    Err(gen_err(
-        token,
-        "Operands must be two numbers or two strings.",
-    ))
+// Indexing with a range takes a slice. Its bounds have to be whole numbers
+// with 0 <= start <= end <= len.
+fn subscript(index: &Value, len: usize, bracket: &Token) -> Result<Subscript, RuntimeException> {
+    match index {
+        Value::Range(start, end) => {
+            if start.fract() != 0.0 || end.fract() != 0.0 {
+                return Err(gen_err(bracket, "Slice bounds must be integers."));
+            }
+            if *start < 0.0 || start > end || *end > len as f64 {
+                let msg = format!("Slice {} is out of bounds for length {}.", index, len);
+                return Err(gen_err(bracket, &msg));
+            }
+            Ok(Subscript::Slice(*start as usize, *end as usize))
+        }
+        _ => element_index(index, len, bracket).map(Subscript::Element),
+    }
+}
+
+// An operand as error messages show it: its type and the start of what it
+// prints as.
+fn describe(value: &Value) -> std::string::String {
+    const MAX_CHARS: usize = 20;
+    let text = match value {
+        Value::Nil => return "nil".to_owned(),
+        Value::String(s) => format!("\"{}\"", s),
+        _ => value.to_string(),
+    };
+    if text.chars().count() > MAX_CHARS {
+        let start: std::string::String = text.chars().take(MAX_CHARS - 3).collect();
+        format!("{} ({}...)", value.type_name(), start)
+    } else {
+        format!("{} ({})", value.type_name(), text)
+    }
+}
+
+pub(crate) fn numeric_operands_message(l: &Value, r: &Value) -> std::string::String {
+    format!("Operands must be numbers, but got {} and {}.", describe(l), describe(r))
+}
+
+pub(crate) fn numstr_operands_message(l: &Value, r: &Value) -> std::string::String {
+    format!("Operands must be two numbers or two strings, but got {} and {}.", describe(l), describe(r))
+}
+
+pub(crate) fn numeric_operand_message(value: &Value) -> std::string::String {
+    format!("Operand must be a number, but got {}.", describe(value))
+}
+
+fn err_numeric_operand(token: &Token, l: &Value, r: &Value) -> Result<Value, RuntimeException> {
+    Err(gen_err(token, &numeric_operands_message(l, r)))
+}
+
+// The integer a number holds, if it's a whole number that fits in an i64.
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 => Some(*n as i64),
+        _ => None,
+    }
+}
+
+// `&`, `|`, `^`, `<<` and `>>` work on the integers their operands hold and
+// give back a number. Shifts are arithmetic, so `>>` keeps the sign.
+pub(crate) fn bitwise(op: &Token, l: &Value, r: &Value) -> Result<Value, RuntimeException> {
+    let (l, r) = match (integer(l), integer(r)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => return Err(gen_err(op, &format!("Operands of '{}' must be integers.", op.lexeme))),
+    };
+    if matches!(op.token_type, LessLess | GreaterGreater) && !(0..64).contains(&r) {
+        return Err(gen_err(op, "Shift count must be between 0 and 63."));
+    }
+    let result = match op.token_type {
+        Ampersand => l & r,
+        Pipe => l | r,
+        Caret => l ^ r,
+        LessLess => l << r,
+        GreaterGreater => l >> r,
+        _ => unreachable!(),
+    };
+    Ok(Value::Number(result as f64))
+}
+
+pub(crate) fn bitwise_not(op: &Token, value: &Value) -> Result<Value, RuntimeException> {
+    match integer(value) {
+        Some(n) => Ok(Value::Number(!n as f64)),
+        None => Err(gen_err(op, "Operand of '~' must be an integer.")),
+    }
+}
+
+fn err_numstr_operand(token: &Token, l: &Value, r: &Value) -> Result<Value, RuntimeException> {
+    Err(gen_err(token, &numstr_operands_message(l, r)))
+}
+
+// Lox calls nest Rust calls, so both limits have to fit the stack of the
+// thread running the interpreter. A call takes about 4 KiB, and these fit the
+// 2 MiB Rust gives a new thread. The command line raises them for its own
+// bigger thread.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+pub const DEFAULT_STACK_LIMIT: usize = 1024 * 1024;
+
+// 2^53, past which not every whole number is a double.
+const MAX_EXACT_INTEGER: f64 = 9007199254740992.0;
+
+// A call in progress, as the debugger shows it.
+pub struct CallFrame {
+    pub callee: std::string::String,
+    // Where the call was made.
+    pub line: usize,
+}
+
+// What the interpreter has been up to, for `stats()` and hosts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    // Environments still alive, including the globals.
+    pub environments: usize,
+    // Globals defined by Lox code rather than built in.
+    pub globals: usize,
+    // Calls made since the interpreter was made or last reset, tail calls
+    // included.
+    pub calls: u64,
+    pub peak_call_depth: usize,
 }
 
 pub struct Interpreter {
     pub globals: Rc<RefCell<Environment>>,
-    locals: HashMap<*const Expr, usize>,
+    // The natives, the prelude and whatever the host defined, which `reset`
+    // keeps.
+    builtins: HashMap<Symbol, Value>,
+    // The prelude's functions, by address.
+    prelude: HashSet<usize>,
+    // What `import` can load, by name, and what it already has.
+    modules: HashMap<std::string::String, Rc<NativeModule>>,
+    imported: HashSet<std::string::String>,
+    // The scripts imported so far, by canonical path.
+    files: HashSet<PathBuf>,
+    // The scripts still running their top-level code, the main one first,
+    // by canonical path and with the name they were imported by.
+    loading: Vec<(PathBuf, std::string::String)>,
+    // Where imports are looked for when they aren't next to the script
+    // importing them.
+    import_paths: Vec<PathBuf>,
+    // The method each `object.name` last found in this run, with the methods
+    // of the class it was found for, which identify the class.
+    method_cache: HashMap<ExprId, (Rc<HashMap<Symbol, loxcallables::Function>>, loxcallables::Function)>,
+    // The methods of strings, numbers and arrays.
+    primitive_methods: natives::PrimitiveMethods,
+    // The natives it was made with. Without `Io`, it doesn't import files.
+    allowed: Profile,
     env: Rc<RefCell<Environment>>,
+    pub(crate) call_depth: usize,
+    // The call depth the running script's top level is at. An imported
+    // script's is the depth it was imported at.
+    script_depth: usize,
+    max_call_depth: usize,
+    // Bytes of stack a run may use, see stack.rs.
+    stack_limit: usize,
+    calls: u64,
+    peak_call_depth: usize,
+    // Remaining loop iterations and calls, unlimited when None.
+    fuel: Option<u64>,
+    warnings: Warnings,
+    strict: bool,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    // Raised from outside, see `interrupt_flag`.
+    interrupt: Arc<AtomicBool>,
+    // Bytes a run may allocate, unlimited when None, and what this run has
+    // allocated so far besides environments, which the collector counts.
+    memory_limit: Option<usize>,
+    allocated: usize,
+    environments_at_start: u64,
+    // What `clock()` and `random()` read.
+    time: Box<dyn TimeSource>,
+    rng: Rng,
+    ticks: u32,
+    output: Output,
+    // Whether `write` left the output's last line unfinished.
+    line_open: bool,
+    trace: Option<Output>,
+    profiler: Option<Profiler>,
+    coverage: Option<Hits>,
+    debugger: Option<Box<dyn Debugger>>,
+    // Pause before the next statement.
+    stepping: bool,
+    // Only kept while a debugger is attached.
+    call_stack: Vec<CallFrame>,
+    // The instances whose `toString()` is running, by their fields.
+    stringifying: Vec<usize>,
+    // Compiled patterns of the regex natives, by their source.
+    #[cfg(feature = "regex")]
+    pub(crate) regexes: HashMap<std::string::String, regex::Regex>,
+    // Where its environments are registered for the cycle collector. Fields
+    // are dropped in order, so this goes last.
+    heap: gc::OwnedHeap,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Functions every interpreter starts with that are simpler to write in Lox.
+const PRELUDE: &str = include_str!("prelude.lox");
+
+// Identifies a callable by its address.
+fn address(callee: &dyn LoxCallable) -> usize {
+    callee as *const dyn LoxCallable as *const () as usize
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let global = Rc::new(RefCell::new(Environment::new()));
-        (*global).borrow_mut().define(
-            "clock",
-            Value::Callable(Rc::new(Native::new(0, |_| {
-                Ok(Value::Number(
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .expect("Could not retrieve time.")
-                        .as_millis() as f64,
-                ))
-            }))),
-        );
+        Self::with_profile(Profile::full())
+    }
+
+    // Only the natives, for hosts that want to define everything else.
+    pub fn without_prelude() -> Self {
+        Self::with_profile(Profile::full().prelude(false))
+    }
+
+    // Only what `profile` allows, see `natives::Profile`.
+    pub fn with_profile(profile: Profile) -> Self {
+        let mut interpreter = Self::with_natives(profile);
+        if interpreter.allowed.has_prelude() {
+            interpreter.load_prelude();
+        }
+        interpreter
+    }
+
+    fn with_natives(profile: Profile) -> Self {
+        let heap = gc::OwnedHeap::new();
+        gc::install(&heap);
+        let global = gc::track(Environment::new());
+        natives::define(&mut global.borrow_mut(), &profile);
+        let builtins = global.borrow().values.iter().map(|(name, cell)| (name.clone(), cell.borrow().clone())).collect();
+        let modules = natives::modules(&profile).into_iter().map(|module| (module.name().to_owned(), Rc::new(module))).collect();
 
         Interpreter {
             globals: Rc::clone(&global),
+            heap,
+            builtins,
+            prelude: HashSet::new(),
+            modules,
+            imported: HashSet::new(),
+            files: HashSet::new(),
+            loading: Vec::new(),
+            import_paths: Vec::new(),
             env: Rc::clone(&global),
-            locals: HashMap::new(),
+            method_cache: HashMap::new(),
+            primitive_methods: natives::primitive_methods(),
+            allowed: profile,
+            call_depth: 0,
+            script_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            calls: 0,
+            peak_call_depth: 0,
+            fuel: None,
+            warnings: Warnings::Report,
+            strict: false,
+            timeout: None,
+            deadline: None,
+            interrupt: Arc::new(AtomicBool::new(false)),
+            memory_limit: None,
+            allocated: 0,
+            environments_at_start: 0,
+            time: Box::new(SystemTime),
+            rng: Rng::from_time(),
+            ticks: 0,
+            output: Box::new(io::BufWriter::new(io::stdout())),
+            line_open: false,
+            trace: None,
+            profiler: None,
+            coverage: None,
+            debugger: None,
+            stepping: false,
+            call_stack: Vec::new(),
+            stringifying: Vec::new(),
+            #[cfg(feature = "regex")]
+            regexes: HashMap::new(),
+        }
+    }
+
+    // Makes a class implemented in Rust available to Lox code as a global.
+    pub fn define_native_class(&mut self, class: NativeClass) {
+        let name = class.name().clone();
+        let class = Value::Class(Rc::new(class::LoxClass::native(class)));
+        self.define_builtin(name, class);
+    }
+
+    // Makes a function implemented in Rust available to Lox code as a global.
+    pub fn define_native(&mut self, name: &str, arity: usize, body: loxcallables::NativeBody) {
+        let native = Value::Callable(Rc::new(loxcallables::Native::new(name, arity, body)));
+        self.define_builtin(Symbol::intern(name), native);
+    }
+
+    // Makes `module` available to `import`, replacing any of the same name.
+    pub fn register_module(&mut self, module: NativeModule) {
+        self.modules.insert(module.name().to_owned(), Rc::new(module));
+    }
+
+    // The script about to run, which its imports are found next to. Without
+    // one, as at the prompt, they're found in the working directory.
+    pub fn set_script(&mut self, path: Option<&Path>) {
+        self.loading.clear();
+        if let Some(path) = path {
+            if let Ok(canonical) = path.canonicalize() {
+                self.loading.push((canonical, path.display().to_string()));
+            }
+        }
+    }
+
+    // Adds a directory to look for imports in, after the importing script's
+    // own. Directories are searched in the order they were added.
+    pub fn add_import_path(&mut self, dir: PathBuf) {
+        self.import_paths.push(dir);
+    }
+
+    // The canonical path of the script `name`, so one reached by different
+    // paths is imported once. Relative names are found next to the script
+    // importing them, or else in the import paths.
+    fn find_import(&self, name: &str) -> io::Result<PathBuf> {
+        let beside = match self.loading.last() {
+            Some((script, _)) => script.with_file_name(name),
+            None => PathBuf::from(name),
+        };
+        let found = beside.canonicalize();
+        if found.is_ok() || Path::new(name).is_absolute() {
+            return found;
+        }
+        match self.import_paths.iter().find_map(|dir| dir.join(name).canonicalize().ok()) {
+            Some(path) => Ok(path),
+            None => found,
+        }
+    }
+
+    // Runs the script `name` in the globals, the first time only. Errors in
+    // it are reported with its name, and then fail the import. Importing a
+    // script whose top-level code is still running is an error rather than
+    // giving it half defined.
+    fn import_file(&mut self, keyword: &Token, name: &str) -> Result<(), RuntimeException> {
+        if !self.allowed.allows(Group::Io) {
+            return Err(gen_err(keyword, &format!("Can't import '{}' without file access.", name)));
+        }
+        let read_error = |err: io::Error| gen_err(keyword, &format!("Could not read '{}': {}.", name, lox::read_failure(&err)));
+        let path = self.find_import(name).map_err(read_error)?;
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == path) {
+            let mut chain: Vec<&str> = self.loading[start..].iter().map(|(_, name)| name.as_str()).collect();
+            chain.push(name);
+            return Err(gen_err(keyword, &format!("Circular import detected: {}.", chain.join(" -> "))));
+        }
+        if !self.files.insert(path.clone()) {
+            return Ok(());
+        }
+        let source = fs::read_to_string(&path).map_err(read_error)?;
+
+        self.loading.push((path, name.to_owned()));
+        let previous = loxerr::set_file(Some(name.to_owned()));
+        // What was printed so far goes out before anything it reports.
+        let _ = self.flush();
+        let failed = match lox::prepare(self, &source, &mut Timings::default()) {
+            Ok(stmts) => {
+                let globals = Rc::clone(&self.globals);
+                let env = std::mem::replace(&mut self.env, globals);
+                let script_depth = std::mem::replace(&mut self.script_depth, self.call_depth);
+                let result = stmts.iter().try_for_each(|stmt| self.execute(stmt));
+                self.script_depth = script_depth;
+                self.env = env;
+                match result {
+                    // Returning from its top level just ends the script.
+                    Ok(()) | Err(RuntimeException::Exit(..)) => false,
+                    Err(e @ RuntimeException::RuntimeError { .. }) => {
+                        let _ = self.flush();
+                        e.error();
+                        true
+                    }
+                    Err(e) => {
+                        loxerr::set_file(previous);
+                        self.loading.pop();
+                        return Err(e);
+                    }
+                }
+            }
+            Err(_) => true,
+        };
+        loxerr::set_file(previous);
+        self.loading.pop();
+        if failed {
+            return Err(gen_err(keyword, &format!("Could not import '{}'.", name)));
+        }
+        Ok(())
+    }
+
+    // Defines the module's natives as globals, the first time only.
+    fn import(&mut self, keyword: &Token, name: &str) -> Result<(), RuntimeException> {
+        if self.imported.contains(name) {
+            return Ok(());
+        }
+        let module = match self.modules.get(name) {
+            Some(module) => Rc::clone(module),
+            None => {
+                let mut available: Vec<_> = self.modules.keys().map(|name| format!("'{}'", name)).collect();
+                available.sort();
+                let msg = if available.is_empty() {
+                    format!("Unknown module '{}'. No modules are available.", name)
+                } else {
+                    format!("Unknown module '{}'. Available modules: {}.", name, available.join(", "))
+                };
+                return Err(gen_err(keyword, &msg));
+            }
+        };
+        for (name, value) in module.definitions() {
+            self.globals.borrow_mut().define(name, value.clone());
+        }
+        self.imported.insert(name.to_owned());
+        Ok(())
+    }
+
+    // Runs the prelude in the globals and keeps what it defines across
+    // `reset`. It should never fail, so a debug build panics if it does.
+    fn load_prelude(&mut self) {
+        let previous = loxerr::set_file(Some("prelude".to_owned()));
+        let loaded = match lox::prepare(self, PRELUDE, &mut Timings::default()) {
+            Ok(stmts) => match stmts.iter().try_for_each(|stmt| self.execute(stmt)) {
+                Ok(()) => true,
+                Err(e) => {
+                    e.error();
+                    false
+                }
+            },
+            Err(_) => false,
+        };
+        loxerr::set_file(previous);
+        debug_assert!(loaded, "The prelude failed to load.");
+
+        let globals: Vec<_> = self.globals.borrow().values.iter().map(|(name, cell)| (name.clone(), cell.borrow().clone())).collect();
+        for (name, value) in globals {
+            if self.builtins.contains_key(&name) {
+                continue;
+            }
+            if let Value::Callable(callee) = &value {
+                self.prelude.insert(address(&**callee));
+            }
+            self.builtins.insert(name, value);
+        }
+    }
+
+    fn define_builtin(&mut self, name: Symbol, value: Value) {
+        self.globals.borrow_mut().define(&name, value.clone());
+        self.builtins.insert(name, value);
+    }
+
+    // Forgets everything Lox code defined, so the next run starts as in a
+    // new interpreter. The natives, the prelude and what the host defined
+    // stay, as do settings such as the output, fuel and timeout.
+    pub fn reset(&mut self) {
+        {
+            // The builtins keep their cells, which the prelude may be using.
+            let mut globals = self.globals.borrow_mut();
+            globals.values.retain(|name, _| self.builtins.contains_key(name));
+            for (name, value) in &self.builtins {
+                globals.define(name, value.clone());
+            }
+            globals.slots.clear();
+        }
+        self.method_cache.clear();
+        self.imported.clear();
+        self.files.clear();
+        self.env = Rc::clone(&self.globals);
+        self.call_depth = 0;
+        self.script_depth = 0;
+        self.calls = 0;
+        self.peak_call_depth = 0;
+        self.stepping = false;
+        self.call_stack.clear();
+        self.stringifying.clear();
+    }
+
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    pub fn set_stack_limit(&mut self, bytes: usize) {
+        self.stack_limit = bytes;
+    }
+
+    pub fn stats(&self) -> Stats {
+        let globals = self.globals.borrow();
+        Stats {
+            environments: self.heap.tracked(),
+            globals: globals.values.keys().filter(|name| !self.builtins.contains_key(*name)).count(),
+            calls: self.calls,
+            peak_call_depth: self.peak_call_depth,
+        }
+    }
+
+    // Bounds the work done by the following runs. Every loop iteration and
+    // every call, tail calls included, uses one unit, and once none is left
+    // execution stops with `RuntimeException::FuelExhausted`.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.fuel = Some(fuel);
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    // Whether the resolver's warnings are reported, reported as errors or
+    // left out. Reported by default, and they don't stop the run.
+    pub fn set_warnings(&mut self, warnings: Warnings) {
+        self.warnings = warnings;
+    }
+
+    pub fn warnings(&self) -> Warnings {
+        self.warnings
+    }
+
+    // Makes the resolver reject assignments to globals nothing declares,
+    // unused locals, shadowing and declaring a global twice. Off by default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    // Where `print` writes to, stdout by default. Stdout is buffered, and
+    // flushed when a run ends or stops with an error, before a debugger
+    // pauses and by the `flush()` native.
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+        self.line_open = false;
+    }
+
+    // Writes out whatever the output is holding, as before exiting.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    // Ends the line `write` left unfinished, if it did, and flushes, so the
+    // prompt starts on a line of its own.
+    pub fn finish_line(&mut self) -> io::Result<()> {
+        if self.line_open {
+            writeln!(self.output)?;
+            self.line_open = false;
+        }
+        self.output.flush()
+    }
+
+    // Logs every statement as it runs and every call with its arguments to
+    // `trace`, indented by call depth. None turns tracing off.
+    pub fn set_trace(&mut self, trace: Option<Output>) {
+        self.trace = trace;
+    }
+
+    pub fn tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    fn trace(&mut self, msg: fmt::Arguments) {
+        if let Some(trace) = &mut self.trace {
+            let indent = "  ".repeat(self.call_depth);
+            writeln!(trace, "{}{}", indent, msg).expect("Could not write trace.");
+        }
+    }
+
+    fn trace_call(&mut self, callee: &Value, args: &[Value]) {
+        if self.trace.is_some() {
+            let args = args.iter().map(Value::to_string).collect::<Vec<_>>().join(", ");
+            self.trace(format_args!("call {}({})", callee, args));
+        }
+    }
+
+    // Starts counting calls and the time spent in every function, method,
+    // class and native, or stops and drops the counts.
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.profiler = if profiling { Some(Profiler::default()) } else { None };
+    }
+
+    pub fn profiling(&self) -> bool {
+        self.profiler.is_some()
+    }
+
+    // What was counted since profiling started, longest total time first.
+    pub fn profile(&self) -> Vec<FunctionProfile> {
+        self.profiler.as_ref().map_or_else(Vec::new, Profiler::functions)
+    }
+
+    // Starts counting how many times the statements on each line run, or
+    // stops and drops the counts.
+    pub fn set_coverage(&mut self, coverage: bool) {
+        self.coverage = if coverage { Some(Hits::new()) } else { None };
+    }
+
+    pub fn coverage(&self) -> Option<&Hits> {
+        self.coverage.as_ref()
+    }
+
+    // Lets `debugger` pause the program at `breakpoint()` calls. Without
+    // one, those do nothing.
+    pub fn set_debugger(&mut self, debugger: Option<Box<dyn Debugger>>) {
+        self.debugger = debugger;
+        self.stepping = false;
+    }
+
+    pub fn debugging(&self) -> bool {
+        self.debugger.is_some()
+    }
+
+    // Makes the debugger, if any, pause before the next statement.
+    pub(crate) fn request_pause(&mut self) {
+        self.stepping = self.debugger.is_some();
+    }
+
+    // Looks `name` up from the innermost scope of the running code outwards.
+    pub fn variable(&self, name: &str) -> Option<Value> {
+        let name = Symbol::intern(name);
+        let mut env = Some(Rc::clone(&self.env));
+        while let Some(current) = env {
+            let current = current.borrow();
+            if let Some((_, value)) = current.slots.iter().rev().find(|(slot, _)| *slot == name) {
+                return Some(value.clone());
+            }
+            if let Some(cell) = current.values.get(&name) {
+                return Some(cell.borrow().clone());
+            }
+            env = current.enclosing.clone();
+        }
+        None
+    }
+
+    // Every variable in scope but the globals, innermost scope first.
+    pub fn local_variables(&self) -> Vec<(Symbol, Value)> {
+        let mut variables = Vec::new();
+        let mut env = Rc::clone(&self.env);
+        loop {
+            let enclosing = match &env.borrow().enclosing {
+                Some(enclosing) => Rc::clone(enclosing),
+                None => break,
+            };
+            for (name, value) in env.borrow().slots.iter().rev() {
+                variables.push((name.clone(), value.clone()));
+            }
+            env = enclosing;
+        }
+        variables
+    }
+
+    // The calls in progress, outermost first.
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.call_stack
+    }
+
+    pub(crate) fn profile_enter(&mut self, name: &Symbol, line: usize) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(name, line);
+        }
+    }
+
+    pub(crate) fn profile_exit(&mut self, name: &Symbol, line: usize) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit(name, line);
+        }
+    }
+
+    // Where `clock()` gets the time, the system's by default. Tests and bug
+    // reports use a `MockTime` so that they run the same way every time.
+    pub fn set_time_source(&mut self, time: Box<dyn TimeSource>) {
+        self.time = time;
+    }
+
+    // Where `random()` gets its numbers, seeded from the time by default.
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
+
+    pub(crate) fn now_millis(&mut self) -> f64 {
+        self.time.now_millis()
+    }
+
+    pub(crate) fn random(&mut self) -> f64 {
+        self.rng.next_f64()
+    }
+
+    // Limits how long each following call to `interpret` may run. A run that
+    // takes longer stops with `RuntimeException::TimedOut`.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    // Limits how much each following call to `interpret` may allocate. A run
+    // that allocates more stops with `RuntimeException::LimitExceeded`. The
+    // count is rough: the lengths of new strings, the elements of new arrays,
+    // tuples and maps, new instances and new environments, whether or not
+    // they've been freed since.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
+
+    // About how many bytes the current or last run allocated.
+    pub fn allocated(&self) -> usize {
+        let environments = gc::allocated_environments() - self.environments_at_start;
+        self.allocated + environments as usize * std::mem::size_of::<Environment>()
+    }
+
+    // Counts `bytes` about to be allocated, and fails rather than go past the
+    // memory limit.
+    pub(crate) fn allocate(&mut self, bytes: usize) -> Result<(), RuntimeException> {
+        self.allocated = self.allocated.saturating_add(bytes);
+        match self.memory_limit {
+            Some(limit) if self.allocated() > limit => Err(RuntimeException::LimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    // A flag that stops the run at its next loop iteration or call with
+    // `RuntimeException::Interrupted` once it's set, from another thread or
+    // a signal handler. Stopping clears it again.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupt)
+    }
+
+    // Called on every loop iteration and every call.
+    pub(crate) fn tick(&mut self) -> Result<(), RuntimeException> {
+        if self.interrupt.load(Ordering::Relaxed) {
+            self.interrupt.store(false, Ordering::Relaxed);
+            return Err(RuntimeException::Interrupted);
+        }
+        match &mut self.fuel {
+            Some(0) => return Err(RuntimeException::FuelExhausted),
+            Some(fuel) => *fuel -= 1,
+            None => (),
+        }
+        // Calls allocate environments without going through `allocate`.
+        if matches!(self.memory_limit, Some(limit) if self.allocated() > limit) {
+            return Err(RuntimeException::LimitExceeded);
+        }
+
+        // Reading the clock costs about as much as a loop iteration, so it
+        // only happens every so often.
+        if let Some(deadline) = self.deadline {
+            self.ticks = self.ticks.wrapping_add(1);
+            if self.ticks.is_multiple_of(1024) && Instant::now() >= deadline {
+                return Err(RuntimeException::TimedOut);
+            }
         }
+        Ok(())
+    }
+
+    // Past the call depth limit, or past the stack limit.
+    pub(crate) fn stack_exhausted(&self) -> bool {
+        self.call_depth >= self.max_call_depth || stack::used() >= self.stack_limit
     }
 
-    pub fn resolve(&mut self, expr: &Rc<Expr>, idx: usize) {
-        let expr_ref: *const Expr = &**expr;
-        // println!("Inserting express {:?} @ {:?} @@ {}", expr, expr_ref, idx);
-        self.locals.insert(expr_ref, idx);
+    pub(crate) fn enter_call(&mut self) {
+        self.call_depth += 1;
+        self.calls += 1;
+        self.peak_call_depth = self.peak_call_depth.max(self.call_depth);
     }
 
-    fn lookup_variable(&self, name: &Token, expr: &Rc<Expr>) -> Result<Value, RuntimeException> {
-        let key: *const Expr = &**expr;
+    // Tail calls reuse the caller's depth.
+    pub(crate) fn count_tail_call(&mut self) {
+        self.calls += 1;
+    }
+
+    pub fn resolve(&mut self, id: &ExprId, depth: usize, slot: usize) {
+        id.bind(Binding::Local(depth, slot));
+    }
 
-        if let Some(dist) = self.locals.get(&key) {
-            // println!("Resolving {:?} @ {:?} @@ {}", expr, key, dist);
-            self.env.borrow().get_at(*dist, name)
-        } else {
-            // println!("Resolving {:?} @ {:?} @@ GLOBAL", expr, key);
-            self.globals.borrow().get(name)
+    // Variables the resolver didn't find are globals. The first use of one
+    // looks it up by name and keeps its cell for the next, until `reset`
+    // drops the global.
+    fn global_cell(&self, name: &Token, id: &ExprId) -> Result<Rc<RefCell<Value>>, RuntimeException> {
+        if let Some(cell) = id.binding().and_then(|binding| match binding {
+            Binding::Global(cell) => cell.upgrade(),
+            Binding::Local(..) => None,
+        }) {
+            return Ok(cell);
+        }
+        // Suggest locals in scope too, not just globals.
+        let cell = self.globals.borrow().cell(&name.lexeme).ok_or_else(|| self.env.borrow().undefined(name, &[]))?;
+        id.bind(Binding::Global(Rc::downgrade(&cell)));
+        Ok(cell)
+    }
+
+    fn lookup_variable(&mut self, name: &Token, id: &ExprId) -> Result<Value, RuntimeException> {
+        match id.binding() {
+            Some(Binding::Local(dist, slot)) => Ok(self.env.borrow().get_at(dist, slot)),
+            _ => Ok(self.global_cell(name, id)?.borrow().clone()),
+        }
+    }
+
+    fn call(
+        &mut self,
+        callee: &dyn LoxCallable,
+        paren: &Token,
+        args: &[Value],
+    ) -> Result<Value, RuntimeException> {
+        if args.len() != callee.arity() {
+            return Err(gen_err(
+                paren,
+                &format!(
+                    "Expected {} arguments but got {}.",
+                    callee.arity(),
+                    args.len()
+                ),
+            ));
+        }
+
+        if self.stack_exhausted() {
+            return Err(gen_err(paren, "Stack overflow."));
+        }
+
+        self.tick()?;
+        let debugging = self.debugger.is_some();
+        if debugging {
+            self.call_stack.push(CallFrame { callee: callee.to_string(), line: paren.line });
+        }
+        // The prelude's lines aren't the script's, so they aren't covered and
+        // its errors are reported at the call.
+        let in_prelude = self.prelude.contains(&address(callee));
+        let coverage = if in_prelude { self.coverage.take() } else { None };
+        self.enter_call();
+        let result = match callee.call(self, args) {
+            Err(RuntimeException::NativeError(error)) => Err(gen_err(paren, &error)),
+            Err(RuntimeException::RuntimeError { error, .. }) if in_prelude => Err(gen_err(paren, &error)),
+            result => result,
+        };
+        self.call_depth -= 1;
+        if in_prelude {
+            self.coverage = coverage;
+        }
+        if debugging {
+            self.call_stack.pop();
+        }
+        result
+    }
+
+    pub(crate) fn call_value(
+        &mut self,
+        callee: Value,
+        paren: &Token,
+        args: &[Value],
+    ) -> Result<Value, RuntimeException> {
+        match callee {
+            Value::Callable(callee) => self.call(&*callee, paren, args),
+            Value::Class(callee) => self.call(&*callee, paren, args),
+            // Instances whose class has a `call` method can be called like
+            // functions.
+            Value::Instance(ref instance) => {
+                let method = instance.borrow().class.find_method(&Symbol::intern("call")).cloned();
+                match method {
+                    Some(method) => self.call(&method.bind(callee), paren, args),
+                    None => Err(gen_err(paren, "Can only call functions and classes.")),
+                }
+            }
+            _ => Err(gen_err(paren, "Can only call functions and classes.")),
+        }
+    }
+
+    // Calls from natives back into Lox. Arity is up to the native to check,
+    // and errors raised by the callee come out unchanged.
+    pub(crate) fn call_back(&mut self, callee: &dyn LoxCallable, args: &[Value]) -> Result<Value, RuntimeException> {
+        if self.stack_exhausted() {
+            return Err(RuntimeException::NativeError("Stack overflow.".to_owned()));
+        }
+        self.tick()?;
+        self.enter_call();
+        let result = callee.call(self, args);
+        self.call_depth -= 1;
+        result
+    }
+
+    fn assign(&mut self, id: &ExprId, name: &Token, value: Value) -> Result<(), RuntimeException> {
+        match id.binding() {
+            Some(Binding::Local(dist, slot)) => (*self.env).borrow_mut().assign_at(dist, slot, value),
+            _ => *self.global_cell(name, id)?.borrow_mut() = value,
+        }
+        Ok(())
+    }
+
+    // Fields shadow methods, so they are looked at first every time. A method
+    // is only looked up again when the class of the instance isn't the one
+    // it was last found for.
+    fn get_property(&mut self, id: &ExprId, instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, RuntimeException> {
+        let receiver = instance.borrow();
+        if let Some(value) = receiver.field(&name.lexeme) {
+            return Ok(value);
+        }
+        let methods = &receiver.class.methods;
+        let method = match self.method_cache.get(id) {
+            Some((class, method)) if Rc::ptr_eq(class, methods) => method.clone(),
+            _ => match receiver.class.find_method(&name.lexeme) {
+                Some(method) => {
+                    self.method_cache.insert(id.clone(), (Rc::clone(methods), method.clone()));
+                    method.clone()
+                }
+                // Methods of native classes, or nothing at all.
+                None => {
+                    drop(receiver);
+                    return LoxInstance::get(instance, name);
+                }
+            },
+        };
+        Ok(Value::Callable(Rc::new(method.bind(Value::Instance(Rc::clone(instance))))))
+    }
+
+    // `"hello".length()` and the like, see `natives::primitive_methods`.
+    fn primitive_method(&self, receiver: &Value, name: &Token) -> Result<Value, RuntimeException> {
+        let type_name = receiver.type_name();
+        let methods = match self.primitive_methods.get(type_name) {
+            Some(methods) => methods,
+            None => return Err(gen_err(name, "Only instances have properties.")),
+        };
+        match methods.get(&name.lexeme) {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(receiver.clone())))),
+            None => {
+                let candidates: Vec<Symbol> = methods.keys().cloned().collect();
+                let msg = format!(
+                    "Undefined property '{}' on {}.{}",
+                    name.lexeme,
+                    type_name,
+                    spelling::did_you_mean(&name.lexeme, &candidates)
+                );
+                Err(gen_err(name, &msg))
+            }
+        }
+    }
+
+    // Calls to Lox functions in tail position are made by the enclosing
+    // `Function::call` once the current frame is gone. Everything else,
+    // arity errors included, is called as usual.
+    fn tail_call(
+        &mut self,
+        callee: &Rc<Expr>,
+        paren: &Token,
+        args: &[Rc<Expr>],
+    ) -> Result<Value, RuntimeException> {
+        let callee = self.evaluate(callee)?;
+        let mut args_evaluated = Vec::new();
+        for arg in args {
+            args_evaluated.push(self.evaluate(arg)?);
+        }
+        self.trace_call(&callee, &args_evaluated);
+
+        match callee {
+            Value::Callable(callee)
+                if callee.as_function().is_some()
+                    && callee.arity() == args_evaluated.len()
+                    && !self.prelude.contains(&address(&*callee)) =>
+            {
+                self.tick()?;
+                self.count_tail_call();
+                Err(RuntimeException::TailCall(callee, args_evaluated))
+            }
+            _ => self.call_value(callee, paren, &args_evaluated),
         }
     }
 
@@ -90,18 +1083,114 @@ impl Interpreter {
         match &**expr {
             Expr::Literal(val) => Ok(val.clone()),
             Expr::Grouping(expr) => self.evaluate(expr),
-            Expr::Variable(token) => self.lookup_variable(token, expr),
-            Expr::Assign(token, right_expr) => {
+            Expr::Range(start, op, end) => match (self.evaluate(start)?, self.evaluate(end)?) {
+                (Value::Number(start), Value::Number(end)) => Ok(Value::Range(start, end)),
+                _ => Err(gen_err(op, "Range bounds must be numbers.")),
+            },
+            Expr::Index(object, bracket, index) => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                match object {
+                    Value::Array(elements) => {
+                        let elements = elements.borrow();
+                        match subscript(&index, elements.len(), bracket)? {
+                            Subscript::Element(idx) => Ok(elements[idx].clone()),
+                            Subscript::Slice(start, end) => {
+                                Ok(Value::Array(Rc::new(RefCell::new(elements[start..end].to_vec()))))
+                            }
+                        }
+                    }
+                    Value::Tuple(elements) => match subscript(&index, elements.len(), bracket)? {
+                        Subscript::Element(idx) => Ok(elements[idx].clone()),
+                        Subscript::Slice(start, end) => Ok(Value::Tuple(Rc::new(elements[start..end].to_vec()))),
+                    },
+                    // Strings are indexed by character, not by byte.
+                    Value::String(s) => match subscript(&index, s.chars().count(), bracket)? {
+                        Subscript::Element(idx) => Ok(Value::String(s.chars().nth(idx).unwrap().to_string().into())),
+                        Subscript::Slice(start, end) => Ok(Value::String(s.chars().skip(start).take(end - start).collect::<std::string::String>().into())),
+                    },
+                    Value::Map(map) => {
+                        let key = map_key(&index, bracket)?;
+                        match map.borrow().get(&key) {
+                            Some(value) => Ok(value.clone()),
+                            None => Err(gen_err(bracket, &format!("Key '{}' is not in the map.", index))),
+                        }
+                    }
+                    _ => Err(gen_err(bracket, "Only arrays, tuples, strings and maps can be indexed.")),
+                }
+            }
+            Expr::SetIndex(object, bracket, index, value) => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let value = self.evaluate(value)?;
+                match object {
+                    Value::Array(elements) => {
+                        let mut elements = elements.borrow_mut();
+                        let idx = element_index(&index, elements.len(), bracket)?;
+                        elements[idx] = value.clone();
+                        Ok(value)
+                    }
+                    Value::Map(map) => {
+                        let key = map_key(&index, bracket)?;
+                        self.allocate(2 * VALUE_SIZE)?;
+                        map.borrow_mut().insert(key, index, value.clone());
+                        Ok(value)
+                    }
+                    Value::Tuple(_) => Err(gen_err(bracket, "Tuples are immutable.")),
+                    Value::String(_) => Err(gen_err(bracket, "Strings are immutable.")),
+                    _ => Err(gen_err(bracket, "Only arrays and maps can be assigned by index.")),
+                }
+            }
+            Expr::Tuple(elements) => {
+                self.allocate(elements.len() * VALUE_SIZE)?;
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                Ok(Value::Tuple(Rc::new(values)))
+            }
+            Expr::Is(value, keyword, class) => {
+                let value = self.evaluate(value)?;
+                let class = self.evaluate(class)?;
+                let instance = match &value {
+                    Value::Instance(instance) => Some(instance.borrow()),
+                    _ => None,
+                };
+                match class {
+                    Value::Class(class) => Ok(Bool(instance.is_some_and(|instance| instance.class.is_subclass_of(&class)))),
+                    Value::Interface(interface) => Ok(Bool(instance.is_some_and(|instance| instance.class.implements(&interface)))),
+                    _ => Err(gen_err(keyword, "Right operand of 'is' must be a class or an interface.")),
+                }
+            }
+            Expr::Variable(id, token) => self.lookup_variable(token, id),
+            Expr::Array(elements) => {
+                self.allocate(elements.len() * VALUE_SIZE)?;
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                Ok(Value::Array(Rc::new(RefCell::new(values))))
+            }
+            Expr::Assign(id, token, right_expr) => {
                 let val = self.evaluate(right_expr)?;
-                let key: *const Expr = &**expr;
-                if let Some(dist) = self.locals.get(&key) {
-                    (*self.env)
-                        .borrow_mut()
-                        .assign_at(*dist, &token, val.clone());
-                } else {
-                    (*self.globals).borrow_mut().assign(token, val.clone())?;
+                self.assign(id, token, val.clone())?;
+                Ok(val)
+            }
+            Expr::AssignArray(targets, equals, right_expr) => {
+                let val = self.evaluate(right_expr)?;
+                let names: Vec<&Token> = targets
+                    .iter()
+                    .map(|target| match &**target {
+                        Expr::Variable(_, name) => name,
+                        _ => unreachable!(),
+                    })
+                    .collect();
+                let values = destructure(&val, &names, equals)?;
+                for (target, value) in targets.iter().zip(values) {
+                    if let Expr::Variable(id, name) = &**target {
+                        self.assign(id, name, value)?;
+                    }
                 }
-
                 Ok(val)
             }
             Expr::Call(callee, paren, args) => {
@@ -110,49 +1199,16 @@ impl Interpreter {
                 for arg in args {
                     args_evaluated.push(self.evaluate(arg)?);
                 }
-
-                match callee {
-                    Value::Callable(callee) => {
-                        if args_evaluated.len() != callee.arity() {
-                            Err(gen_err(
-                                paren,
-                                &format!(
-                                    "Expected {} arguments but got {}.",
-                                    callee.arity(),
-                                    args_evaluated.len()
-                                ),
-                            ))
-                        } else {
-                            callee.call(self, &args_evaluated)
-                        }
-                    }
-                    Value::Class(callee) => {
-                        if args_evaluated.len() != callee.arity() {
-                            Err(gen_err(
-                                paren,
-                                &format!(
-                                    "Expected {} arguments but got {}.",
-                                    callee.arity(),
-                                    args_evaluated.len()
-                                ),
-                            ))
-                        } else {
-                            callee.call(self, &args_evaluated)
-                        }
-                    }
-                    _ => Err(gen_err(paren, "Can only call functions and classes.")),
-                }
+                self.trace_call(&callee, &args_evaluated);
+                self.call_value(callee, paren, &args_evaluated)
             }
 
-            Expr::Get(object, field) => {
+            Expr::Get(id, object, field) => {
                 let object = self.evaluate(object)?;
-                if let Value::Instance(ref instance) = object {
-                    Ok(instance.borrow().get(field)?)
-                } else {
-                    Err(RuntimeException::RuntimeError {
-                        token: field.clone(),
-                        error: "Only instances have properties.".to_owned(),
-                    })
+                match object {
+                    Value::Instance(ref instance) => self.get_property(id, instance, field),
+                    Value::Class(ref class) => class.get(field),
+                    _ => self.primitive_method(&object, field),
                 }
             }
 
@@ -162,8 +1218,9 @@ impl Interpreter {
                     Bang => Ok(Bool(!is_truthy(&rhs))),
                     Minus => match rhs {
                         Value::Number(n) => Ok(Value::Number(-n)),
-                        _ => Err(gen_err(op, "Operand must be a number.")),
+                        _ => Err(gen_err(op, &numeric_operand_message(&rhs))),
                     },
+                    Tilde => bitwise_not(op, &rhs),
                     _ => unreachable!(),
                 }
             }
@@ -178,100 +1235,158 @@ impl Interpreter {
 
             Expr::Set(object, name, value) => {
                 let object = self.evaluate(object)?;
-                if let Value::Instance(instance) = object {
-                    let value = self.evaluate(value)?;
-                    (*instance).borrow_mut().set(name, value.clone());
-                    Ok(value)
-                } else {
-                    Err(RuntimeException::RuntimeError {
+                match object {
+                    Value::Instance(instance) => {
+                        let value = self.evaluate(value)?;
+                        (*instance).borrow_mut().set(name, value.clone())?;
+                        Ok(value)
+                    }
+                    Value::Class(class) => {
+                        let value = self.evaluate(value)?;
+                        class.set(name, value.clone());
+                        Ok(value)
+                    }
+                    _ => Err(RuntimeException::RuntimeError {
                         token: name.clone(),
                         error: "Only instances have fields.".to_owned(),
-                    })
+                    }),
                 }
             }
 
-            Expr::Super(keyword, identifier) => {
-                let expr_ref: *const Expr = &**expr;
-                let dist = *self.locals.get(&expr_ref).unwrap();
-                let superclass = self.env.borrow_mut().get_at(dist, keyword)?;
-
-                let dummy_token = Token {
-                    token_type: This,
-                    lexeme: "this".to_owned(),
-                    line: 0,
+            Expr::Super(id, _keyword, identifier) => {
+                let (dist, slot) = match id.binding() {
+                    Some(Binding::Local(dist, slot)) => (dist, slot),
+                    _ => unreachable!("'super' is always a local."),
                 };
-                let this = self.env.borrow().get_at(dist - 1, &dummy_token)?;
+                let superclass = self.env.borrow().get_at(dist, slot);
+                // `this` is the only variable in the scope just inside `super`'s.
+                let this = self.env.borrow().get_at(dist - 1, 0);
 
                 if let Value::Class(superclass) = superclass {
                     let method = superclass.find_method(&identifier.lexeme);
-                    if let None = method {
+                    if method.is_none() {
                         return Err(RuntimeException::RuntimeError {
                             token: identifier.clone(),
-                            error: format!("Undefined property '{}'.", identifier.lexeme),
+                            error: format!(
+                                "Undefined property '{}'.{}",
+                                identifier.lexeme,
+                                spelling::did_you_mean(&identifier.lexeme, &superclass.method_names())
+                            ),
                         });
                     }
                     let method = method.unwrap().bind(this);
-                    return Ok(Value::Callable(Rc::new(method)));
+                    Ok(Value::Callable(Rc::new(method)))
                 } else {
                     unreachable!()
                 }
             }
 
-            Expr::This(token) => self.lookup_variable(token, expr),
+            Expr::This(id, token) => self.lookup_variable(token, id),
 
             Expr::Binary(e1, op, e2) => {
                 let l = self.evaluate(e1)?;
                 let r = self.evaluate(e2)?;
+                if let Value::Instance(_) = l {
+                    if let Some(result) = self.overloaded_operator(&l, op, &r)? {
+                        return Ok(result);
+                    }
+                }
                 match op.token_type {
                     EqualEqual => Ok(Value::Bool(l.eq(&r))),
                     BangEqual => Ok(Value::Bool(!l.eq(&r))),
-                    Greater => match (l, r) {
+                    Greater => match (&l, &r) {
                         (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l > r)),
-                        _ => err_numeric_operand(op),
+                        _ => err_numeric_operand(op, &l, &r),
                     },
-                    GreaterEqual => match (l, r) {
+                    GreaterEqual => match (&l, &r) {
                         (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l >= r)),
-                        _ => err_numeric_operand(op),
+                        _ => err_numeric_operand(op, &l, &r),
                     },
-                    Less => match (l, r) {
+                    Less => match (&l, &r) {
                         (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l < r)),
-                        _ => err_numeric_operand(op),
+                        _ => err_numeric_operand(op, &l, &r),
                     },
-                    LessEqual => match (l, r) {
+                    LessEqual => match (&l, &r) {
                         (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l <= r)),
-                        _ => err_numeric_operand(op),
+                        _ => err_numeric_operand(op, &l, &r),
                     },
-                    Minus => match (l, r) {
+                    Minus => match (&l, &r) {
                         (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l - r)),
-                        _ => err_numeric_operand(op),
+                        _ => err_numeric_operand(op, &l, &r),
                     },
-                    Slash => match (l, r) {
+                    Slash => match (&l, &r) {
                         (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l / r)),
-                        _ => err_numeric_operand(op),
+                        _ => err_numeric_operand(op, &l, &r),
+                    },
+                    // Like `/`, dividing by zero gives infinity or NaN.
+                    Div => match (&l, &r) {
+                        (Value::Number(l), Value::Number(r)) => Ok(Value::Number((l / r).floor())),
+                        _ => err_numeric_operand(op, &l, &r),
                     },
-                    Star => match (l, r) {
+                    Star => match (&l, &r) {
                         (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l * r)),
-                        _ => err_numeric_operand(op),
+                        _ => err_numeric_operand(op, &l, &r),
                     },
-                    Plus => match (l, r) {
+                    Plus => match (&l, &r) {
                         (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
                         (Value::String(ref l), Value::String(ref r)) => {
-                            Ok(Value::String(format!("{}{}", l, r)))
+                            self.allocate(l.len() + r.len())?;
+                            Ok(Value::String(format!("{}{}", l, r).into()))
                         }
-                        _ => err_numstr_operand(op),
+                        _ => err_numstr_operand(op, &l, &r),
                     },
+                    Ampersand | Pipe | Caret | LessLess | GreaterGreater => bitwise(op, &l, &r),
                     _ => unreachable!(),
                 }
             }
         }
     }
 
+    // Instances can overload `+`, `-`, `*`, `==`, `!=` and `<` with methods
+    // named plus, minus, times, equals and less. Both operands are evaluated
+    // first, left to right. Then only the left operand's class is asked, and
+    // the method gets the right operand. Without a method, or when only the
+    // right operand is an instance, the operator works as usual: `==`
+    // compares identity and the others fail on the operand types.
+    // Comparisons give `true` or `false` whatever the method returned, and
+    // `!=` negates `equals`.
+    pub(crate) fn overloaded_operator(
+        &mut self,
+        left: &Value,
+        op: &Token,
+        right: &Value,
+    ) -> Result<Option<Value>, RuntimeException> {
+        let instance = match left {
+            Value::Instance(instance) => instance,
+            _ => return Ok(None),
+        };
+        let name = match op.token_type {
+            Plus => "plus",
+            Minus => "minus",
+            Star => "times",
+            EqualEqual | BangEqual => "equals",
+            Less => "less",
+            _ => return Ok(None),
+        };
+        let method = match instance.borrow().class.find_method(&Symbol::intern(name)) {
+            Some(method) => method.bind(left.clone()),
+            None => return Ok(None),
+        };
+
+        let result = self.call(&method, op, std::slice::from_ref(right))?;
+        Ok(Some(match op.token_type {
+            EqualEqual | Less => Bool(is_truthy(&result)),
+            BangEqual => Bool(!is_truthy(&result)),
+            _ => result,
+        }))
+    }
+
     pub fn execute_block(
         &mut self,
-        stmts: &Vec<Stmt>,
+        stmts: &[Stmt],
         env: Environment,
     ) -> Result<(), RuntimeException> {
-        let new_env = Rc::new(RefCell::new(env));
+        let new_env = gc::track(env);
         let old_env = std::mem::replace(&mut self.env, new_env);
 
         for stmt in stmts {
@@ -284,28 +1399,157 @@ impl Interpreter {
         Ok(())
     }
 
+    fn execute_for(
+        &mut self,
+        initializer: &Option<Box<Stmt>>,
+        condition: &Option<Rc<Expr>>,
+        increment: &Option<Rc<Expr>>,
+        body: &Stmt,
+    ) -> Result<(), RuntimeException> {
+        let declares = matches!(initializer.as_deref(), Some(Stmt::Var(..)) | Some(Stmt::VarArray(..)));
+        if let Some(initializer) = initializer {
+            self.execute(initializer)?;
+        }
+        loop {
+            if let Some(condition) = condition {
+                if !is_truthy(&self.evaluate(condition)?) {
+                    return Ok(());
+                }
+            }
+            self.tick()?;
+            self.execute(body)?;
+            // The increment updates the next iteration's copy, leaving the
+            // variables closures in this one captured as they were.
+            if declares {
+                let next = gc::track(self.env.borrow().copy());
+                self.env = next;
+            }
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+    }
+
+    // Arrays are read an element at a time, so elements pushed while looping
+    // are visited too. Maps give the keys they had when the loop started, in
+    // insertion order, and sets the elements they had.
+    fn execute_for_in(&mut self, keyword: &Token, name: &Token, iterable: Value, body: &Stmt) -> Result<(), RuntimeException> {
+        match iterable {
+            // Past 2^53 adding one no longer changes a number, so the loop
+            // would never end.
+            Value::Range(start, end) => {
+                if start.abs() >= MAX_EXACT_INTEGER {
+                    return Err(gen_err(keyword, "Range start is too large to count from."));
+                }
+                let mut count = 0.0;
+                while start + count < end {
+                    self.iteration(name, Value::Number(start + count), body)?;
+                    count += 1.0;
+                }
+            }
+            Value::Array(elements) => {
+                let mut idx = 0;
+                loop {
+                    let element = match elements.borrow().get(idx) {
+                        Some(element) => element.clone(),
+                        None => break,
+                    };
+                    self.iteration(name, element, body)?;
+                    idx += 1;
+                }
+            }
+            Value::Tuple(elements) => {
+                for element in elements.iter() {
+                    self.iteration(name, element.clone(), body)?;
+                }
+            }
+            Value::Map(map) => {
+                let keys = map.borrow().keys();
+                for key in keys {
+                    self.iteration(name, key, body)?;
+                }
+            }
+            Value::Set(set) => {
+                let elements = set.borrow().elements();
+                for element in elements {
+                    self.iteration(name, element, body)?;
+                }
+            }
+            _ => return Err(gen_err(keyword, "Can only iterate over arrays, tuples, maps, sets and ranges.")),
+        }
+        Ok(())
+    }
+
+    fn iteration(&mut self, name: &Token, value: Value, body: &Stmt) -> Result<(), RuntimeException> {
+        self.tick()?;
+        let mut env = Environment::encloser(&self.env);
+        env.define(&name.lexeme, value);
+        self.execute_block(std::slice::from_ref(body), env)
+    }
+
     fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeException> {
+        if self.trace.is_some() {
+            if let Some(line) = stmt.line() {
+                self.trace(format_args!("[line {}] {}", line, formatter::header(stmt)));
+            }
+        }
+        if let Some(coverage) = &mut self.coverage {
+            if let Some(line) = stmt.line() {
+                *coverage.entry(line).or_insert(0) += 1;
+            }
+        }
+        if self.stepping && stmt.line().is_some() {
+            self.pause(stmt);
+        }
+
         match stmt {
-            Stmt::If(expr, if_part, else_part) => {
+            Stmt::If(_, expr, if_part, else_part) => {
                 if is_truthy(&self.evaluate(expr)?) {
                     self.execute(if_part)?;
                 } else if let Some(else_part) = else_part {
                     self.execute(else_part)?;
                 }
             }
-            Stmt::Expression(e) => {
+            Stmt::Expression(_, e) => {
                 self.evaluate(e)?;
             }
 
-            Stmt::While(condition, body) => {
+            Stmt::While(_, condition, body) => {
                 while is_truthy(&self.evaluate(condition)?) {
+                    self.tick()?;
                     self.execute(body)?;
                 }
             }
 
-            Stmt::Print(e) => {
+            Stmt::For(_, initializer, condition, increment, body) => {
+                let env = gc::track(Environment::encloser(&self.env));
+                let old_env = std::mem::replace(&mut self.env, env);
+                let result = self.execute_for(initializer, condition, increment, body);
+                self.env = old_env;
+                result?;
+            }
+
+            Stmt::ForIn(keyword, name, iterable, body) => {
+                let iterable = self.evaluate(iterable)?;
+                self.execute_for_in(keyword, name, iterable, body)?;
+            }
+
+            Stmt::Print(keyword, e) => {
                 let res = self.evaluate(e)?;
-                println!("{}", res);
+                self.print(&res, keyword)?;
+            }
+
+            // Names of scripts end in `.lox`, anything else is a native module.
+            Stmt::Import(keyword, name) if name.ends_with(".lox") => self.import_file(keyword, name)?,
+            Stmt::Import(keyword, name) => self.import(keyword, name)?,
+
+            Stmt::VarArray(names, value) => {
+                let value = self.evaluate(value)?;
+                let names: Vec<&Token> = names.iter().collect();
+                let values = destructure(&value, &names, names[0])?;
+                for (name, value) in names.iter().zip(values) {
+                    (*self.env).borrow_mut().define(&name.lexeme, value);
+                }
             }
 
             Stmt::Var(token, init) => {
@@ -316,16 +1560,27 @@ impl Interpreter {
                 (*self.env).borrow_mut().define(&token.lexeme, value);
             }
 
+            Stmt::Block(stmts) if !stmt::needs_scope(stmts) => {
+                for stmt in stmts {
+                    self.execute(stmt)?;
+                }
+            }
             Stmt::Block(stmts) => {
                 self.execute_block(stmts, Environment::encloser(&self.env))?;
             }
 
-            Stmt::Class(name, superclass, methods) => {
+            Stmt::Class(name, superclass, mixins, interfaces, methods) => {
                 let mut superclass_t = None;
                 if let Some(superclass) = superclass {
-                    if let Expr::Variable(token) = &**superclass {
+                    if let Expr::Variable(_, token) = &**superclass {
                         let superclass = self.evaluate(superclass)?;
                         if let Value::Class(tmp) = &superclass {
+                            if tmp.is_native() {
+                                return Err(RuntimeException::RuntimeError {
+                                    token: token.clone(),
+                                    error: format!("Cannot inherit from native class '{}'.", tmp.name),
+                                });
+                            }
                             superclass_t = Some(tmp.clone())
                         } else {
                             return Err(RuntimeException::RuntimeError {
@@ -338,37 +1593,85 @@ impl Interpreter {
                     }
                 }
 
-                (*self.env).borrow_mut().define(&name.lexeme, Value::Nil);
+                // Mixed in methods are copied, later mixins replacing earlier
+                // ones. They keep their own closures, so `super` in them is
+                // the mixin's superclass.
+                let mut methods_hm = HashMap::new();
+                for mixin in mixins {
+                    let token = match &**mixin {
+                        Expr::Variable(_, token) => token,
+                        _ => unreachable!(),
+                    };
+                    match self.evaluate(mixin)? {
+                        Value::Class(mixin) if !mixin.is_native() => methods_hm.extend(mixin.all_methods()),
+                        _ => {
+                            return Err(RuntimeException::RuntimeError {
+                                token: token.clone(),
+                                error: "Mixin must be a class.".to_owned(),
+                            })
+                        }
+                    }
+                }
+
+                let mut interfaces_t = Vec::new();
+                for interface in interfaces {
+                    match self.evaluate(interface)? {
+                        Value::Interface(interface) => interfaces_t.push(interface),
+                        _ => {
+                            let token = match &**interface {
+                                Expr::Variable(_, token) => token,
+                                _ => unreachable!(),
+                            };
+                            return Err(gen_err(token, "Can only implement interfaces."));
+                        }
+                    }
+                }
 
                 let mut old_env = None;
                 if let Some(superclass) = &superclass_t {
-                    let new_env = Rc::new(RefCell::new(Environment::encloser(&self.env)));
+                    let new_env = gc::track(Environment::encloser(&self.env));
                     let superclass = Value::Class(Rc::clone(superclass));
-                    (*new_env).borrow_mut().define("super", superclass);
+                    (*new_env).borrow_mut().define(&Symbol::super_(), superclass);
                     old_env = Some(std::mem::replace(&mut self.env, new_env));
                 }
 
-                let mut methods_hm = HashMap::new();
                 for method in methods {
                     if let Stmt::Function(token, _, _) = method {
                         let fun =
-                            loxcallables::Function::new(method, &self.env, token.lexeme == "init");
+                            loxcallables::Function::new(method, &self.env, token.lexeme == Symbol::init());
                         methods_hm.insert(token.lexeme.clone(), fun);
                     }
                 }
                 let methods = Rc::new(methods_hm);
-                let klass = Rc::new(class::LoxClass::new(
-                    name.lexeme.clone(),
+                let mut klass = class::LoxClass::new(
+                    name.lexeme.to_string(),
+                    name.line,
                     superclass_t,
                     &methods,
-                ));
+                );
+                for interface in &interfaces_t {
+                    if let Some(error) = klass.conformance_error(interface) {
+                        return Err(gen_err(name, &error));
+                    }
+                }
+                klass.interfaces = interfaces_t;
+                let klass = Rc::new(klass);
 
-                if let Some(_) = superclass {
+                if superclass.is_some() {
                     if let Some(old_env) = old_env {
                         self.env = old_env;
                     }
                 }
-                (*self.env).borrow_mut().assign(name, Value::Class(klass))?;
+                // Nothing else is declared in this scope while the class is
+                // built, so defining it last keeps it in the slot the resolver
+                // gave it. Methods only look the name up once they run.
+                (*self.env).borrow_mut().define(&name.lexeme, Value::Class(klass));
+            }
+
+            Stmt::Interface(name, methods) => {
+                let methods = methods.iter().map(|(method, params)| (method.lexeme.clone(), params.len())).collect();
+                let interface = LoxInterface::new(name.lexeme.to_string(), methods);
+                (*self.env).borrow_mut().define(&name.lexeme, Value::Interface(Rc::new(interface)));
             }
 
             Stmt::Function(id, _, _) => {
@@ -379,22 +1682,114 @@ impl Interpreter {
                     .define(&id.lexeme, Value::Callable(fun))
             }
             Stmt::Null => (),
-            Stmt::Return(_token, expr) => {
-                let return_value = if let Some(expr) = expr {
-                    self.evaluate(expr)?
-                } else {
-                    Value::Nil
+            Stmt::Return(keyword, expr) => {
+                let top_level = self.call_depth == self.script_depth;
+                let return_value = match expr {
+                    Some(expr) => match &**expr {
+                        Expr::Call(callee, paren, args) if !top_level => self.tail_call(callee, paren, args)?,
+                        _ => self.evaluate(expr)?,
+                    },
+                    None => Value::Nil,
                 };
+                if top_level {
+                    return Err(RuntimeException::Exit(keyword.clone(), return_value));
+                }
                 return Err(RuntimeException::Return(return_value));
             }
         };
         Ok(())
     }
 
-    pub fn interpret(&mut self, stmts: &Vec<Stmt>) -> Result<(), RuntimeException> {
-        for stmt in stmts {
-            self.execute(stmt)?;
+    fn pause(&mut self, stmt: &Stmt) {
+        if let Some(mut debugger) = self.debugger.take() {
+            let _ = self.flush();
+            self.stepping = debugger.pause(self, stmt) == Command::Step;
+            self.debugger = Some(debugger);
+        }
+    }
+
+    // Starts the clock for `set_timeout`.
+    pub(crate) fn start_run(&mut self) {
+        gc::install(&self.heap);
+        // The classes it was filled for may be gone by now, and it shouldn't
+        // keep them alive.
+        self.method_cache.clear();
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.allocated = 0;
+        self.environments_at_start = gc::allocated_environments();
+    }
+
+    // The text `print` shows for a value. An instance whose class has a
+    // `toString()` method shows what that returns, unless it's already
+    // running for the same instance, say because it prints `this`.
+    pub fn stringify(&mut self, value: &Value, token: &Token) -> Result<std::string::String, RuntimeException> {
+        let instance = match value {
+            Value::Instance(instance) => instance.borrow(),
+            _ => return Ok(value.to_string()),
+        };
+        let id = Rc::as_ptr(&instance.fields) as usize;
+        let method = match instance.class.find_method(&Symbol::intern("toString")) {
+            Some(method) if method.arity() == 0 && !self.stringifying.contains(&id) => method.bind(value.clone()),
+            _ => return Ok(value.to_string()),
+        };
+        let class = instance.class.name.clone();
+        drop(instance);
+
+        self.stringifying.push(id);
+        let result = self.call(&method, token, &[]);
+        self.stringifying.pop();
+        match result? {
+            Value::String(s) => Ok(s.to_string()),
+            _ => Err(gen_err(token, &format!("{}.toString() must return a string.", class))),
+        }
+    }
+
+    pub(crate) fn print(&mut self, value: &Value, token: &Token) -> Result<(), RuntimeException> {
+        let text = self.stringify(value, token)?;
+        writeln!(self.output, "{}", text).expect("Could not write output.");
+        self.line_open = false;
+        Ok(())
+    }
+
+    // Like `print`, but without ending the line.
+    pub(crate) fn write(&mut self, value: &Value, token: &Token) -> Result<(), RuntimeException> {
+        let text = self.stringify(value, token)?;
+        write!(self.output, "{}", text).expect("Could not write output.");
+        if !text.is_empty() {
+            self.line_open = !text.ends_with('\n');
         }
         Ok(())
     }
+
+    // Gives back the script's exit status: 0 when it runs to the end, or what
+    // a `return` outside any function gave.
+    // The output is flushed either way, so what was printed shows before any
+    // error is reported.
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<i32, RuntimeException> {
+        let _stack = stack::mark();
+        self.start_run();
+        let result = stmts.iter().try_for_each(|stmt| self.execute(stmt));
+        let _ = self.flush();
+        script_status(result)
+    }
+
+    // Like `interpret`, but when the last statement is an expression, gives
+    // back its value, for the prompt to echo. So does a `return` outside any
+    // function, which ends the line.
+    pub fn interpret_echo(&mut self, stmts: &[Stmt]) -> Result<Option<Value>, RuntimeException> {
+        let _stack = stack::mark();
+        self.start_run();
+        let (last, init) = match stmts.split_last() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+        let result = init.iter().try_for_each(|stmt| self.execute(stmt)).and_then(|()| match last {
+            Stmt::Expression(_, expr) => self.evaluate(expr).map(Some),
+            _ => self.execute(last).map(|_| None),
+        });
+        match result {
+            Err(RuntimeException::Exit(_, value)) => Ok(Some(value)),
+            result => result,
+        }
+    }
 }
@@ -0,0 +1,167 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::expr::Value;
+use crate::interpreter::Interpreter;
+use crate::loxcallables::LoxCallable;
+use crate::loxerr::RuntimeException;
+use crate::map::{Key, LoxMap};
+use crate::shared::{Rc, RefCell};
+use crate::symbol::Symbol;
+
+// Objects become maps with their keys in the order they're written, arrays
+// become arrays, and `null` becomes nil.
+pub fn parse(source: &str) -> Result<Value, String> {
+    serde_json::from_str::<Json>(source)
+        .map(|json| json.0)
+        .map_err(|err| format!("Invalid JSON: {}.", err))
+}
+
+struct Json(Value);
+
+impl<'de> Deserialize<'de> for Json {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(JsonVisitor).map(Json)
+    }
+}
+
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Value, E> {
+        Ok(Value::Number(n as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Value, E> {
+        Ok(Value::Number(n as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<Value, E> {
+        Ok(Value::Number(n))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.into()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(Json(element)) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Value::Array(Rc::new(RefCell::new(elements))))
+    }
+
+    // A key written twice keeps its first place and its last value.
+    fn visit_map<A: MapAccess<'de>>(self, mut entries: A) -> Result<Value, A::Error> {
+        let mut map = LoxMap::new();
+        while let Some((key, Json(value))) = entries.next_entry::<String, Json>()? {
+            let key: Rc<str> = key.into();
+            map.insert(Key::String(Rc::clone(&key)), Value::String(key), value);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+}
+
+// Maps with string keys become objects, arrays and tuples become arrays.
+// Instances are converted through what their `toJson()` method returns.
+pub fn stringify(interpreter: &mut Interpreter, value: &Value) -> Result<String, RuntimeException> {
+    let mut out = String::new();
+    Stringifier { interpreter, open: Vec::new() }.write(value, &mut out)?;
+    Ok(out)
+}
+
+struct Stringifier<'a> {
+    interpreter: &'a mut Interpreter,
+    // The arrays, maps and instances being written, to catch cycles.
+    open: Vec<usize>,
+}
+
+fn error(msg: String) -> RuntimeException {
+    RuntimeException::NativeError(msg)
+}
+
+impl Stringifier<'_> {
+    fn write(&mut self, value: &Value, out: &mut String) -> Result<(), RuntimeException> {
+        match value {
+            Value::Nil => out.push_str("null"),
+            Value::Bool(b) => out.push_str(&b.to_string()),
+            Value::Number(n) if n.is_finite() => out.push_str(&n.to_string()),
+            Value::String(s) => out.push_str(&serde_json::to_string(s).unwrap()),
+            Value::Array(array) => {
+                self.enter(Rc::as_ptr(array) as usize)?;
+                let elements = array.borrow().clone();
+                self.write_array(&elements, out)?;
+                self.open.pop();
+            }
+            Value::Tuple(elements) => self.write_array(elements, out)?,
+            Value::Map(map) => {
+                self.enter(Rc::as_ptr(map) as usize)?;
+                let entries = map.borrow().entries();
+                out.push('{');
+                for (idx, (key, value)) in entries.iter().enumerate() {
+                    let name = match key {
+                        Value::String(name) => name,
+                        _ => return Err(error(format!("Can't convert map key {} to JSON, keys must be strings.", key))),
+                    };
+                    if idx > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&serde_json::to_string(name).unwrap());
+                    out.push(':');
+                    self.write(value, out)?;
+                }
+                out.push('}');
+                self.open.pop();
+            }
+            Value::Instance(instance) => {
+                let id = Rc::as_ptr(&instance.borrow().fields) as usize;
+                let method = match instance.borrow().class.find_method(&Symbol::intern("toJson")) {
+                    Some(method) if method.arity() == 0 => method.bind(value.clone()),
+                    _ => return Err(error(format!("Can't convert {} to JSON.", value))),
+                };
+                self.enter(id)?;
+                let converted = self.interpreter.call_back(&method, &[])?;
+                self.write(&converted, out)?;
+                self.open.pop();
+            }
+            _ => return Err(error(format!("Can't convert {} to JSON.", value))),
+        }
+        Ok(())
+    }
+
+    fn write_array(&mut self, elements: &[Value], out: &mut String) -> Result<(), RuntimeException> {
+        out.push('[');
+        for (idx, element) in elements.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            self.write(element, out)?;
+        }
+        out.push(']');
+        Ok(())
+    }
+
+    fn enter(&mut self, id: usize) -> Result<(), RuntimeException> {
+        if self.open.contains(&id) {
+            return Err(error("Can't convert a value that contains itself to JSON.".to_owned()));
+        }
+        self.open.push(id);
+        Ok(())
+    }
+}
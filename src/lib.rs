@@ -0,0 +1,42 @@
+pub mod analysis;
+pub mod ast_printer;
+pub mod chunk;
+pub mod class;
+pub mod clock;
+pub mod compiler;
+pub mod convert;
+pub mod copy;
+pub mod coverage;
+pub mod debugger;
+pub mod env;
+pub mod expr;
+pub mod formatter;
+pub mod gc;
+pub mod highlight;
+pub mod instance;
+pub mod interface;
+pub mod interpreter;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod lox;
+pub mod loxcallables;
+pub mod loxerr;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+pub mod map;
+pub mod module;
+pub mod natives;
+pub mod parser;
+pub mod profile;
+pub mod repl;
+pub mod resolver;
+pub mod rng;
+pub mod scanner;
+pub mod set;
+pub mod shared;
+pub mod spelling;
+pub mod stack;
+pub mod stmt;
+pub mod symbol;
+pub mod token;
+pub mod vm;
@@ -0,0 +1,315 @@
+//! Running Lox source, from the command line or a host. Hosts that want
+//! everything a run printed and reported back, rather than written to stdout
+//! and stderr, use a `Lox`:
+//!
+//! ```
+//! use rlox::lox::{Lox, RunStatus};
+//!
+//! let mut lox = Lox::new();
+//! let outcome = lox.run_source("var greeting = \"hi\";\nprint greeting;\nprint -greeting;");
+//! assert_eq!(outcome.stdout, "hi\n");
+//! assert_eq!(outcome.status, RunStatus::RuntimeError);
+//! assert_eq!(outcome.diagnostics[0].line, Some(3));
+//! assert_eq!(outcome.diagnostics[0].message, "Operand must be a number, but got string (\"hi\").");
+//!
+//! // Later runs see what earlier ones defined.
+//! assert_eq!(lox.run_source("print greeting + \"!\";").stdout, "hi!\n");
+//! ```
+
+use std::{fmt, io};
+use std::time::{Duration, Instant};
+
+use crate::expr::Value;
+use crate::interpreter::{script_status, Interpreter};
+use crate::loxerr::{self, Diagnostic, LoxError, Phase, RuntimeException};
+use crate::shared::{Rc, RefCell};
+use crate::stmt::Stmt;
+use crate::symbol::Symbol;
+use crate::vm::Vm;
+use crate::{analysis, compiler, parser, scanner, stack};
+
+// What runs a program once it's resolved.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend {
+    #[default]
+    Tree,
+    // Compiles to bytecode first. Programs the compiler doesn't support yet
+    // use the tree-walker, as do runs that are traced, profiled, debugged or
+    // measure coverage.
+    Vm,
+}
+
+// How long each phase of a run took. Phases that never ran, because an
+// earlier one reported errors, stay at zero.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timings {
+    pub scan: Duration,
+    pub parse: Duration,
+    pub resolve: Duration,
+    pub run: Duration,
+    // Not counting the end of file token.
+    pub tokens: usize,
+    pub statements: usize,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.scan + self.parse + self.resolve + self.run
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "scan: {:.1}ms  parse: {:.1}ms  resolve: {:.1}ms  run: {:.1}ms  total: {:.1}ms  tokens: {}  statements: {}",
+            millis(self.scan),
+            millis(self.parse),
+            millis(self.resolve),
+            millis(self.run),
+            millis(self.total()),
+            self.tokens,
+            self.statements
+        )
+    }
+}
+
+// A writer that keeps what is written to it, to be read back after a run.
+#[derive(Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.text().lines().map(str::to_owned).collect()
+    }
+}
+
+impl io::Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Why a script couldn't be read, worded for the user.
+pub fn read_failure(err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::NotFound => "no such file".to_owned(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_owned(),
+        io::ErrorKind::InvalidData => "not valid UTF-8".to_owned(),
+        _ => err.to_string(),
+    }
+}
+
+// Only the tree-walker reports what it runs.
+fn observed(interpreter: &Interpreter) -> bool {
+    interpreter.tracing()
+        || interpreter.profiling()
+        || interpreter.coverage().is_some()
+        || interpreter.debugging()
+}
+
+// Scans and parses `source`, reporting errors to stderr. Returns None if
+// there were any.
+pub fn parse(source: &str) -> Option<Vec<Stmt>> {
+    let mut scanner = scanner::Scanner::new(source);
+    let tokens = scanner.scan_tokens();
+    let mut parser = parser::Parser::new(tokens);
+    let stmts = parser.parse();
+
+    if parser.has_error || scanner.has_error {
+        None
+    } else {
+        Some(stmts)
+    }
+}
+
+// The exit status for how a run ended: the script's own when it succeeded,
+// or else the status for the error it ended with.
+pub fn exit_code(result: &Result<i32, Vec<LoxError>>) -> i32 {
+    match result {
+        Ok(status) => *status,
+        Err(errors) => errors.last().map_or(70, LoxError::exit_code),
+    }
+}
+
+// Scans, parses, resolves and interprets `source`, reporting errors to stderr
+// as it goes. Ok with the script's exit status, see `Interpreter::interpret`,
+// or Err with every error reported, in order, the one that ended the run last.
+// Also gives back how long each phase took.
+pub fn run(interpreter: &mut Interpreter, source: &str) -> (Result<i32, Vec<LoxError>>, Timings) {
+    run_with(interpreter, source, Backend::Tree)
+}
+
+pub fn run_with(interpreter: &mut Interpreter, source: &str, backend: Backend) -> (Result<i32, Vec<LoxError>>, Timings) {
+    let mut timings = Timings::default();
+    let (result, reported) = loxerr::collect(|| execute(interpreter, source, backend, &mut timings));
+    (result.map_err(|_| reported.errors), timings)
+}
+
+// Err with the phase that failed.
+fn execute(interpreter: &mut Interpreter, source: &str, backend: Backend, timings: &mut Timings) -> Result<i32, Phase> {
+    let stmts = prepare(interpreter, source, timings)?;
+
+    let start = Instant::now();
+    let result = match backend {
+        Backend::Vm if !observed(interpreter) => match compiler::compile(&stmts) {
+            Ok(script) => {
+                let _stack = stack::mark();
+                interpreter.start_run();
+                let result = script_status(Vm::new(interpreter).run(&script, &[]).map(|_| ()));
+                let _ = interpreter.flush();
+                result
+            }
+            Err(_) => interpreter.interpret(&stmts),
+        },
+        _ => interpreter.interpret(&stmts),
+    };
+    timings.run = start.elapsed();
+    result.map_err(runtime_failure)
+}
+
+// Runs a line typed at the prompt on the tree-walker. When it ends with an
+// expression, also gives back its value to echo and binds it to the global
+// `_`. After an error `_` keeps the value it had.
+pub fn run_echo(interpreter: &mut Interpreter, source: &str) -> (Result<Option<Value>, Vec<LoxError>>, Timings) {
+    let mut timings = Timings::default();
+    let (result, reported) = loxerr::collect(|| echo(interpreter, source, &mut timings));
+    (result.map_err(|_| reported.errors), timings)
+}
+
+fn echo(interpreter: &mut Interpreter, source: &str, timings: &mut Timings) -> Result<Option<Value>, Phase> {
+    let stmts = prepare(interpreter, source, timings)?;
+    let start = Instant::now();
+    let result = interpreter.interpret_echo(&stmts);
+    timings.run = start.elapsed();
+    let _ = interpreter.finish_line();
+    let value = result.map_err(runtime_failure)?;
+    if let Some(value) = &value {
+        interpreter.globals.borrow_mut().define(&Symbol::intern("_"), value.clone());
+    }
+    Ok(value)
+}
+
+// Reports a runtime error.
+fn runtime_failure(e: RuntimeException) -> Phase {
+    e.error();
+    Phase::Runtime
+}
+
+// How a run ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunStatus {
+    Ok,
+    // It didn't scan, parse or resolve, so it never started.
+    CompileError,
+    RuntimeError,
+    TimedOut,
+    Interrupted,
+    // It returned a status other than 0 from its top level.
+    Exited(i32),
+}
+
+impl RunStatus {
+    // 0, 65, 70, 75 or 130, or the status it returned, as the command line
+    // exits with.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            RunStatus::Ok => 0,
+            RunStatus::Exited(status) => status,
+            RunStatus::CompileError => 65,
+            RunStatus::RuntimeError => 70,
+            RunStatus::TimedOut => 75,
+            RunStatus::Interrupted => 130,
+        }
+    }
+}
+
+// Everything a run printed and reported, warnings included, and how it
+// ended.
+#[derive(Debug)]
+pub struct RunOutcome {
+    pub stdout: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub status: RunStatus,
+    pub timings: Timings,
+}
+
+// An interpreter whose runs give back what they printed and reported
+// instead of writing it out. Its globals last from one run to the next.
+pub struct Lox {
+    interpreter: Interpreter,
+    stdout: Capture,
+    backend: Backend,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Self::with_interpreter(Interpreter::new())
+    }
+
+    // Takes over `interpreter`'s output, keeping its other settings.
+    pub fn with_interpreter(mut interpreter: Interpreter) -> Self {
+        let stdout = Capture::default();
+        interpreter.set_output(Box::new(stdout.clone()));
+        Lox { interpreter, stdout, backend: Backend::Tree }
+    }
+
+    // For settings such as fuel and timeouts. Setting its output stops runs
+    // from capturing what they print.
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    pub fn run_source(&mut self, source: &str) -> RunOutcome {
+        let mut timings = Timings::default();
+        let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+        let (result, reported) =
+            loxerr::collect(|| execute(&mut self.interpreter, source, self.backend, &mut timings));
+        loxerr::set_error_output(previous);
+
+        let status = match result {
+            Ok(0) => RunStatus::Ok,
+            Ok(status) => RunStatus::Exited(status),
+            Err(Phase::Runtime) if reported.errors.last() == Some(&LoxError::TimedOut) => RunStatus::TimedOut,
+            Err(Phase::Runtime) if reported.errors.last() == Some(&LoxError::Interrupted) => RunStatus::Interrupted,
+            Err(Phase::Runtime) => RunStatus::RuntimeError,
+            Err(_) => RunStatus::CompileError,
+        };
+        let stdout = self.stdout.text();
+        self.stdout.0.borrow_mut().clear();
+        RunOutcome { stdout, diagnostics: reported.diagnostics, status, timings }
+    }
+}
+
+// Scans, parses and resolves `source`. Err with the phase that reported
+// errors, if any did.
+pub(crate) fn prepare(interpreter: &mut Interpreter, source: &str, timings: &mut Timings) -> Result<Vec<Stmt>, Phase> {
+    let front_end = analysis::front_end(interpreter, source, timings, false);
+    match front_end.failed {
+        Some(phase) => Err(phase),
+        None => Ok(front_end.stmts),
+    }
+}
@@ -1,35 +1,101 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
 use crate::env::Environment;
 use crate::expr::Value;
+use crate::gc;
 use crate::interpreter::Interpreter;
 use crate::loxerr::RuntimeException;
+use crate::shared::{Rc, RefCell, Shareable};
 use crate::stmt::Stmt;
+use crate::symbol::Symbol;
 use crate::token::Token;
+use crate::vm::VmFunction;
 
-pub trait LoxCallable: std::fmt::Display {
+pub trait LoxCallable: std::fmt::Display + Shareable {
     fn arity(&self) -> usize;
     fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException>;
+
+    // The environment captured by the callable, if any. Used by the cycle
+    // collector to follow references.
+    fn closure(&self) -> Option<&Rc<RefCell<Environment>>> {
+        None
+    }
+
+    // Lox functions can be tail called, see `RuntimeException::TailCall`.
+    fn as_function(&self) -> Option<&Function> {
+        None
+    }
+
+    // Functions compiled to bytecode get a frame on the VM when the VM calls
+    // them.
+    fn as_vm_function(&self) -> Option<&VmFunction> {
+        None
+    }
 }
 
+pub type NativeBody = fn(&mut Interpreter, &[Value]) -> Result<Value, RuntimeException>;
+
 #[derive(Clone)]
 pub struct Native {
+    name: Symbol,
     arity: usize,
-    body: fn(&[Value]) -> Result<Value, RuntimeException>,
+    body: NativeBody,
 }
 
 impl Native{
-    pub fn new(arity: usize, body: fn(&[Value])->Result<Value, RuntimeException>) -> Self {
-        Native { arity, body: body}
+    pub fn new(name: &str, arity: usize, body: NativeBody) -> Self {
+        Native { name: Symbol::intern(name), arity, body}
+    }
+}
+
+pub type PrimitiveBody = fn(&mut Interpreter, &Value, &[Value]) -> Result<Value, RuntimeException>;
+
+// A method of strings, numbers or arrays, see `natives::primitive_methods`.
+// Its body gets the value it was called on along with the arguments.
+#[derive(Clone)]
+pub struct PrimitiveMethod {
+    name: Symbol,
+    arity: usize,
+    body: PrimitiveBody,
+}
+
+impl PrimitiveMethod {
+    pub fn new(name: &str, arity: usize, body: PrimitiveBody) -> Self {
+        PrimitiveMethod { name: Symbol::intern(name), arity, body }
+    }
+
+    pub fn bind(&self, receiver: Value) -> BoundPrimitiveMethod {
+        BoundPrimitiveMethod { method: self.clone(), receiver }
+    }
+}
+
+pub struct BoundPrimitiveMethod {
+    method: PrimitiveMethod,
+    receiver: Value,
+}
+
+impl LoxCallable for BoundPrimitiveMethod {
+    fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
+        interpreter.profile_enter(&self.method.name, 0);
+        let result = (self.method.body)(interpreter, &self.receiver, args);
+        interpreter.profile_exit(&self.method.name, 0);
+        result
+    }
+
+    fn arity(&self) -> usize {
+        self.method.arity
+    }
+}
+
+impl std::fmt::Display for BoundPrimitiveMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.method.name)
     }
 }
 
 #[derive(Clone)]
 pub struct Function {
     id: Token,
-    params: Vec<Token>,
-    body: Vec<Stmt>,
+    params: Rc<Vec<Token>>,
+    body: Rc<Vec<Stmt>>,
     closure: Rc<RefCell<Environment>>,
     is_init: bool,
 }
@@ -40,8 +106,8 @@ impl Function {
             Stmt::Function(id, params, body) => {
                 Function {
                     id: id.clone(), 
-                    params: params.clone(), 
-                    body: body.clone(),
+                    params: Rc::clone(params),
+                    body: Rc::clone(body),
                     closure: Rc::clone(closure),
                     is_init
                 }
@@ -52,27 +118,25 @@ impl Function {
 
     pub fn bind(&self, instance: Value) -> Function {
         let mut env = Environment::encloser(&self.closure);
-        env.define(&"this", instance);
+        env.define(&Symbol::this(), instance);
         
         let mut fun = self.clone();
-        fun.closure = Rc::new(RefCell::new(env));
+        fun.closure = gc::track(env);
         fun
     }
-}
 
-
-impl LoxCallable for Native {
-    fn call(&self, _interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
-        (self.body)(args)
-    }
-
-    fn arity(&self) -> usize {
-        self.arity
+    // Every tail call counts as a call of its own, though it runs in the
+    // frame of the call it replaced.
+    fn profiled_invoke(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
+        interpreter.profile_enter(&self.id.lexeme, self.id.line);
+        let result = self.invoke(interpreter, args);
+        interpreter.profile_exit(&self.id.lexeme, self.id.line);
+        result
     }
-}
 
-impl LoxCallable for Function {
-    fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
+    // Runs the body once. A tail call at the end is handed back to the caller
+    // instead of being made from here.
+    fn invoke(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
         let mut env = Environment::encloser(&self.closure);
         for (i, param) in self.params.iter().enumerate() {
             env.define(&param.lexeme, args[i].clone())
@@ -80,17 +144,11 @@ impl LoxCallable for Function {
 
         let result = interpreter.execute_block(&self.body, env);
 
-        let dummy_token = Token{
-            token_type: crate::token::TokenType::This, 
-            lexeme: "this".to_owned(),
-            line: 0
-        };
-
         match result {
             Ok(()) => (),
             Err(RuntimeException::Return(value)) =>  {
                 if self.is_init {
-                    return self.closure.borrow().get_at(0, &dummy_token)
+                    return Ok(self.closure.borrow().get_at(0, 0))
                 } else {
                     return Ok(value)
                 }
@@ -100,16 +158,50 @@ impl LoxCallable for Function {
         }
         
         if self.is_init {
-            self.closure.borrow().get_at(0, &dummy_token)
+            Ok(self.closure.borrow().get_at(0, 0))
         } else {
             Ok(Value::Nil)
         }
-        
+    }
+}
+
+
+impl LoxCallable for Native {
+    fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
+        interpreter.profile_enter(&self.name, 0);
+        let result = (self.body)(interpreter, args);
+        interpreter.profile_exit(&self.name, 0);
+        result
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+}
+
+impl LoxCallable for Function {
+    fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
+        let mut result = self.profiled_invoke(interpreter, args);
+        while let Err(RuntimeException::TailCall(callee, args)) = result {
+            result = match callee.as_function() {
+                Some(fun) => fun.profiled_invoke(interpreter, &args),
+                None => callee.call(interpreter, &args),
+            };
+        }
+        result
     }
 
     fn arity(&self) -> usize {
         self.params.len()
     }
+
+    fn closure(&self) -> Option<&Rc<RefCell<Environment>>> {
+        Some(&self.closure)
+    }
+
+    fn as_function(&self) -> Option<&Function> {
+        Some(self)
+    }
 }
 
 impl std::fmt::Display for Native {
@@ -1,12 +1,192 @@
-use crate::token::{Token, TokenType};
+use crate::token::{Span, Token, TokenType};
 use crate::expr::Value;
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
 
-fn report(line: usize, wher: &str, msg: &str) {
-    eprintln!("[line {}] Error{}: {}", line, wher, msg);
+use serde::Serialize;
+
+use crate::loxcallables::LoxCallable;
+use crate::shared::Rc;
+
+thread_local! {
+    static ERROR_OUTPUT: RefCell<Option<Box<dyn Write>>> = const { RefCell::new(None) };
+    static FORMAT: Cell<DiagnosticFormat> = const { Cell::new(DiagnosticFormat::Human) };
+    static FILE: RefCell<Option<String>> = const { RefCell::new(None) };
+    static COLLECTED: RefCell<Option<Reported>> = const { RefCell::new(None) };
 }
 
-pub fn error(line: usize, msg: &str) {
-    report(line, "", msg);
+// Sends every error reported on this thread to `output` instead of stderr.
+// Returns the previous sink, if any.
+pub fn set_error_output(output: Option<Box<dyn Write>>) -> Option<Box<dyn Write>> {
+    ERROR_OUTPUT.with(|sink| sink.replace(output))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DiagnosticFormat {
+    // `[line 1] Error at 'x': message`, as jlox reports them.
+    #[default]
+    Human,
+    // One `Diagnostic` per line as a JSON object, for tools.
+    Json,
+}
+
+// How errors reported on this thread are written.
+pub fn set_diagnostic_format(format: DiagnosticFormat) {
+    FORMAT.with(|current| current.set(format));
+}
+
+// Names the file that errors reported on this thread are in, such as an
+// imported script. None, the default, for the script being run. Returns the
+// previous name.
+pub fn set_file(file: Option<String>) -> Option<String> {
+    FILE.with(|current| current.replace(file))
+}
+
+// Everything reported while collecting: every diagnostic, and the errors
+// among them as a host sees them.
+#[derive(Default)]
+pub(crate) struct Reported {
+    pub diagnostics: Vec<Diagnostic>,
+    pub errors: Vec<LoxError>,
+}
+
+// Runs `f` and gives back what it returned along with what was reported on
+// this thread meanwhile, which is still written out as usual.
+pub(crate) fn collect<T>(f: impl FnOnce() -> T) -> (T, Reported) {
+    let outer = COLLECTED.with(|collected| collected.replace(Some(Reported::default())));
+    let result = f();
+    let reported = COLLECTED.with(|collected| collected.replace(outer)).unwrap_or_default();
+    COLLECTED.with(|collected| {
+        if let Some(outer) = &mut *collected.borrow_mut() {
+            outer.diagnostics.extend(reported.diagnostics.iter().cloned());
+            outer.errors.extend(reported.errors.iter().cloned());
+        }
+    });
+    (result, reported)
+}
+
+fn collect_error(error: LoxError) {
+    COLLECTED.with(|collected| {
+        if let Some(collected) = &mut *collected.borrow_mut() {
+            collected.errors.push(error);
+        }
+    });
+}
+
+fn emit(msg: std::fmt::Arguments) {
+    ERROR_OUTPUT.with(|sink| match &mut *sink.borrow_mut() {
+        Some(output) => writeln!(output, "{}", msg).expect("Could not write error."),
+        None => writeln!(io::stderr(), "{}", msg).expect("Could not write error."),
+    });
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    // Reported, but doesn't stop the program from running.
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Scan,
+    Parse,
+    Resolve,
+    Runtime,
+}
+
+// What the JSON format writes for each error or warning. Tools depend on
+// its shape, see tests/snapshots/diagnostics.jsonl.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub phase: Phase,
+    // Only for errors in imported scripts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    // The bytes of the source the diagnostic is about, when it's about one
+    // place in it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    pub lexeme: Option<String>,
+    pub message: String,
+}
+
+// How the human format writes it: `[line 1] Error at 'x': message`, as jlox
+// does, and runtime errors with their line after the message. Lines in
+// imported files read `line 3 in lib.lox`.
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let at_line = match (self.line, &self.file) {
+            (Some(line), Some(file)) => format!("line {} in {}", line, file),
+            (Some(line), None) => format!("line {}", line),
+            (None, _) => String::new(),
+        };
+        match (self.phase, &self.lexeme) {
+            (Phase::Runtime, _) if self.line.is_none() => write!(f, "{}", self.message),
+            (Phase::Runtime, _) => write!(f, "{}\n[{}]", self.message, at_line),
+            (Phase::Scan, _) => write!(f, "[{}] {}: {}", at_line, self.severity, self.message),
+            (_, Some(lexeme)) => write!(f, "[{}] {} at '{}': {}", at_line, self.severity, lexeme, self.message),
+            (_, None) => write!(f, "[{}] {} at end: {}", at_line, self.severity, self.message),
+        }
+    }
+}
+
+// Writes `diagnostic` in the current format.
+fn report(mut diagnostic: Diagnostic) {
+    diagnostic.file = FILE.with(|file| file.borrow().clone());
+    COLLECTED.with(|collected| {
+        if let Some(collected) = &mut *collected.borrow_mut() {
+            collected.diagnostics.push(diagnostic.clone());
+        }
+    });
+    match FORMAT.with(|format| format.get()) {
+        DiagnosticFormat::Human => emit(format_args!("{}", diagnostic)),
+        DiagnosticFormat::Json => {
+            let json = serde_json::to_string(&diagnostic).expect("Could not serialize a diagnostic.");
+            emit(format_args!("{}", json));
+        }
+    }
+}
+
+// What happens to warnings, see `Interpreter::set_warnings`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Warnings {
+    #[default]
+    Report,
+    // Reported as errors, so the program doesn't run (`--werror`).
+    Deny,
+    // Not reported at all (`--no-warnings`).
+    Ignore,
+}
+
+// A scanning error, which has no token to point at.
+pub fn error(line: usize, span: Span, msg: &str) {
+    let diagnostic = Diagnostic {
+        severity: Severity::Error,
+        phase: Phase::Scan,
+        file: None,
+        line: Some(line),
+        column: None,
+        span: Some(span),
+        lexeme: None,
+        message: msg.to_owned(),
+    };
+    report(diagnostic);
+    collect_error(LoxError::ScanError { line, message: msg.to_owned() });
 }
 
 pub struct ParseError;
@@ -17,24 +197,156 @@ pub enum RuntimeException {
         error: String,
     },
     Return(Value),
+    // A `return` outside any function, which ends the script it's in.
+    Exit(Token, Value),
+    // A `return` whose value is a call to a Lox function. The calling
+    // function's frame unwinds first and then makes the call itself, so tail
+    // calls run in constant Rust stack.
+    TailCall(Rc<dyn LoxCallable>, Vec<Value>),
+    // The interpreter used up the fuel it was given with `set_fuel`.
+    FuelExhausted,
+    // The run went past the interpreter's timeout.
+    TimedOut,
+    // The run allocated more than the interpreter's memory limit.
+    LimitExceeded,
+    // The host raised the interpreter's interrupt flag, as Ctrl-C does.
+    Interrupted,
+    // An error raised by a native, which doesn't know where it was called
+    // from. The call turns it into a `RuntimeError` at its parenthesis.
+    NativeError(String),
 }
 
 pub fn parse_error(token: &Token, msg: &str) {
-    if matches!(token.token_type, TokenType::Eof) {
-        report(token.line, " at end", msg);
-    } else {
-        report(token.line, &format!(" at '{}'", token.lexeme), msg);
+    diagnostic(Severity::Error, Phase::Parse, token, msg);
+}
+
+pub fn resolve_error(token: &Token, msg: &str) {
+    diagnostic(Severity::Error, Phase::Resolve, token, msg);
+}
+
+pub fn diagnostic(severity: Severity, phase: Phase, token: &Token, msg: &str) {
+    let at_end = matches!(token.token_type, TokenType::Eof);
+    let diagnostic = Diagnostic {
+        severity,
+        phase,
+        file: None,
+        line: Some(token.line),
+        column: None,
+        span: Some(token.span),
+        lexeme: if at_end { None } else { Some(token.lexeme.to_string()) },
+        message: msg.to_owned(),
+    };
+    let lexeme = diagnostic.lexeme.clone();
+    report(diagnostic);
+    let (line, message) = (token.line, msg.to_owned());
+    match (severity, phase) {
+        (Severity::Warning, _) => (),
+        (_, Phase::Scan) => collect_error(LoxError::ScanError { line, message }),
+        (_, Phase::Parse) => collect_error(LoxError::ParseError { line, lexeme, message }),
+        (_, Phase::Resolve) => collect_error(LoxError::ResolveError { line, lexeme, message }),
+        (_, Phase::Runtime) => collect_error(LoxError::RuntimeError { line: Some(line), message }),
     }
 }
 
 impl RuntimeException {
     pub fn error(&self) {
+        let mut diagnostic = Diagnostic {
+            severity: Severity::Error,
+            phase: Phase::Runtime,
+            file: None,
+            line: None,
+            column: None,
+            span: None,
+            lexeme: None,
+            message: String::new(),
+        };
         match &self {
             RuntimeException::RuntimeError { token, error } => {
-                eprintln!("{}\n[line {}]", error, token.line);
+                diagnostic.line = Some(token.line);
+                // The VM doesn't keep the operator's token, only its line.
+                if token.token_type != TokenType::Eof {
+                    diagnostic.span = Some(token.span);
+                    diagnostic.lexeme = Some(token.lexeme.to_string());
+                }
+                diagnostic.message = error.clone();
             }
+            RuntimeException::FuelExhausted => diagnostic.message = "Fuel exhausted.".to_owned(),
+            RuntimeException::TimedOut => diagnostic.message = "Execution timed out.".to_owned(),
+            RuntimeException::LimitExceeded => diagnostic.message = "Memory limit exceeded.".to_owned(),
+            RuntimeException::Interrupted => diagnostic.message = "Interrupted.".to_owned(),
             _ => unreachable!()
         }
-       
+        report(diagnostic);
+        collect_error(LoxError::from(self));
+    }
+}
+
+// An error from any phase, as hosts see it. Only errors that end a run come
+// out of the interpreter, never the exceptions it uses to return from
+// functions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoxError {
+    ScanError { line: usize, message: String },
+    // No lexeme when the error is at the end of the source.
+    ParseError { line: usize, lexeme: Option<String>, message: String },
+    ResolveError { line: usize, lexeme: Option<String>, message: String },
+    // No line when the error isn't at any one place, as when fuel runs out.
+    RuntimeError { line: Option<usize>, message: String },
+    TimedOut,
+    Interrupted,
+}
+
+impl LoxError {
+    // The exit status for a run that ended with this error: 65 for static
+    // errors, 70 for runtime errors, 75 when the run timed out and 130, as
+    // for SIGINT, when it was interrupted.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoxError::ScanError { .. } | LoxError::ParseError { .. } | LoxError::ResolveError { .. } => 65,
+            LoxError::RuntimeError { .. } => 70,
+            LoxError::TimedOut => 75,
+            LoxError::Interrupted => 130,
+        }
+    }
+}
+
+// As the human format reports it, leaving out the file.
+impl std::fmt::Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoxError::ScanError { line, message } => write!(f, "[line {}] Error: {}", line, message),
+            LoxError::ParseError { line, lexeme, message } | LoxError::ResolveError { line, lexeme, message } => {
+                match lexeme {
+                    Some(lexeme) => write!(f, "[line {}] Error at '{}': {}", line, lexeme, message),
+                    None => write!(f, "[line {}] Error at end: {}", line, message),
+                }
+            }
+            LoxError::RuntimeError { line: Some(line), message } => write!(f, "{}\n[line {}]", message, line),
+            LoxError::RuntimeError { line: None, message } => write!(f, "{}", message),
+            LoxError::TimedOut => write!(f, "Execution timed out."),
+            LoxError::Interrupted => write!(f, "Interrupted."),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+impl From<&RuntimeException> for LoxError {
+    fn from(e: &RuntimeException) -> Self {
+        match e {
+            RuntimeException::RuntimeError { token, error } => {
+                LoxError::RuntimeError { line: Some(token.line), message: error.clone() }
+            }
+            RuntimeException::NativeError(error) => LoxError::RuntimeError { line: None, message: error.clone() },
+            RuntimeException::FuelExhausted => LoxError::RuntimeError { line: None, message: "Fuel exhausted.".to_owned() },
+            RuntimeException::TimedOut => LoxError::TimedOut,
+            RuntimeException::Interrupted => LoxError::Interrupted,
+            RuntimeException::LimitExceeded => {
+                LoxError::RuntimeError { line: None, message: "Memory limit exceeded.".to_owned() }
+            }
+            RuntimeException::Return(_) | RuntimeException::TailCall(..) | RuntimeException::Exit(..) => {
+                unreachable!("Returns don't escape the function they return from.")
+            }
+        }
     }
 }
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::analysis::{self, Analysis, Declaration, DeclarationKind};
+use crate::loxerr::{Diagnostic, Severity};
+use crate::token::Span;
+
+// A language server speaking JSON-RPC over `input` and `output`, as
+// `rlox --lsp` does over stdin and stdout. It publishes diagnostics for every
+// open document as it changes, and answers requests for its symbols and for
+// where its variables are declared. Returns when the client says `exit` or
+// closes `input`.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut server = Server { documents: HashMap::new() };
+    while let Some(message) = read_message(&mut input)? {
+        let message = match serde_json::from_slice::<Value>(&message) {
+            Ok(message) => message,
+            Err(_) => {
+                write_message(&mut output, &error_response(Value::Null, PARSE_ERROR, "Invalid JSON."))?;
+                continue;
+            }
+        };
+        if message["method"] == "exit" {
+            break;
+        }
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+    Ok(())
+}
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// The body of the next message, or None once `input` is closed. Messages are
+// headers, a blank line and a body of `Content-Length` bytes.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length."))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+struct Document {
+    text: String,
+    analysis: Analysis,
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+}
+
+impl Server {
+    // What to send back for `message`: a response for requests, and for
+    // notifications nothing or the diagnostics they changed.
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let params = &message["params"];
+        let method = message["method"].as_str().unwrap_or_default();
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return self.notification(method, params),
+        };
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    // The whole text on every change.
+                    "textDocumentSync": 1,
+                    "documentSymbolProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "rlox", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/definition" => self.definition(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method))),
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        };
+        vec![response]
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_owned();
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            "textDocument/didChange" => params["contentChanges"].as_array().and_then(|changes| changes.last()).and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            }
+            _ => return Vec::new(),
+        };
+        let text = match text {
+            Some(text) => text.to_owned(),
+            None => return Vec::new(),
+        };
+        let analysis = analysis::analyze(&text);
+        let diagnostics = analysis.diagnostics.iter().map(|diagnostic| lsp_diagnostic(&text, diagnostic)).collect();
+        self.documents.insert(uri.clone(), Document { text, analysis });
+        vec![publish_diagnostics(&uri, diagnostics)]
+    }
+
+    fn document(&self, params: &Value) -> Result<&Document, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents.get(uri).ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{}'.", uri)))
+    }
+
+    fn document_symbols(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let document = self.document(params)?;
+        let symbols: Vec<Value> = document.analysis.declarations.iter().map(|declaration| document_symbol(&document.text, declaration)).collect();
+        Ok(Value::from(symbols))
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let document = self.document(params)?;
+        let offset = offset(&document.text, &params["position"]).ok_or((INVALID_PARAMS, "Invalid position.".to_owned()))?;
+        Ok(match document.analysis.definition(offset) {
+            Some(span) => json!({ "uri": params["textDocument"]["uri"], "range": range(&document.text, span) }),
+            None => Value::Null,
+        })
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn lsp_diagnostic(text: &str, diagnostic: &Diagnostic) -> Value {
+    // Without a span it covers the start of its line.
+    let range = match (diagnostic.span, diagnostic.line) {
+        (Some(span), _) => range(text, span),
+        (None, line) => {
+            let line = line.unwrap_or(1).saturating_sub(1);
+            json!({ "start": { "line": line, "character": 0 }, "end": { "line": line, "character": 0 } })
+        }
+    };
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+    json!({ "range": range, "severity": severity, "source": "rlox", "message": diagnostic.message })
+}
+
+fn document_symbol(text: &str, declaration: &Declaration) -> Value {
+    // Function, class and method, as LSP numbers symbol kinds.
+    let kind = match declaration.kind {
+        DeclarationKind::Function => 12,
+        DeclarationKind::Class => 5,
+        DeclarationKind::Method => 6,
+    };
+    let children: Vec<Value> = declaration.children.iter().map(|child| document_symbol(text, child)).collect();
+    let range = range(text, declaration.span);
+    json!({ "name": declaration.name, "kind": kind, "range": range, "selectionRange": range, "children": children })
+}
+
+fn range(text: &str, span: Span) -> Value {
+    json!({ "start": position(text, span.start), "end": position(text, span.end) })
+}
+
+// LSP positions count lines from 0 and characters in UTF-16 code units.
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count();
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+// The byte offset of an LSP position, or None if it isn't in `text`.
+fn offset(text: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let line_start = if line == 0 {
+        0
+    } else {
+        text.match_indices('\n').nth(line - 1)?.0 + 1
+    };
+    let mut units = 0;
+    for (idx, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + idx);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
@@ -1,43 +1,78 @@
-use std::{fs, process};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fs, io, process, thread};
 
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
-mod expr;
-// mod ast_printer;
-mod loxerr;
-mod parser;
-mod stmt;
-mod scanner;
-mod token;
-mod interpreter;
-mod resolver;
-mod env;
-mod loxcallables;
-mod class;
-mod instance;
-
-use crate::interpreter::Interpreter;
+use rlox::clock::MockTime;
+use rlox::debugger::Prompt;
+use rlox::highlight::LoxHelper;
+use rlox::interpreter::Interpreter;
+use rlox::lox::Backend;
+use rlox::loxerr::{self, DiagnosticFormat, Warnings};
+use rlox::natives::Profile;
+use rlox::rng::Rng;
+use rlox::{coverage, formatter, lox, profile, repl};
 
 struct Lox {
     interpreter: Interpreter,
+    // Print how long each phase took after every run.
+    time: bool,
+    backend: Backend,
+    // Report coverage as an LCOV tracefile rather than an annotated listing.
+    lcov: bool,
+    // Highlight the prompt's line as it's typed.
+    color: bool,
 }
 
 impl Lox {
-    fn new() -> Self {
-        Lox{interpreter: Interpreter::new()}
+    fn new(config: &Config) -> Self {
+        let profile = if config.sandbox { Profile::pure() } else { Profile::full() };
+        let mut interpreter = Interpreter::with_profile(profile.prelude(!config.no_prelude));
+        interpreter.set_max_call_depth(MAX_CALL_DEPTH);
+        interpreter.set_stack_limit(INTERPRETER_STACK_SIZE / 2);
+        interpreter.set_timeout(config.timeout);
+        if config.deterministic {
+            interpreter.set_time_source(Box::new(MockTime::new(1.0)));
+            interpreter.set_rng(Rng::seeded(0));
+        }
+        if config.trace {
+            interpreter.set_trace(Some(Box::new(io::stderr())));
+        }
+        interpreter.set_warnings(config.warnings);
+        interpreter.set_strict(config.strict);
+        let lox_path = std::env::var_os("LOX_PATH").unwrap_or_default();
+        let lox_path = std::env::split_paths(&lox_path).filter(|dir| !dir.as_os_str().is_empty());
+        for dir in config.import_paths.iter().cloned().chain(lox_path) {
+            interpreter.add_import_path(dir);
+        }
+        interpreter.set_profiling(config.profile);
+        interpreter.set_coverage(config.coverage);
+        if config.debug {
+            interpreter.set_debugger(Some(Box::new(Prompt)));
+        }
+        // NO_COLOR only counts when it's set to something, see no-color.org.
+        let color = !config.no_color && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+        Lox { interpreter, time: config.time, backend: config.backend, lcov: config.lcov, color }
     }
 
     fn run_prompt(&mut self) {
-        let mut rl = Editor::<()>::new();
-        rl.load_history("history.txt").unwrap();
+        let mut rl = Editor::<LoxHelper>::new();
+        if self.color {
+            rl.set_helper(Some(LoxHelper));
+        }
+        // There's no history before the first session.
+        let _ = rl.load_history("history.txt");
     
         loop {
             let readline = rl.readline(">> ");
             match readline {
                 Ok(line) => {
                     rl.add_history_entry(line.as_str());
-                    self.run(line.as_str());
+                    self.run_line(&line);
                 }
                 Err(ReadlineError::Interrupted) => (),
                 Err(ReadlineError::Eof) => break,
@@ -50,50 +85,360 @@ impl Lox {
         rl.save_history("history.txt").unwrap();
     }
 
+    // Echoes the value of a line ending in an expression, which can leave
+    // out the `;`. `:type expression` shows the expression's type instead,
+    // and `:load path` runs a script into the session.
+    fn run_line(&mut self, line: &str) {
+        let line = line.trim();
+        if let Some(path) = line.strip_prefix(":load") {
+            return self.load(path.trim());
+        }
+        let (source, show_type) = match line.strip_prefix(":type") {
+            Some(expr) => (expr.trim(), true),
+            None => (line, false),
+        };
+        let mut source = source.to_owned();
+        if !source.is_empty() && !source.ends_with(';') && !source.ends_with('}') {
+            source.push(';');
+        }
+
+        let (result, timings) = lox::run_echo(&mut self.interpreter, &source);
+        if let Ok(Some(value)) = result {
+            if show_type {
+                println!("{}", value.type_name());
+            } else {
+                println!("{}", repl::show(&value));
+            }
+        }
+        self.report(timings);
+    }
+
+    fn load(&mut self, path: &str) {
+        if path.is_empty() {
+            return eprintln!("Usage: :load path.lox");
+        }
+        match repl::load(&mut self.interpreter, path) {
+            Ok((_, timings)) => self.report(timings),
+            Err(msg) => eprintln!("{}", msg),
+        }
+    }
+
     fn run_file(&mut self, file_name: &str) {
-        let file = fs::read_to_string(file_name).expect("Error while reading the file");
-        process::exit(self.run(&file));
+        let file = read_source(file_name);
+        self.interpreter.set_script(Some(Path::new(file_name)));
+        handle_interrupts(self.interpreter.interrupt_flag());
+        let status = self.run(&file);
+        if status != 65 {
+            self.report_coverage(file_name, &file);
+        }
+        let _ = self.interpreter.flush();
+        process::exit(status);
+    }
+
+    fn report_coverage(&self, file_name: &str, source: &str) {
+        let hits = match self.interpreter.coverage() {
+            Some(hits) => hits,
+            None => return,
+        };
+        let lines = match lox::parse(source) {
+            Some(stmts) => coverage::statement_lines(&stmts),
+            None => return,
+        };
+        if self.lcov {
+            eprint!("{}", coverage::lcov(file_name, &lines, hits));
+        } else {
+            eprint!("{}", coverage::report(file_name, source, &lines, hits));
+        }
+    }
+
+    fn format_file(&self, file_name: &str, write: bool) {
+        let file = read_source(file_name);
+        let stmts = match lox::parse(&file) {
+            Some(stmts) => stmts,
+            None => process::exit(65),
+        };
+        let formatted = formatter::format_with_comments(&stmts, &file);
+
+        if !write {
+            print!("{}", formatted);
+        } else if formatted != file {
+            if let Err(err) = fs::write(file_name, formatted) {
+                eprintln!("Could not write '{}': {}.", file_name, err);
+                process::exit(74);
+            }
+        }
+    }
+
+    fn print_ast_json(&self, file_name: &str) {
+        let file = read_source(file_name);
+        match lox::parse(&file) {
+            Some(stmts) => {
+                let json = serde_json::to_string_pretty(&stmts).expect("Could not serialize the AST.");
+                println!("{}", json);
+            }
+            None => process::exit(65),
+        }
     }
     
     fn run(&mut self, line: &str) -> i32 {
-        let mut scanner = scanner::Scanner::new(line);
-        let tokens = scanner.scan_tokens();
+        let (result, timings) = lox::run_with(&mut self.interpreter, line, self.backend);
+        self.report(timings);
+        lox::exit_code(&result)
+    }
+
+    fn report(&self, timings: lox::Timings) {
+        if self.time {
+            eprintln!("{}", timings);
+        }
+        if self.interpreter.profiling() {
+            eprint!("{}", profile::report(&self.interpreter.profile()));
+        }
+    }
+}
+
+// Scripts run on a thread with a big stack, so that they can recurse much
+// deeper than the interpreter's defaults, which fit an ordinary thread, allow.
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+const MAX_CALL_DEPTH: usize = 3000;
+
+fn main() {
+    let interpreter_thread = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(lox_main)
+        .expect("Could not spawn the interpreter thread.");
 
-        let mut parser = parser::Parser::new(&tokens);
-        let stmts = parser.parse();
-        
-        if parser.has_error || scanner.has_error {
-            return 65;
-        } 
+    if interpreter_thread.join().is_err() {
+        process::exit(70);
+    }
+}
+
+// Accepts durations like `5s`, `250ms` or `2m`. A bare number is in seconds.
+fn parse_duration(arg: &str) -> Option<Duration> {
+    let (number, unit) = match arg.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(idx) => arg.split_at(idx),
+        None => (arg, "s"),
+    };
+    let number: f64 = number.parse().ok()?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        _ => return None,
+    };
+    if seconds.is_finite() && seconds >= 0.0 {
+        Some(Duration::from_secs_f64(seconds))
+    } else {
+        None
+    }
+}
+
+const USAGE: &str = "\
+Usage: rlox [options] [script]
+
+Runs the script, or starts a prompt without one. `return n;` outside any
+function ends the script with exit status n.
+
+Options:
+  --time                   Print how long each phase took
+  --trace                  Print every statement as it runs
+  --profile                Print how long each function took
+  --coverage[=lcov]        Report which lines ran
+  --debug                  Pause at breakpoint() calls
+  --timeout <duration>     Stop runs after a duration like 5s, 250ms or 2m
+  --import-path <dir>      Look for imports in dir too, before LOX_PATH
+  --backend=tree|vm        Choose what runs the program
+  --werror                 Report warnings as errors
+  --no-warnings            Don't report warnings
+  --strict                 Reject undeclared assignments, unused locals,
+                           shadowing and redeclared globals
+  --no-color               Don't highlight the prompt, as does NO_COLOR
+  --no-prelude             Start without abs, min, max, toArray and assert
+  --sandbox                Leave out clock, random and importing files
+  --deterministic          Make clock() count from 0 by 1 and random() use
+                           a fixed seed, so every run prints the same
+  --diagnostics=human|json Choose how errors are reported
+  --ast-json               Print the script's syntax tree as JSON
+  --fmt [--write]          Print the script formatted, or rewrite it
+  --lsp                    Run as a language server over stdin and stdout
+  --help                   Print this message
+  --version                Print the version";
 
-        let mut resolver = resolver::Resolver::new(&mut self.interpreter);
-        resolver.resolve(&stmts);
-        if resolver.has_error {
-            return 65;
+// Everything the command line asks for.
+#[derive(Default)]
+struct Config {
+    time: bool,
+    trace: bool,
+    profile: bool,
+    coverage: bool,
+    lcov: bool,
+    debug: bool,
+    ast_json: bool,
+    fmt: bool,
+    write: bool,
+    timeout: Option<Duration>,
+    backend: Backend,
+    warnings: Warnings,
+    strict: bool,
+    diagnostics: DiagnosticFormat,
+    no_color: bool,
+    no_prelude: bool,
+    sandbox: bool,
+    deterministic: bool,
+    import_paths: Vec<PathBuf>,
+    script: Option<String>,
+}
+
+enum Command {
+    Run(Config),
+    Lsp,
+    Help,
+    Version,
+}
+
+// Usage errors come back as the message to print before the usage.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut config = Config::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(Command::Help),
+            "--version" => return Ok(Command::Version),
+            "--lsp" => return Ok(Command::Lsp),
+            "--time" => config.time = true,
+            "--trace" => config.trace = true,
+            "--profile" => config.profile = true,
+            "--debug" => config.debug = true,
+            "--coverage" => config.coverage = true,
+            "--coverage=lcov" => {
+                config.coverage = true;
+                config.lcov = true;
+            }
+            "--ast-json" => config.ast_json = true,
+            "--fmt" => config.fmt = true,
+            "--write" => config.write = true,
+            "--backend=tree" => config.backend = Backend::Tree,
+            "--backend=vm" => config.backend = Backend::Vm,
+            "--werror" => config.warnings = Warnings::Deny,
+            "--no-warnings" => config.warnings = Warnings::Ignore,
+            "--strict" => config.strict = true,
+            "--no-color" => config.no_color = true,
+            "--no-prelude" => config.no_prelude = true,
+            "--sandbox" => config.sandbox = true,
+            "--deterministic" => config.deterministic = true,
+            "--diagnostics=human" => config.diagnostics = DiagnosticFormat::Human,
+            "--diagnostics=json" => config.diagnostics = DiagnosticFormat::Json,
+            "--import-path" => match args.next() {
+                Some(dir) => config.import_paths.push(PathBuf::from(dir)),
+                None => return Err("Missing directory for --import-path.".to_owned()),
+            },
+            "--timeout" => match args.next() {
+                Some(duration) => match parse_duration(&duration) {
+                    Some(duration) => config.timeout = Some(duration),
+                    None => return Err(format!("Invalid duration '{}' for --timeout.", duration)),
+                },
+                None => return Err("Missing duration for --timeout.".to_owned()),
+            },
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("Unknown option '{}'.", arg)),
+            _ if config.script.is_some() => return Err(format!("Unexpected argument '{}', only one script can run.", arg)),
+            _ => config.script = Some(arg),
         }
+    }
+
+    if config.write && !config.fmt {
+        return Err("--write only works with --fmt.".to_owned());
+    }
+    if (config.ast_json || config.fmt) && config.script.is_none() {
+        return Err("--ast-json and --fmt need a script.".to_owned());
+    }
+    Ok(Command::Run(config))
+}
+
+// Ctrl-C while a script runs raises the interpreter's interrupt flag, so it
+// stops at the next loop iteration or call with "Interrupted." and exits
+// with 130. A second Ctrl-C before then exits right away.
+#[cfg(unix)]
+fn handle_interrupts(flag: Arc<AtomicBool>) {
+    use std::sync::atomic::Ordering;
+    use std::sync::OnceLock;
+
+    static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-        if let Err(e) = self.interpreter.interpret(&stmts) {
-            e.error();
-            return 70;
+    extern "C" fn on_sigint(_: libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            // Only async-signal-safe calls here, so no flushing.
+            unsafe { libc::_exit(130) };
+        }
+        if let Some(flag) = FLAG.get() {
+            flag.store(true, Ordering::SeqCst);
         }
-        0
     }
-    
+
+    if FLAG.set(flag).is_ok() {
+        let handler = on_sigint as extern "C" fn(libc::c_int);
+        unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    }
 }
 
+#[cfg(not(unix))]
+fn handle_interrupts(_: Arc<AtomicBool>) {}
 
+// Exits with 66 when the file can't be opened and 74 when it can't be read,
+// as in sysexits.h.
+fn read_source(file_name: &str) -> String {
+    match fs::read_to_string(file_name) {
+        Ok(source) => source,
+        Err(err) => {
+            let status = match err.kind() {
+                io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied => 66,
+                _ => 74,
+            };
+            let reason = lox::read_failure(&err);
+            eprintln!("Could not read '{}': {}.", file_name, reason);
+            process::exit(status);
+        }
+    }
+}
 
+#[cfg(feature = "lsp")]
+fn serve_lsp() {
+    let stdin = io::stdin();
+    if let Err(err) = rlox::lsp::serve(stdin.lock(), io::stdout()) {
+        eprintln!("Language server failed: {}.", err);
+        process::exit(74);
+    }
+}
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    let mut lox = Lox::new();
-
-    match args.len() {
-        1 => lox.run_prompt(),
-        2 => lox.run_file(&args[1]),
-        _ => {
-            println!("Usage: rlox [script_name]");
+#[cfg(not(feature = "lsp"))]
+fn serve_lsp() {
+    eprintln!("This rlox was built without the lsp feature.");
+    process::exit(64);
+}
+
+fn lox_main() {
+    let config = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(config)) => config,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("rlox {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Ok(Command::Lsp) => return serve_lsp(),
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, USAGE);
             process::exit(64);
         }
     };
+    loxerr::set_diagnostic_format(config.diagnostics);
+    let mut lox = Lox::new(&config);
+
+    match &config.script {
+        Some(script) if config.ast_json => lox.print_ast_json(script),
+        Some(script) if config.fmt => lox.format_file(script, config.write),
+        Some(script) => lox.run_file(script),
+        None => lox.run_prompt(),
+    };
 }
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::expr::Value;
+use crate::shared::Rc;
+
+// The values that can be map keys, compared by what they hold.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Nil,
+    Bool(bool),
+    Number(u64),
+    String(Rc<str>),
+}
+
+impl Key {
+    // None for values that can't be keys.
+    pub fn new(value: &Value) -> Option<Key> {
+        match value {
+            Value::Nil => Some(Key::Nil),
+            Value::Bool(b) => Some(Key::Bool(*b)),
+            // 0 and -0 are the same key, and so is every NaN.
+            Value::Number(n) if *n == 0.0 => Some(Key::Number(0)),
+            Value::Number(n) if n.is_nan() => Some(Key::Number(f64::NAN.to_bits())),
+            Value::Number(n) => Some(Key::Number(n.to_bits())),
+            Value::String(s) => Some(Key::String(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+// Entries stay in the order their keys were first inserted.
+#[derive(Default)]
+pub struct LoxMap {
+    entries: Vec<(Value, Value)>,
+    index: HashMap<Key, usize>,
+}
+
+impl LoxMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        self.index.get(key).map(|&idx| &self.entries[idx].1)
+    }
+
+    // Replacing the value of a key keeps its place.
+    pub fn insert(&mut self, key: Key, key_value: Value, value: Value) {
+        match self.index.get(&key) {
+            Some(&idx) => self.entries[idx].1 = value,
+            None => {
+                self.index.insert(key, self.entries.len());
+                self.entries.push((key_value, value));
+            }
+        }
+    }
+
+    pub fn keys(&self) -> Vec<Value> {
+        self.entries.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    pub fn entries(&self) -> Vec<(Value, Value)> {
+        self.entries.clone()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+
+    // Leaves the map empty, for the cycle collector.
+    pub(crate) fn take(&mut self) -> Vec<(Value, Value)> {
+        self.index.clear();
+        std::mem::take(&mut self.entries)
+    }
+}
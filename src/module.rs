@@ -0,0 +1,37 @@
+use crate::expr::Value;
+use crate::loxcallables::{Native, NativeBody};
+use crate::shared::Rc;
+use crate::symbol::Symbol;
+
+// A library of natives that `import "name";` defines as globals the first
+// time it runs, so programs that don't import it don't see its names. Hosts
+// add their own with `Interpreter::register_module`.
+pub struct NativeModule {
+    name: String,
+    definitions: Vec<(Symbol, Value)>,
+}
+
+impl NativeModule {
+    pub fn new(name: &str) -> Self {
+        NativeModule { name: name.to_owned(), definitions: Vec::new() }
+    }
+
+    pub fn function(mut self, name: &str, arity: usize, body: NativeBody) -> Self {
+        let native = Value::Callable(Rc::new(Native::new(name, arity, body)));
+        self.definitions.push((Symbol::intern(name), native));
+        self
+    }
+
+    pub fn value(mut self, name: &str, value: Value) -> Self {
+        self.definitions.push((Symbol::intern(name), value));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn definitions(&self) -> &[(Symbol, Value)] {
+        &self.definitions
+    }
+}
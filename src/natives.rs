@@ -0,0 +1,711 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+use crate::class::{LoxClass, NativeClass};
+use crate::env::Environment;
+use crate::expr::{number_to_string, Value};
+use crate::instance::LoxInstance;
+use crate::interpreter::{is_truthy, Interpreter, VALUE_SIZE};
+use crate::loxcallables::{LoxCallable, Native, NativeBody, PrimitiveBody, PrimitiveMethod};
+use crate::loxerr::RuntimeException;
+use crate::map::{Key, LoxMap};
+use crate::module::NativeModule;
+use crate::set::LoxSet;
+use crate::shared::{Rc, RefCell};
+use crate::symbol::Symbol;
+use crate::token::{Token, TokenType};
+
+fn native(globals: &mut Environment, name: &str, arity: usize, body: NativeBody) {
+    globals.define(&Symbol::intern(name), Value::Callable(Rc::new(Native::new(name, arity, body))));
+}
+
+fn error(msg: String) -> RuntimeException {
+    RuntimeException::NativeError(msg)
+}
+
+fn instance<'a>(native: &str, arg: &'a Value) -> Result<&'a Rc<RefCell<LoxInstance>>, RuntimeException> {
+    match arg {
+        Value::Instance(instance) => Ok(instance),
+        _ => Err(error(format!("First argument to {} must be an instance.", native))),
+    }
+}
+
+fn name(native: &str, arg: &Value) -> Result<Symbol, RuntimeException> {
+    match arg {
+        Value::String(name) => Ok(Symbol::intern(name)),
+        _ => Err(error(format!("Second argument to {} must be a string.", native))),
+    }
+}
+
+fn array<'a>(native: &str, arg: &'a Value) -> Result<&'a Rc<RefCell<Vec<Value>>>, RuntimeException> {
+    match arg {
+        Value::Array(array) => Ok(array),
+        _ => Err(error(format!("First argument to {} must be an array.", native))),
+    }
+}
+
+// A function, bound method or class taking `arity` arguments.
+fn callback(native: &str, ordinal: &str, arg: &Value, arity: usize) -> Result<Rc<dyn LoxCallable>, RuntimeException> {
+    let callee: Rc<dyn LoxCallable> = match arg {
+        Value::Callable(callee) => Rc::clone(callee),
+        Value::Class(class) => Rc::clone(class) as Rc<dyn LoxCallable>,
+        _ => return Err(error(format!("{} argument to {} must be a function.", ordinal, native))),
+    };
+    if callee.arity() != arity {
+        let plural = if arity == 1 { "" } else { "s" };
+        return Err(error(format!(
+            "Function passed to {} must take {} argument{} but takes {}.",
+            native,
+            arity,
+            plural,
+            callee.arity()
+        )));
+    }
+    Ok(callee)
+}
+
+// An index into `len` elements, or just past them with `past_end`.
+fn position(arg: &Value, len: usize, past_end: bool) -> Result<usize, RuntimeException> {
+    let limit = if past_end { len + 1 } else { len };
+    match arg {
+        Value::Number(n) if n.fract() == 0.0 => {
+            if *n >= 0.0 && *n < limit as f64 {
+                Ok(*n as usize)
+            } else {
+                Err(error(format!("Index {} is out of bounds for length {}.", number_to_string(*n), len)))
+            }
+        }
+        _ => Err(error("Index must be an integer.".to_owned())),
+    }
+}
+
+fn set<'a>(native: &str, arg: &'a Value) -> Result<&'a Rc<RefCell<LoxSet>>, RuntimeException> {
+    match arg {
+        Value::Set(set) => Ok(set),
+        _ => Err(error(format!("First argument to {} must be a set.", native))),
+    }
+}
+
+fn element(arg: &Value) -> Result<Key, RuntimeException> {
+    Key::new(arg).ok_or_else(|| error("Set elements must be strings, numbers, booleans or nil.".to_owned()))
+}
+
+// `ordinal` says which argument it is, as in "Second".
+fn string<'a>(native: &str, ordinal: &str, arg: &'a Value) -> Result<&'a str, RuntimeException> {
+    match arg {
+        Value::String(s) => Ok(s),
+        _ => Err(error(format!("{} argument to {} must be a string.", ordinal, native))),
+    }
+}
+
+fn number(native: &str, ordinal: &str, arg: &Value) -> Result<f64, RuntimeException> {
+    match arg {
+        Value::Number(n) => Ok(*n),
+        _ => Err(error(format!("{} argument to {} must be a number.", ordinal, native))),
+    }
+}
+
+fn strings(names: &[Symbol]) -> Value {
+    let names = names.iter().map(|name| Value::String(name.as_str().into())).collect();
+    Value::Array(Rc::new(RefCell::new(names)))
+}
+
+// Converting between Lox values and JSON text, see `json.rs`.
+#[cfg(feature = "json")]
+fn define_json(globals: &mut Environment) {
+    native(globals, "jsonParse", 1, |_, args| {
+        crate::json::parse(string("jsonParse", "First", &args[0])?).map_err(error)
+    });
+    native(globals, "jsonStringify", 1, |interpreter, args| {
+        Ok(Value::String(crate::json::stringify(interpreter, &args[0])?.into()))
+    });
+}
+
+#[cfg(feature = "regex")]
+fn regex(interpreter: &mut Interpreter, native: &str, arg: &Value) -> Result<regex::Regex, RuntimeException> {
+    let pattern = string(native, "First", arg)?;
+    if let Some(regex) = interpreter.regexes.get(pattern) {
+        return Ok(regex.clone());
+    }
+    // The crate's message ends with a line saying what's wrong, after the
+    // pattern and a marker under the offending part.
+    let regex = regex::Regex::new(pattern).map_err(|err| {
+        let msg = err.to_string();
+        let reason = msg.lines().last().unwrap_or_default();
+        error(format!("Invalid regex '{}': {}.", pattern, reason.trim_start_matches("error: ")))
+    })?;
+    interpreter.regexes.insert(pattern.to_owned(), regex.clone());
+    Ok(regex)
+}
+
+#[cfg(feature = "regex")]
+fn define_regex(globals: &mut Environment) {
+    native(globals, "regexMatch", 2, |interpreter, args| {
+        let regex = regex(interpreter, "regexMatch", &args[0])?;
+        Ok(Value::Bool(regex.is_match(string("regexMatch", "Second", &args[1])?)))
+    });
+    // The text of the leftmost match, nil when there is none.
+    native(globals, "regexFind", 2, |interpreter, args| {
+        let regex = regex(interpreter, "regexFind", &args[0])?;
+        let found = regex.find(string("regexFind", "Second", &args[1])?);
+        Ok(found.map_or(Value::Nil, |found| Value::String(found.as_str().into())))
+    });
+    // The groups of the leftmost match, not counting the whole match. Groups
+    // that took no part in it are nil. Nil when there is no match.
+    native(globals, "regexCaptures", 2, |interpreter, args| {
+        let regex = regex(interpreter, "regexCaptures", &args[0])?;
+        let captures = match regex.captures(string("regexCaptures", "Second", &args[1])?) {
+            Some(captures) => captures,
+            None => return Ok(Value::Nil),
+        };
+        let groups = captures
+            .iter()
+            .skip(1)
+            .map(|group| group.map_or(Value::Nil, |group| Value::String(group.as_str().into())))
+            .collect();
+        Ok(Value::Array(Rc::new(RefCell::new(groups))))
+    });
+    // Replaces every match. `$1` or `${name}` in the replacement stand for a
+    // group, `$$` for a dollar sign.
+    native(globals, "regexReplace", 3, |interpreter, args| {
+        let regex = regex(interpreter, "regexReplace", &args[0])?;
+        let s = string("regexReplace", "Second", &args[1])?;
+        let replacement = string("regexReplace", "Third", &args[2])?;
+        Ok(Value::String(regex.replace_all(s, replacement).into()))
+    });
+}
+
+// The groups of natives an interpreter can be made without, see `Profile`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Group {
+    // Everything that only computes, native modules such as `math` included.
+    Core,
+    // Importing scripts from the filesystem.
+    Io,
+    // `clock` and `random`, which give something different every run.
+    Time,
+}
+
+// What an interpreter defines: which groups of natives and whether the
+// prelude, see `Interpreter::with_profile`. Hosts running code they don't
+// trust start from `pure` and allow what they need.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    groups: HashSet<Group>,
+    prelude: bool,
+}
+
+impl Profile {
+    // Everything, as the command line has.
+    pub fn full() -> Self {
+        Profile { groups: [Group::Core, Group::Io, Group::Time].iter().copied().collect(), prelude: true }
+    }
+
+    // Nothing that can see or change what's outside the interpreter, as
+    // `--sandbox` has. Programs still print to the interpreter's output.
+    pub fn pure() -> Self {
+        Profile::full().deny(Group::Io).deny(Group::Time)
+    }
+
+    pub fn allow(mut self, group: Group) -> Self {
+        self.groups.insert(group);
+        self
+    }
+
+    pub fn deny(mut self, group: Group) -> Self {
+        self.groups.remove(&group);
+        self
+    }
+
+    pub fn prelude(mut self, prelude: bool) -> Self {
+        self.prelude = prelude;
+        self
+    }
+
+    pub fn allows(&self, group: Group) -> bool {
+        self.groups.contains(&group)
+    }
+
+    pub fn has_prelude(&self) -> bool {
+        self.prelude
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::full()
+    }
+}
+
+// Defines the natives of the groups `profile` allows. Io has none, as it's
+// the interpreter that reads imported files.
+pub fn define(globals: &mut Environment, profile: &Profile) {
+    if profile.allows(Group::Core) {
+        define_core(globals);
+    }
+    if profile.allows(Group::Time) {
+        // Milliseconds, see `Interpreter::set_time_source`.
+        native(globals, "clock", 0, |interpreter, _| Ok(Value::Number(interpreter.now_millis())));
+        // In [0, 1), see `Interpreter::set_rng`.
+        native(globals, "random", 0, |interpreter, _| Ok(Value::Number(interpreter.random())));
+    }
+}
+
+// Prints `value` for `println` or `write`, which have no token of their own
+// to report errors at, so the ones `toString` raises without a place are
+// reported at the call.
+fn output(interpreter: &mut Interpreter, value: &Value, end_line: bool) -> Result<Value, RuntimeException> {
+    let here = Token::new(TokenType::Eof, "", 0);
+    let result = if end_line { interpreter.print(value, &here) } else { interpreter.write(value, &here) };
+    match result {
+        Ok(()) => Ok(Value::Nil),
+        Err(RuntimeException::RuntimeError { token, error }) if token == here => Err(RuntimeException::NativeError(error)),
+        Err(err) => Err(err),
+    }
+}
+
+// The functions every program starts with.
+fn define_core(globals: &mut Environment) {
+    native(globals, "isNan", 1, |_, args| {
+        Ok(Value::Bool(matches!(args[0], Value::Number(n) if n.is_nan())))
+    });
+    native(globals, "type", 1, |_, args| Ok(Value::String(args[0].type_name().into())));
+    // Does nothing unless a debugger is attached.
+    native(globals, "breakpoint", 0, |interpreter, _| {
+        interpreter.request_pause();
+        Ok(Value::Nil)
+    });
+    // The `print` statement as a function, to pass around: `map(xs, println)`.
+    // It can't be named `print`, which is a keyword, so `print(x);` still
+    // prints the grouping `(x)`.
+    native(globals, "println", 1, |interpreter, args| output(interpreter, &args[0], true));
+    // Prints without ending the line, for prompts and progress. Like `print`,
+    // it doesn't flush.
+    native(globals, "write", 1, |interpreter, args| output(interpreter, &args[0], false));
+    native(globals, "flush", 0, |interpreter, _| {
+        interpreter.flush().map_err(|err| error(format!("Could not flush output: {}.", err)))?;
+        Ok(Value::Nil)
+    });
+    // Stops the program with a runtime error.
+    native(globals, "error", 1, |_, args| Err(error(string("error", "First", &args[0])?.to_owned())));
+
+    // Copies all the way down, see copy.rs.
+    native(globals, "copy", 1, |_, args| crate::copy::deep_copy(&args[0]).map_err(error));
+
+    // Fields only, methods don't count.
+    native(globals, "hasField", 2, |_, args| {
+        let instance = instance("hasField", &args[0])?;
+        let name = name("hasField", &args[1])?;
+        let has_field = instance.borrow().contains(&name);
+        Ok(Value::Bool(has_field))
+    });
+    // Methods the instance's class defines or inherits.
+    native(globals, "hasMethod", 2, |_, args| {
+        let instance = instance("hasMethod", &args[0])?;
+        let name = name("hasMethod", &args[1])?;
+        let has_method = instance.borrow().class.has_method(&name);
+        Ok(Value::Bool(has_method))
+    });
+    // Returns whether there was such a field. Methods can't be deleted.
+    native(globals, "deleteField", 2, |_, args| {
+        let instance = instance("deleteField", &args[0])?;
+        let name = name("deleteField", &args[1])?;
+        instance.borrow().check_unfrozen(&name).map_err(error)?;
+        let removed = instance.borrow().remove(&name);
+        Ok(Value::Bool(removed))
+    });
+    // Like `obj.name` and `obj.name = value` with a name computed at runtime.
+    native(globals, "getField", 2, |_, args| {
+        let instance = instance("getField", &args[0])?;
+        let name = name("getField", &args[1])?;
+        let value = LoxInstance::lookup(instance, &name);
+        value.ok_or_else(|| error(instance.borrow().undefined_property(&name)))
+    });
+    native(globals, "setField", 3, |_, args| {
+        let instance = instance("setField", &args[0])?;
+        let name = name("setField", &args[1])?;
+        instance.borrow().check_unfrozen(&name).map_err(error)?;
+        instance.borrow_mut().set_field(name, args[2].clone());
+        Ok(args[2].clone())
+    });
+    // Stops the instance's fields from being set or deleted, see
+    // LoxInstance::freeze. Returns the instance.
+    native(globals, "freeze", 1, |_, args| {
+        match &args[0] {
+            Value::Instance(instance) => instance.borrow().freeze(),
+            _ => return Err(error("Argument to freeze must be an instance.".to_owned())),
+        }
+        Ok(args[0].clone())
+    });
+    // False for anything that isn't an instance, as only instances freeze.
+    native(globals, "frozen", 1, |_, args| {
+        Ok(Value::Bool(matches!(&args[0], Value::Instance(instance) if instance.borrow().is_frozen())))
+    });
+    // The names of the instance's fields, in alphabetical order.
+    native(globals, "fields", 1, |_, args| {
+        let names = match &args[0] {
+            Value::Instance(instance) => instance.borrow().field_names(),
+            _ => return Err(error("Argument to fields must be an instance.".to_owned())),
+        };
+        Ok(strings(&names))
+    });
+    // The methods a class or an instance's class defines or inherits, in
+    // alphabetical order.
+    native(globals, "methods", 1, |_, args| {
+        let names = match &args[0] {
+            Value::Class(class) => class.method_names(),
+            Value::Instance(instance) => instance.borrow().class.method_names(),
+            _ => return Err(error("Argument to methods must be a class or an instance.".to_owned())),
+        };
+        Ok(strings(&names))
+    });
+
+    // What the interpreter has been up to, see `Stats`.
+    native(globals, "stats", 0, |interpreter, _| {
+        let stats = interpreter.stats();
+        let mut map = LoxMap::new();
+        let counters = [
+            ("environments", stats.environments as f64),
+            ("globals", stats.globals as f64),
+            ("calls", stats.calls as f64),
+            ("peakCallDepth", stats.peak_call_depth as f64),
+        ];
+        for (name, count) in counters.iter() {
+            let key = Value::String((*name).into());
+            map.insert(Key::new(&key).unwrap(), key, Value::Number(*count));
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    });
+    // An empty map, filled in by assigning to `map[key]`.
+    native(globals, "Map", 0, |_, _| Ok(Value::Map(Rc::new(RefCell::new(LoxMap::new())))));
+    native(globals, "len", 1, |_, args| match &args[0] {
+        Value::Array(array) => Ok(Value::Number(array.borrow().len() as f64)),
+        Value::Tuple(tuple) => Ok(Value::Number(tuple.len() as f64)),
+        Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
+        Value::Map(map) => Ok(Value::Number(map.borrow().len() as f64)),
+        Value::Set(set) => Ok(Value::Number(set.borrow().len() as f64)),
+        _ => Err(error("Argument to len must be an array, a tuple, a string, a map or a set.".to_owned())),
+    });
+    native(globals, "push", 2, |interpreter, args| {
+        let array = array("push", &args[0])?;
+        interpreter.allocate(VALUE_SIZE)?;
+        array.borrow_mut().push(args[1].clone());
+        Ok(Value::Nil)
+    });
+    // Nil when the array is empty.
+    native(globals, "pop", 1, |_, args| {
+        let popped = array("pop", &args[0])?.borrow_mut().pop();
+        Ok(popped.unwrap_or(Value::Nil))
+    });
+    // The index can be the length, which appends.
+    native(globals, "insert", 3, |interpreter, args| {
+        let mut array = array("insert", &args[0])?.borrow_mut();
+        let idx = position(&args[1], array.len(), true)?;
+        interpreter.allocate(VALUE_SIZE)?;
+        array.insert(idx, args[2].clone());
+        Ok(Value::Nil)
+    });
+    // Returns the element removed.
+    native(globals, "removeAt", 2, |_, args| {
+        let mut array = array("removeAt", &args[0])?.borrow_mut();
+        let idx = position(&args[1], array.len(), false)?;
+        Ok(array.remove(idx))
+    });
+    // Compares elements like `==`. For maps, looks for a key.
+    native(globals, "contains", 2, |_, args| {
+        let found = match &args[0] {
+            Value::Array(array) => array.borrow().contains(&args[1]),
+            Value::Tuple(tuple) => tuple.contains(&args[1]),
+            Value::Map(map) => Key::new(&args[1]).is_some_and(|key| map.borrow().get(&key).is_some()),
+            _ => return Err(error("First argument to contains must be an array, a tuple or a map.".to_owned())),
+        };
+        Ok(Value::Bool(found))
+    });
+
+    native(globals, "map", 2, |interpreter, args| {
+        let function = callback("map", "Second", &args[1], 1)?;
+        map(interpreter, array("map", &args[0])?, &*function)
+    });
+    native(globals, "filter", 2, |interpreter, args| {
+        let predicate = callback("filter", "Second", &args[1], 1)?;
+        filter(interpreter, array("filter", &args[0])?, &*predicate)
+    });
+    native(globals, "reduce", 3, |interpreter, args| {
+        let function = callback("reduce", "Second", &args[1], 2)?;
+        reduce(interpreter, array("reduce", &args[0])?, &*function, &args[2])
+    });
+
+    native(globals, "Set", 0, |_, _| Ok(Value::Set(Rc::new(RefCell::new(LoxSet::new())))));
+    // True when the element wasn't in the set yet.
+    native(globals, "add", 2, |interpreter, args| {
+        let set = set("add", &args[0])?;
+        let key = element(&args[1])?;
+        interpreter.allocate(VALUE_SIZE)?;
+        Ok(Value::Bool(set.borrow_mut().insert(key, args[1].clone())))
+    });
+    // True when the element was in the set.
+    native(globals, "remove", 2, |_, args| {
+        let set = set("remove", &args[0])?;
+        let key = element(&args[1])?;
+        Ok(Value::Bool(set.borrow_mut().remove(&key)))
+    });
+    native(globals, "has", 2, |_, args| {
+        let set = set("has", &args[0])?;
+        let key = element(&args[1])?;
+        Ok(Value::Bool(set.borrow().contains(&key)))
+    });
+
+    native(globals, "split", 2, |interpreter, args| {
+        split(interpreter, string("split", "First", &args[0])?, string("split", "Second", &args[1])?)
+    });
+    native(globals, "join", 2, |interpreter, args| {
+        join(interpreter, array("join", &args[0])?, string("join", "Second", &args[1])?)
+    });
+    native(globals, "trim", 1, |interpreter, args| trim(interpreter, string("trim", "First", &args[0])?));
+    // Replaces every occurrence. An empty pattern matches between every two
+    // characters and at both ends.
+    native(globals, "replace", 3, |interpreter, args| {
+        let s = string("replace", "First", &args[0])?;
+        let from = string("replace", "Second", &args[1])?;
+        let to = string("replace", "Third", &args[2])?;
+        replace(interpreter, s, from, to)
+    });
+    native(globals, "startsWith", 2, |_, args| {
+        let s = string("startsWith", "First", &args[0])?;
+        Ok(Value::Bool(s.starts_with(string("startsWith", "Second", &args[1])?)))
+    });
+    native(globals, "endsWith", 2, |_, args| {
+        let s = string("endsWith", "First", &args[0])?;
+        Ok(Value::Bool(s.ends_with(string("endsWith", "Second", &args[1])?)))
+    });
+
+    globals.define(&Symbol::intern("StringBuilder"), Value::Class(Rc::new(LoxClass::native(string_builder()))));
+
+    #[cfg(feature = "json")]
+    define_json(globals);
+    #[cfg(feature = "regex")]
+    define_regex(globals);
+}
+
+// An empty separator splits into characters. Otherwise there is one more
+// piece than separators, so an empty string gives one empty piece.
+fn split(interpreter: &mut Interpreter, s: &str, sep: &str) -> Result<Value, RuntimeException> {
+    let count = if sep.is_empty() { s.chars().count() } else { s.matches(sep).count() + 1 };
+    interpreter.allocate(s.len() + count * VALUE_SIZE)?;
+    let pieces: Vec<Value> = if sep.is_empty() {
+        s.chars().map(|c| Value::String(c.to_string().into())).collect()
+    } else {
+        s.split(sep).map(|piece| Value::String(piece.into())).collect()
+    };
+    Ok(Value::Array(Rc::new(RefCell::new(pieces))))
+}
+
+fn join(interpreter: &mut Interpreter, array: &RefCell<Vec<Value>>, sep: &str) -> Result<Value, RuntimeException> {
+    let elements = array.borrow();
+    let mut pieces = Vec::with_capacity(elements.len());
+    for element in elements.iter() {
+        match element {
+            Value::String(s) => pieces.push(&**s),
+            _ => return Err(error("Can only join arrays of strings.".to_owned())),
+        }
+    }
+    let length = pieces.iter().map(|piece| piece.len()).sum::<usize>() + sep.len() * pieces.len().saturating_sub(1);
+    interpreter.allocate(length)?;
+    Ok(Value::String(pieces.join(sep).into()))
+}
+
+fn trim(interpreter: &mut Interpreter, s: &str) -> Result<Value, RuntimeException> {
+    let trimmed = s.trim();
+    interpreter.allocate(trimmed.len())?;
+    Ok(Value::String(trimmed.into()))
+}
+
+fn replace(interpreter: &mut Interpreter, s: &str, from: &str, to: &str) -> Result<Value, RuntimeException> {
+    let count = if from.is_empty() { s.chars().count() + 1 } else { s.matches(from).count() };
+    interpreter.allocate((s.len() + count * to.len()).saturating_sub(count * from.len()))?;
+    Ok(Value::String(s.replace(from, to).into()))
+}
+
+// These go over a copy of the elements, so the callback may change the array
+// without affecting the iteration.
+fn map(interpreter: &mut Interpreter, array: &RefCell<Vec<Value>>, function: &dyn LoxCallable) -> Result<Value, RuntimeException> {
+    let elements = array.borrow().clone();
+    interpreter.allocate(elements.len() * VALUE_SIZE)?;
+    let mut mapped = Vec::with_capacity(elements.len());
+    for element in elements {
+        mapped.push(interpreter.call_back(function, &[element])?);
+    }
+    Ok(Value::Array(Rc::new(RefCell::new(mapped))))
+}
+
+// Keeps the elements the predicate returns something truthy for.
+fn filter(interpreter: &mut Interpreter, array: &RefCell<Vec<Value>>, predicate: &dyn LoxCallable) -> Result<Value, RuntimeException> {
+    let elements = array.borrow().clone();
+    interpreter.allocate(elements.len() * VALUE_SIZE)?;
+    let mut kept = Vec::new();
+    for element in elements {
+        if is_truthy(&interpreter.call_back(predicate, std::slice::from_ref(&element))?) {
+            kept.push(element);
+        }
+    }
+    Ok(Value::Array(Rc::new(RefCell::new(kept))))
+}
+
+// Folds from the left, starting with the initial value.
+fn reduce(
+    interpreter: &mut Interpreter,
+    array: &RefCell<Vec<Value>>,
+    function: &dyn LoxCallable,
+    initial: &Value,
+) -> Result<Value, RuntimeException> {
+    let elements = array.borrow().clone();
+    let mut acc = initial.clone();
+    for element in elements {
+        acc = interpreter.call_back(function, &[acc, element])?;
+    }
+    Ok(acc)
+}
+
+pub type PrimitiveMethods = HashMap<&'static str, HashMap<Symbol, PrimitiveMethod>>;
+
+fn method(methods: &mut HashMap<Symbol, PrimitiveMethod>, name: &str, arity: usize, body: PrimitiveBody) {
+    methods.insert(Symbol::intern(name), PrimitiveMethod::new(name, arity, body));
+}
+
+// What a method is called on, which for the methods of strings, numbers and
+// arrays is always a string, a number or an array.
+fn this_string(receiver: &Value) -> &str {
+    match receiver {
+        Value::String(s) => s,
+        _ => unreachable!(),
+    }
+}
+
+fn this_number(receiver: &Value) -> f64 {
+    match receiver {
+        Value::Number(n) => *n,
+        _ => unreachable!(),
+    }
+}
+
+fn this_array(receiver: &Value) -> &Rc<RefCell<Vec<Value>>> {
+    match receiver {
+        Value::Array(array) => array,
+        _ => unreachable!(),
+    }
+}
+
+// The methods of strings, numbers and arrays, by what `type()` calls them.
+// Most work like the global function of the same name: `"a,b".split(",")` is
+// `split("a,b", ",")`.
+pub fn primitive_methods() -> PrimitiveMethods {
+    let mut strings = HashMap::new();
+    method(&mut strings, "length", 0, |_, s, _| Ok(Value::Number(this_string(s).chars().count() as f64)));
+    method(&mut strings, "split", 1, |interpreter, s, args| {
+        split(interpreter, this_string(s), string("split", "First", &args[0])?)
+    });
+    method(&mut strings, "trim", 0, |interpreter, s, _| trim(interpreter, this_string(s)));
+    method(&mut strings, "replace", 2, |interpreter, s, args| {
+        let from = string("replace", "First", &args[0])?;
+        let to = string("replace", "Second", &args[1])?;
+        replace(interpreter, this_string(s), from, to)
+    });
+    method(&mut strings, "startsWith", 1, |_, s, args| {
+        Ok(Value::Bool(this_string(s).starts_with(string("startsWith", "First", &args[0])?)))
+    });
+    method(&mut strings, "endsWith", 1, |_, s, args| {
+        Ok(Value::Bool(this_string(s).ends_with(string("endsWith", "First", &args[0])?)))
+    });
+    method(&mut strings, "contains", 1, |_, s, args| {
+        Ok(Value::Bool(this_string(s).contains(string("contains", "First", &args[0])?)))
+    });
+
+    let mut numbers = HashMap::new();
+    method(&mut numbers, "floor", 0, |_, n, _| Ok(Value::Number(this_number(n).floor())));
+    method(&mut numbers, "ceil", 0, |_, n, _| Ok(Value::Number(this_number(n).ceil())));
+    // Halfway cases round away from zero.
+    method(&mut numbers, "round", 0, |_, n, _| Ok(Value::Number(this_number(n).round())));
+    method(&mut numbers, "abs", 0, |_, n, _| Ok(Value::Number(this_number(n).abs())));
+
+    let mut arrays = HashMap::new();
+    method(&mut arrays, "size", 0, |_, a, _| Ok(Value::Number(this_array(a).borrow().len() as f64)));
+    method(&mut arrays, "push", 1, |interpreter, a, args| {
+        interpreter.allocate(VALUE_SIZE)?;
+        this_array(a).borrow_mut().push(args[0].clone());
+        Ok(Value::Nil)
+    });
+    method(&mut arrays, "pop", 0, |_, a, _| Ok(this_array(a).borrow_mut().pop().unwrap_or(Value::Nil)));
+    method(&mut arrays, "contains", 1, |_, a, args| Ok(Value::Bool(this_array(a).borrow().contains(&args[0]))));
+    method(&mut arrays, "join", 1, |interpreter, a, args| {
+        join(interpreter, this_array(a), string("join", "First", &args[0])?)
+    });
+    method(&mut arrays, "map", 1, |interpreter, a, args| {
+        let function = callback("map", "First", &args[0], 1)?;
+        map(interpreter, this_array(a), &*function)
+    });
+    method(&mut arrays, "filter", 1, |interpreter, a, args| {
+        let predicate = callback("filter", "First", &args[0], 1)?;
+        filter(interpreter, this_array(a), &*predicate)
+    });
+    method(&mut arrays, "reduce", 2, |interpreter, a, args| {
+        let function = callback("reduce", "First", &args[0], 2)?;
+        reduce(interpreter, this_array(a), &*function, &args[1])
+    });
+
+    let mut methods = HashMap::new();
+    methods.insert("string", strings);
+    methods.insert("number", numbers);
+    methods.insert("array", arrays);
+    methods
+}
+
+// Collects pieces of text without copying what it has so far, which `s = s +
+// piece` does every time.
+fn string_builder() -> NativeClass {
+    fn text(state: &mut dyn Any) -> &mut String {
+        state.downcast_mut::<String>().unwrap()
+    }
+
+    NativeClass::new("StringBuilder", 0, |_, _| Ok(Box::new(String::new())))
+        .method("append", 1, |interpreter, state, args| {
+            let piece = string("append", "First", &args[0])?;
+            interpreter.allocate(piece.len())?;
+            text(state).push_str(piece);
+            Ok(Value::Nil)
+        })
+        .method("toString", 0, |_, state, _| Ok(Value::String(text(state).as_str().into())))
+}
+
+// The modules programs can import, see `Interpreter::register_module`.
+pub fn modules(profile: &Profile) -> Vec<NativeModule> {
+    if profile.allows(Group::Core) {
+        vec![math()]
+    } else {
+        Vec::new()
+    }
+}
+
+fn math() -> NativeModule {
+    NativeModule::new("math")
+        .value("pi", Value::Number(std::f64::consts::PI))
+        .value("e", Value::Number(std::f64::consts::E))
+        .function("sqrt", 1, |_, args| Ok(Value::Number(number("sqrt", "First", &args[0])?.sqrt())))
+        .function("floor", 1, |_, args| Ok(Value::Number(number("floor", "First", &args[0])?.floor())))
+        .function("ceil", 1, |_, args| Ok(Value::Number(number("ceil", "First", &args[0])?.ceil())))
+        // Halfway cases round away from zero.
+        .function("round", 1, |_, args| Ok(Value::Number(number("round", "First", &args[0])?.round())))
+        .function("pow", 2, |_, args| {
+            let base = number("pow", "First", &args[0])?;
+            Ok(Value::Number(base.powf(number("pow", "Second", &args[1])?)))
+        })
+        .function("exp", 1, |_, args| Ok(Value::Number(number("exp", "First", &args[0])?.exp())))
+        // The natural logarithm.
+        .function("log", 1, |_, args| Ok(Value::Number(number("log", "First", &args[0])?.ln())))
+        .function("sin", 1, |_, args| Ok(Value::Number(number("sin", "First", &args[0])?.sin())))
+        .function("cos", 1, |_, args| Ok(Value::Number(number("cos", "First", &args[0])?.cos())))
+        .function("tan", 1, |_, args| Ok(Value::Number(number("tan", "First", &args[0])?.tan())))
+        .function("atan2", 2, |_, args| {
+            let y = number("atan2", "First", &args[0])?;
+            Ok(Value::Number(y.atan2(number("atan2", "Second", &args[1])?)))
+        })
+}
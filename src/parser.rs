@@ -1,8 +1,8 @@
-use std::rc::Rc;
 use std::vec;
 
-use crate::expr::{Expr, Value};
+use crate::expr::{Expr, ExprId, Value};
 use crate::loxerr::{self, ParseError};
+use crate::shared::Rc;
 use crate::stmt::Stmt;
 use crate::token::{Token, TokenType};
 use TokenType::*;
@@ -11,6 +11,8 @@ pub struct Parser<'a> {
     current: usize,
     tokens: &'a [Token],
     pub has_error: bool,
+    // How many blocks the current token is inside.
+    blocks: usize,
 }
 
 impl<'a> Parser<'a> {
@@ -19,6 +21,7 @@ impl<'a> Parser<'a> {
             current: 0,
             tokens,
             has_error: false,
+            blocks: 0,
         }
     }
 
@@ -38,8 +41,10 @@ impl<'a> Parser<'a> {
         self.peek().token_type == Eof
     }
 
+    // Before anything is consumed, the first token, so errors reported at
+    // the start of the input still point somewhere.
     fn previous(&self) -> &Token {
-        &self.tokens[self.current - 1]
+        &self.tokens[self.current.saturating_sub(1)]
     }
 
     fn advance(&mut self) -> &Token {
@@ -53,6 +58,29 @@ impl<'a> Parser<'a> {
         !self.is_at_end() && self.peek().token_type == *token_type
     }
 
+    // Words like `with` are only special in some places, so they are
+    // scanned as identifiers and still usable as names elsewhere.
+    fn check_word(&self, word: &str) -> bool {
+        self.check(&Identifier) && self.peek().lexeme == word
+    }
+
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == *token_type)
+    }
+
+    // Whether the token at `index` starts right where the one before it
+    // ends, with nothing between them.
+    fn touches_previous(&self, index: usize) -> bool {
+        index > 0 && self.tokens.get(index).is_some_and(|token| token.span.start == self.tokens[index - 1].span.end)
+    }
+
+    // `var name in` starts a for-in loop.
+    fn check_for_in(&self) -> bool {
+        self.check(&Var)
+            && self.check_next(&Identifier)
+            && self.tokens.get(self.current + 2).is_some_and(|token| token.token_type == Identifier && token.lexeme == "in")
+    }
+
     fn consume(&mut self, typ: TokenType, msg: &str) -> Result<&Token, ParseError> {
         if self.check(&typ) {
             Ok(self.advance())
@@ -73,20 +101,47 @@ impl<'a> Parser<'a> {
         false
     }
 
+    // Skips to where the next statement probably starts. Nested blocks are
+    // skipped whole, and the `}` closing the block being parsed is left for
+    // it, so a mistake doesn't end the enclosing function.
     fn synchronize(&mut self) {
-        self.advance();
+        let mut nesting = 0;
+        let mut first = true;
         while !self.is_at_end() {
-            if self.previous().token_type == Semicolon {
-                return;
+            match self.peek().token_type {
+                RightBrace if nesting == 0 && self.blocks > 0 => return,
+                Class | Fun | Var | For | If | While | Print | Return if nesting == 0 && !first => return,
+                _ => (),
             }
+            first = false;
+            match self.advance().token_type {
+                LeftBrace => nesting += 1,
+                RightBrace if nesting > 0 => nesting -= 1,
+                Semicolon if nesting == 0 => return,
+                _ => (),
+            }
+        }
+    }
 
+    // After a mistake inside an argument or parameter list, skips past the
+    // `)` closing it so the rest of the statement still parses. Gives up,
+    // returning false, where the statement or a block seems to start or end.
+    fn skip_to_paren(&mut self) -> bool {
+        let mut nesting = 0;
+        while !self.is_at_end() {
             match self.peek().token_type {
-                Class | Fun | Var | For | If | While | Print | Return => return,
-                _ => {
+                LeftParen => nesting += 1,
+                RightParen if nesting == 0 => {
                     self.advance();
+                    return true;
                 }
+                RightParen => nesting -= 1,
+                Semicolon | LeftBrace | RightBrace => return false,
+                _ => (),
             }
+            self.advance();
         }
+        false
     }
 
     fn declaration(&mut self) -> Stmt {
@@ -94,6 +149,12 @@ impl<'a> Parser<'a> {
             self.class_declaration()
         } else if self.is_match(&[Fun]) {
             self.function("function")
+        } else if self.check_word("interface") && self.check_next(&Identifier) {
+            self.advance();
+            self.interface_declaration()
+        } else if self.check_word("import") && self.tokens.get(self.current + 1).is_some_and(|token| matches!(token.token_type, StringLiteral(_))) {
+            self.advance();
+            self.import_declaration()
         } else if self.is_match(&[Var]) {
             self.var_declaration()
         } else {
@@ -114,11 +175,24 @@ impl<'a> Parser<'a> {
         
         let superclass = if self.is_match(&[Less]) {
             let superclass = self.consume(Identifier, "Expect superclass name.")?;
-            Some(Rc::new(Expr::Variable(superclass.clone())))
+            Some(Rc::new(Expr::Variable(ExprId::fresh(), superclass.clone())))
         } else {
             None
         };
 
+        let mixins = if self.check_word("with") {
+            self.advance();
+            self.class_names("Expect mixin name.")?
+        } else {
+            vec![]
+        };
+        let interfaces = if self.check_word("implements") {
+            self.advance();
+            self.class_names("Expect interface name.")?
+        } else {
+            vec![]
+        };
+
         self.consume(LeftBrace, "Expect '{' before class body.")?;
 
         let mut methods = vec![];
@@ -129,11 +203,59 @@ impl<'a> Parser<'a> {
         
 
         self.consume(RightBrace, "Expect '}' after class body.")?;
-        Ok(Stmt::Class(name, superclass, methods))
+        Ok(Stmt::Class(name, superclass, mixins, interfaces, methods))
+
+    }
+
+    // A comma separated list of at least one name.
+    fn class_names(&mut self, msg: &str) -> Result<Vec<Rc<Expr>>, ParseError> {
+        let mut names = vec![];
+        loop {
+            let name = self.consume(Identifier, msg)?;
+            names.push(Rc::new(Expr::Variable(ExprId::fresh(), name.clone())));
+            if !self.is_match(&[Comma]) {
+                return Ok(names);
+            }
+        }
+    }
+
+    fn interface_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.consume(Identifier, "Expect interface name.")?.clone();
+        self.consume(LeftBrace, "Expect '{' before interface body.")?;
+
+        let mut methods = vec![];
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            let method = self.consume(Identifier, "Expect method name.")?.clone();
+            self.consume(LeftParen, "Expect '(' after method name.")?;
+            let params = self.parameters()?;
+            self.consume(Semicolon, "Expect ';' after method signature.")?;
+            methods.push((method, params));
+        }
+
+        self.consume(RightBrace, "Expect '}' after interface body.")?;
+        Ok(Stmt::Interface(name, methods))
+    }
 
+    fn import_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let name = match &self.peek().token_type {
+            StringLiteral(name) => Rc::clone(name),
+            _ => {
+                loxerr::parse_error(self.peek(), "Expect module name after 'import'.");
+                self.has_error = true;
+                return Err(ParseError);
+            }
+        };
+        self.advance();
+        self.consume(Semicolon, "Expect ';' after import.")?;
+        Ok(Stmt::Import(keyword, name))
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        if self.is_match(&[LeftBracket]) {
+            return self.var_array_declaration();
+        }
+
         let name = self.consume(Identifier, "Expect variable name.")?.clone();
         let init = if self.is_match(&[Equal]) {
             Some(self.expression()?)
@@ -145,6 +267,22 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Var(name, init))
     }
 
+    fn var_array_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let mut names = vec![];
+        loop {
+            names.push(self.consume(Identifier, "Expect variable name.")?.clone());
+            if !self.is_match(&[Comma]) {
+                break;
+            }
+        }
+        self.consume(RightBracket, "Expect ']' after variable names.")?;
+        self.consume(Equal, "Expect '=' after variable names.")?;
+        let value = self.expression()?;
+        self.consume(Semicolon, "Expect ';' after variable declaration.")?;
+
+        Ok(Stmt::VarArray(names, value))
+    }
+
     fn statement(&mut self) -> Result<Stmt, ParseError> {
         if self.is_match(&[If]) {
             self.if_statement()
@@ -164,6 +302,7 @@ impl<'a> Parser<'a> {
     }
 
     fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
         self.consume(LeftParen, "Expect '(' after 'if'.")?;
         let expr = self.expression()?;
         self.consume(RightParen, "Expect ')' after if condition.")?;
@@ -176,39 +315,52 @@ impl<'a> Parser<'a> {
             None
         };
 
-        Ok(Stmt::If(expr, if_stmt, else_stmt))
+        Ok(Stmt::If(keyword, expr, if_stmt, else_stmt))
     }
 
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
         let expr = self.expression()?;
-        self.consume(Semicolon, &"Expect ';' after value.")?;
-        Ok(Stmt::Print(expr))
+        self.consume(Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(keyword, expr))
     }
 
     fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
         self.consume(LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(RightParen, "Expect ')' after condition.")?;
 
         let body = self.statement()?;
-        Ok(Stmt::While(condition, Box::new(body)))
+        Ok(Stmt::While(keyword, condition, Box::new(body)))
     }
 
     fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
         self.consume(LeftParen, "Expect '(' after 'for'.")?;
 
+        if self.check_for_in() {
+            self.advance();
+            let name = self.advance().clone();
+            self.advance();
+            let iterable = self.expression()?;
+            self.consume(RightParen, "Expect ')' after for-in clause.")?;
+            let body = self.statement()?;
+            return Ok(Stmt::ForIn(keyword, name, iterable, Box::new(body)));
+        }
+
         let initializer = if self.is_match(&[Semicolon]) {
-            Stmt::Null
+            None
         } else if self.is_match(&[Var]) {
-            self.var_declaration()?
+            Some(Box::new(self.var_declaration()?))
         } else {
-            self.expression_statement()?
+            Some(Box::new(self.expression_statement()?))
         };
 
         let condition = if !self.check(&Semicolon) {
-            self.expression()?
+            Some(self.expression()?)
         } else {
-            Rc::new(Expr::Literal(Value::Bool(true)))
+            None
         };
         self.consume(Semicolon, "Expect ';' after loop condition.")?;
 
@@ -219,16 +371,9 @@ impl<'a> Parser<'a> {
         };
         self.consume(RightParen, "Expect ')' after for clauses.")?;
 
-        let mut body = self.statement()?;
-
-        if let Some(increment) = increment {
-            body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
-        }
-
-        body = Stmt::While(condition, Box::new(body));
-        body = Stmt::Block(vec![initializer, body]);
+        let body = self.statement()?;
 
-        return Ok(body);
+        Ok(Stmt::For(keyword, initializer, condition, increment, Box::new(body)))
     }
 
     fn return_statement(&mut self) -> Result<Stmt, ParseError> {
@@ -245,9 +390,10 @@ impl<'a> Parser<'a> {
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let first = self.peek().clone();
         let expr = self.expression()?;
-        self.consume(Semicolon, &"Expect ';' after expression.")?;
-        Ok(Stmt::Expression(expr))
+        self.consume(Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression(first, expr))
     }
 
     fn function(&mut self, kind: &str) -> Result<Stmt, ParseError> {
@@ -255,7 +401,20 @@ impl<'a> Parser<'a> {
             .consume(Identifier, &format!("Expect {} name", kind))?
             .clone();
         self.consume(LeftParen, &format!("Expect '(' after {} name.", kind))?;
+        let parameters = match self.parameters() {
+            Ok(parameters) => parameters,
+            Err(_) if self.skip_to_paren() => vec![],
+            Err(e) => return Err(e),
+        };
+
+        self.consume(LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
 
+        let body = self.block()?;
+        Ok(Stmt::Function(name, Rc::new(parameters), Rc::new(body)))
+    }
+
+    // The parameter list after the opening parenthesis, closing one included.
+    fn parameters(&mut self) -> Result<Vec<Token>, ParseError> {
         let mut parameters = vec![];
         if !self.check(&RightParen) {
             loop {
@@ -272,19 +431,17 @@ impl<'a> Parser<'a> {
             }
         }
         self.consume(RightParen, "Expect ')' after parameters.")?;
-
-        self.consume(LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
-
-        let body = self.block()?;
-        Ok(Stmt::Function(name, parameters, body))
+        Ok(parameters)
     }
 
     fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut stmts = Vec::new();
 
+        self.blocks += 1;
         while !self.check(&RightBrace) && !self.is_at_end() {
             stmts.push(self.declaration());
         }
+        self.blocks -= 1;
 
         self.consume(RightBrace, "Expect '}' after block.")?;
         Ok(stmts)
@@ -301,10 +458,16 @@ impl<'a> Parser<'a> {
             let equals = self.previous().clone();
             let value = self.assignment()?;
 
-            if let Expr::Variable(t) = &*expr {
-                return Ok(Rc::new(Expr::Assign(t.clone(), value)));
-            } else if let Expr::Get(obj, token) = &*expr {
+            if let Expr::Variable(_, t) = &*expr {
+                return Ok(Rc::new(Expr::Assign(ExprId::fresh(), t.clone(), value)));
+            } else if let Expr::Get(_, obj, token) = &*expr {
                 return Ok(Rc::new(Expr::Set(Rc::clone(obj), token.clone(), value)));
+            } else if let Expr::Index(obj, bracket, index) = &*expr {
+                return Ok(Rc::new(Expr::SetIndex(Rc::clone(obj), bracket.clone(), Rc::clone(index), value)));
+            } else if let Expr::Array(targets) = &*expr {
+                if targets.iter().all(|target| matches!(**target, Expr::Variable(..))) {
+                    return Ok(Rc::new(Expr::AssignArray(targets.clone(), equals, value)));
+                }
             }
 
             loxerr::parse_error(&equals, "Invalid assignment target.");
@@ -335,8 +498,42 @@ impl<'a> Parser<'a> {
     }
 
     fn equality(&mut self) -> Result<Rc<Expr>, ParseError> {
-        let mut expr = self.comparison()?;
+        let mut expr = self.bit_or()?;
         while self.is_match(&[BangEqual, EqualEqual]) {
+            let op = self.previous().clone();
+            let right = self.bit_or()?;
+            expr = Rc::new(Expr::Binary(expr, op, right));
+        }
+        Ok(expr)
+    }
+
+    // The bitwise operators bind tighter than equality, unlike in C, so
+    // `flags & mask == 0` means `(flags & mask) == 0`. From loosest to
+    // tightest: `|`, `^`, `&`, then comparison, ranges and the shifts, which
+    // sit just above `+` and `-`.
+    fn bit_or(&mut self) -> Result<Rc<Expr>, ParseError> {
+        let mut expr = self.bit_xor()?;
+        while self.is_match(&[Pipe]) {
+            let op = self.previous().clone();
+            let right = self.bit_xor()?;
+            expr = Rc::new(Expr::Binary(expr, op, right));
+        }
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> Result<Rc<Expr>, ParseError> {
+        let mut expr = self.bit_and()?;
+        while self.is_match(&[Caret]) {
+            let op = self.previous().clone();
+            let right = self.bit_and()?;
+            expr = Rc::new(Expr::Binary(expr, op, right));
+        }
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> Result<Rc<Expr>, ParseError> {
+        let mut expr = self.comparison()?;
+        while self.is_match(&[Ampersand]) {
             let op = self.previous().clone();
             let right = self.comparison()?;
             expr = Rc::new(Expr::Binary(expr, op, right));
@@ -345,8 +542,36 @@ impl<'a> Parser<'a> {
     }
 
     fn comparison(&mut self) -> Result<Rc<Expr>, ParseError> {
+        let mut expr = self.range()?;
+        loop {
+            if self.is_match(&[Greater, GreaterEqual, Less, LessEqual]) {
+                let op = self.previous().clone();
+                let right = self.range()?;
+                expr = Rc::new(Expr::Binary(expr, op, right));
+            } else if self.check_word("is") {
+                let keyword = self.advance().clone();
+                let right = self.range()?;
+                expr = Rc::new(Expr::Is(expr, keyword, right));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // Ranges don't chain, `a..b..c` is an error.
+    fn range(&mut self) -> Result<Rc<Expr>, ParseError> {
+        let expr = self.shift()?;
+        if self.is_match(&[DotDot]) {
+            let op = self.previous().clone();
+            let end = self.shift()?;
+            return Ok(Rc::new(Expr::Range(expr, op, end)));
+        }
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> Result<Rc<Expr>, ParseError> {
         let mut expr = self.term()?;
-        while self.is_match(&[Greater, GreaterEqual, Less, LessEqual]) {
+        while self.is_match(&[LessLess, GreaterGreater]) {
             let op = self.previous().clone();
             let right = self.term()?;
             expr = Rc::new(Expr::Binary(expr, op, right));
@@ -364,18 +589,26 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    // `div` is floored division. It's only an operator here, so it stays
+    // usable as a name.
     fn factor(&mut self) -> Result<Rc<Expr>, ParseError> {
         let mut expr = self.unary()?;
-        while self.is_match(&vec![Slash, Star]) {
-            let op = self.previous().clone();
+        loop {
+            let op = if self.is_match(&[Slash, Star]) {
+                self.previous().clone()
+            } else if self.check_word("div") {
+                let word = self.advance();
+                Token::new(Div, "div", word.line).with_span(word.span)
+            } else {
+                return Ok(expr);
+            };
             let right = self.unary()?;
-            expr = Rc::new(Expr::Binary(expr, op,right));
+            expr = Rc::new(Expr::Binary(expr, op, right));
         }
-        Ok(expr)
     }
 
     fn unary(&mut self) -> Result<Rc<Expr>, ParseError> {
-        if self.is_match(&vec![Bang, Minus]) {
+        if self.is_match(&[Bang, Minus, Tilde]) {
             let op = self.previous().clone();
             let right = self.unary()?;
             return Ok(Rc::new(Expr::Unary(op, right)));
@@ -390,8 +623,19 @@ impl<'a> Parser<'a> {
             if self.is_match(&[LeftParen]) {
                 expr = self.finish_call(expr)?;
             } else if self.is_match(&[Dot]){
+                // `5.` isn't a number, as `5.floor()` is a method call.
+                if !self.check(&Identifier) && matches!(*expr, Expr::Literal(Value::Number(_))) && self.touches_previous(self.current - 1) {
+                    loxerr::parse_error(self.previous(), "Expect digits after decimal point.");
+                    self.has_error = true;
+                    return Err(ParseError);
+                }
                 let name = self.consume(Identifier, "Expect property name after '.'.")?;
-                expr = Rc::new(Expr::Get(expr, name.clone()));
+                expr = Rc::new(Expr::Get(ExprId::fresh(), expr, name.clone()));
+            } else if self.is_match(&[LeftBracket]) {
+                let bracket = self.previous().clone();
+                let index = self.expression()?;
+                self.consume(RightBracket, "Expect ']' after index.")?;
+                expr = Rc::new(Expr::Index(expr, bracket, index));
             } else {
                 break;
             }
@@ -400,6 +644,17 @@ impl<'a> Parser<'a> {
     }
 
     fn finish_call(&mut self, callee: Rc<Expr>) -> Result<Rc<Expr>, ParseError> {
+        let args = match self.arguments() {
+            Ok(args) => args,
+            Err(_) if self.skip_to_paren() => vec![],
+            Err(e) => return Err(e),
+        };
+        let paren = self.previous().clone();
+        Ok(Rc::new(Expr::Call(callee, paren, args)))
+    }
+
+    // The argument list after the opening parenthesis, closing one included.
+    fn arguments(&mut self) -> Result<Vec<Rc<Expr>>, ParseError> {
         let mut args = vec![];
 
         if !self.check(&RightParen) {
@@ -415,9 +670,8 @@ impl<'a> Parser<'a> {
             }
         }
 
-        let paren = self.consume(RightParen, "Expect ')' after arguments.")?;
-
-        Ok(Rc::new(Expr::Call(callee, paren.clone(), args)))
+        self.consume(RightParen, "Expect ')' after arguments.")?;
+        Ok(args)
     }
 
     fn primary(&mut self) -> Result<Rc<Expr>, ParseError> {
@@ -440,7 +694,7 @@ impl<'a> Parser<'a> {
                 Ok(l)
             }
             StringLiteral(x) => {
-                let l = Rc::new(Expr::Literal(Value::String(x.clone())));
+                let l = Rc::new(Expr::Literal(Value::String(Rc::clone(x))));
                 self.advance();
                 Ok(l)
             }
@@ -450,22 +704,54 @@ impl<'a> Parser<'a> {
                 self.consume(Dot, "Expect '.' after 'super'.")?;
 
                 let method = self.consume(Identifier, "Expect superclass method name.")?;
-                Ok(Rc::new(Expr::Super(keyword, method.clone())))
+                Ok(Rc::new(Expr::Super(ExprId::fresh(), keyword, method.clone())))
             }
             This => {
                 self.advance();
-                Ok(Rc::new(Expr::This(self.previous().clone())))
+                Ok(Rc::new(Expr::This(ExprId::fresh(), self.previous().clone())))
             }
             Identifier => {
                 self.advance();
-                Ok(Rc::new(Expr::Variable(self.previous().clone())))
+                Ok(Rc::new(Expr::Variable(ExprId::fresh(), self.previous().clone())))
+            }
+            LeftBracket => {
+                self.advance();
+                let mut elements = vec![];
+                if !self.check(&RightBracket) {
+                    loop {
+                        elements.push(self.expression()?);
+                        if !self.is_match(&[Comma]) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(RightBracket, "Expect ']' after array elements.")?;
+                Ok(Rc::new(Expr::Array(elements)))
             }
             LeftParen => {
                 self.advance();
                 let e = self.expression()?;
+                // A comma makes it a tuple.
+                if self.is_match(&[Comma]) {
+                    let mut elements = vec![e];
+                    loop {
+                        elements.push(self.expression()?);
+                        if !self.is_match(&[Comma]) {
+                            break;
+                        }
+                    }
+                    self.consume(RightParen, "Expect ')' after tuple elements.")?;
+                    return Ok(Rc::new(Expr::Tuple(elements)));
+                }
                 self.consume(RightParen, "Expect ')' after expression.")?;
                 Ok(Rc::new(Expr::Grouping(e)))
             }
+            // `.5` isn't a number either, it needs a digit before the point.
+            Dot if self.tokens.get(self.current + 1).is_some_and(|token| matches!(token.token_type, Number(_))) && self.touches_previous(self.current + 1) => {
+                self.has_error = true;
+                loxerr::parse_error(self.peek(), "Expect digits before decimal point.");
+                Err(ParseError)
+            }
             _ => {
                 self.has_error = true;
                 loxerr::parse_error(self.peek(), "Expect expression.");
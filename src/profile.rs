@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::symbol::Symbol;
+
+// Calls and time spent in one function, method, class or native. `total`
+// runs from call to return, counting only the outermost of recursive calls,
+// and `self_time` leaves out the time spent in the calls it made.
+#[derive(Clone, Debug)]
+pub struct FunctionProfile {
+    pub name: String,
+    // Where the function or class is declared, 0 for natives.
+    pub line: usize,
+    pub calls: u64,
+    pub total: Duration,
+    pub self_time: Duration,
+}
+
+type Key = (Symbol, usize);
+
+#[derive(Default)]
+pub struct Profiler {
+    functions: HashMap<Key, FunctionProfile>,
+    // One entry per call in progress: when it started and how long the calls
+    // it made took.
+    stack: Vec<(Instant, Duration)>,
+    active: HashMap<Key, usize>,
+}
+
+impl Profiler {
+    pub fn enter(&mut self, name: &Symbol, line: usize) {
+        self.stack.push((Instant::now(), Duration::ZERO));
+        *self.active.entry((name.clone(), line)).or_insert(0) += 1;
+    }
+
+    pub fn exit(&mut self, name: &Symbol, line: usize) {
+        let (start, callees) = self.stack.pop().expect("Profiler exit without enter.");
+        let elapsed = start.elapsed();
+        if let Some((_, parent_callees)) = self.stack.last_mut() {
+            *parent_callees += elapsed;
+        }
+
+        let key = (name.clone(), line);
+        let active = self.active.get_mut(&key).unwrap();
+        *active -= 1;
+        let outermost = *active == 0;
+
+        let function = self.functions.entry(key).or_insert_with(|| FunctionProfile {
+            name: name.to_string(),
+            line,
+            calls: 0,
+            total: Duration::ZERO,
+            self_time: Duration::ZERO,
+        });
+        function.calls += 1;
+        function.self_time += elapsed.saturating_sub(callees);
+        if outermost {
+            function.total += elapsed;
+        }
+    }
+
+    // Sorted by total time, longest first.
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let mut functions: Vec<_> = self.functions.values().cloned().collect();
+        functions.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+        functions
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// A table with one function per row, in the order given.
+pub fn report(functions: &[FunctionProfile]) -> String {
+    let mut out = String::new();
+    let width = functions.iter().map(|function| function.name.len()).max().unwrap_or(0).max(8);
+    writeln!(out, "{:<width$}  {:>6}  {:>10}  {:>12}  {:>12}", "function", "line", "calls", "total", "self", width = width).unwrap();
+    for function in functions {
+        let line = match function.line {
+            0 => "native".to_owned(),
+            line => line.to_string(),
+        };
+        writeln!(
+            out,
+            "{:<width$}  {:>6}  {:>10}  {:>10.1}ms  {:>10.1}ms",
+            function.name,
+            line,
+            function.calls,
+            millis(function.total),
+            millis(function.self_time),
+            width = width
+        )
+        .unwrap();
+    }
+    out
+}
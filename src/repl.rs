@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::expr::Value;
+use crate::interpreter::Interpreter;
+use crate::lox::{self, Timings};
+use crate::loxerr::LoxError;
+use crate::shared::Rc;
+
+// How the prompt echoes a value. Unlike `print`, strings are quoted, so `"3"`
+// and `3` or `""` and `nil` can be told apart, and instances show their
+// fields. A value inside itself shows as `...`.
+pub fn show(value: &Value) -> String {
+    let mut out = String::new();
+    Shower { open: HashSet::new() }.write(value, &mut out);
+    out
+}
+
+struct Shower {
+    // The arrays, maps and instances being written.
+    open: HashSet<usize>,
+}
+
+impl Shower {
+    fn write(&mut self, value: &Value, out: &mut String) {
+        match value {
+            Value::String(s) => quote(s, out),
+            Value::Array(array) => {
+                let id = Rc::as_ptr(array) as usize;
+                if !self.open.insert(id) {
+                    return out.push_str("...");
+                }
+                let elements = array.borrow().clone();
+                self.list("[", &elements, "]", out);
+                self.open.remove(&id);
+            }
+            Value::Tuple(elements) => self.list("(", elements, ")", out),
+            Value::Set(set) => {
+                let elements = set.borrow().elements();
+                self.list("{", &elements, "}", out);
+            }
+            Value::Map(map) => {
+                let id = Rc::as_ptr(map) as usize;
+                if !self.open.insert(id) {
+                    return out.push_str("...");
+                }
+                let entries = map.borrow().entries();
+                out.push('{');
+                for (idx, (key, value)) in entries.iter().enumerate() {
+                    if idx > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write(key, out);
+                    out.push_str(": ");
+                    self.write(value, out);
+                }
+                out.push('}');
+                self.open.remove(&id);
+            }
+            Value::Instance(instance) => {
+                let instance = instance.borrow();
+                out.push_str(&instance.to_string());
+                let id = Rc::as_ptr(&instance.fields) as usize;
+                let names = instance.field_names();
+                if names.is_empty() {
+                    return;
+                }
+                if !self.open.insert(id) {
+                    return out.push_str(" {...}");
+                }
+                out.push_str(" {");
+                for (idx, name) in names.iter().enumerate() {
+                    if idx > 0 {
+                        out.push_str(", ");
+                    }
+                    out.push_str(name);
+                    out.push_str(": ");
+                    let value = instance.fields.borrow().get(name).cloned().unwrap();
+                    self.write(&value, out);
+                }
+                out.push('}');
+                self.open.remove(&id);
+            }
+            _ => out.push_str(&value.to_string()),
+        }
+    }
+
+    fn list(&mut self, open: &str, elements: &[Value], close: &str, out: &mut String) {
+        out.push_str(open);
+        for (idx, element) in elements.iter().enumerate() {
+            if idx > 0 {
+                out.push_str(", ");
+            }
+            self.write(element, out);
+        }
+        out.push_str(close);
+    }
+}
+
+// Double quotes, with the escapes Lox reads for anything that wouldn't show.
+fn quote(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// Runs the script at `path` into the session, as `:load path` does, so its
+// definitions replace any of the same name in the globals. Errors in the
+// script are reported as for a line typed at the prompt, and its imports
+// are found next to it. Err with the message to show when it can't be read.
+pub fn load(interpreter: &mut Interpreter, path: &str) -> Result<(Result<i32, Vec<LoxError>>, Timings), String> {
+    let source =
+        fs::read_to_string(path).map_err(|err| format!("Could not read '{}': {}.", path, lox::read_failure(&err)))?;
+    interpreter.set_script(Some(Path::new(path)));
+    let result = lox::run(interpreter, &source);
+    let _ = interpreter.finish_line();
+    interpreter.set_script(None);
+    Ok(result)
+}
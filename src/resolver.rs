@@ -1,12 +1,15 @@
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
 
-use crate::expr::Expr;
+use crate::expr::{Expr, ExprId};
 use crate::interpreter::Interpreter;
-use crate::loxerr;
-use crate::stmt::Stmt;
-use crate::token::Token;
+use crate::loxerr::{self, Phase, Severity, Warnings};
+use crate::shared::Rc;
+use crate::spelling;
+use crate::stmt::{self, Stmt};
+use crate::symbol::Symbol;
+use crate::token::{Span, Token};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq)]
 enum FunctionType {
     NONE,
@@ -15,6 +18,7 @@ enum FunctionType {
     METHOD,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq)]
 enum ClassType {
     NONE, 
@@ -22,12 +26,36 @@ enum ClassType {
     SUBCLASS,
 }
 
+// A local's slot is its position among the declarations of its scope, which
+// is also where the interpreter stores it at runtime.
+struct Local {
+    slot: usize,
+    defined: bool,
+    // Where a `var` declared it, as strict mode wants those read.
+    declaration: Option<Token>,
+    read: bool,
+    // Where its name is, for `this` and `super` nowhere.
+    span: Option<Span>,
+}
+
 pub struct Resolver<'a> {
     interpreter: &'a mut Interpreter,
-    scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<Symbol, Local>>,
     pub has_error: bool,
     current_function: FunctionType,
     current_class: ClassType,
+    // What strict mode knows of the globals: the ones the program declares
+    // and the ones the interpreter already has, and whether the program
+    // imports any it can't see.
+    globals: HashSet<Symbol>,
+    declared_globals: HashSet<Symbol>,
+    imports: bool,
+    // Where each use of a variable was declared, when `record_definitions`
+    // asked for it: the locals as they are resolved, and the globals by name,
+    // as they can be declared after their uses.
+    definitions: Option<Vec<(Span, Span)>>,
+    global_uses: Vec<(Span, Symbol)>,
+    global_declarations: HashMap<Symbol, Span>,
 }
 
 impl<'a> Resolver<'a> {
@@ -38,51 +66,115 @@ impl<'a> Resolver<'a> {
             has_error: false,
             current_function: FunctionType::NONE,
             current_class: ClassType::NONE,
+            globals: HashSet::new(),
+            declared_globals: HashSet::new(),
+            imports: false,
+            definitions: None,
+            global_uses: Vec::new(),
+            global_declarations: HashMap::new(),
         }
     }
 
+    // For the language server's go-to-definition, see `definitions`.
+    pub fn record_definitions(&mut self) {
+        self.definitions = Some(Vec::new());
+    }
+
+    // The span of every use of a variable resolved so far, with the span of
+    // the name it was declared with. Globals are declared by their first
+    // declaration in the program. Uses of builtins and of what's imported
+    // aren't there.
+    pub fn definitions(&self) -> Vec<(Span, Span)> {
+        let mut definitions = self.definitions.clone().unwrap_or_default();
+        for (span, name) in &self.global_uses {
+            if let Some(declaration) = self.global_declarations.get(name) {
+                definitions.push((*span, *declaration));
+            }
+        }
+        definitions.sort_by_key(|(span, _)| span.start);
+        definitions
+    }
+
     pub fn resolve(&mut self, stmts: &Vec<Stmt>) {
+        if self.interpreter.strict() && self.scopes.is_empty() {
+            self.collect_globals(stmts);
+        }
         for stmt in stmts {
             self.resolve_stmt(stmt);
         }
     }
 
+    // Functions can assign globals declared further down, so they are all
+    // known before anything is resolved.
+    fn collect_globals(&mut self, stmts: &[Stmt]) {
+        self.globals.extend(self.interpreter.globals.borrow().values.keys().cloned());
+        for stmt in stmts {
+            match stmt {
+                Stmt::Class(name, ..)
+                | Stmt::Function(name, ..)
+                | Stmt::Interface(name, _)
+                | Stmt::Var(name, _) => {
+                    self.globals.insert(name.lexeme.clone());
+                }
+                Stmt::VarArray(names, _) => self.globals.extend(names.iter().map(|name| name.lexeme.clone())),
+                Stmt::Import(..) => self.imports = true,
+                _ => (),
+            }
+        }
+    }
+
     fn resolve_stmt(&mut self, stmt: &Stmt) {
         match stmt {
             Stmt::Null => (),
+            Stmt::Block(stmts) if !stmt::needs_scope(stmts) => self.resolve(stmts),
             Stmt::Block(stmts) => {
                 self.begin_scope();
                 self.resolve(stmts);
                 self.end_scope();
             }
-            Stmt::Class(name, superclass, methods) => {
+            Stmt::Class(name, superclass, mixins, interfaces, methods) => {
                 let enclosing_class = std::mem::replace(&mut self.current_class, ClassType::CLASS);
                 self.declare(name);
                 self.define(name);
 
                 if let Some(superclass) = superclass {
                     self.current_class = ClassType::SUBCLASS;
-                    if let Expr::Variable(token) = &**superclass {
+                    if let Expr::Variable(_, token) = &**superclass {
                         if  token.lexeme == name.lexeme {
-                            loxerr::parse_error(name, "A class can't inherit from itself.");
+                            loxerr::resolve_error(name, "A class can't inherit from itself.");
                             self.has_error = true;
                         }
                     }
 
                     self.resolve_expr(superclass);
+                }
+
+                for mixin in mixins {
+                    if let Expr::Variable(_, token) = &**mixin {
+                        if token.lexeme == name.lexeme {
+                            loxerr::resolve_error(token, "A class can't mix in itself.");
+                            self.has_error = true;
+                        }
+                    }
+                    self.resolve_expr(mixin);
+                }
+                for interface in interfaces {
+                    self.resolve_expr(interface);
+                }
 
+                if superclass.is_some() {
                     self.begin_scope();
-                    self.scopes.last_mut().unwrap().insert("super".to_owned(), true);
+                    self.define_implicit(Symbol::super_());
                 }
 
                 
 
                 self.begin_scope();
-                self.scopes.last_mut().unwrap().insert("this".to_owned(), true);
+                self.define_implicit(Symbol::this());
 
                 for stmt in methods {
                     if let Stmt::Function(name, params , body) = stmt {
-                        let  declaration = if name.lexeme == "init" {
+                        let  declaration = if name.lexeme == Symbol::init() {
                             FunctionType::INITIALIZER
                         } else {
                             FunctionType::METHOD
@@ -94,64 +186,112 @@ impl<'a> Resolver<'a> {
                 }
 
                 self.end_scope();
-                if let Some(_) = superclass { self.end_scope(); }
+                if superclass.is_some() { self.end_scope(); }
 
                 self.current_class = enclosing_class;
             }
-            Stmt::Expression(expr) => self.resolve_expr(expr),
+            Stmt::Expression(_, expr) => self.resolve_expr(expr),
             Stmt::Function(token, params, body) => {
                 self.declare(token);
                 self.define(token);
                 self.resolve_function(params, body, FunctionType::FUNCTION);
             }
-            Stmt::If(condition, then_branch, else_branch) => {
+            Stmt::Interface(name, _) => {
+                self.declare(name);
+                self.define(name);
+            }
+            Stmt::If(_, condition, then_branch, else_branch) => {
                 self.resolve_expr(condition);
                 self.resolve_stmt(then_branch);
                 if let Some(else_branch) = else_branch {
                     self.resolve_stmt(else_branch);
                 }
             }
-            Stmt::Print(expr) => self.resolve_expr(expr),
+            Stmt::Print(_, expr) => self.resolve_expr(expr),
             Stmt::Return(name, ret_expr) => {
-                if self.current_function == FunctionType::NONE {
-                    loxerr::parse_error(
-                        name,
-                        "Can't return from top-level code."
-                    );
-                    self.has_error = true;
-                    ()
-                } else if let Some(value) = ret_expr {
+                // Outside any function, it ends the script.
+                if let Some(value) = ret_expr {
                     if self.current_function == FunctionType::INITIALIZER {
-                        loxerr::parse_error(
+                        loxerr::resolve_error(
                             name,
                             "Can't return a value from an initializer."
                         );
                         self.has_error = true;
                     }
                     self.resolve_expr(value)
-                } else {
-                    ()
+                }
+            }
+            // What it defines is global, so there's nothing to resolve. It
+            // has to run while its script does, to be found next to it.
+            Stmt::Import(keyword, _) => {
+                if self.current_function != FunctionType::NONE {
+                    loxerr::resolve_error(keyword, "Can't import inside a function.");
+                    self.has_error = true;
+                }
+            }
+            Stmt::VarArray(names, value) => {
+                for name in names {
+                    self.declare_variable(name);
+                }
+                self.resolve_expr(value);
+                for name in names {
+                    self.define(name);
                 }
             }
             Stmt::Var(token, init) => {
-                self.declare(token);
+                self.declare_variable(token);
                 if let Some(init) = init {
                     self.resolve_expr(init);
                 }
                 self.define(token);
             }
-            Stmt::While(condition, body) => {
+            Stmt::While(_, condition, body) => {
                 self.resolve_expr(condition);
                 self.resolve_stmt(body);
             }
+            Stmt::ForIn(_, name, iterable, body) => {
+                self.resolve_expr(iterable);
+                self.begin_scope();
+                self.declare(name);
+                self.define(name);
+                self.resolve_stmt(body);
+                self.end_scope();
+            }
+            Stmt::For(_, initializer, condition, increment, body) => {
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.resolve_stmt(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.resolve_expr(condition);
+                }
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+                self.resolve_stmt(body);
+                self.end_scope();
+            }
         }
     }
 
     fn resolve_expr(&mut self, expr: &Rc<Expr>) {
         match &**expr {
-            Expr::Assign(token, right) => {
+            Expr::Array(elements) => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Assign(id, token, right) => {
+                self.resolve_expr(right);
+                self.resolve_assignment(id, token);
+            }
+            Expr::AssignArray(targets, _, right) => {
                 self.resolve_expr(right);
-                self.resolve_local(expr, token)
+                for target in targets {
+                    if let Expr::Variable(id, token) = &**target {
+                        self.resolve_assignment(id, token);
+                    }
+                }
             }
 
             Expr::Binary(left, _op, right) => {
@@ -160,13 +300,35 @@ impl<'a> Resolver<'a> {
             }
 
             Expr::Call(callee, _, body) => {
-                self.resolve_expr(&callee);
+                self.resolve_expr(callee);
                 for expr in body {
                     self.resolve_expr(expr);
                 }
             }
-            Expr::Get(object, _) => self.resolve_expr(object),
+            Expr::Get(_, object, _) => self.resolve_expr(object),
             Expr::Grouping(expr) => self.resolve_expr(expr),
+            Expr::Range(start, _, end) => {
+                self.resolve_expr(start);
+                self.resolve_expr(end);
+            }
+            Expr::Index(object, _, index) => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::SetIndex(object, _, index, value) => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::Tuple(elements) => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Is(value, _, class) => {
+                self.resolve_expr(value);
+                self.resolve_expr(class);
+            }
             Expr::Literal(..) => (),
             Expr::Logical(left, _op, right) => {
                 self.resolve_expr(left);
@@ -177,60 +339,91 @@ impl<'a> Resolver<'a> {
               self.resolve_expr(value);  
             }
             
-            Expr::Super(keyword, _) => {
+            Expr::Super(id, keyword, _) => {
                 if self.current_class == ClassType::NONE {
-                    loxerr::parse_error(
+                    loxerr::resolve_error(
                         keyword,
                         "Can't use 'super' outside of a class."
                     );
                     self.has_error = true;
                 } else if self.current_class != ClassType::SUBCLASS {
-                    loxerr::parse_error(
+                    loxerr::resolve_error(
                         keyword,
                         "Can't use 'super' in a class with no superclass."
                     );
                     self.has_error = true;
                 }
-                self.resolve_local(expr, keyword)
+                self.resolve_local(id, keyword, true);
             }
 
-            Expr::This(token) => {
+            Expr::This(id, token) => {
                 if self.current_class == ClassType::NONE {
-                    loxerr::parse_error(
+                    loxerr::resolve_error(
                         token,
                         "Can't use 'this' outside of a class."
                     );
                     self.has_error = true;
                 } else {
-                    self.resolve_local(expr, token);
+                    self.resolve_local(id, token, true);
                 }
                 
 
             } 
             Expr::Unary(_, expr) => self.resolve_expr(expr),
-            Expr::Variable(token) => {
-                if let Some(scope) = self.scopes.last() {
-                    if scope.get(&token.lexeme) == Some(&false) {
-                        loxerr::parse_error(
-                            token,
-                            "Can't read local variable in its own initializer.",
-                        );
-                        self.has_error = true;
-                    } else {
-                        self.resolve_local(expr, token);
-                    }
+            Expr::Variable(id, token) => {
+                let scope = self.scopes.last();
+                if matches!(scope.and_then(|scope| scope.get(&token.lexeme)), Some(Local { defined: false, .. })) {
+                    loxerr::resolve_error(
+                        token,
+                        "Can't read local variable in its own initializer.",
+                    );
+                    self.has_error = true;
+                } else {
+                    self.resolve_local(id, token, true);
                 }
             }
         }
     }
 
-    fn resolve_local(&mut self, expr: &Rc<Expr>, name: &Token) {
-        for (i, scope) in self.scopes.iter().rev().enumerate() {
-            if scope.contains_key(&name.lexeme) {
-                self.interpreter.resolve(expr, i);
-                return;
+    // Returns whether `name` is a local. Assigning one doesn't count as
+    // reading it.
+    fn resolve_local(&mut self, id: &ExprId, name: &Token, read: bool) -> bool {
+        for (i, scope) in self.scopes.iter_mut().rev().enumerate() {
+            if let Some(local) = scope.get_mut(&name.lexeme) {
+                local.read |= read;
+                self.interpreter.resolve(id, i, local.slot);
+                if let (Some(definitions), Some(span)) = (&mut self.definitions, local.span) {
+                    definitions.push((name.span, span));
+                }
+                return true;
             }
         }
+        if self.definitions.is_some() {
+            self.global_uses.push((name.span, name.lexeme.clone()));
+        }
+        false
+    }
+
+    fn resolve_assignment(&mut self, id: &ExprId, name: &Token) {
+        let scope = self.scopes.last();
+        if matches!(scope.and_then(|scope| scope.get(&name.lexeme)), Some(Local { defined: false, .. })) {
+            loxerr::resolve_error(name, "Can't assign to local variable in its own initializer.");
+            self.has_error = true;
+            return;
+        }
+        if self.resolve_local(id, name, false) {
+            return;
+        }
+        if self.interpreter.strict() && !self.imports && !self.globals.contains(&name.lexeme) {
+            let mut candidates: Vec<Symbol> = self.globals.iter().cloned().collect();
+            candidates.extend(self.scopes.iter().flat_map(|scope| scope.keys().cloned()));
+            let msg = format!(
+                "Assignment to undeclared variable '{}'.{}",
+                name.lexeme,
+                spelling::did_you_mean(&name.lexeme, &candidates)
+            );
+            self.strict_error(name, &msg);
+        }
     }
 
     fn resolve_function(&mut self, params: &Vec<Token>, body: &Vec<Stmt>, ftype: FunctionType) {
@@ -251,25 +444,91 @@ impl<'a> Resolver<'a> {
     }
 
     fn end_scope(&mut self) {
-        self.scopes.pop();
+        let scope = self.scopes.pop().unwrap();
+        if !self.interpreter.strict() {
+            return;
+        }
+        let mut unread: Vec<&Local> = scope.values().filter(|local| !local.read && local.declaration.is_some()).collect();
+        unread.sort_by_key(|local| local.slot);
+        for local in unread {
+            let name = local.declaration.as_ref().unwrap();
+            // An underscore says it's meant to go unused.
+            if !name.lexeme.starts_with('_') {
+                self.strict_error(name, &format!("Local variable '{}' is never read.", name.lexeme));
+            }
+        }
+    }
+
+    // A local declared by `var`, which strict mode wants read.
+    fn declare_variable(&mut self, name: &Token) {
+        self.declare(name);
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(&name.lexeme)) {
+            local.declaration = Some(name.clone());
+        }
     }
 
     fn declare(&mut self, name: &Token) {
+        if self.scopes.is_empty() && self.interpreter.strict() && !self.declared_globals.insert(name.lexeme.clone()) {
+            self.strict_error(name, "Already a global variable with this name.");
+        }
+        if self.scopes.is_empty() && self.definitions.is_some() {
+            self.global_declarations.entry(name.lexeme.clone()).or_insert(name.span);
+        }
         if let Some(scope) = self.scopes.last_mut() {
-            if scope.contains_key(&name.lexeme) {
-                loxerr::parse_error(
+            if let Some(local) = scope.get_mut(&name.lexeme) {
+                local.defined = false;
+                loxerr::resolve_error(
                     name,
                     "Already a variable with this name in this scope.",
                 );
                 self.has_error = true;
+            } else {
+                let slot = scope.len();
+                scope.insert(
+                    name.lexeme.clone(),
+                    Local { slot, defined: false, declaration: None, read: false, span: Some(name.span) },
+                );
+                let enclosing = &self.scopes[..self.scopes.len() - 1];
+                if enclosing.iter().any(|scope| scope.contains_key(&name.lexeme)) {
+                    let msg = "Local variable shadows a variable in an enclosing scope.";
+                    if self.interpreter.strict() {
+                        self.strict_error(name, msg);
+                    } else {
+                        self.warning(name, msg);
+                    }
+                }
+            }
+        }
+    }
+
+    fn warning(&mut self, token: &Token, msg: &str) {
+        match self.interpreter.warnings() {
+            Warnings::Report => loxerr::diagnostic(Severity::Warning, Phase::Resolve, token, msg),
+            Warnings::Deny => {
+                loxerr::diagnostic(Severity::Error, Phase::Resolve, token, msg);
+                self.has_error = true;
             }
-            scope.insert(name.lexeme.clone(), false);
+            Warnings::Ignore => (),
         }
     }
 
+    fn strict_error(&mut self, token: &Token, msg: &str) {
+        loxerr::resolve_error(token, msg);
+        self.has_error = true;
+    }
+
     fn define(&mut self, name: &Token) {
         if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name.lexeme.clone(), true);
+            if let Some(local) = scope.get_mut(&name.lexeme) {
+                local.defined = true;
+            }
         }
     }
+
+    // For `this` and `super`, which live in scopes of their own.
+    fn define_implicit(&mut self, name: Symbol) {
+        let scope = self.scopes.last_mut().unwrap();
+        let slot = scope.len();
+        scope.insert(name, Local { slot, defined: true, declaration: None, read: true, span: None });
+    }
 }
@@ -0,0 +1,33 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Where `random()` gets its numbers, see `Interpreter::set_rng`. SplitMix64:
+// not for anything secret, but small and the same everywhere for a seed.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    // Seeded from the time, as interpreters start with.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+        Rng::seeded(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // In [0, 1), from the top 53 bits, as many as a double holds.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
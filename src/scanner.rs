@@ -1,16 +1,25 @@
+use std::ops::Range;
+
 use crate::loxerr;
-use crate::token::{Token, TokenType};
+use crate::shared::Rc;
+use crate::symbol::Symbol;
+use crate::token::{Span, Token, TokenType};
 use TokenType::*;
 
 pub struct Scanner<'a> {
     source: &'a str,
-    chars: Vec<char>,
-    source_current: usize,
-    chars_current: usize,
     tokens: Vec<Token>,
+    // Both are byte offsets into `source`, always on a char boundary.
     start: usize,
+    current: usize,
     line: usize,
     pub has_error: bool,
+    // Where each token, comment and error is in `source`, for highlighting.
+    pub spans: Vec<Range<usize>>,
+    pub comments: Vec<Range<usize>>,
+    pub errors: Vec<Range<usize>>,
+    // Off while highlighting, which rescans the line on every keystroke.
+    pub report_errors: bool,
 }
 
 fn keyword_or_identifier(lexeme: &str) -> TokenType {
@@ -36,64 +45,59 @@ fn keyword_or_identifier(lexeme: &str) -> TokenType {
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(source: &'a str) -> Scanner {
+    pub fn new(source: &'a str) -> Scanner<'a> {
         Scanner {
             source,
-            source_current: 0,
-            chars: source.chars().collect(),
-            chars_current: 0,
             tokens: Vec::new(),
-            start: 0, // in bytes, not unicode code points
+            start: 0,
+            current: 0,
             line: 1,
             has_error: false,
+            spans: Vec::new(),
+            comments: Vec::new(),
+            errors: Vec::new(),
+            report_errors: true,
         }
     }
 
     pub fn scan_tokens(&mut self) -> &Vec<Token> {
         while !self.is_at_end() {
-            self.start = self.source_current;
+            self.start = self.current;
             self.scan_token();
         }
 
-        self.tokens.push(Token::new(Eof, "", self.line));
+        let end = self.source.len();
+        self.tokens.push(Token::new(Eof, "", self.line).with_span(Span::new(end, end)));
+        self.spans.push(self.source.len()..self.source.len());
 
         &self.tokens
     }
 
     fn is_at_end(&self) -> bool {
-        self.source_current >= self.source.len()
+        self.current >= self.source.len()
     }
 
     fn advance(&mut self) -> char {
-        let c = self.chars[self.chars_current];
-        self.chars_current += 1;
-        self.source_current += c.len_utf8();
+        let c = self.peek();
+        self.current += c.len_utf8();
         c
     }
 
     fn is_match(&mut self, expected: char) -> bool {
-        if self.is_at_end() || self.chars[self.chars_current] != expected {
+        if self.is_at_end() || self.peek() != expected {
             false
         } else {
-            self.advance();
+            self.current += expected.len_utf8();
             true
         }
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.chars[self.chars_current]
-        }
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.chars_current + 1 >= self.chars.len() {
-            '\0'
-        } else {
-            self.chars[self.chars_current + 1]
-        }
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn scan_token(&mut self) {
@@ -103,14 +107,29 @@ impl<'a> Scanner<'a> {
             '}' => self.add_token(RightBrace),
             '(' => self.add_token(LeftParen),
             ')' => self.add_token(RightParen),
+            '[' => self.add_token(LeftBracket),
+            ']' => self.add_token(RightBracket),
             ',' => self.add_token(Comma),
-            '.' => self.add_token(Dot),
+            '.' => {
+                let token = if self.is_match('.') { DotDot } else { Dot };
+                self.add_token(token);
+            }
             '-' => self.add_token(Minus),
             '+' => self.add_token(Plus),
             ';' => self.add_token(Semicolon),
             '*' => self.add_token(Star),
+            '&' => self.add_token(Ampersand),
+            '|' => self.add_token(Pipe),
+            '^' => self.add_token(Caret),
+            '~' => self.add_token(Tilde),
             '<' => {
-                let token = if self.is_match('=') { LessEqual } else { Less };
+                let token = if self.is_match('=') {
+                    LessEqual
+                } else if self.is_match('<') {
+                    LessLess
+                } else {
+                    Less
+                };
                 self.add_token(token);
             }
             '!' => {
@@ -120,6 +139,8 @@ impl<'a> Scanner<'a> {
             '>' => {
                 let token = if self.is_match('=') {
                     GreaterEqual
+                } else if self.is_match('>') {
+                    GreaterGreater
                 } else {
                     Greater
                 };
@@ -138,13 +159,14 @@ impl<'a> Scanner<'a> {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.comments.push(self.start..self.current);
                 } else {
                     self.add_token(Slash)
                 }
             }
             '\t' | '\r' | ' ' => {}
             '\n' => self.line += 1,
-            '"' => self.string(),
+            '"' | '\'' => self.string(c),
             '0'..='9' => self.number(),
             _ => {
                 if is_alpha(c) {
@@ -157,53 +179,163 @@ impl<'a> Scanner<'a> {
     }
 
     fn add_token(&mut self, token_type: TokenType) {
-        let lexeme = &self.source[self.start..self.source_current];
+        let lexeme = &self.source[self.start..self.current];
         let t = Token {
             token_type,
-            lexeme: lexeme.to_owned(),
+            lexeme: Symbol::intern(lexeme),
             line: self.line,
+            span: Span::new(self.start, self.current),
         };
         self.tokens.push(t);
+        self.spans.push(self.start..self.current);
     }
 
     fn error(&mut self, msg: &str) {
-        loxerr::error(self.line, msg);
+        if self.report_errors {
+            loxerr::error(self.line, Span::new(self.start, self.current), msg);
+        }
         self.has_error = true;
+        self.errors.push(self.start..self.current);
     }
 
-    fn string(&mut self) {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    // Strings are quoted with `"` or `'`, and the other quote needs no escaping
+    // inside. Both understand the same escape sequences.
+    fn string(&mut self, quote: char) {
+        let closed = self.is_closed(quote);
+        let mut value = String::new();
+        while self.peek() != quote && !self.is_at_end() && (closed || self.peek() != '\n') {
+            match self.advance() {
+                '\\' => {
+                    if let Some(c) = self.escape(quote) {
+                        value.push(c);
+                    }
+                }
+                c => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    value.push(c);
+                }
             }
-            self.advance();
         }
 
-        if self.is_at_end() {
+        // A string that never closes is reported on the line it starts on,
+        // and taken to end with that line so the rest of the file still
+        // scans. The statement it's in most likely ended there too, and
+        // lost its `;` to the string, so one is assumed.
+        if !closed {
             self.error("Unterminated string.");
+            self.add_token(StringLiteral(Rc::from(value)));
+            self.tokens.push(Token::new(Semicolon, ";", self.line).with_span(Span::new(self.current, self.current)));
+            self.spans.push(self.current..self.current);
             return;
         }
 
-        self.advance(); //swallow the terminating "
-        let s = &self.source[self.start + 1..self.source_current - 1];
-        self.add_token(StringLiteral(s.to_owned()));
+        self.advance(); // swallow the closing quote
+        self.add_token(StringLiteral(Rc::from(value)));
     }
 
-    fn number(&mut self) {
-        while is_digit(self.peek()) {
-            self.advance();
+    // Whether the string starting here has a closing quote before the end of
+    // the file.
+    fn is_closed(&self, quote: char) -> bool {
+        let mut chars = self.source[self.current..].chars();
+        while let Some(c) = chars.next() {
+            if c == quote {
+                return true;
+            }
+            if c == '\\' {
+                chars.next();
+            }
         }
+        false
+    }
 
-        if self.peek() == '.' && is_digit(self.peek_next()) {
+    // The character after a backslash, None when it's malformed.
+    fn escape(&mut self, quote: char) -> Option<char> {
+        if self.is_at_end() {
+            return None;
+        }
+        match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            c @ ('\\' | '"' | '\'') => Some(c),
+            'u' => self.unicode_escape(quote),
+            c => {
+                if c == '\n' {
+                    self.line += 1;
+                }
+                self.error(&format!("Invalid escape sequence '\\{}'.", c.escape_debug()));
+                None
+            }
+        }
+    }
+
+    // `\u{...}` with one to six hex digits naming a Unicode scalar value.
+    fn unicode_escape(&mut self, quote: char) -> Option<char> {
+        if !self.is_match('{') {
+            self.error("Expect '{' after '\\u'.");
+            return None;
+        }
+        let start = self.current;
+        while !matches!(self.peek(), '}' | '\n') && self.peek() != quote && !self.is_at_end() {
             self.advance();
+        }
+        let digits = &self.source[start..self.current];
+        if !self.is_match('}') {
+            self.error("Unterminated unicode escape.");
+            return None;
+        }
 
-            while is_digit(self.peek()) {
-                self.advance();
+        if digits.is_empty() {
+            self.error("Empty unicode escape.");
+            return None;
+        }
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+            self.error(&format!("Invalid hex digit '{}' in unicode escape.", c));
+            return None;
+        }
+        if digits.len() > 6 {
+            self.error("Unicode escape can have at most 6 hex digits.");
+            return None;
+        }
+        let code = u32::from_str_radix(digits, 16).unwrap();
+        match char::from_u32(code) {
+            Some(c) => Some(c),
+            None if (0xD800..=0xDFFF).contains(&code) => {
+                self.error(&format!("Unicode escape U+{:X} is a surrogate.", code));
+                None
             }
+            None => {
+                self.error(&format!("Unicode escape U+{:X} is out of range.", code));
+                None
+            }
+        }
+    }
+
+    // Digits can be grouped with underscores, as in `1_000.000_1`.
+    fn digits(&mut self) {
+        while is_digit(self.peek()) || self.peek() == '_' {
+            self.advance();
+        }
+    }
+
+    fn number(&mut self) {
+        self.digits();
+
+        if self.peek() == '.' && (is_digit(self.peek_next()) || self.peek_next() == '_') {
+            self.advance();
+            self.digits();
         }
 
-        let lexeme = &self.source[self.start..self.source_current];
-        let number = lexeme.parse::<f64>().unwrap();
+        let lexeme = &self.source[self.start..self.current];
+        let number = lexeme.replace('_', "").parse::<f64>().unwrap();
+        // The token is still added, so the parser doesn't report an error of
+        // its own.
+        if lexeme.contains("__") || lexeme.contains("_.") || lexeme.contains("._") || lexeme.ends_with('_') {
+            self.error("Underscores in numbers can only go between digits.");
+        }
         self.add_token(Number(number));
     }
 
@@ -212,22 +344,25 @@ impl<'a> Scanner<'a> {
             self.advance();
         }
 
-        let lexeme = &self.source[self.start..self.source_current];
+        let lexeme = &self.source[self.start..self.current];
         self.add_token(keyword_or_identifier(lexeme));
     }
 }
 
 #[inline]
 fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
+    c.is_ascii_digit()
 }
 
+// Identifiers start with a letter from any script or `_`, and go on with
+// letters, digits and `_`. Emoji, symbols and combining marks are none of
+// these. Keywords are all ASCII.
 #[inline]
 fn is_alpha(c: char) -> bool {
-    (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+    c.is_alphabetic() || c == '_'
 }
 
 #[inline]
 fn is_alpha_numeric(c: char) -> bool {
-    is_alpha(c) || is_digit(c)
+    c.is_alphanumeric() || c == '_'
 }
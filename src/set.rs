@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::expr::Value;
+use crate::map::Key;
+
+// Elements are map keys, so only values compared by what they hold can be
+// added. They stay in the order they were added.
+#[derive(Clone, Default)]
+pub struct LoxSet {
+    elements: Vec<Value>,
+    index: HashMap<Key, usize>,
+}
+
+impl LoxSet {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.index.contains_key(key)
+    }
+
+    // False when the element was already there.
+    pub fn insert(&mut self, key: Key, element: Value) -> bool {
+        if self.index.contains_key(&key) {
+            return false;
+        }
+        self.index.insert(key, self.elements.len());
+        self.elements.push(element);
+        true
+    }
+
+    // False when the element wasn't there.
+    pub fn remove(&mut self, key: &Key) -> bool {
+        let idx = match self.index.remove(key) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        self.elements.remove(idx);
+        for later in self.index.values_mut() {
+            if *later > idx {
+                *later -= 1;
+            }
+        }
+        true
+    }
+
+    pub fn elements(&self) -> Vec<Value> {
+        self.elements.clone()
+    }
+}
+
+// Sets with the same elements are equal, whatever order they were added in.
+impl PartialEq for LoxSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.index.keys().all(|key| other.contains(key))
+    }
+}
+
+impl fmt::Display for LoxSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+        for (idx, element) in self.elements.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", element)?;
+        }
+        write!(f, "}}")
+    }
+}
@@ -0,0 +1,158 @@
+// The pointers and cells that values, environments and trees are shared
+// through. Normally they're `Rc`, `RefCell` and `Cell`. With the `sync`
+// feature `Rc` is `Arc` and the cells are locks, so that an interpreter and
+// everything it made can move to another thread. Each interpreter still runs
+// on one thread at a time: the locks are never contended, they only stand in
+// for the borrow flags.
+
+use std::any::Any;
+use std::io::Write;
+
+#[cfg(not(feature = "sync"))]
+pub use std::cell::{Cell, Ref, RefCell, RefMut};
+#[cfg(not(feature = "sync"))]
+pub use std::rc::{Rc, Weak};
+
+#[cfg(feature = "sync")]
+pub use self::sync::{Cell, Ref, RefCell, RefMut};
+#[cfg(feature = "sync")]
+pub use std::sync::{Arc as Rc, Weak};
+
+// What the traits of things an interpreter holds require of their
+// implementations: nothing, or with `sync` that they can be sent and shared.
+#[cfg(not(feature = "sync"))]
+pub trait Shareable {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> Shareable for T {}
+
+#[cfg(feature = "sync")]
+pub trait Shareable: Send + Sync {}
+#[cfg(feature = "sync")]
+impl<T: ?Sized + Send + Sync> Shareable for T {}
+
+// The state of a native class's instance, see `NativeClass`.
+#[cfg(not(feature = "sync"))]
+pub type AnyBox = Box<dyn Any>;
+#[cfg(feature = "sync")]
+pub type AnyBox = Box<dyn Any + Send + Sync>;
+
+// Where an interpreter prints, see `Interpreter::set_output`.
+#[cfg(not(feature = "sync"))]
+pub type Output = Box<dyn Write>;
+#[cfg(feature = "sync")]
+pub type Output = Box<dyn Write + Send>;
+
+#[cfg(feature = "sync")]
+mod sync {
+    use std::fmt;
+    use std::sync::{Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+    pub type Ref<'a, T> = RwLockReadGuard<'a, T>;
+    pub type RefMut<'a, T> = RwLockWriteGuard<'a, T>;
+
+    // `std::cell::RefCell`'s interface over an `RwLock`. Where a `RefCell`
+    // would panic on a conflicting borrow this waits instead, which on one
+    // thread never ends, so the same rules apply. A panic while borrowed
+    // doesn't poison it, as it doesn't a `RefCell`.
+    #[derive(Default)]
+    pub struct RefCell<T: ?Sized>(RwLock<T>);
+
+    #[derive(Debug)]
+    pub struct BorrowError;
+
+    impl<T> RefCell<T> {
+        pub const fn new(value: T) -> Self {
+            RefCell(RwLock::new(value))
+        }
+
+        pub fn replace(&self, value: T) -> T {
+            std::mem::replace(&mut *self.borrow_mut(), value)
+        }
+
+        pub fn take(&self) -> T
+        where
+            T: Default,
+        {
+            self.replace(T::default())
+        }
+
+        pub fn into_inner(self) -> T {
+            self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    impl<T: ?Sized> RefCell<T> {
+        pub fn borrow(&self) -> Ref<'_, T> {
+            self.0.read().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub fn borrow_mut(&self) -> RefMut<'_, T> {
+            self.0.write().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+            match self.0.try_read() {
+                Ok(guard) => Ok(guard),
+                Err(TryLockError::Poisoned(poisoned)) => Ok(poisoned.into_inner()),
+                Err(TryLockError::WouldBlock) => Err(BorrowError),
+            }
+        }
+
+        pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowError> {
+            match self.0.try_write() {
+                Ok(guard) => Ok(guard),
+                Err(TryLockError::Poisoned(poisoned)) => Ok(poisoned.into_inner()),
+                Err(TryLockError::WouldBlock) => Err(BorrowError),
+            }
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    impl<T: Clone> Clone for RefCell<T> {
+        fn clone(&self) -> Self {
+            RefCell::new(self.borrow().clone())
+        }
+    }
+
+    impl<T: PartialEq + ?Sized> PartialEq for RefCell<T> {
+        fn eq(&self, other: &Self) -> bool {
+            *self.borrow() == *other.borrow()
+        }
+    }
+
+    impl<T: fmt::Debug + ?Sized> fmt::Debug for RefCell<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self.try_borrow() {
+                Ok(value) => f.debug_tuple("RefCell").field(&&*value).finish(),
+                Err(_) => f.write_str("RefCell(<borrowed>)"),
+            }
+        }
+    }
+
+    // `std::cell::Cell`'s interface over a `Mutex`.
+    #[derive(Default)]
+    pub struct Cell<T>(Mutex<T>);
+
+    impl<T: Copy> Cell<T> {
+        pub const fn new(value: T) -> Self {
+            Cell(Mutex::new(value))
+        }
+
+        pub fn get(&self) -> T {
+            *self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub fn set(&self, value: T) {
+            *self.0.lock().unwrap_or_else(PoisonError::into_inner) = value;
+        }
+    }
+
+    impl<T: Copy + fmt::Debug> fmt::Debug for Cell<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_tuple("Cell").field(&self.get()).finish()
+        }
+    }
+}
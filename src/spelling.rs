@@ -0,0 +1,34 @@
+use crate::symbol::Symbol;
+
+// The fewest single-character insertions, deletions and substitutions that
+// turn one name into the other.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// " Did you mean 'x'?" with the candidate closest to `name`, or nothing when
+// none is within two edits. Names no longer than the distance don't count:
+// `x` is one edit from every other one-letter name.
+pub fn did_you_mean(name: &str, candidates: &[Symbol]) -> String {
+    let closest = candidates
+        .iter()
+        .map(|candidate| candidate.as_str())
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= 2 && distance < name.chars().count())
+        .min();
+    match closest {
+        Some((_, candidate)) => format!(" Did you mean '{}'?", candidate),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,51 @@
+// How much stack a run has used. Lox calls nest Rust calls, so a deep
+// recursion has to stop with "Stack overflow." before the thread really
+// overflows. A run notes where the stack was when it started, and each call
+// measures how far the stack has grown since, against the interpreter's
+// stack limit.
+
+use std::cell::Cell;
+
+thread_local! {
+    // Where the outermost run on this thread started, or 0 outside any run.
+    static BASE: Cell<usize> = const { Cell::new(0) };
+}
+
+// Marks the start of a run on this thread until it is dropped. A run started
+// inside another one, say by a native, keeps the outer mark.
+pub struct Mark {
+    outermost: bool,
+}
+
+pub fn mark() -> Mark {
+    BASE.with(|base| {
+        let outermost = base.get() == 0;
+        if outermost {
+            base.set(position());
+        }
+        Mark { outermost }
+    })
+}
+
+impl Drop for Mark {
+    fn drop(&mut self) {
+        if self.outermost {
+            BASE.with(|base| base.set(0));
+        }
+    }
+}
+
+// The bytes of stack used since the run started, or 0 outside a run. Stacks
+// grow down on most targets but not all, so either direction counts.
+pub fn used() -> usize {
+    match BASE.with(Cell::get) {
+        0 => 0,
+        base => base.abs_diff(position()),
+    }
+}
+
+#[inline(never)]
+fn position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
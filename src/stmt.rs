@@ -1,18 +1,91 @@
-use std::rc::Rc;
+use serde::{Deserialize, Serialize};
 
 use crate::expr::Expr;
-use crate::token::Token;
+use crate::shared::Rc;
+use crate::token::{Span, Token};
 
-#[derive(Clone)]
+// Every statement but blocks holds a token for its line: its name, its
+// keyword or, for expression statements, the first token of the expression.
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Stmt {
     Null,
     Block(Vec<Stmt>),
-    Class(Token, Option<Rc<Expr>>, Vec<Stmt>), // more specifically, will contain Stmt.Function
-    Expression(Rc<Expr>),
-    Function(Token, Vec<Token>, Vec<Stmt>),
-    If(Rc<Expr>, Box<Stmt>, Option<Box<Stmt>>),
-    Print(Rc<Expr>),
+    // Name, superclass, mixins, interfaces and methods, each a
+    // Stmt.Function.
+    Class(Token, Option<Rc<Expr>>, Vec<Rc<Expr>>, Vec<Rc<Expr>>, Vec<Stmt>),
+    Expression(Token, Rc<Expr>),
+    // Initializer, condition, increment and body. The initializer lives in a
+    // scope of its own around the loop, which every iteration gets a copy of
+    // so closures in the body see that iteration's variables.
+    For(Token, Option<Box<Stmt>>, Option<Rc<Expr>>, Option<Rc<Expr>>, Box<Stmt>),
+    // `for (var name in iterable) body`. Every iteration gets a new scope
+    // holding `name`.
+    ForIn(Token, Token, Rc<Expr>, Box<Stmt>),
+    // Parameters and body are shared with every closure created from the
+    // declaration, so evaluating it or binding a method never copies the tree.
+    Function(Token, Rc<Vec<Token>>, Rc<Vec<Stmt>>),
+    If(Token, Rc<Expr>, Box<Stmt>, Option<Box<Stmt>>),
+    // `import "name";` with the `import` token and the name.
+    Import(Token, Rc<str>),
+    // Name and the signatures of its methods: name and parameters.
+    Interface(Token, Vec<(Token, Vec<Token>)>),
+    Print(Token, Rc<Expr>),
     Return(Token, Option<Rc<Expr>>),
     Var(Token, Option<Rc<Expr>>),
-    While(Rc<Expr>, Box<Stmt>),
+    // `var [a, b] = value;`, binding each name to an element in turn.
+    VarArray(Vec<Token>, Rc<Expr>),
+    While(Token, Rc<Expr>, Box<Stmt>),
+}
+
+// Whether a block declares anything. Blocks that don't are run in the scope
+// around them, as the one of their own would stay empty. The resolver and the
+// interpreter have to agree on this for resolved distances to be right.
+pub fn needs_scope(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| {
+        matches!(stmt, Stmt::Class(..) | Stmt::Function(..) | Stmt::Interface(..) | Stmt::Var(..) | Stmt::VarArray(..))
+    })
+}
+
+impl Stmt {
+    // The line the statement starts on.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Stmt::Null | Stmt::Block(_) => None,
+            Stmt::Class(token, ..)
+            | Stmt::Expression(token, _)
+            | Stmt::For(token, ..)
+            | Stmt::ForIn(token, ..)
+            | Stmt::Function(token, ..)
+            | Stmt::If(token, ..)
+            | Stmt::Import(token, _)
+            | Stmt::Interface(token, _)
+            | Stmt::Print(token, _)
+            | Stmt::Return(token, _)
+            | Stmt::Var(token, _)
+            | Stmt::While(token, ..) => Some(token.line),
+            Stmt::VarArray(names, _) => Some(names[0].line),
+        }
+    }
+
+    // The span of the token the statement is reported at, which is the same
+    // one `line` gives the line of. Blocks cover everything in them.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Stmt::Null => None,
+            Stmt::Block(stmts) => stmts.iter().filter_map(Stmt::span).reduce(Span::to),
+            Stmt::Class(token, ..)
+            | Stmt::Expression(token, _)
+            | Stmt::For(token, ..)
+            | Stmt::ForIn(token, ..)
+            | Stmt::Function(token, ..)
+            | Stmt::If(token, ..)
+            | Stmt::Import(token, _)
+            | Stmt::Interface(token, _)
+            | Stmt::Print(token, _)
+            | Stmt::Return(token, _)
+            | Stmt::Var(token, _)
+            | Stmt::While(token, ..) => Some(token.span),
+            Stmt::VarArray(names, _) => Some(names[0].span.to(names[names.len() - 1].span)),
+        }
+    }
 }
@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::shared::Rc;
+
+#[cfg(not(feature = "sync"))]
+thread_local! {
+    static INTERNER: std::cell::RefCell<HashSet<Rc<str>>> = std::cell::RefCell::new(HashSet::new());
+}
+
+// Symbols made on one thread can be compared with ones made on another once
+// interpreters move between threads, so there's only one table.
+#[cfg(feature = "sync")]
+static INTERNER: std::sync::LazyLock<std::sync::Mutex<HashSet<Rc<str>>>> = std::sync::LazyLock::new(Default::default);
+
+#[cfg(not(feature = "sync"))]
+fn with_interner<T>(f: impl FnOnce(&mut HashSet<Rc<str>>) -> T) -> T {
+    INTERNER.with(|interner| f(&mut interner.borrow_mut()))
+}
+
+#[cfg(feature = "sync")]
+fn with_interner<T>(f: impl FnOnce(&mut HashSet<Rc<str>>) -> T) -> T {
+    f(&mut INTERNER.lock().unwrap_or_else(std::sync::PoisonError::into_inner))
+}
+
+thread_local! {
+    static THIS: Symbol = Symbol::intern("this");
+    static SUPER: Symbol = Symbol::intern("super");
+    static INIT: Symbol = Symbol::intern("init");
+}
+
+// An interned string. Every spelling maps to exactly one allocation, so
+// comparing and hashing symbols only looks at the pointer, while the original
+// text stays available for error messages.
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        with_interner(|interner| {
+            if let Some(symbol) = interner.get(name) {
+                return Symbol(Rc::clone(symbol));
+            }
+            let symbol: Rc<str> = Rc::from(name);
+            interner.insert(Rc::clone(&symbol));
+            Symbol(symbol)
+        })
+    }
+
+    pub fn this() -> Symbol {
+        THIS.with(Symbol::clone)
+    }
+
+    pub fn super_() -> Symbol {
+        SUPER.with(Symbol::clone)
+    }
+
+    pub fn init() -> Symbol {
+        INIT.with(Symbol::clone)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Rc::as_ptr(&self.0) as *const u8).hash(state);
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
@@ -1,10 +1,17 @@
-#[derive(Debug, PartialEq, Clone)]
+use serde::{Deserialize, Serialize};
+
+use crate::shared::Rc;
+use crate::symbol::Symbol;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -12,20 +19,30 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
+    DotDot,
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
     Greater,
     GreaterEqual,
+    GreaterGreater,
     Less,
     LessEqual,
+    LessLess,
+
+    // `div`, which is scanned as an identifier, see `Parser::factor`.
+    Div,
 
     // Literals.
     Identifier,
-    StringLiteral(String),
+    StringLiteral(Rc<str>),
     Number(f64),
 
     // Keywords.
@@ -49,21 +66,51 @@ pub enum TokenType {
     Eof,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// Where a token is in the source, as byte offsets: `start` is the first byte
+// of its lexeme and `end` is one past the last. Tokens that aren't in the
+// source, such as the ones the VM makes up for its errors, have an empty span
+// at 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    // The smallest span covering both.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Symbol,
     pub line: usize,
+    // ASTs written before tokens had spans still load.
+    #[serde(default)]
+    pub span: Span,
 }
 
 impl Token {
     pub fn new(token_type: TokenType, lexeme: &str, line: usize) -> Token {
         Token {
             token_type,
-            lexeme: String::from(lexeme),
+            lexeme: Symbol::intern(lexeme),
             line,
+            span: Span::default(),
         }
     }
+
+    pub fn with_span(mut self, span: Span) -> Token {
+        self.span = span;
+        self
+    }
 }
 
 // fn main() {
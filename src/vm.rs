@@ -0,0 +1,385 @@
+use std::fmt;
+
+use crate::chunk::{Chunk, Op};
+use crate::expr::Value;
+use crate::interpreter::{
+    bitwise, bitwise_not, is_truthy, numeric_operand_message, numeric_operands_message, numstr_operands_message,
+    Interpreter,
+};
+use crate::loxcallables::LoxCallable;
+use crate::loxerr::RuntimeException;
+use crate::shared::Rc;
+use crate::symbol::Symbol;
+use crate::token::{Token, TokenType};
+
+// A function compiled to bytecode. Calling one from the tree-walker runs it
+// on a VM of its own.
+#[derive(Clone)]
+pub struct VmFunction {
+    name: Symbol,
+    arity: usize,
+    chunk: Rc<Chunk>,
+}
+
+impl VmFunction {
+    pub fn new(name: Symbol, arity: usize, chunk: Chunk) -> Self {
+        VmFunction { name, arity, chunk: Rc::new(chunk) }
+    }
+
+    pub fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+}
+
+impl LoxCallable for VmFunction {
+    fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, RuntimeException> {
+        Vm::new(interpreter).run(self, args)
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn as_vm_function(&self) -> Option<&VmFunction> {
+        Some(self)
+    }
+}
+
+impl fmt::Display for VmFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name)
+    }
+}
+
+struct Frame {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    // Where the frame's slot 0 is on the stack.
+    base: usize,
+}
+
+// Runs bytecode functions. Globals, output, call depth and fuel are the
+// interpreter's, and errors read the same as the tree-walker's, so which of
+// the two ran a program makes no difference to it.
+pub struct Vm<'a> {
+    interpreter: &'a mut Interpreter,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+fn error(line: usize, msg: &str) -> RuntimeException {
+    RuntimeException::RuntimeError {
+        token: Token::new(TokenType::Eof, "", line),
+        error: msg.to_owned(),
+    }
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Vm { interpreter, stack: Vec::new(), frames: Vec::new() }
+    }
+
+    pub fn run(&mut self, function: &VmFunction, args: &[Value]) -> Result<Value, RuntimeException> {
+        let call_depth = self.interpreter.call_depth;
+        self.stack.push(Value::Callable(Rc::new(function.clone())));
+        self.stack.extend_from_slice(args);
+        self.frames.push(Frame { chunk: Rc::clone(&function.chunk), ip: 0, base: 0 });
+
+        let result = self.execute();
+        // Frames left behind by an error were still counted.
+        self.interpreter.call_depth = call_depth;
+        self.stack.clear();
+        self.frames.clear();
+        result
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("VM stack underflow.")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn numbers(&mut self, line: usize) -> Result<(f64, f64), RuntimeException> {
+        let r = self.pop();
+        let l = self.pop();
+        match (l, r) {
+            (Value::Number(l), Value::Number(r)) => Ok((l, r)),
+            (l, r) => Err(error(line, &numeric_operands_message(&l, &r))),
+        }
+    }
+
+    fn undefined(&self, chunk: &Chunk, idx: usize) -> RuntimeException {
+        self.interpreter.globals.borrow().undefined(&chunk.names[idx], &chunk.scopes[idx])
+    }
+
+    fn bitwise(&mut self, token_type: TokenType, lexeme: &str, line: usize) -> Result<(), RuntimeException> {
+        let r = self.pop();
+        let l = self.pop();
+        let op = Token::new(token_type, lexeme, line);
+        self.stack.push(bitwise(&op, &l, &r)?);
+        Ok(())
+    }
+
+    // Hands operators on instances to the interpreter, see
+    // `Interpreter::overloaded_operator`. Returns whether it took care of it.
+    fn overloaded(&mut self, token_type: TokenType, lexeme: &str, line: usize) -> Result<bool, RuntimeException> {
+        if !matches!(self.peek(1), Value::Instance(_)) {
+            return Ok(false);
+        }
+        let op = Token::new(token_type, lexeme, line);
+        let left = self.peek(1).clone();
+        let right = self.peek(0).clone();
+        match self.interpreter.overloaded_operator(&left, &op, &right)? {
+            Some(result) => {
+                self.pop();
+                self.pop();
+                self.stack.push(result);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Pushes a frame for calls to bytecode functions and makes every other
+    // call right away. Returns whether a frame was pushed.
+    fn call(&mut self, argc: usize, line: usize) -> Result<bool, RuntimeException> {
+        let callee_slot = self.stack.len() - 1 - argc;
+        let function = match &self.stack[callee_slot] {
+            Value::Callable(callee) => callee.as_vm_function().map(|function| {
+                (Rc::clone(&function.chunk), function.arity)
+            }),
+            _ => None,
+        };
+
+        match function {
+            Some((chunk, arity)) => {
+                if argc != arity {
+                    return Err(error(line, &format!("Expected {} arguments but got {}.", arity, argc)));
+                }
+                if self.interpreter.stack_exhausted() {
+                    return Err(error(line, "Stack overflow."));
+                }
+                self.interpreter.tick()?;
+                self.interpreter.enter_call();
+                self.frames.push(Frame { chunk, ip: 0, base: callee_slot });
+                Ok(true)
+            }
+            None => {
+                let args = self.stack.split_off(callee_slot + 1);
+                let callee = self.pop();
+                let paren = Token::new(TokenType::RightParen, ")", line);
+                let result = self.interpreter.call_value(callee, &paren, &args)?;
+                self.stack.push(result);
+                Ok(false)
+            }
+        }
+    }
+
+    // Replaces the current frame with a call to a bytecode function, like the
+    // tree-walker does for tail calls. Anything else is called and returned
+    // as usual. Returns whether the frame was replaced.
+    fn tail_call(&mut self, argc: usize, line: usize) -> Result<bool, RuntimeException> {
+        let callee_slot = self.stack.len() - 1 - argc;
+        let chunk = match &self.stack[callee_slot] {
+            Value::Callable(callee) => match callee.as_vm_function() {
+                Some(function) if function.arity == argc => Rc::clone(&function.chunk),
+                _ => return self.call(argc, line).map(|_| false),
+            },
+            _ => return self.call(argc, line).map(|_| false),
+        };
+
+        self.interpreter.tick()?;
+        self.interpreter.count_tail_call();
+        let frame = self.frames.last_mut().unwrap();
+        self.stack.drain(frame.base..callee_slot);
+        frame.chunk = chunk;
+        frame.ip = 0;
+        Ok(true)
+    }
+
+    fn execute(&mut self) -> Result<Value, RuntimeException> {
+        let (mut chunk, mut ip, mut base) = self.current_frame();
+
+        loop {
+            let op = chunk.code[ip];
+            let line = chunk.lines[ip];
+            ip += 1;
+
+            match op {
+                Op::Constant(idx) => self.stack.push(chunk.constants[idx].clone()),
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::GetLocal(slot) => {
+                    let value = self.stack[base + slot].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => self.stack[base + slot] = self.peek(0).clone(),
+                Op::GetGlobal(idx) => {
+                    let value = self.interpreter.globals.borrow().get(&chunk.names[idx]);
+                    let value = value.map_err(|_| self.undefined(&chunk, idx))?;
+                    self.stack.push(value);
+                }
+                Op::DefineGlobal(idx) => {
+                    let value = self.pop();
+                    self.interpreter.globals.borrow_mut().define(&chunk.names[idx].lexeme, value);
+                }
+                Op::SetGlobal(idx) => {
+                    let value = self.peek(0).clone();
+                    let assigned = self.interpreter.globals.borrow_mut().assign(&chunk.names[idx], value);
+                    assigned.map_err(|_| self.undefined(&chunk, idx))?;
+                }
+                Op::Equal => {
+                    if self.overloaded(TokenType::EqualEqual, "==", line)? {
+                        continue;
+                    }
+                    let r = self.pop();
+                    let l = self.pop();
+                    self.stack.push(Value::Bool(l == r));
+                }
+                Op::Greater => {
+                    let (l, r) = self.numbers(line)?;
+                    self.stack.push(Value::Bool(l > r));
+                }
+                Op::GreaterEqual => {
+                    let (l, r) = self.numbers(line)?;
+                    self.stack.push(Value::Bool(l >= r));
+                }
+                Op::Less => {
+                    if self.overloaded(TokenType::Less, "<", line)? {
+                        continue;
+                    }
+                    let (l, r) = self.numbers(line)?;
+                    self.stack.push(Value::Bool(l < r));
+                }
+                Op::LessEqual => {
+                    let (l, r) = self.numbers(line)?;
+                    self.stack.push(Value::Bool(l <= r));
+                }
+                Op::Add => {
+                    if self.overloaded(TokenType::Plus, "+", line)? {
+                        continue;
+                    }
+                    let r = self.pop();
+                    let l = self.pop();
+                    let sum = match (l, r) {
+                        (Value::Number(l), Value::Number(r)) => Value::Number(l + r),
+                        (Value::String(l), Value::String(r)) => {
+                            self.interpreter.allocate(l.len() + r.len())?;
+                            Value::String(format!("{}{}", l, r).into())
+                        }
+                        (l, r) => return Err(error(line, &numstr_operands_message(&l, &r))),
+                    };
+                    self.stack.push(sum);
+                }
+                Op::Subtract => {
+                    if self.overloaded(TokenType::Minus, "-", line)? {
+                        continue;
+                    }
+                    let (l, r) = self.numbers(line)?;
+                    self.stack.push(Value::Number(l - r));
+                }
+                Op::Multiply => {
+                    if self.overloaded(TokenType::Star, "*", line)? {
+                        continue;
+                    }
+                    let (l, r) = self.numbers(line)?;
+                    self.stack.push(Value::Number(l * r));
+                }
+                Op::Divide => {
+                    let (l, r) = self.numbers(line)?;
+                    self.stack.push(Value::Number(l / r));
+                }
+                Op::FloorDivide => {
+                    let (l, r) = self.numbers(line)?;
+                    self.stack.push(Value::Number((l / r).floor()));
+                }
+                Op::BitAnd => self.bitwise(TokenType::Ampersand, "&", line)?,
+                Op::BitOr => self.bitwise(TokenType::Pipe, "|", line)?,
+                Op::BitXor => self.bitwise(TokenType::Caret, "^", line)?,
+                Op::ShiftLeft => self.bitwise(TokenType::LessLess, "<<", line)?,
+                Op::ShiftRight => self.bitwise(TokenType::GreaterGreater, ">>", line)?,
+                Op::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!is_truthy(&value)));
+                }
+                Op::Negate => match self.pop() {
+                    Value::Number(n) => self.stack.push(Value::Number(-n)),
+                    value => return Err(error(line, &numeric_operand_message(&value))),
+                },
+                Op::BitNot => {
+                    let value = self.pop();
+                    let op = Token::new(TokenType::Tilde, "~", line);
+                    self.stack.push(bitwise_not(&op, &value)?);
+                }
+                Op::Print => {
+                    let value = self.pop();
+                    let keyword = Token::new(TokenType::Print, "print", line);
+                    self.interpreter.print(&value, &keyword)?;
+                }
+                Op::Function(idx) => {
+                    let function = chunk.functions[idx].clone();
+                    self.stack.push(Value::Callable(Rc::new(function)));
+                }
+                Op::Tick => self.interpreter.tick()?,
+                Op::Jump(target) => ip = target,
+                Op::JumpIfFalse(target) => {
+                    if !is_truthy(self.peek(0)) {
+                        ip = target;
+                    }
+                }
+                Op::Call(argc) => {
+                    self.frames.last_mut().unwrap().ip = ip;
+                    if self.call(argc, line)? {
+                        (chunk, ip, base) = self.current_frame();
+                    }
+                }
+                Op::TailCall(argc) => {
+                    if self.tail_call(argc, line)? {
+                        chunk = Rc::clone(&self.frames.last().unwrap().chunk);
+                        ip = 0;
+                        continue;
+                    }
+                    if let Some(result) = self.return_from_frame() {
+                        return Ok(result);
+                    }
+                    (chunk, ip, base) = self.current_frame();
+                }
+                Op::Exit => {
+                    let keyword = Token::new(TokenType::Return, "return", line);
+                    return Err(RuntimeException::Exit(keyword, self.pop()));
+                }
+                Op::Return => {
+                    if let Some(result) = self.return_from_frame() {
+                        return Ok(result);
+                    }
+                    (chunk, ip, base) = self.current_frame();
+                }
+            }
+        }
+    }
+
+    fn current_frame(&self) -> (Rc<Chunk>, usize, usize) {
+        let frame = self.frames.last().unwrap();
+        (Rc::clone(&frame.chunk), frame.ip, frame.base)
+    }
+
+    // Pops the current frame, leaving the result on top of the stack for the
+    // caller. Returns the result once the outermost frame returns.
+    fn return_from_frame(&mut self) -> Option<Value> {
+        let result = self.pop();
+        let frame = self.frames.pop().unwrap();
+        if self.frames.is_empty() {
+            return Some(result);
+        }
+        self.interpreter.call_depth -= 1;
+        self.stack.truncate(frame.base);
+        self.stack.push(result);
+        None
+    }
+}
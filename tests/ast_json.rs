@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::Path;
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::resolver::Resolver;
+use rlox::stmt::Stmt;
+
+fn snapshot(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(name);
+    fs::read_to_string(path).unwrap()
+}
+
+// The command line tool prints the pretty JSON with a trailing newline.
+fn to_json(stmts: &[Stmt]) -> String {
+    serde_json::to_string_pretty(stmts).unwrap() + "\n"
+}
+
+#[test]
+fn matches_snapshot() {
+    let stmts = lox::parse(&snapshot("ast.lox")).unwrap();
+    assert_eq!(to_json(&stmts), snapshot("ast.json"));
+}
+
+#[test]
+fn round_trips() {
+    let json = snapshot("ast.json");
+    let stmts: Vec<Stmt> = serde_json::from_str(&json).unwrap();
+    assert_eq!(to_json(&stmts), json);
+}
+
+#[test]
+fn deserialized_ast_runs() {
+    let source = "
+        fun makeCounter() {
+          var i = 0;
+          fun count() { i = i + 1; return i; }
+          return count;
+        }
+        var counter = makeCounter();
+        counter();
+        class A { name() { return \"A\"; } }
+        class B < A { name() { return super.name() + \"B\"; } }
+        print counter() + 1;
+        print B().name();";
+    let json = serde_json::to_string(&lox::parse(source).unwrap()).unwrap();
+
+    let stmts: Vec<Stmt> = serde_json::from_str(&json).unwrap();
+    let output = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve(&stmts);
+    assert!(!resolver.has_error);
+    assert!(interpreter.interpret(&stmts).is_ok());
+
+    assert_eq!(output.text(), "3\nAB\n");
+}
+
+#[test]
+fn runtime_values_are_not_serializable() {
+    let mut interpreter = Interpreter::new();
+    assert!(lox::run(&mut interpreter, "fun f() {}").0.is_ok());
+    let token = rlox::token::Token::new(rlox::token::TokenType::Identifier, "f", 1);
+    let f = interpreter.globals.borrow().get(&token).ok().unwrap();
+    assert!(serde_json::to_string(&f).is_err());
+}
@@ -0,0 +1,104 @@
+use rlox::ast_printer;
+use rlox::lox;
+use rlox::stmt::Stmt;
+
+// Each top-level statement of `source`, printed.
+fn printed(source: &str) -> Vec<String> {
+    lox::parse(source).unwrap().iter().map(ast_printer::print_stmt).collect()
+}
+
+// The expression statement `source;`, printed.
+fn expression(source: &str) -> String {
+    match &lox::parse(&format!("{};", source)).unwrap()[..] {
+        [Stmt::Expression(_, expr)] => ast_printer::print(expr),
+        _ => panic!("Not one expression: {}", source),
+    }
+}
+
+#[test]
+fn precedence_and_associativity() {
+    assert_eq!(expression("-123 * (45.67)"), "(* (- 123) (group 45.67))");
+    assert_eq!(expression("1 + 2 * 3 - 4"), "(- (+ 1 (* 2 3)) 4)");
+    assert_eq!(expression("!a == b < c"), "(== (! a) (< b c))");
+    assert_eq!(expression("a or b and c"), "(or a (and b c))");
+    assert_eq!(expression("1..n"), "(.. 1 n)");
+}
+
+#[test]
+fn literals() {
+    assert_eq!(expression("\"hi\""), "\"hi\"");
+    assert_eq!(expression("nil"), "nil");
+    assert_eq!(expression("true"), "true");
+}
+
+#[test]
+fn assignment_is_right_associative() {
+    assert_eq!(expression("a = b = c"), "(= a (= b c))");
+    assert_eq!(expression("[a, b] = pair"), "(= (array a b) pair)");
+}
+
+#[test]
+fn calls_and_properties() {
+    assert_eq!(expression("f(1, g())"), "(call f 1 (call g))");
+    assert_eq!(expression("a.b.c()"), "(call (. (. a b) c))");
+    assert_eq!(expression("a.b = c"), "(= a b c)");
+    assert_eq!(expression("xs[0] = xs[1]"), "([]= xs 0 ([] xs 1))");
+}
+
+#[test]
+fn collections() {
+    assert_eq!(expression("[1, [2]]"), "(array 1 (array 2))");
+    assert_eq!(expression("(1, 2)"), "(tuple 1 2)");
+    assert_eq!(expression("x is Point"), "(is x Point)");
+}
+
+#[test]
+fn this_and_super() {
+    let printed = printed("class A < B { m() { return super.m(this); } }");
+    assert_eq!(printed, vec!["(class A < B (fun m() (return (call (super m) this))))"]);
+}
+
+#[test]
+fn classes_with_methods() {
+    let printed = printed("class Point with Eq implements Shape { init(x, y) { this.x = x; } norm() {} }");
+    assert_eq!(
+        printed,
+        vec!["(class Point (with Eq) (implements Shape) (fun init(x y) (; (= this x x))) (fun norm()))"]
+    );
+}
+
+#[test]
+fn functions_with_parameters() {
+    assert_eq!(printed("fun add(a, b) { return a + b; }"), vec!["(fun add(a b) (return (+ a b)))"]);
+    assert_eq!(printed("fun f() { return; }"), vec!["(fun f() (return))"]);
+}
+
+#[test]
+fn declarations() {
+    let printed = printed("var a; var b = 1; var [c, d] = pair; interface Shape { area(); scale(by); } import \"lib.lox\";");
+    assert_eq!(
+        printed,
+        vec!["(var a)", "(var b = 1)", "(var (array c d) = pair)", "(interface Shape area() scale(by))", "(import \"lib.lox\")"]
+    );
+}
+
+#[test]
+fn control_flow() {
+    let printed = printed(
+        "if (a) print 1; else { print 2; }
+         while (a) a = a - 1;
+         for (var i = 0; i < 3; i = i + 1) print i;
+         for (;;) {}
+         for (var x in xs) print x;",
+    );
+    assert_eq!(
+        printed,
+        vec![
+            "(if-else a (print 1) (block (print 2)))",
+            "(while a (; (= a (- a 1))))",
+            "(for (var i = 0) (< i 3) (= i (+ i 1)) (print i))",
+            "(for _ _ _ (block))",
+            "(for-in x xs (print x))",
+        ]
+    );
+}
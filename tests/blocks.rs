@@ -0,0 +1,37 @@
+use rlox::gc;
+use rlox::interpreter::Interpreter;
+use rlox::lox;
+
+fn allocations(source: &str) -> u64 {
+    let mut interpreter = Interpreter::new();
+    let before = gc::allocated_environments();
+    assert!(lox::run(&mut interpreter, source).0.is_ok());
+    gc::allocated_environments() - before
+}
+
+#[test]
+fn blocks_without_declarations_get_no_environment() {
+    let allocated = allocations(
+        "var total = 0;
+         while (total < 1000) {
+           if (total > 10) {
+             total = total + 2;
+           } else {
+             total = total + 1;
+           }
+         }",
+    );
+    assert_eq!(allocated, 0);
+}
+
+#[test]
+fn blocks_with_declarations_still_do() {
+    let allocated = allocations(
+        "var total = 0;
+         while (total < 1000) {
+           var step = 1;
+           total = total + step;
+         }",
+    );
+    assert_eq!(allocated, 1000);
+}
@@ -0,0 +1,42 @@
+use std::time::Instant;
+
+use rlox::interpreter::Interpreter;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+
+fn generate(methods: usize, iterations: usize) -> String {
+    let mut source = String::from("fun make(k) {\n  class Big {\n    init(k) { this.k = k; }\n");
+    for i in 0..methods {
+        source.push_str(&format!(
+            "    method{i}(a, b) {{ var x = a + b; if (x > {i}) {{ return x - {i}; }} return x * 2 + this.k; }}\n",
+            i = i
+        ));
+    }
+    source.push_str(&format!(
+        "  }}
+  return Big(k);
+}}
+var total = 0;
+for (var i = 0; i < {n}; i = i + 1) {{
+  total = total + make(i).method7(1, 2);
+}}\n",
+        n = iterations
+    ));
+    source
+}
+
+// Run with `cargo test --release --test class_bench -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn define_class_in_loop() {
+    let source = generate(50, 20_000);
+    let tokens = Scanner::new(&source).scan_tokens().clone();
+    let stmts = Parser::new(&tokens).parse();
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter).resolve(&stmts);
+
+    let start = Instant::now();
+    assert!(interpreter.interpret(&stmts).is_ok());
+    println!("50 methods x 20000 classes: {:?}", start.elapsed());
+}
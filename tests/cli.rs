@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn help_prints_usage() {
+    let output = rlox(&["--help"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("Usage: rlox [options] [script]\n"));
+}
+
+#[test]
+fn version_prints_the_package_version() {
+    let output = rlox(&["--version"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), format!("rlox {}\n", env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn unknown_option_is_a_usage_error() {
+    let output = rlox(&["--frobnicate", "script.lox"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(stderr(&output).starts_with("Unknown option '--frobnicate'.\n\nUsage: rlox"));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn two_scripts_are_a_usage_error() {
+    let output = rlox(&["a.lox", "b.lox"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(stderr(&output).starts_with("Unexpected argument 'b.lox', only one script can run.\n"));
+}
+
+#[test]
+fn bad_timeout_is_a_usage_error() {
+    let output = rlox(&["--timeout", "soon", "a.lox"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(stderr(&output).starts_with("Invalid duration 'soon' for --timeout.\n"));
+
+    let output = rlox(&["--timeout"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(stderr(&output).starts_with("Missing duration for --timeout.\n"));
+}
+
+#[test]
+fn write_needs_fmt() {
+    let output = rlox(&["--write", "a.lox"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(stderr(&output).starts_with("--write only works with --fmt.\n"));
+}
+
+#[test]
+fn fmt_write_keeps_comments() {
+    let script = temp_file("rlox_cli_fmt_write.lox", b"// Greets.\nprint  \"hi\" ;   // Once.\n");
+    let output = rlox(&["--fmt", "--write", script.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(fs::read_to_string(&script).unwrap(), "// Greets.\nprint \"hi\"; // Once.\n");
+}
+
+#[test]
+fn missing_file_exits_with_no_input() {
+    let output = rlox(&["no/such/script.lox"]);
+    assert_eq!(output.status.code(), Some(66));
+    assert_eq!(stderr(&output), "Could not read 'no/such/script.lox': no such file.\n");
+}
+
+#[test]
+fn invalid_utf8_exits_with_io_error() {
+    let script = temp_file("rlox_cli_invalid_utf8.lox", b"print \"\xff\";\n");
+    let output = rlox(&[script.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(74));
+    assert_eq!(stderr(&output), format!("Could not read '{}': not valid UTF-8.\n", script.display()));
+}
+
+#[test]
+fn missing_file_is_reported_by_every_mode() {
+    for mode in ["--fmt", "--ast-json"] {
+        let output = rlox(&[mode, "missing.lox"]);
+        assert_eq!(output.status.code(), Some(66));
+        assert_eq!(stderr(&output), "Could not read 'missing.lox': no such file.\n");
+    }
+}
+
+#[test]
+fn options_can_come_after_the_script() {
+    let script = temp_file("rlox_cli_options_after.lox", b"print 1 + 2;\n");
+    let output = rlox(&[script.to_str().unwrap(), "--backend=vm"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn a_top_level_return_sets_the_exit_status() {
+    let script = temp_file("rlox_cli_return.lox", b"print \"checking\";\nreturn 3;\nprint \"unreachable\";\n");
+    for backend in ["--backend=tree", "--backend=vm"].iter() {
+        let output = rlox(&[backend, script.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(stdout(&output), "checking\n");
+        assert_eq!(stderr(&output), "");
+    }
+}
+
+// Printing is buffered, but what was printed still comes out before the error.
+#[cfg(unix)]
+#[test]
+fn output_comes_before_a_runtime_error() {
+    let script = temp_file("rlox_cli_error_order.lox", b"print \"one\";\nprint \"two\";\nprint -\"three\";\nprint \"four\";\n");
+    for backend in ["--backend=tree", "--backend=vm"].iter() {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg("\"$0\" \"$1\" \"$2\" 2>&1")
+            .arg(env!("CARGO_BIN_EXE_rlox"))
+            .arg(backend)
+            .arg(&script)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(70));
+        assert_eq!(stdout(&output), "one\ntwo\nOperand must be a number, but got string (\"three\").\n[line 3]\n");
+    }
+}
@@ -0,0 +1,15 @@
+// Helpers shared by the tests that run every script under `test/`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn collect_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            scripts.push(path);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::convert::TryFrom;
+use std::io;
+
+use rlox::convert::ConversionError;
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::lox;
+
+// The value of the global `name` after running `source`.
+fn global(source: &str, name: &str) -> Value {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let status = lox::exit_code(&lox::run(&mut interpreter, source).0);
+    assert_eq!(status, 0);
+    interpreter.variable(name).unwrap()
+}
+
+#[test]
+fn rust_values_round_trip() {
+    assert_eq!(f64::try_from(Value::from(2.5)), Ok(2.5));
+    assert_eq!(bool::try_from(Value::from(true)), Ok(true));
+    assert_eq!(String::try_from(Value::from("hi")), Ok("hi".to_owned()));
+    assert_eq!(String::try_from(Value::from("hi".to_owned())), Ok("hi".to_owned()));
+    assert_eq!(Option::<f64>::try_from(Value::from(Some(1.0))), Ok(Some(1.0)));
+    assert_eq!(Option::<f64>::try_from(Value::from(None::<f64>)), Ok(None));
+    assert_eq!(Vec::<Value>::try_from(Value::from(vec![Value::Nil])), Ok(vec![Value::Nil]));
+}
+
+#[test]
+fn arrays_and_tuples_convert_to_vectors() {
+    let array = Vec::<Value>::try_from(global("var a = [1, \"b\"];", "a")).unwrap();
+    assert_eq!(array, vec![Value::Number(1.0), Value::String("b".into())]);
+    let tuple = Vec::<Value>::try_from(global("var t = (1, 2);", "t")).unwrap();
+    assert_eq!(tuple, vec![Value::Number(1.0), Value::Number(2.0)]);
+}
+
+#[test]
+fn mismatches_name_both_types() {
+    let message = |err: ConversionError| err.to_string();
+    assert_eq!(f64::try_from(Value::Nil).map_err(message), Err("Expected a number, but got nil.".to_owned()));
+    assert_eq!(
+        Option::<bool>::try_from(Value::from(1.0)).map_err(message),
+        Err("Expected a boolean, but got number.".to_owned())
+    );
+    assert_eq!(
+        String::try_from(global("fun f() {}", "f")).map_err(message),
+        Err("Expected a string, but got function.".to_owned())
+    );
+    assert_eq!(
+        Vec::<Value>::try_from(Value::from("abc")).map_err(message),
+        Err("Expected an array, but got string.".to_owned())
+    );
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use std::convert::TryFrom;
+
+    use serde_json::json;
+
+    use super::global;
+    use rlox::convert::ConversionError;
+    use rlox::expr::Value;
+
+    fn to_json(value: &Value) -> Result<serde_json::Value, ConversionError> {
+        serde_json::Value::try_from(value)
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let json = json!({"a": [1, 2.5, "x", null, true], "b": {"c": {}}});
+        assert_eq!(to_json(&Value::from(json.clone())), Ok(json));
+    }
+
+    #[test]
+    fn lox_values_convert_as_json_stringify_writes_them() {
+        let value = global("var m = Map(); m[\"t\"] = (1, [nil]); m[\"n\"] = -0.5;", "m");
+        assert_eq!(to_json(&value), Ok(json!({"t": [1, [null]], "n": -0.5})));
+    }
+
+    #[test]
+    fn values_without_json_are_errors() {
+        let err = |message: &str| Err(ConversionError(message.to_owned()));
+        assert_eq!(to_json(&global("fun f() {}", "f")), err("Can't convert function to JSON."));
+        assert_eq!(to_json(&global("class A {}", "A")), err("Can't convert class to JSON."));
+        assert_eq!(to_json(&global("class A { toJson() { return 1; } } var a = A();", "a")), err("Can't convert instance to JSON."));
+        assert_eq!(to_json(&Value::Number(f64::NAN)), err("Can't convert number to JSON."));
+        assert_eq!(to_json(&global("var m = Map(); m[1] = 2;", "m")), err("Can't convert map key 1 to JSON, keys must be strings."));
+        assert_eq!(to_json(&global("var a = [1]; push(a, a);", "a")), err("Can't convert a value that contains itself to JSON."));
+    }
+}
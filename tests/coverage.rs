@@ -0,0 +1,101 @@
+use std::io;
+use std::process::Command;
+
+use rlox::coverage::{self, Hits};
+use rlox::interpreter::Interpreter;
+use rlox::lox;
+
+const LIBRARY: &str = "\
+fun abs(n) {
+  if (n < 0) {
+    return -n;
+  }
+  return n;
+}
+
+class Counter {
+  init() {
+    this.count = 0;
+  }
+  unused() {
+    print \"never\";
+  }
+}
+
+for (var i = 0; i < 3; i = i + 1) abs(i);
+Counter();
+";
+
+fn hits(source: &str) -> Hits {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    interpreter.set_coverage(true);
+    let status = lox::exit_code(&lox::run(&mut interpreter, source).0);
+    assert_eq!(status, 0);
+    interpreter.coverage().unwrap().clone()
+}
+
+#[test]
+fn counts_statements_per_line() {
+    let hits = hits(LIBRARY);
+    let counts: Vec<_> = hits.into_iter().collect();
+    // The `for` line counts the loop, its initializer and each run of the body.
+    assert_eq!(counts, vec![(1, 1), (2, 3), (5, 3), (8, 1), (10, 1), (17, 5), (18, 1)]);
+}
+
+#[test]
+fn lines_with_statements() {
+    let stmts = lox::parse(LIBRARY).unwrap();
+    let lines: Vec<_> = coverage::statement_lines(&stmts).into_iter().collect();
+    assert_eq!(lines, vec![1, 2, 3, 5, 8, 10, 13, 17, 18]);
+}
+
+#[test]
+fn report_marks_lines_that_never_ran() {
+    let stmts = lox::parse(LIBRARY).unwrap();
+    let lines = coverage::statement_lines(&stmts);
+    let report = coverage::report("lib.lox", LIBRARY, &lines, &hits(LIBRARY));
+    let report: Vec<_> = report.lines().collect();
+
+    assert_eq!(report[0], "lib.lox: 7 of 9 lines covered (77.8%)");
+    assert_eq!(report[1], "        1:    1:fun abs(n) {");
+    assert_eq!(report[3], "    #####:    3:    return -n;");
+    assert_eq!(report[4], "        -:    4:  }");
+    assert_eq!(report[13], "    #####:   13:    print \"never\";");
+}
+
+#[test]
+fn lcov_output() {
+    let stmts = lox::parse("var a = 1;\nif (a > 1)\n  print a;\n").unwrap();
+    let lines = coverage::statement_lines(&stmts);
+    let hits = hits("var a = 1;\nif (a > 1)\n  print a;\n");
+    assert_eq!(
+        coverage::lcov("a.lox", &lines, &hits),
+        "TN:\nSF:a.lox\nDA:1,1\nDA:2,1\nDA:3,0\nLF:3\nLH:2\nend_of_record\n"
+    );
+}
+
+#[test]
+fn nothing_is_counted_without_coverage() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    assert!(lox::run(&mut interpreter, "print 1;").0.is_ok());
+    assert!(interpreter.coverage().is_none());
+}
+
+#[test]
+fn cli_prints_coverage_to_stderr() {
+    let script = std::env::temp_dir().join("rlox_coverage_test.lox");
+    std::fs::write(&script, "print 1;\nif (false)\n  print 2;\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("--coverage=lcov")
+        .arg(&script)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("DA:1,1\nDA:2,1\nDA:3,0\n"), "{}", stderr);
+}
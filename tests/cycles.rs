@@ -0,0 +1,167 @@
+use rlox::expr::Value;
+use rlox::gc;
+use rlox::interpreter::Interpreter;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+use rlox::shared::Rc;
+use rlox::token::{Token, TokenType};
+
+fn run(interpreter: &mut Interpreter, source: &str) {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens().clone();
+    let stmts = Parser::new(&tokens).parse();
+    Resolver::new(interpreter).resolve(&stmts);
+    assert!(interpreter.interpret(&stmts).is_ok());
+}
+
+fn global(interpreter: &Interpreter, name: &str) -> Value {
+    let token = Token::new(TokenType::Identifier, name, 1);
+    interpreter.globals.borrow().get(&token).ok().unwrap()
+}
+
+#[test]
+fn returned_closure_environment_is_freed() {
+    let mut interpreter = Interpreter::new();
+    run(
+        &mut interpreter,
+        "fun makeCounter() {
+           var i = 0;
+           fun count() { i = i + 1; return i; }
+           return count;
+         }
+         var counter = makeCounter();
+         counter();",
+    );
+
+    let closure = match global(&interpreter, "counter") {
+        Value::Callable(counter) => Rc::downgrade(counter.closure().unwrap()),
+        _ => panic!("counter is not a function"),
+    };
+    // The closure's environment holds `count`, which holds the environment.
+    gc::collect();
+    assert!(closure.upgrade().is_some());
+
+    run(&mut interpreter, "counter = nil;");
+    assert!(closure.upgrade().is_some());
+    assert_eq!(gc::collect(), 1);
+    assert!(closure.upgrade().is_none());
+}
+
+#[test]
+fn closures_created_in_a_loop_do_not_accumulate() {
+    let mut interpreter = Interpreter::new();
+    run(
+        &mut interpreter,
+        "fun makeCounter() {
+           var i = 0;
+           fun count() { i = i + 1; return i; }
+           return count;
+         }
+         for (var j = 0; j < 10000; j = j + 1) {
+           var counter = makeCounter();
+           counter();
+         }",
+    );
+
+    gc::collect();
+    assert!(gc::tracked_environments() < 10);
+}
+
+#[test]
+fn class_referring_to_itself_through_a_static_field_is_freed() {
+    let mut interpreter = Interpreter::new();
+    run(
+        &mut interpreter,
+        "fun make() {
+           class Node { next() { return Node.last; } }
+           Node.last = Node;
+           return Node;
+         }
+         var node = make();",
+    );
+
+    let class = match global(&interpreter, "node") {
+        Value::Class(class) => Rc::downgrade(&class),
+        _ => panic!("node is not a class"),
+    };
+    run(&mut interpreter, "node = nil;");
+    assert!(class.upgrade().is_some());
+    gc::collect();
+    assert!(class.upgrade().is_none());
+}
+
+#[test]
+fn map_holding_a_closure_over_itself_is_freed() {
+    let mut interpreter = Interpreter::new();
+    run(
+        &mut interpreter,
+        "fun make() {
+           var m = Map();
+           fun get() { return m; }
+           m[\"get\"] = get;
+           return m;
+         }
+         var m = make();",
+    );
+
+    let map = match global(&interpreter, "m") {
+        Value::Map(map) => Rc::downgrade(&map),
+        _ => panic!("m is not a map"),
+    };
+    run(&mut interpreter, "m = nil;");
+    assert!(map.upgrade().is_some());
+    gc::collect();
+    assert!(map.upgrade().is_none());
+}
+
+#[test]
+fn dropping_an_interpreter_frees_its_globals() {
+    let mut interpreter = Interpreter::new();
+    // `f` is a global whose closure is the globals.
+    run(&mut interpreter, "fun f() { return f; } var kept = f;");
+    let globals = Rc::downgrade(&interpreter.globals);
+    let kept = global(&interpreter, "kept");
+    drop(interpreter);
+    assert!(globals.upgrade().is_some());
+    drop(kept);
+
+    let mut interpreter = Interpreter::new();
+    run(&mut interpreter, "fun f() { return f; }");
+    let globals = Rc::downgrade(&interpreter.globals);
+    drop(interpreter);
+    assert!(globals.upgrade().is_none());
+}
+
+#[test]
+fn reset_frees_a_function_naming_itself() {
+    let mut interpreter = Interpreter::new();
+    // The body's use of `f` keeps track of the global it found.
+    run(&mut interpreter, "fun f() { return f; } f();");
+    let f = match global(&interpreter, "f") {
+        Value::Callable(f) => Rc::downgrade(&f),
+        _ => panic!("f is not a function"),
+    };
+    interpreter.reset();
+    assert!(f.upgrade().is_none());
+}
+
+#[test]
+fn classes_whose_methods_were_called_are_freed_by_the_next_run() {
+    let mut interpreter = Interpreter::new();
+    run(
+        &mut interpreter,
+        "class A {}
+         class B < A { m() { return 1; } }
+         var b = B();
+         b.m();",
+    );
+
+    let class = match global(&interpreter, "A") {
+        Value::Class(class) => Rc::downgrade(&class),
+        _ => panic!("A is not a class"),
+    };
+    run(&mut interpreter, "A = nil; B = nil; b = nil;");
+    gc::collect();
+    assert!(class.upgrade().is_none());
+}
@@ -0,0 +1,105 @@
+use std::io;
+
+use rlox::debugger::{Command, Debugger};
+use rlox::interpreter::Interpreter;
+use rlox::lox;
+use rlox::shared::{Rc, RefCell};
+use rlox::stmt::Stmt;
+
+// Records what it sees at every pause and answers with the next command.
+struct Scripted {
+    commands: Vec<Command>,
+    pauses: Rc<RefCell<Vec<String>>>,
+}
+
+impl Debugger for Scripted {
+    fn pause(&mut self, interpreter: &Interpreter, stmt: &Stmt) -> Command {
+        let locals: Vec<String> = interpreter
+            .local_variables()
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        let stack: Vec<String> = interpreter
+            .call_stack()
+            .iter()
+            .map(|frame| format!("{}@{}", frame.callee, frame.line))
+            .collect();
+        self.pauses.borrow_mut().push(format!(
+            "line {} locals [{}] stack [{}] g={}",
+            stmt.line().unwrap(),
+            locals.join(" "),
+            stack.join(" "),
+            interpreter.variable("g").map_or("none".to_owned(), |g| g.to_string()),
+        ));
+        if self.commands.is_empty() {
+            Command::Continue
+        } else {
+            self.commands.remove(0)
+        }
+    }
+}
+
+fn debug(source: &str, commands: Vec<Command>) -> Vec<String> {
+    let pauses = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    interpreter.set_debugger(Some(Box::new(Scripted { commands, pauses: Rc::clone(&pauses) })));
+    let status = lox::exit_code(&lox::run(&mut interpreter, source).0);
+    assert_eq!(status, 0);
+    let pauses = pauses.borrow().clone();
+    pauses
+}
+
+const PROGRAM: &str = "\
+var g = \"global\";
+fun f(a) {
+  var b = a * 2;
+  breakpoint();
+  print b;
+  return b;
+}
+print f(3);
+print g;
+";
+
+#[test]
+fn pauses_after_breakpoint_and_continues() {
+    let pauses = debug(PROGRAM, vec![Command::Continue]);
+    assert_eq!(pauses, vec!["line 5 locals [b=6 a=3] stack [<fn f>@8] g=global"]);
+}
+
+#[test]
+fn steps_through_statements() {
+    let pauses = debug(PROGRAM, vec![Command::Step, Command::Step, Command::Continue]);
+    assert_eq!(
+        pauses,
+        vec![
+            "line 5 locals [b=6 a=3] stack [<fn f>@8] g=global",
+            "line 6 locals [b=6 a=3] stack [<fn f>@8] g=global",
+            "line 9 locals [] stack [] g=global",
+        ]
+    );
+}
+
+#[test]
+fn inner_scopes_shadow_outer_ones() {
+    let source = "\
+var g = 1;
+{
+  var g = 2;
+  breakpoint();
+  g;
+}
+";
+    let pauses = debug(source, vec![]);
+    assert_eq!(pauses, vec!["line 5 locals [g=2] stack [] g=2"]);
+}
+
+#[test]
+fn breakpoint_does_nothing_without_a_debugger() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let status = lox::exit_code(&lox::run(&mut interpreter, PROGRAM).0);
+    assert_eq!(status, 0);
+    assert!(interpreter.call_stack().is_empty());
+}
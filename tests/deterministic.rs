@@ -0,0 +1,89 @@
+use std::process::Command;
+
+use rlox::clock::{MockTime, TimeSource};
+use rlox::interpreter::Interpreter;
+use rlox::lox::Lox;
+use rlox::natives::Profile;
+use rlox::rng::Rng;
+
+const TIMED: &str = "
+    var start = clock();
+    var sum = 0;
+    for (var i = 0; i < 1000; i = i + 1) sum = sum + random();
+    print clock() - start;
+    print start;
+    print sum;
+";
+
+fn run_command_line(script: &str, args: &[&str]) -> String {
+    let path = std::env::temp_dir().join(script);
+    std::fs::write(&path, TIMED).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox")).args(args).arg(&path).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn command_line_runs_print_the_same() {
+    let first = run_command_line("rlox_deterministic.lox", &["--deterministic"]);
+    let second = run_command_line("rlox_deterministic.lox", &["--deterministic"]);
+    assert_eq!(first, second);
+    assert!(first.starts_with("1\n0\n"));
+
+    // Without it, clock() is the time since the epoch.
+    let real = run_command_line("rlox_nondeterministic.lox", &[]);
+    assert_ne!(real, first);
+}
+
+fn run(interpreter: Interpreter, source: &str) -> String {
+    Lox::with_interpreter(interpreter).run_source(source).stdout
+}
+
+fn mocked() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_time_source(Box::new(MockTime::new(250.0)));
+    interpreter.set_rng(Rng::seeded(42));
+    interpreter
+}
+
+#[test]
+fn mock_time_moves_a_step_per_read() {
+    assert_eq!(run(mocked(), "print clock(); print clock(); print clock();"), "0\n250\n500\n");
+}
+
+#[test]
+fn a_seed_gives_the_same_numbers() {
+    let source = "print random(); print random();";
+    assert_eq!(run(mocked(), source), run(mocked(), source));
+
+    let mut other = mocked();
+    other.set_rng(Rng::seeded(43));
+    assert_ne!(run(mocked(), source), run(other, source));
+}
+
+struct Frozen(f64);
+
+impl TimeSource for Frozen {
+    fn now_millis(&mut self) -> f64 {
+        self.0
+    }
+}
+
+#[test]
+fn hosts_can_bring_their_own_clock() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_time_source(Box::new(Frozen(1234.0)));
+    assert_eq!(run(interpreter, "print clock(); print clock();"), "1234\n1234\n");
+}
+
+#[test]
+fn sandboxes_have_no_random() {
+    let outcome = Lox::with_interpreter(Interpreter::with_profile(Profile::pure())).run_source("random();");
+    assert!(outcome.diagnostics[0].message.starts_with("Undefined variable 'random'."));
+}
+
+#[test]
+fn seeded_numbers_are_in_range() {
+    let mut rng = Rng::seeded(0);
+    assert!((0..10_000).map(|_| rng.next_f64()).all(|n| (0.0..1.0).contains(&n)));
+}
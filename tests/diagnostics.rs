@@ -0,0 +1,59 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::loxerr::{self, DiagnosticFormat};
+
+// One script per phase, as a run stops at the first phase with errors.
+const SCRIPTS: [&str; 3] = [
+    "var a = 1 @ 2;\nprint (;\n",
+    "{\n  var b = 1;\n  {\n    var b = 2;\n    var b = 3;\n  }\n}\nthis;\n",
+    "print 1;\nprint -\"one\";\n",
+];
+
+// The exit status and what was reported.
+fn run(source: &str, format: DiagnosticFormat) -> (i32, String) {
+    let errors = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    loxerr::set_diagnostic_format(format);
+    let status = lox::exit_code(&lox::run(&mut interpreter, source).0);
+    loxerr::set_diagnostic_format(DiagnosticFormat::Human);
+    loxerr::set_error_output(previous);
+    (status, errors.text())
+}
+
+#[test]
+fn json_matches_snapshot() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/diagnostics.jsonl");
+    let mut reported = String::new();
+    let mut statuses = Vec::new();
+    for source in SCRIPTS {
+        let (status, errors) = run(source, DiagnosticFormat::Json);
+        statuses.push(status);
+        reported.push_str(&errors);
+    }
+    assert_eq!(statuses, vec![65, 65, 70]);
+    assert_eq!(reported, fs::read_to_string(path).unwrap());
+}
+
+#[test]
+fn every_line_is_one_object() {
+    for source in SCRIPTS {
+        let (_, errors) = run(source, DiagnosticFormat::Json);
+        for line in errors.lines() {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(value.is_object());
+        }
+    }
+}
+
+#[test]
+fn human_format_is_unchanged() {
+    let (status, errors) = run(SCRIPTS[2], DiagnosticFormat::Human);
+    assert_eq!(status, 70);
+    assert_eq!(errors, "Operand must be a number, but got string (\"one\").\n[line 2]\n");
+}
@@ -0,0 +1,84 @@
+use std::io;
+
+use rlox::compiler;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Backend, Lox, RunStatus};
+use rlox::loxerr;
+
+fn status(source: &str) -> RunStatus {
+    Lox::new().run_source(source).status
+}
+
+#[test]
+fn a_top_level_return_ends_the_script_with_its_status() {
+    for backend in [Backend::Tree, Backend::Vm].iter().copied() {
+        let mut lox = Lox::new();
+        lox.set_backend(backend);
+        let outcome = lox.run_source("print 1;\nif (true) { return 3; }\nprint 2;");
+        assert_eq!(outcome.stdout, "1\n");
+        assert_eq!(outcome.status, RunStatus::Exited(3));
+        assert_eq!(outcome.status.exit_code(), 3);
+    }
+}
+
+#[test]
+fn returning_nothing_or_zero_succeeds() {
+    assert_eq!(status("return;"), RunStatus::Ok);
+    assert_eq!(status("return nil;"), RunStatus::Ok);
+    assert_eq!(status("return 0;"), RunStatus::Ok);
+    assert_eq!(status("return 255;"), RunStatus::Exited(255));
+}
+
+#[test]
+fn statuses_are_whole_numbers_from_0_to_255() {
+    for source in ["return 256;", "return -1;", "return 0.5;", "return true;", "return \"1\";"].iter() {
+        let outcome = Lox::new().run_source(source);
+        assert_eq!(outcome.status, RunStatus::RuntimeError, "{}", source);
+        assert!(outcome.diagnostics[0].message.starts_with("Exit status must be a whole number from 0 to 255"));
+    }
+}
+
+#[test]
+fn the_vm_runs_top_level_returns_itself() {
+    let source = "var i = 0;\nwhile (true) {\n  i = i + 1;\n  if (i == 4) return i;\n}\nreturn 1.5;";
+    assert!(compiler::compile(&lox::parse(source).unwrap()).is_ok());
+    let mut lox = Lox::new();
+    lox.set_backend(Backend::Vm);
+    assert_eq!(lox.run_source(source).status, RunStatus::Exited(4));
+
+    let outcome = lox.run_source("print 1;\nreturn -1;");
+    assert_eq!(outcome.status, RunStatus::RuntimeError);
+    assert_eq!(outcome.diagnostics[0].line, Some(2));
+    assert_eq!(outcome.diagnostics[0].message, "Exit status must be a whole number from 0 to 255, but got number (-1).");
+}
+
+#[test]
+fn returns_inside_functions_return_from_them() {
+    let outcome = Lox::new().run_source("fun f() { return 3; }\nprint f();\nfun g() { return f(); }\nprint g();");
+    assert_eq!(outcome.status, RunStatus::Ok);
+    assert_eq!(outcome.stdout, "3\n3\n");
+}
+
+#[test]
+fn run_gives_back_the_status() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let result = lox::run(&mut interpreter, "return 7;").0;
+    assert_eq!(result, Ok(7));
+    assert_eq!(lox::exit_code(&result), 7);
+
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let result = lox::run(&mut interpreter, "return \"seven\";").0;
+    loxerr::set_error_output(previous);
+    assert_eq!(lox::exit_code(&result), 70);
+}
+
+#[test]
+fn the_prompt_echoes_what_is_returned() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let value = lox::run_echo(&mut interpreter, "var a = 1; return \"done\"; a = 2;").0.unwrap();
+    assert_eq!(value.unwrap().to_string(), "done");
+    let value = lox::run_echo(&mut interpreter, "a;").0.unwrap();
+    assert_eq!(value.unwrap().to_string(), "1");
+}
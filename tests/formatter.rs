@@ -0,0 +1,144 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rlox::scanner::Scanner;
+use rlox::stmt::Stmt;
+use rlox::{formatter, lox, loxerr};
+use serde_json::Value;
+
+mod common;
+
+use common::collect_scripts;
+
+// The tree as JSON without line numbers or spans, which formatting is free
+// to change.
+fn shape(stmts: &[Stmt]) -> Value {
+    fn strip_positions(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.remove("line");
+                map.remove("span");
+                map.values_mut().for_each(strip_positions);
+            }
+            Value::Array(items) => items.iter_mut().for_each(strip_positions),
+            _ => (),
+        }
+    }
+
+    let mut value = serde_json::to_value(stmts).unwrap();
+    strip_positions(&mut value);
+    value
+}
+
+fn comments(source: &str) -> Vec<String> {
+    let mut scanner = Scanner::new(source);
+    scanner.report_errors = false;
+    scanner.scan_tokens();
+    scanner.comments.iter().map(|comment| source[comment.clone()].trim_end().to_owned()).collect()
+}
+
+fn snapshot(name: &str) -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(name)).unwrap()
+}
+
+fn check_round_trip(name: &str, source: &str) -> Result<(), String> {
+    let stmts = match lox::parse(source) {
+        Some(stmts) => stmts,
+        // Scripts testing syntax errors have nothing to format.
+        None => return Ok(()),
+    };
+    let formatted = formatter::format(&stmts);
+
+    let reparsed = lox::parse(&formatted)
+        .ok_or_else(|| format!("{}: formatted source doesn't parse:\n{}", name, formatted))?;
+    if shape(&reparsed) != shape(&stmts) {
+        return Err(format!("{}: formatting changed the tree:\n{}", name, formatted));
+    }
+    if formatter::format(&reparsed) != formatted {
+        return Err(format!("{}: formatting isn't stable:\n{}", name, formatted));
+    }
+
+    // Keeping the comments changes nothing else, and keeps them in order.
+    let formatted = formatter::format_with_comments(&stmts, source);
+    let reparsed = lox::parse(&formatted)
+        .ok_or_else(|| format!("{}: formatted source doesn't parse:\n{}", name, formatted))?;
+    if shape(&reparsed) != shape(&stmts) {
+        return Err(format!("{}: formatting changed the tree:\n{}", name, formatted));
+    }
+    if comments(&formatted) != comments(source) {
+        return Err(format!("{}: formatting lost or moved comments:\n{}", name, formatted));
+    }
+    if formatter::format_with_comments(&reparsed, &formatted) != formatted {
+        return Err(format!("{}: formatting with comments isn't stable:\n{}", name, formatted));
+    }
+    Ok(())
+}
+
+#[test]
+fn formatting_preserves_the_tree() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut scripts = Vec::new();
+    collect_scripts(&root.join("test"), &mut scripts);
+    collect_scripts(&root.join("tests/snapshots"), &mut scripts);
+    scripts.sort();
+
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let failures: Vec<String> = scripts
+        .iter()
+        .filter_map(|path| {
+            let name = path.strip_prefix(root).unwrap().display().to_string();
+            check_round_trip(&name, &fs::read_to_string(path).unwrap()).err()
+        })
+        .collect();
+    loxerr::set_error_output(previous);
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+#[test]
+fn canonical_layout() {
+    let source = "class A<B{m(a,b){if(a)return b;else{print a;}} n(){}}
+fun f(){var x=-(1+2)*3;for(;;)while(x<1)x=x+1;}
+print f()==nil and !true or \"s\";";
+    let expected = "class A < B {
+  m(a, b) {
+    if (a) return b; else {
+      print a;
+    }
+  }
+
+  n() {}
+}
+
+fun f() {
+  var x = -(1 + 2) * 3;
+  for (;;) while (x < 1) x = x + 1;
+}
+
+print f() == nil and !true or \"s\";
+";
+    assert_eq!(formatter::format(&lox::parse(source).unwrap()), expected);
+}
+
+#[test]
+fn comments_are_kept() {
+    let source = snapshot("comments.lox");
+    let stmts = lox::parse(&source).unwrap();
+    assert_eq!(formatter::format_with_comments(&stmts, &source), snapshot("comments_formatted.lox"));
+    assert_eq!(check_round_trip("comments", &source), Ok(()));
+}
+
+// Cases the round trip tests in tests/round_trip.rs found.
+#[test]
+fn properties_of_whole_numbers() {
+    let stmts = lox::parse("print 1 ._y; 2 ._z = 1.5._w;").unwrap();
+    assert_eq!(formatter::format(&stmts), "print 1 ._y;\n2 ._z = 1.5._w;\n");
+    assert_eq!(check_round_trip("properties", "print 1 ._y;"), Ok(()));
+}
+
+#[test]
+fn numbers_too_long_for_a_double() {
+    let source = format!("print {}0;", "9".repeat(400));
+    assert_eq!(check_round_trip("infinity", &source), Ok(()));
+}
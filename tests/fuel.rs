@@ -0,0 +1,79 @@
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::loxerr::RuntimeException;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+use rlox::token::{Token, TokenType};
+
+fn run(interpreter: &mut Interpreter, source: &str) -> Result<i32, RuntimeException> {
+    let tokens = Scanner::new(source).scan_tokens().clone();
+    let stmts = Parser::new(&tokens).parse();
+    let mut resolver = Resolver::new(interpreter);
+    resolver.resolve(&stmts);
+    assert!(!resolver.has_error);
+    interpreter.interpret(&stmts)
+}
+
+fn global(interpreter: &Interpreter, name: &str) -> Value {
+    let token = Token::new(TokenType::Identifier, name, 1);
+    interpreter.globals.borrow().get(&token).ok().unwrap()
+}
+
+#[test]
+fn infinite_loop_runs_out_of_fuel() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_fuel(10_000);
+    let result = run(&mut interpreter, "var i = 0; while (true) i = i + 1;");
+
+    assert!(matches!(result, Err(RuntimeException::FuelExhausted)));
+    assert_eq!(interpreter.fuel(), Some(0));
+    assert_eq!(global(&interpreter, "i"), Value::Number(10_000.0));
+}
+
+#[test]
+fn recursion_runs_out_of_fuel() {
+    let source = "
+        var calls = 0;
+        fun ping() { calls = calls + 1; return pong(); }
+        fun pong() { calls = calls + 1; return ping(); }
+        fun deep(n) { calls = calls + 1; deep(n + 1); }
+        ping();";
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_fuel(5_000);
+    assert!(matches!(run(&mut interpreter, source), Err(RuntimeException::FuelExhausted)));
+    assert_eq!(global(&interpreter, "calls"), Value::Number(5_000.0));
+
+    // Shallow enough for a test thread's stack in a debug build.
+    interpreter.set_fuel(20);
+    let result = run(&mut interpreter, "calls = 0; deep(0);");
+    assert!(matches!(result, Err(RuntimeException::FuelExhausted)));
+    assert_eq!(global(&interpreter, "calls"), Value::Number(20.0));
+}
+
+#[test]
+fn stops_at_the_same_point_every_time() {
+    let source = "
+        var log = \"\";
+        fun step(n) { log = log + \".\"; return n + 1; }
+        for (var i = 0; i < 100; i = step(i)) {}";
+
+    let mut logs = Vec::new();
+    for _ in 0..3 {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_fuel(75);
+        assert!(matches!(run(&mut interpreter, source), Err(RuntimeException::FuelExhausted)));
+        logs.push(global(&interpreter, "log").to_string());
+    }
+
+    assert!(logs.iter().all(|log| *log == logs[0]));
+    assert_eq!(logs[0].len(), 37);
+}
+
+#[test]
+fn unlimited_by_default() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.fuel(), None);
+    assert!(run(&mut interpreter, "for (var i = 0; i < 100000; i = i + 1) {}").is_ok());
+}
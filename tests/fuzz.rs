@@ -0,0 +1,165 @@
+// Throws generated input at the front end, which has to report errors for
+// anything it can't make sense of and never panic. The generators are seeded,
+// so a failure happens again on every run; `fuzz/` has cargo-fuzz targets for
+// searching further.
+
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::thread;
+
+use rlox::analysis;
+use rlox::ast_printer;
+use rlox::formatter;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{Backend, Lox};
+use rlox::loxerr;
+use rlox::natives::Profile;
+use rlox::parser::Parser;
+use rlox::rng::Rng;
+use rlox::scanner::Scanner;
+
+const CASES: usize = 2000;
+
+const VOCABULARY: &[&str] = &[
+    "(", ")", "{", "}", "[", "]", ",", ".", "..", "...", "-", "+", ";", "/", "*", "!", "!=", "=", "==", ">", ">=",
+    "<", "<=", "?", ":", "|", "=>", "+=", "and", "or", "class", "fun", "var", "if", "else", "for", "while", "return",
+    "this", "super", "print", "nil", "true", "false", "break", "continue", "import", "interface", "implements",
+    "with", "in", "try", "catch", "throw", "x", "y", "f", "A", "init", "0", "1.5", "1_000", "_", "\"s\"", "\"",
+    "\"\\u{41}\"", "\"\\u{", "\"\\", "'", "\"${x}\"", "\"${", "//", "/*", "*/", "\n", " ", "é", "\u{1F600}",
+];
+
+// Scans, parses and resolves `source`, prints whatever was parsed and, if
+// that worked, runs it briefly on both backends.
+fn check(source: &str) {
+    let analysis = analysis::analyze(source);
+    formatter::format(&analysis.stmts);
+    for stmt in &analysis.stmts {
+        ast_printer::print_stmt(stmt);
+    }
+
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens().clone();
+    Parser::new(&tokens).parse();
+
+    if analysis.diagnostics.is_empty() {
+        for backend in [Backend::Tree, Backend::Vm].iter().copied() {
+            let mut interpreter = Interpreter::with_profile(Profile::pure());
+            interpreter.set_fuel(1000);
+            interpreter.set_max_call_depth(32);
+            interpreter.set_memory_limit(Some(1 << 20));
+            interpreter.set_output(Box::new(io::sink()));
+            let mut lox = Lox::with_interpreter(interpreter);
+            lox.set_backend(backend);
+            lox.run_source(source);
+        }
+    }
+    loxerr::set_error_output(previous);
+}
+
+// Lox calls nest Rust calls, and debug builds need more stack for them than a
+// test thread has.
+fn assert_no_panics(inputs: impl Iterator<Item = String>) {
+    let inputs: Vec<String> = inputs.collect();
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let panicked = thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(move || inputs.into_iter().filter(|input| panic::catch_unwind(AssertUnwindSafe(|| check(input))).is_err()).collect::<Vec<_>>())
+        .unwrap()
+        .join()
+        .unwrap();
+    panic::set_hook(hook);
+    assert!(panicked.is_empty(), "{} inputs panicked, first {:?}", panicked.len(), panicked.first());
+}
+
+// Inputs that once panicked.
+#[test]
+fn regressions() {
+    let inputs = [
+        "for (var i = i = 0; i < 3; i = i + 1) print i;",
+        "{ var a = a = 1; }",
+        "import",
+        "import;",
+        "\"\\",
+        "\"\\u{",
+        "\"${",
+    ];
+    assert_no_panics(inputs.iter().map(|input| input.to_string()));
+}
+
+fn below(rng: &mut Rng, n: usize) -> usize {
+    (rng.next_u64() % n as u64) as usize
+}
+
+#[test]
+fn random_bytes() {
+    let mut rng = Rng::seeded(1);
+    let inputs = (0..CASES).map(move |_| {
+        let len = below(&mut rng, 64);
+        let bytes: Vec<u8> = (0..len).map(|_| rng.next_u64() as u8).collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    });
+    assert_no_panics(inputs);
+}
+
+#[test]
+fn random_tokens() {
+    let mut rng = Rng::seeded(2);
+    let inputs = (0..CASES).map(move |_| {
+        let len = below(&mut rng, 32);
+        (0..len).map(|_| VOCABULARY[below(&mut rng, VOCABULARY.len())]).collect::<Vec<_>>().join(" ")
+    });
+    assert_no_panics(inputs);
+}
+
+fn corpus(dir: &Path, found: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            corpus(&path, found);
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            found.push(fs::read_to_string(&path).unwrap());
+        }
+    }
+}
+
+// Cuts, repeats or adds a few pieces of a test script, which gives mostly
+// valid programs broken in one or two places.
+fn mutate(rng: &mut Rng, source: &str) -> String {
+    let mut chars: Vec<char> = source.chars().collect();
+    for _ in 0..1 + below(rng, 3) {
+        let at = below(rng, chars.len() + 1);
+        let len = below(rng, 8).min(chars.len() - at);
+        match below(rng, 3) {
+            0 => {
+                chars.drain(at..at + len);
+            }
+            1 => {
+                let piece: Vec<char> = chars[at..at + len].to_vec();
+                let to = below(rng, chars.len() + 1);
+                chars.splice(to..to, piece);
+            }
+            _ => {
+                let token = VOCABULARY[below(rng, VOCABULARY.len())];
+                chars.splice(at..at, token.chars());
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+#[test]
+fn broken_test_scripts() {
+    let mut scripts = Vec::new();
+    corpus(Path::new("test"), &mut scripts);
+    scripts.sort();
+    let mut rng = Rng::seeded(3);
+    let inputs = (0..CASES).map(move |_| {
+        let script = &scripts[below(&mut rng, scripts.len())];
+        mutate(&mut rng, script)
+    });
+    assert_no_panics(inputs);
+}
@@ -0,0 +1,35 @@
+use std::time::Instant;
+
+use rlox::interpreter::Interpreter;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+
+fn time(label: &str, source: &str) {
+    let tokens = Scanner::new(source).scan_tokens().clone();
+    let stmts = Parser::new(&tokens).parse();
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter).resolve(&stmts);
+
+    let start = Instant::now();
+    assert!(interpreter.interpret(&stmts).is_ok());
+    println!("{}: {:?}", label, start.elapsed());
+}
+
+// Run with `cargo test --release --test global_bench -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn global_function_in_loop() {
+    time(
+        "2000000 calls to a global function",
+        "var count = 0;
+         fun inc() { count = count + 1; }
+         for (var i = 0; i < 2000000; i = i + 1) inc();",
+    );
+}
+
+#[test]
+#[ignore]
+fn recursive_fib() {
+    time("fib(27)", "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } fib(27);");
+}
@@ -0,0 +1,153 @@
+// Runs every script under `test/` and checks it against the expectations in
+// its comments, in the format of the Crafting Interpreters test suite:
+//
+//     print 1 + 2;      // expect: 3
+//     nil();            // expect runtime error: Can only call functions and classes.
+//     var a = ;         // Error at ';': Expect expression.
+//     // [line 5] Error at end: Expect '}' after block.
+//
+// Set LOX_TEST to a part of a path to only run the matching scripts.
+
+use std::fs;
+use std::path::Path;
+
+use rlox::clock::MockTime;
+use rlox::interpreter::Interpreter;
+use rlox::lox::Capture;
+use rlox::rng::Rng;
+use rlox::{lox, loxerr};
+
+mod common;
+
+use common::collect_scripts;
+
+#[derive(Default)]
+struct Expectations {
+    output: Vec<String>,
+    errors: Vec<String>,
+    runtime_error: Option<String>,
+    status: i32,
+}
+
+fn parse_expectations(source: &str) -> Expectations {
+    let mut expected = Expectations::default();
+    for (idx, line) in source.lines().enumerate() {
+        let line_number = idx + 1;
+        let comment = match line.find("// ") {
+            Some(start) => &line[start + 3..],
+            None => continue,
+        };
+
+        if let Some(output) = comment.strip_prefix("expect: ") {
+            expected.output.push(output.to_owned());
+        } else if let Some(error) = comment.strip_prefix("expect runtime error: ") {
+            expected.runtime_error = Some(format!("{}\n[line {}]", error, line_number));
+            expected.status = 70;
+        } else if comment.starts_with("Error") {
+            expected.errors.push(format!("[line {}] {}", line_number, comment));
+            expected.status = 65;
+        } else if comment.starts_with("Warning") {
+            // Warnings don't change the exit status.
+            expected.errors.push(format!("[line {}] {}", line_number, comment));
+        } else if let Some(rest) = comment
+            .strip_prefix("[line ")
+            .or_else(|| comment.strip_prefix("[java line "))
+        {
+            if let Some(end) = rest.find("] Error") {
+                expected.errors.push(format!("[line {}] {}", &rest[..end], &rest[end + 2..]));
+                expected.status = 65;
+            }
+        }
+    }
+    expected
+}
+
+fn check(path: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let expected = parse_expectations(&source);
+
+    let output = Capture::default();
+    let errors = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    interpreter.set_time_source(Box::new(MockTime::new(1.0)));
+    interpreter.set_rng(Rng::seeded(0));
+    interpreter.set_script(Some(path));
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    let status = lox::exit_code(&lox::run(&mut interpreter, &source).0);
+    loxerr::set_error_output(previous);
+
+    let mut problems = Vec::new();
+    let output = output.lines();
+    if output != expected.output {
+        problems.push(format!("expected output {:?}\n  got {:?}", expected.output, output));
+    }
+
+    let errors = errors.lines();
+    match &expected.runtime_error {
+        Some(error) => {
+            // Any warnings come first.
+            let warnings = &errors[..expected.errors.len().min(errors.len())];
+            if warnings != expected.errors.as_slice() {
+                problems.push(format!("expected warnings {:?}\n  got {:?}", expected.errors, warnings));
+            }
+            let got = errors.iter().skip(expected.errors.len()).take(2).cloned().collect::<Vec<_>>().join("\n");
+            if got != *error {
+                problems.push(format!("expected runtime error {:?}\n  got {:?}", error, got));
+            }
+        }
+        None => {
+            if errors != expected.errors {
+                problems.push(format!("expected errors {:?}\n  got {:?}", expected.errors, errors));
+            }
+        }
+    }
+
+    if status != expected.status {
+        problems.push(format!("expected exit status {}, got {}", expected.status, status));
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("\n  "))
+    }
+}
+
+#[test]
+fn golden_files() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("test");
+    let mut scripts = Vec::new();
+    collect_scripts(&root, &mut scripts);
+    scripts.sort();
+
+    // Some natives can be compiled out.
+    if cfg!(not(feature = "json")) {
+        scripts.retain(|path| !path.starts_with(root.join("json")));
+    }
+    if cfg!(not(feature = "regex")) {
+        scripts.retain(|path| !path.starts_with(root.join("regex")));
+    }
+    if let Ok(filter) = std::env::var("LOX_TEST") {
+        scripts.retain(|path| path.to_string_lossy().contains(&filter));
+    }
+    assert!(!scripts.is_empty(), "no test scripts found");
+
+    let total = scripts.len();
+    let failures = scripts
+        .iter()
+        .filter_map(|path| {
+            let name = path.strip_prefix(&root).unwrap().display().to_string();
+            check(path).err().map(|problem| format!("{}:\n  {}", name, problem))
+        })
+        .collect::<Vec<_>>();
+
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} scripts failed:\n\n{}",
+            failures.len(),
+            total,
+            failures.join("\n\n")
+        );
+    }
+}
@@ -0,0 +1,63 @@
+
+use rlox::highlight::{paint, spans, Style};
+use rlox::lox::Capture;
+use rlox::loxerr;
+
+// Each styled part of `line` as its text, with the cursor at the end.
+fn styled(line: &str) -> Vec<(&str, Style)> {
+    styled_at(line, line.len())
+}
+
+fn styled_at(line: &str, cursor: usize) -> Vec<(&str, Style)> {
+    spans(line, cursor).into_iter().map(|(range, style)| (&line[range], style)).collect()
+}
+
+#[test]
+fn keywords_literals_and_comments() {
+    assert_eq!(
+        styled("var a = \"hi\" + 1.5; // note"),
+        vec![("var", Style::Keyword), ("\"hi\"", Style::String), ("1.5", Style::Number), ("// note", Style::Comment)]
+    );
+    assert_eq!(styled("if (x) print nil;"), vec![("if", Style::Keyword), ("print", Style::Keyword), ("nil", Style::Keyword)]);
+}
+
+#[test]
+fn identifiers_and_operators_are_plain() {
+    assert_eq!(styled("a * b_1 >= c"), vec![]);
+    assert_eq!(styled(""), vec![]);
+}
+
+#[test]
+fn scan_errors_are_red_and_the_rest_still_highlights() {
+    assert_eq!(styled("print \"open"), vec![("print", Style::Keyword), ("\"open", Style::Error)]);
+    assert_eq!(styled("1 @ true"), vec![("1", Style::Number), ("@", Style::Error), ("true", Style::Keyword)]);
+    assert_eq!(styled("\"a\\q\" + 2"), vec![("\"a\\q", Style::Error), ("\"", Style::String), ("2", Style::Number)]);
+}
+
+#[test]
+fn scan_errors_are_not_reported() {
+    let errors = Capture::default();
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    spans("print \"open @", 0);
+    loxerr::set_error_output(previous);
+    assert!(errors.text().is_empty());
+}
+
+#[test]
+fn bracket_matching_the_cursor_is_dimmed() {
+    let line = "f((1), [2])";
+    // Just after the last `)`.
+    assert_eq!(styled_at(line, 11), vec![("(", Style::MatchingBracket), ("1", Style::Number), ("2", Style::Number)]);
+    // On the `[`.
+    assert_eq!(styled_at(line, 7), vec![("1", Style::Number), ("2", Style::Number), ("]", Style::MatchingBracket)]);
+    // Unbalanced brackets have no match.
+    assert_eq!(styled_at("(()", 0), vec![]);
+    assert_eq!(styled_at("{(}", 3), vec![("{", Style::MatchingBracket)]);
+}
+
+#[test]
+fn painting_wraps_spans_in_escapes() {
+    let line = "var a = 1;";
+    assert_eq!(paint(line, &spans(line, 0)), "\x1b[35mvar\x1b[0m a = \x1b[33m1\x1b[0m;");
+    assert_eq!(paint("a", &[]), "a");
+}
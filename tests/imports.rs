@@ -0,0 +1,161 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+// Runs a script under tests/fixtures/import from the crate's root.
+fn rlox(script: &str, args: &[&str]) -> Output {
+    let path = Path::new("tests/fixtures/import").join(script);
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+// Runs `script` from its directory under tests/fixtures.
+fn rlox_in(dir: &str, script: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(dir))
+        .arg(script)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn imported_definitions_are_global_and_run_once() {
+    let output = rlox("main.lox", &[]);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "loading helpers\n42\nonce\n");
+}
+
+#[test]
+fn static_errors_name_the_imported_file() {
+    let output = rlox("imports_broken.lox", &[]);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "main\n");
+    assert_eq!(
+        stderr(&output),
+        "[line 2 in broken.lox] Error at '=': Expect variable name.\nCould not import 'broken.lox'.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn runtime_errors_name_the_imported_file() {
+    let output = rlox("imports_failing.lox", &[]);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "before\n");
+    assert_eq!(
+        stderr(&output),
+        "Operand must be a number, but got string (\"x\").\n[line 2 in failing.lox]\nCould not import 'failing.lox'.\n[line 1]\n"
+    );
+}
+
+// Its status only matters for the script being run.
+#[test]
+fn returning_from_an_imported_script_ends_only_it() {
+    let output = rlox("imports_returning.lox", &[]);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "imported\nmain\n");
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn returning_from_an_imported_script_leaves_the_status_alone() {
+    let output = rlox("imports_returning_three.lox", &[]);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "imported\nmain\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn json_diagnostics_name_the_imported_file() {
+    let output = rlox("imports_broken.lox", &["--diagnostics=json"]);
+    let lines: Vec<serde_json::Value> = stderr(&output).lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["file"], "broken.lox");
+    assert_eq!(lines[0]["phase"], "parse");
+    assert!(lines[1].get("file").is_none());
+    assert_eq!(lines[1]["message"], "Could not import 'broken.lox'.");
+}
+
+#[test]
+fn two_file_cycle_reports_the_chain() {
+    let output = rlox_in("cycle2", "a.lox");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "a\nb\n");
+    assert_eq!(
+        stderr(&output),
+        "Circular import detected: a.lox -> b.lox -> a.lox.\n[line 2 in b.lox]\n\
+         Could not import 'b.lox'.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn three_file_cycle_reports_the_chain() {
+    let output = rlox_in("cycle3", "a.lox");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "a\nb\nc\n");
+    assert_eq!(
+        stderr(&output),
+        "Circular import detected: a.lox -> b.lox -> c.lox -> a.lox.\n[line 2 in c.lox]\n\
+         Could not import 'c.lox'.\n[line 2 in b.lox]\n\
+         Could not import 'b.lox'.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn main_script_is_named_as_given() {
+    let output = rlox_in("cycle3", "../cycle3/b.lox");
+    assert_eq!(stdout(&output), "b\nc\na\n");
+    assert!(stderr(&output).starts_with("Circular import detected: ../cycle3/b.lox -> c.lox -> a.lox -> b.lox.\n"));
+}
+
+// Runs `script` under tests/fixtures from the crate's root.
+fn rlox_from_root(script: &str, args: &[&str], lox_path: Option<&str>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rlox"));
+    command.current_dir(env!("CARGO_MANIFEST_DIR")).args(args).arg(Path::new("tests/fixtures").join(script));
+    match lox_path {
+        Some(lox_path) => command.env("LOX_PATH", lox_path),
+        None => command.env_remove("LOX_PATH"),
+    };
+    command.output().unwrap()
+}
+
+#[test]
+fn imports_are_found_next_to_the_importing_script() {
+    let output = rlox_from_root("nested/app/main.lox", &[], None);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "loading util\nutil and helper\ncommon\n");
+}
+
+#[test]
+fn import_paths_are_searched_after_the_script_directory() {
+    let output = rlox_from_root("nested/app/uses_extra.lox", &[], None);
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr(&output).starts_with("Could not read 'extra.lox': no such file.\n"));
+
+    let output = rlox_from_root("nested/app/uses_extra.lox", &["--import-path", "tests/fixtures/nested/libs"], None);
+    assert_eq!(stdout(&output), "extra\n");
+
+    let output = rlox_from_root("nested/app/uses_extra.lox", &[], Some("no/such/dir:tests/fixtures/nested/libs"));
+    assert_eq!(stdout(&output), "extra\n");
+}
+
+#[test]
+fn absolute_imports_are_used_as_they_are() {
+    let common = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/nested/shared/common.lox");
+    let script = std::env::temp_dir().join("rlox_absolute_import.lox");
+    std::fs::write(&script, format!("import \"{}\";\nprint common();\n", common.display())).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox")).arg(&script).output().unwrap();
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "common\n");
+}
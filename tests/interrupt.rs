@@ -0,0 +1,79 @@
+use std::io::{self, BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Backend, Lox, RunStatus};
+use rlox::loxerr::{self, LoxError};
+
+const FOREVER: &str = "var i = 0; while (true) i = i + 1;";
+
+#[test]
+fn a_raised_flag_stops_the_next_loop_iteration() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    interpreter.interrupt_flag().store(true, Ordering::SeqCst);
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let result = lox::run(&mut interpreter, "print 1; while (true) {}").0;
+    loxerr::set_error_output(previous);
+    assert_eq!(result, Err(vec![LoxError::Interrupted]));
+    assert_eq!(lox::exit_code(&result), 130);
+
+    // Stopping lowered it again.
+    assert!(!interpreter.interrupt_flag().load(Ordering::SeqCst));
+    assert!(lox::run(&mut interpreter, "for (var i = 0; i < 10; i = i + 1) {}").0.is_ok());
+}
+
+#[test]
+fn another_thread_can_interrupt_a_run() {
+    for backend in [Backend::Tree, Backend::Vm].iter().copied() {
+        let interpreter = Interpreter::new();
+        let flag = interpreter.interrupt_flag();
+        let interrupter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            flag.store(true, Ordering::SeqCst);
+        });
+        let mut lox = Lox::with_interpreter(interpreter);
+        lox.set_backend(backend);
+        let outcome = lox.run_source(FOREVER);
+        interrupter.join().unwrap();
+        assert_eq!(outcome.status, RunStatus::Interrupted);
+        assert_eq!(outcome.status.exit_code(), 130);
+        assert_eq!(outcome.diagnostics[0].message, "Interrupted.");
+    }
+}
+
+#[test]
+fn calls_check_the_flag_too() {
+    let interpreter = Interpreter::new();
+    interpreter.interrupt_flag().store(true, Ordering::SeqCst);
+    let outcome = Lox::with_interpreter(interpreter).run_source("fun f() { print \"ran\"; } f();");
+    assert_eq!(outcome.status, RunStatus::Interrupted);
+    assert_eq!(outcome.stdout, "");
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_stops_a_script_with_130() {
+    let script = std::env::temp_dir().join("rlox_interrupt.lox");
+    std::fs::write(&script, format!("print \"started\";\nflush();\n{}\n", FOREVER)).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg(&script)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut first = String::new();
+    stdout.read_line(&mut first).unwrap();
+    assert_eq!(first, "started\n");
+
+    let status = Command::new("kill").arg("-INT").arg(child.id().to_string()).status().unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Interrupted.\n");
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "json")]
+
+use std::io;
+
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::json;
+
+// Arrays and maps compare by identity with `==`, so this compares what they
+// hold instead.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Array(a), Value::Array(b)) => {
+            let (a, b) = (a.borrow(), b.borrow());
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a, b))
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            let (a, b) = (a.borrow().entries(), b.borrow().entries());
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|((ka, va), (kb, vb))| ka == kb && same(va, vb))
+        }
+        _ => a == b,
+    }
+}
+
+fn round_trip(source: &str) {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let value = json::parse(source).unwrap();
+    let text = json::stringify(&mut interpreter, &value).ok().unwrap();
+    let again = json::parse(&text).unwrap();
+    assert!(same(&value, &again), "{} became {}", source, text);
+    assert_eq!(json::stringify(&mut interpreter, &again).ok().unwrap(), text);
+}
+
+#[test]
+fn values_survive_a_round_trip() {
+    round_trip("null");
+    round_trip("[true, false, null]");
+    round_trip("\"quotes \\\" and \\\\ and \\n and \\u00e9\"");
+    round_trip("{\"z\": 1, \"a\": {\"nested\": [1, 2, {\"deep\": []}]}, \"m\": {}}");
+}
+
+#[test]
+fn numbers_keep_their_text() {
+    for number in ["0", "-1", "42", "0.5", "3.14159", "1e-7", "123456789012345", "9007199254740993"] {
+        round_trip(number);
+    }
+    let mut interpreter = Interpreter::new();
+    let value = json::parse("[1.0, 2.50, 100]").unwrap();
+    assert_eq!(json::stringify(&mut interpreter, &value).ok().unwrap(), "[1,2.5,100]");
+}
+
+#[test]
+fn parse_errors_give_the_position() {
+    match json::parse("{\"a\": tru}") {
+        Err(msg) => assert_eq!(msg, "Invalid JSON: expected ident at line 1 column 10."),
+        Ok(value) => panic!("parsed {}", value),
+    }
+}
@@ -0,0 +1,179 @@
+#![cfg(feature = "lsp")]
+
+use std::io::{Cursor, Write};
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+
+use rlox::lsp;
+
+fn frame(message: &Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+// The messages the server sends back.
+fn unframe(mut output: &str) -> Vec<Value> {
+    let mut messages = Vec::new();
+    while let Some(idx) = output.find("\r\n\r\n") {
+        let length: usize = output[..idx].trim_start_matches("Content-Length: ").parse().unwrap();
+        let body = &output[idx + 4..idx + 4 + length];
+        messages.push(serde_json::from_str(body).unwrap());
+        output = &output[idx + 4 + length..];
+    }
+    messages
+}
+
+// Sends `messages` and the ones that end a session, and gives back what the
+// server sent in reply.
+fn session(messages: &[Value]) -> Vec<Value> {
+    let mut input = frame(&json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }));
+    for message in messages {
+        input.push_str(&frame(message));
+    }
+    input.push_str(&frame(&json!({ "jsonrpc": "2.0", "id": 99, "method": "shutdown" })));
+    input.push_str(&frame(&json!({ "jsonrpc": "2.0", "method": "exit" })));
+    let mut output = Vec::new();
+    lsp::serve(Cursor::new(input), &mut output).unwrap();
+    let replies = unframe(&String::from_utf8(output).unwrap());
+    assert_eq!(replies[0]["id"], 0);
+    assert_eq!(replies.last().unwrap()["id"], 99);
+    replies[1..replies.len() - 1].to_vec()
+}
+
+const URI: &str = "file:///test.lox";
+
+fn open(text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": { "uri": URI, "languageId": "lox", "version": 1, "text": text } },
+    })
+}
+
+fn change(text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": { "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": text }] },
+    })
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn range(start: (u64, u64), end: (u64, u64)) -> Value {
+    json!({ "start": { "line": start.0, "character": start.1 }, "end": { "line": end.0, "character": end.1 } })
+}
+
+#[test]
+fn initialize_lists_capabilities() {
+    let mut output = Vec::new();
+    let input = frame(&request(1, "initialize", json!({})));
+    lsp::serve(Cursor::new(input), &mut output).unwrap();
+    let replies = unframe(&String::from_utf8(output).unwrap());
+    let capabilities = &replies[0]["result"]["capabilities"];
+    assert_eq!(capabilities["textDocumentSync"], 1);
+    assert_eq!(capabilities["documentSymbolProvider"], true);
+    assert_eq!(capabilities["definitionProvider"], true);
+}
+
+#[test]
+fn diagnostics_are_published_on_open_and_change() {
+    let replies = session(&[open("var s = \"é\";\nprint s +;\n"), change("print 1;\n")]);
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+    assert_eq!(replies[0]["params"]["uri"], URI);
+    let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["message"], "Expect expression.");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"], range((1, 9), (1, 10)));
+    assert_eq!(replies[1]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn ranges_count_utf16_units() {
+    let replies = session(&[open("var s = \"😀\"; @")]);
+    let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics[0]["message"], "Unexpected character.");
+    assert_eq!(diagnostics[0]["range"], range((0, 14), (0, 15)));
+}
+
+#[test]
+fn warnings_are_published_too() {
+    let replies = session(&[open("{\n  var a = 1;\n  {\n    var a = 2;\n    print a;\n  }\n  print a;\n}\n")]);
+    let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics[0]["severity"], 2);
+    assert_eq!(diagnostics[0]["range"], range((3, 8), (3, 9)));
+}
+
+#[test]
+fn document_symbols_are_functions_classes_and_methods() {
+    let source = "fun outer() {\n  fun inner() {}\n}\nclass Point {\n  init(x) {}\n  norm() {}\n}\nvar x = 1;\n";
+    let replies = session(&[open(source), request(1, "textDocument/documentSymbol", json!({ "textDocument": { "uri": URI } }))]);
+    let symbols = &replies[1]["result"];
+    assert_eq!(symbols[0]["name"], "outer");
+    assert_eq!(symbols[0]["kind"], 12);
+    assert_eq!(symbols[0]["selectionRange"], range((0, 4), (0, 9)));
+    assert_eq!(symbols[0]["children"][0]["name"], "inner");
+    assert_eq!(symbols[1]["name"], "Point");
+    assert_eq!(symbols[1]["kind"], 5);
+    let methods: Vec<&Value> = symbols[1]["children"].as_array().unwrap().iter().map(|method| &method["name"]).collect();
+    assert_eq!(methods, vec!["init", "norm"]);
+    assert_eq!(symbols[1]["children"][1]["kind"], 6);
+    assert_eq!(symbols.as_array().unwrap().len(), 2);
+}
+
+fn definition(source: &str, line: u64, character: u64) -> Value {
+    let params = json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } });
+    let replies = session(&[open(source), request(1, "textDocument/definition", params)]);
+    replies[1]["result"].clone()
+}
+
+#[test]
+fn definitions_of_locals_follow_scopes() {
+    let source = "fun f(a) {\n  var b = a;\n  {\n    var a = b;\n    print a;\n  }\n  return a;\n}\n";
+    assert_eq!(definition(source, 1, 10)["range"], range((0, 6), (0, 7)));
+    assert_eq!(definition(source, 4, 10)["range"], range((3, 8), (3, 9)));
+    assert_eq!(definition(source, 6, 9)["range"], range((0, 6), (0, 7)));
+    assert_eq!(definition(source, 3, 12)["range"], range((1, 6), (1, 7)));
+    assert_eq!(definition(source, 4, 10)["uri"], URI);
+}
+
+#[test]
+fn definitions_of_globals_can_come_later() {
+    let source = "fun f() { return g(); }\nfun g() { return 1; }\nprint f();\n";
+    assert_eq!(definition(source, 0, 17)["range"], range((1, 4), (1, 5)));
+    assert_eq!(definition(source, 2, 6)["range"], range((0, 4), (0, 5)));
+    // Builtins aren't declared anywhere in the document.
+    assert_eq!(definition("print clock();", 0, 7), Value::Null);
+    // Nor is anything that isn't a variable.
+    assert_eq!(definition(source, 0, 1), Value::Null);
+}
+
+#[test]
+fn unknown_requests_are_errors() {
+    let replies = session(&[request(1, "textDocument/hover", json!({}))]);
+    assert_eq!(replies[0]["id"], 1);
+    assert_eq!(replies[0]["error"]["code"], -32601);
+}
+
+#[test]
+fn command_line_serves_over_stdio() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("--lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(frame(&request(1, "initialize", json!({}))).as_bytes()).unwrap();
+    stdin.write_all(frame(&json!({ "jsonrpc": "2.0", "method": "exit" })).as_bytes()).unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let replies = unframe(&String::from_utf8(output.stdout).unwrap());
+    assert_eq!(replies[0]["result"]["serverInfo"]["name"], "rlox");
+}
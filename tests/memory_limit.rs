@@ -0,0 +1,105 @@
+use std::io;
+
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Backend, Lox, RunStatus};
+use rlox::loxerr::{self, RuntimeException};
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+use rlox::token::{Token, TokenType};
+
+fn run(interpreter: &mut Interpreter, source: &str) -> Result<i32, RuntimeException> {
+    let tokens = Scanner::new(source).scan_tokens().clone();
+    let stmts = Parser::new(&tokens).parse();
+    let mut resolver = Resolver::new(interpreter);
+    resolver.resolve(&stmts);
+    assert!(!resolver.has_error);
+    interpreter.interpret(&stmts)
+}
+
+fn global(interpreter: &Interpreter, name: &str) -> Value {
+    let token = Token::new(TokenType::Identifier, name, 1);
+    interpreter.globals.borrow().get(&token).ok().unwrap()
+}
+
+const DOUBLING: &str = "var s = \"x\"; while (true) s = s + s;";
+
+#[test]
+fn doubling_a_string_stops_at_the_limit() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_memory_limit(Some(1 << 20));
+    assert!(matches!(run(&mut interpreter, DOUBLING), Err(RuntimeException::LimitExceeded)));
+    // It stopped before making the string that would have gone over.
+    let s = global(&interpreter, "s").to_string();
+    assert_eq!(s.len(), 1 << 19);
+    assert!(interpreter.allocated() > 1 << 20);
+}
+
+#[test]
+fn the_limit_is_per_run() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_memory_limit(Some(1 << 16));
+    let source = "var xs = []; for (var i = 0; i < 100; i = i + 1) push(xs, \"item\" + \"s\");";
+    for _ in 0..100 {
+        assert!(run(&mut interpreter, source).is_ok());
+    }
+}
+
+#[test]
+fn collections_count_too() {
+    let cases = [
+        "var xs = []; while (true) push(xs, nil);",
+        "var xs = []; while (true) xs.push(nil);",
+        "var xs = nil; while (true) xs = [xs, xs];",
+        "var m = Map(); var i = 0; while (true) { m[i] = i; i = i + 1; }",
+        "var b = StringBuilder(); while (true) b.append(\"abcdefgh\");",
+        "var xs = \"a\"; while (true) xs = split(join([xs, xs], \",\"), \"\").join(\"\");",
+        "class A {} var xs = []; while (true) xs.push(A());",
+    ];
+    for source in cases.iter() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_memory_limit(Some(1 << 20));
+        assert!(matches!(run(&mut interpreter, source), Err(RuntimeException::LimitExceeded)), "{}", source);
+    }
+}
+
+#[test]
+fn environments_count_too() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_memory_limit(Some(1 << 16));
+    let source = "var fs = []; for (var i = 0; ; i = i + 1) { fun f() { return i; } fs.push(f); }";
+    assert!(matches!(run(&mut interpreter, source), Err(RuntimeException::LimitExceeded)));
+}
+
+#[test]
+fn unlimited_by_default() {
+    let mut interpreter = Interpreter::new();
+    let source = "var s = \"x\"; for (var i = 0; i < 20; i = i + 1) s = s + s;";
+    assert!(run(&mut interpreter, source).is_ok());
+    assert_eq!(global(&interpreter, "s").to_string().len(), 1 << 20);
+    assert!(interpreter.allocated() >= 1 << 20);
+}
+
+#[test]
+fn reported_like_fuel() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_memory_limit(Some(1 << 20));
+    let mut lox = Lox::with_interpreter(interpreter);
+    let outcome = lox.run_source(DOUBLING);
+    assert_eq!(outcome.status, RunStatus::RuntimeError);
+    assert_eq!(outcome.diagnostics[0].message, "Memory limit exceeded.");
+    assert_eq!(outcome.diagnostics[0].line, None);
+}
+
+#[test]
+fn the_vm_counts_concatenation() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    interpreter.set_memory_limit(Some(1 << 20));
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let status = lox::exit_code(&lox::run_with(&mut interpreter, DOUBLING, Backend::Vm).0);
+    loxerr::set_error_output(previous);
+    assert_eq!(status, 70);
+    assert!(interpreter.allocated() > 1 << 20);
+}
@@ -0,0 +1,36 @@
+use std::time::Instant;
+
+use rlox::interpreter::Interpreter;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+
+// Calls an inherited method, three classes up, in a loop.
+const SOURCE: &str = "
+class Base {
+  value() { return this.n; }
+}
+class Middle < Base {}
+class Leaf < Middle {
+  init() { this.n = 1; }
+}
+var leaf = Leaf();
+var total = 0;
+for (var i = 0; i < 1000000; i = i + 1) {
+  total = total + leaf.value();
+}
+";
+
+// Run with `cargo test --release --test method_bench -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn inherited_method_in_loop() {
+    let tokens = Scanner::new(SOURCE).scan_tokens().clone();
+    let stmts = Parser::new(&tokens).parse();
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter).resolve(&stmts);
+
+    let start = Instant::now();
+    assert!(interpreter.interpret(&stmts).is_ok());
+    println!("1000000 calls to an inherited method: {:?}", start.elapsed());
+}
@@ -0,0 +1,61 @@
+
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::loxerr;
+use rlox::module::NativeModule;
+
+// The exit status and what was printed and reported.
+fn run(interpreter: &mut Interpreter, source: &str) -> (i32, String, String) {
+    let output = Capture::default();
+    let errors = Capture::default();
+    interpreter.set_output(Box::new(output.clone()));
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    let status = lox::exit_code(&lox::run(interpreter, source).0);
+    loxerr::set_error_output(previous);
+    (status, output.text(), errors.text())
+}
+
+fn greetings() -> NativeModule {
+    NativeModule::new("greetings")
+        .value("greeting", Value::String("hello".into()))
+        .function("greet", 1, |_, args| Ok(Value::String(format!("hello, {}", args[0]).into())))
+}
+
+#[test]
+fn hosts_can_add_modules() {
+    let mut interpreter = Interpreter::new();
+    interpreter.register_module(greetings());
+    let (status, output, _) = run(&mut interpreter, "import \"greetings\";\nprint greeting;\nprint greet(\"lox\");");
+    assert_eq!(status, 0);
+    assert_eq!(output, "hello\nhello, lox\n");
+}
+
+#[test]
+fn unknown_modules_list_the_available_ones() {
+    let mut interpreter = Interpreter::new();
+    interpreter.register_module(greetings());
+    let (status, _, errors) = run(&mut interpreter, "import \"strings\";");
+    assert_eq!(status, 70);
+    assert_eq!(errors, "Unknown module 'strings'. Available modules: 'greetings', 'math'.\n[line 1]\n");
+}
+
+#[test]
+fn modules_stay_out_of_the_globals_until_imported() {
+    let mut interpreter = Interpreter::new();
+    interpreter.register_module(greetings());
+    let (status, _, _) = run(&mut interpreter, "print greet;");
+    assert_eq!(status, 70);
+}
+
+#[test]
+fn reset_forgets_imports() {
+    let mut interpreter = Interpreter::new();
+    run(&mut interpreter, "import \"math\";");
+    interpreter.reset();
+    let (status, _, _) = run(&mut interpreter, "print sqrt;");
+    assert_eq!(status, 70);
+    let (status, output, _) = run(&mut interpreter, "import \"math\";\nprint sqrt(4);");
+    assert_eq!(status, 0);
+    assert_eq!(output, "2\n");
+}
@@ -0,0 +1,112 @@
+use std::any::Any;
+
+use rlox::class::NativeClass;
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::loxerr::RuntimeException;
+
+fn count(state: &mut dyn Any) -> &mut f64 {
+    state.downcast_mut::<f64>().unwrap()
+}
+
+fn counter() -> NativeClass {
+    NativeClass::new("Counter", 1, |_, args| match args[0] {
+        Value::Number(start) => Ok(Box::new(start)),
+        _ => Err(RuntimeException::NativeError("Start must be a number.".to_owned())),
+    })
+    .method("increment", 0, |_, state, _| {
+        *count(state) += 1.0;
+        Ok(Value::Nil)
+    })
+    .method("add", 1, |_, state, args| match args[0] {
+        Value::Number(n) => {
+            *count(state) += n;
+            Ok(Value::Nil)
+        }
+        _ => Err(RuntimeException::NativeError("Can only add numbers.".to_owned())),
+    })
+    .method("value", 0, |_, state, _| Ok(Value::Number(*count(state))))
+}
+
+fn run(source: &str) -> (i32, String) {
+    let output = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    interpreter.define_native_class(counter());
+    let status = lox::exit_code(&lox::run(&mut interpreter, source).0);
+    (status, output.text())
+}
+
+#[test]
+fn methods_mutate_the_instance_state() {
+    let (status, output) = run("\
+var c = Counter(10);
+c.increment();
+c.increment();
+c.add(5);
+print c.value();
+print c;
+");
+    assert_eq!(status, 0);
+    assert_eq!(output, "17\nCounter instance\n");
+}
+
+#[test]
+fn instances_have_their_own_state() {
+    let (status, output) = run("\
+var a = Counter(0);
+var b = Counter(100);
+var alias = a;
+alias.increment();
+print a.value();
+print b.value();
+");
+    assert_eq!(status, 0);
+    assert_eq!(output, "1\n100\n");
+}
+
+#[test]
+fn bound_methods_keep_their_instance() {
+    let (status, output) = run("\
+var c = Counter(0);
+var increment = c.increment;
+increment();
+increment();
+print c.value();
+print increment;
+");
+    assert_eq!(status, 0);
+    assert_eq!(output, "2\n<native fn increment>\n");
+}
+
+#[test]
+fn instances_also_hold_lox_fields() {
+    let (status, output) = run("\
+var c = Counter(0);
+c.label = \"clicks\";
+print c.label;
+print methods(c);
+print hasMethod(c, \"add\");
+");
+    assert_eq!(status, 0);
+    assert_eq!(output, "clicks\n[add, increment, value]\ntrue\n");
+}
+
+#[test]
+fn errors() {
+    assert_eq!(run("Counter();").0, 70);
+    assert_eq!(run("Counter(\"one\");").0, 70);
+    assert_eq!(run("Counter(0).add(nil);").0, 70);
+    assert_eq!(run("Counter(0).reset();").0, 70);
+    assert_eq!(run("class Sub < Counter {}").0, 70);
+}
+
+#[test]
+fn instances_cannot_be_copied() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(Capture::default()));
+    interpreter.define_native_class(counter());
+    let errors = lox::run(&mut interpreter, "copy([Counter(0)]);").0.unwrap_err();
+    assert_eq!(errors[0].to_string(), "Can't copy an instance of the native class Counter.\n[line 1]");
+}
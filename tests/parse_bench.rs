@@ -0,0 +1,76 @@
+use std::time::Instant;
+
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+
+fn generate(functions: usize) -> String {
+    let mut source = String::new();
+    for i in 0..functions {
+        source.push_str(&format!(
+            "fun function{i}(alpha, beta, gamma) {{
+               var message = \"a moderately long string literal number {i}\";
+               if (alpha < beta and beta < gamma) {{
+                 return alpha + beta * gamma - {i};
+               }}
+               for (var index = 0; index < {i}; index = index + 1) {{
+                 message = message + \"!\";
+               }}
+               return message;
+             }}\n",
+            i = i
+        ));
+    }
+    source
+}
+
+// Run with `cargo test --release --test parse_bench -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn scan_and_parse_large_file() {
+    let source = generate(20_000);
+
+    let start = Instant::now();
+    let mut scanner = Scanner::new(&source);
+    let tokens = scanner.scan_tokens().clone();
+    let scanned = start.elapsed();
+    let stmts = Parser::new(&tokens).parse();
+    let parsed = start.elapsed() - scanned;
+
+    assert_eq!(stmts.len(), 20_000);
+    println!(
+        "{} bytes, {} tokens: scan {:?}, parse {:?}",
+        source.len(),
+        tokens.len(),
+        scanned,
+        parsed
+    );
+}
+
+// Mostly multi-byte text, which is where the scanner has to be careful about
+// char boundaries.
+#[test]
+#[ignore]
+fn scan_unicode_heavy_file() {
+    let mut source = String::new();
+    for i in 0..200_000 {
+        source.push_str(&format!(
+            "var v{i} = \"строка {i} 🦀🦀 日本語のテキスト\"; // コメント {i} ✓\n",
+            i = i
+        ));
+    }
+
+    let start = Instant::now();
+    let mut scanner = Scanner::new(&source);
+    let tokens = scanner.scan_tokens().len();
+    let scanned = start.elapsed();
+
+    assert!(!scanner.has_error);
+    assert_eq!(tokens, 200_000 * 5 + 1);
+    println!(
+        "{} bytes, {} chars, {} tokens: scan {:?}",
+        source.len(),
+        source.chars().count(),
+        tokens,
+        scanned
+    );
+}
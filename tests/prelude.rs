@@ -0,0 +1,65 @@
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::loxerr;
+
+// The exit status and what was printed and reported.
+fn run(interpreter: &mut Interpreter, source: &str) -> (i32, String, String) {
+    let output = Capture::default();
+    let errors = Capture::default();
+    interpreter.set_output(Box::new(output.clone()));
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    let status = lox::exit_code(&lox::run(interpreter, source).0);
+    loxerr::set_error_output(previous);
+    (status, output.text(), errors.text())
+}
+
+const CALLS: &str = "print abs(-1);\nprint min(1, 2);\nprint max(1, 2);\nprint toArray(0..2);\nassert(true, \"no\");\n";
+
+#[test]
+fn every_prelude_function_runs() {
+    let mut interpreter = Interpreter::new();
+    let (status, output, errors) = run(&mut interpreter, CALLS);
+    assert_eq!(errors, "");
+    assert_eq!(status, 0);
+    assert_eq!(output, "1\n1\n2\n[0, 1]\n");
+}
+
+#[test]
+fn without_prelude_has_only_the_natives() {
+    let mut interpreter = Interpreter::without_prelude();
+    for name in &["abs", "min", "max", "toArray", "assert"] {
+        assert!(interpreter.variable(name).is_none(), "{} is defined", name);
+    }
+    let (status, output, _) = run(&mut interpreter, "print len([1]);");
+    assert_eq!(status, 0);
+    assert_eq!(output, "1\n");
+}
+
+#[test]
+fn reset_keeps_the_prelude() {
+    let mut interpreter = Interpreter::new();
+    run(&mut interpreter, "fun abs(x) { return x; }");
+    interpreter.reset();
+    let (status, output, errors) = run(&mut interpreter, CALLS);
+    assert_eq!(errors, "");
+    assert_eq!(status, 0);
+    assert_eq!(output, "1\n1\n2\n[0, 1]\n");
+}
+
+#[test]
+fn failed_asserts_are_reported_at_the_call() {
+    let mut interpreter = Interpreter::new();
+    let (status, _, errors) = run(&mut interpreter, "var x = 1;\nassert(x == 2, \"x should be 2.\");");
+    assert_eq!(status, 70);
+    assert_eq!(errors, "x should be 2.\n[line 2]\n");
+}
+
+#[test]
+fn prelude_lines_are_not_covered() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_coverage(true);
+    run(&mut interpreter, "print max(1, 2);");
+    let lines: Vec<usize> = interpreter.coverage().unwrap().keys().copied().collect();
+    assert_eq!(lines, vec![1]);
+}
@@ -0,0 +1,16 @@
+use std::fs;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+// Run with `cargo test --release --test print_bench -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn print_a_million_lines() {
+    let script = std::env::temp_dir().join("rlox_print_bench.lox");
+    fs::write(&script, "for (var i = 0; i < 1000000; i = i + 1) print i;\n").unwrap();
+
+    let start = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_rlox")).arg(&script).stdout(Stdio::null()).status().unwrap();
+    assert!(status.success());
+    println!("1000000 prints: {:?}", start.elapsed());
+}
@@ -0,0 +1,119 @@
+use std::io;
+use std::time::Instant;
+
+use rlox::interpreter::Interpreter;
+use rlox::lox;
+use rlox::profile::{self, FunctionProfile};
+
+fn profile(source: &str) -> Vec<FunctionProfile> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    interpreter.set_profiling(true);
+    let status = lox::exit_code(&lox::run(&mut interpreter, source).0);
+    assert_eq!(status, 0);
+    interpreter.profile()
+}
+
+fn find<'a>(functions: &'a [FunctionProfile], name: &str) -> &'a FunctionProfile {
+    functions.iter().find(|function| function.name == name).unwrap()
+}
+
+#[test]
+fn counts_calls_of_functions_classes_and_natives() {
+    let functions = profile(
+        "\
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+class Point {
+  init(x) { this.x = x; }
+}
+fib(10);
+Point(1);
+Point(2);
+clock();
+",
+    );
+    assert_eq!(find(&functions, "fib").calls, 177);
+    assert_eq!(find(&functions, "fib").line, 1);
+    assert_eq!(find(&functions, "Point").calls, 2);
+    assert_eq!(find(&functions, "Point").line, 5);
+    assert_eq!(find(&functions, "init").calls, 2);
+    assert_eq!(find(&functions, "init").line, 6);
+    assert_eq!(find(&functions, "clock").calls, 1);
+    assert_eq!(find(&functions, "clock").line, 0);
+}
+
+#[test]
+fn functions_with_the_same_name_are_kept_apart() {
+    let functions = profile(
+        "\
+fun f() {}
+f();
+fun f() {}
+f();
+f();
+",
+    );
+    let mut calls: Vec<_> = functions.iter().map(|function| (function.line, function.calls)).collect();
+    calls.sort();
+    assert_eq!(calls, vec![(1, 1), (3, 2)]);
+}
+
+#[test]
+fn recursion_is_not_counted_twice() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    interpreter.set_profiling(true);
+    let start = Instant::now();
+    let (result, _) = lox::run(
+        &mut interpreter,
+        "\
+fun even(n) { if (n == 0) return true; return !odd(n - 1); }
+fun odd(n) { if (n == 0) return false; return !even(n - 1); }
+fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+fib(15);
+even(16);
+",
+    );
+    assert!(result.is_ok());
+    let elapsed = start.elapsed();
+    let functions = interpreter.profile();
+
+    let fib = find(&functions, "fib");
+    assert!(fib.total <= elapsed);
+    assert!(fib.self_time <= fib.total);
+    let even = find(&functions, "even");
+    let odd = find(&functions, "odd");
+    assert_eq!((even.calls, odd.calls), (9, 8));
+    assert!(even.total <= elapsed);
+    // Every call to either runs inside the outermost call to `even`.
+    assert!(even.self_time + odd.self_time <= even.total);
+}
+
+#[test]
+fn report_is_sorted_by_total_time() {
+    let functions = profile(
+        "\
+fun slow() { for (var i = 0; i < 10000; i = i + 1) {} }
+fun fast() {}
+fast();
+slow();
+",
+    );
+    assert_eq!(functions[0].name, "slow");
+    let report = profile::report(&functions);
+    let lines: Vec<_> = report.lines().collect();
+    assert!(lines[0].starts_with("function"));
+    assert!(lines[1].starts_with("slow"));
+    assert!(lines[2].starts_with("fast"));
+}
+
+#[test]
+fn nothing_is_counted_without_profiling() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    assert!(lox::run(&mut interpreter, "fun f() {} f();").0.is_ok());
+    assert!(interpreter.profile().is_empty());
+}
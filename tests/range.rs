@@ -0,0 +1,54 @@
+use rlox::expr::Value;
+use rlox::gc;
+use rlox::interpreter::Interpreter;
+use rlox::loxerr::RuntimeException;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+use rlox::token::{Token, TokenType};
+
+fn run(interpreter: &mut Interpreter, source: &str) -> Result<i32, RuntimeException> {
+    let tokens = Scanner::new(source).scan_tokens().clone();
+    let mut parser = Parser::new(&tokens);
+    let stmts = parser.parse();
+    assert!(!parser.has_error);
+    let mut resolver = Resolver::new(interpreter);
+    resolver.resolve(&stmts);
+    assert!(!resolver.has_error);
+    interpreter.interpret(&stmts)
+}
+
+fn global(interpreter: &Interpreter, name: &str) -> Value {
+    let token = Token::new(TokenType::Identifier, name, 1);
+    interpreter.globals.borrow().get(&token).ok().unwrap()
+}
+
+#[test]
+fn ranges_are_not_materialized() {
+    // Building a list of 10^18 numbers would never get to the first
+    // iteration.
+    let mut interpreter = Interpreter::new();
+    interpreter.set_fuel(1000);
+    let result = run(&mut interpreter, "var last; for (var i in 0..1000000000000000000) last = i;");
+
+    assert!(matches!(result, Err(RuntimeException::FuelExhausted)));
+    assert_eq!(global(&interpreter, "last"), Value::Number(999.0));
+}
+
+#[test]
+fn iteration_scopes_are_freed() {
+    let mut interpreter = Interpreter::new();
+    let result = run(
+        &mut interpreter,
+        "var total = 0;
+         for (var i in 0..100000) {
+           fun f() { return i; }
+           total = total + f();
+         }",
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(global(&interpreter, "total"), Value::Number(4_999_950_000.0));
+    gc::collect();
+    assert!(gc::tracked_environments() < 10);
+}
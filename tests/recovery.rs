@@ -0,0 +1,45 @@
+use std::io;
+
+use rlox::loxerr;
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+use rlox::stmt::Stmt;
+
+fn parse(source: &str) -> Vec<Stmt> {
+    let tokens = Scanner::new(source).scan_tokens().clone();
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let mut parser = Parser::new(&tokens);
+    let stmts = parser.parse();
+    loxerr::set_error_output(previous);
+    assert!(parser.has_error);
+    stmts
+}
+
+// How many statements the body of each top-level function kept.
+fn body_lengths(stmts: &[Stmt]) -> Vec<usize> {
+    stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Function(_, _, body) => Some(body.len()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn broken_parameter_list_keeps_the_body() {
+    let stmts = parse("fun f(a b) { print a; print b; } fun g() { print 1; }");
+    assert_eq!(body_lengths(&stmts), vec![2, 1]);
+}
+
+#[test]
+fn mistake_in_a_block_keeps_the_enclosing_function() {
+    let stmts = parse("fun f() { { print 1 } print 2; print 3; } fun g() {}");
+    assert_eq!(body_lengths(&stmts), vec![3, 0]);
+}
+
+#[test]
+fn broken_argument_list_keeps_the_statement() {
+    let stmts = parse("fun f() { print g(1 2) + 3; print 4; }");
+    assert_eq!(body_lengths(&stmts), vec![2]);
+}
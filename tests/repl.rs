@@ -0,0 +1,156 @@
+use std::io;
+
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::lox;
+use rlox::loxerr::LoxError;
+use rlox::repl;
+
+// What the prompt echoes for `expression`, evaluated after `setup`.
+fn show(setup: &str, expression: &str) -> String {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    assert!(lox::run(&mut interpreter, setup).0.is_ok());
+    let value = lox::run_echo(&mut interpreter, &format!("{};", expression)).0.unwrap();
+    repl::show(&value.unwrap())
+}
+
+#[test]
+fn scalars_show_as_print_does() {
+    assert_eq!(show("", "true"), "true");
+    assert_eq!(show("", "3"), "3");
+    assert_eq!(show("", "2.5"), "2.5");
+    assert_eq!(show("", "nil"), "nil");
+    assert_eq!(show("", "0..3"), "0..3");
+}
+
+#[test]
+fn strings_are_quoted_and_escaped() {
+    assert_eq!(show("", "\"3\""), "\"3\"");
+    assert_eq!(show("", "\"\""), "\"\"");
+    assert_eq!(repl::show(&Value::String("say \"hi\"\\\n\t\u{1}".into())), "\"say \\\"hi\\\"\\\\\\n\\t\\u{1}\"");
+}
+
+#[test]
+fn functions_and_classes_show_as_print_does() {
+    assert_eq!(show("fun f() {}", "f"), "<fn f>");
+    assert_eq!(show("", "clock"), "<native fn>");
+    assert_eq!(show("class Point {}", "Point"), "Point");
+    assert_eq!(show("interface Shape { area(); }", "Shape"), "Shape");
+}
+
+#[test]
+fn instances_show_their_fields() {
+    assert_eq!(show("class Point {}", "Point()"), "Point instance");
+    assert_eq!(
+        show("class Point {} var p = Point(); p.y = 2; p.x = \"1\";", "p"),
+        "Point instance {x: \"1\", y: 2}"
+    );
+    assert_eq!(show("class Node {} var n = Node(); n.next = n;", "n"), "Node instance {next: Node instance {...}}");
+}
+
+#[test]
+fn collections_show_their_elements() {
+    assert_eq!(show("", "[1, \"a\", nil]"), "[1, \"a\", nil]");
+    assert_eq!(show("", "(\"a\", 2)"), "(\"a\", 2)");
+    assert_eq!(show("var s = Set(); add(s, \"a\"); add(s, 1);", "s"), "{\"a\", 1}");
+    assert_eq!(show("var m = Map(); m[\"a\"] = [\"b\"];", "m"), "{\"a\": [\"b\"]}");
+    assert_eq!(show("var a = [1]; push(a, a);", "a"), "[1, ...]");
+}
+
+#[test]
+fn lines_without_an_expression_echo_nothing() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let value = lox::run_echo(&mut interpreter, "var a = 1; print a;").0.unwrap();
+    assert!(value.is_none());
+}
+
+fn temp_script(name: &str, source: &str) -> String {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, source).unwrap();
+    path.to_str().unwrap().to_owned()
+}
+
+#[test]
+fn loaded_definitions_land_in_the_session() {
+    let path = temp_script("rlox_repl_load.lox", "fun double(n) { return n * 2; }\nclass Box {}\n");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let (result, _) = repl::load(&mut interpreter, &path).unwrap();
+    assert!(result.is_ok());
+    let value = lox::run_echo(&mut interpreter, "double(21);").0.unwrap();
+    assert_eq!(repl::show(&value.unwrap()), "42");
+}
+
+#[test]
+fn loading_again_redefines() {
+    let path = temp_script("rlox_repl_reload.lox", "var answer = 1;\nfun f() { return answer; }\n");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    assert!(repl::load(&mut interpreter, &path).unwrap().0.is_ok());
+    std::fs::write(&path, "var answer = 2;\nfun f() { return answer + 1; }\n").unwrap();
+    let (result, _) = repl::load(&mut interpreter, &path).unwrap();
+    assert!(result.is_ok());
+    let value = lox::run_echo(&mut interpreter, "f();").0.unwrap();
+    assert_eq!(repl::show(&value.unwrap()), "3");
+}
+
+#[test]
+fn errors_in_a_loaded_script_keep_the_session() {
+    let path = temp_script("rlox_repl_load_error.lox", "var kept = 1;\nprint -\"one\";\n");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let previous = rlox::loxerr::set_error_output(Some(Box::new(io::sink())));
+    let (result, _) = repl::load(&mut interpreter, &path).unwrap();
+    rlox::loxerr::set_error_output(previous);
+    assert!(matches!(result.unwrap_err()[..], [LoxError::RuntimeError { line: Some(2), .. }]));
+    let value = lox::run_echo(&mut interpreter, "kept;").0.unwrap();
+    assert_eq!(repl::show(&value.unwrap()), "1");
+}
+
+#[test]
+fn missing_script_is_a_friendly_error() {
+    let mut interpreter = Interpreter::new();
+    let err = repl::load(&mut interpreter, "no/such/helpers.lox").unwrap_err();
+    assert_eq!(err, "Could not read 'no/such/helpers.lox': no such file.");
+}
+
+#[test]
+fn underscore_is_the_last_value() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let mut echo = |line: &str| lox::run_echo(&mut interpreter, line).0.ok().flatten().map(|value| repl::show(&value));
+    assert_eq!(echo("1 + 2;"), Some("3".to_owned()));
+    assert_eq!(echo("_ * 10;"), Some("30".to_owned()));
+    assert_eq!(echo("[_, _ + 1];"), Some("[30, 31]".to_owned()));
+    // Lines without a value and lines with errors leave it alone.
+    assert_eq!(echo("var x = _;"), None);
+    let previous = rlox::loxerr::set_error_output(Some(Box::new(io::sink())));
+    assert_eq!(echo("-\"one\";"), None);
+    assert_eq!(echo("1 +;"), None);
+    rlox::loxerr::set_error_output(previous);
+    assert_eq!(echo("x;"), Some("[30, 31]".to_owned()));
+    assert_eq!(echo("_;"), Some("[30, 31]".to_owned()));
+}
+
+#[test]
+fn scripts_do_not_bind_underscore() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let previous = rlox::loxerr::set_error_output(Some(Box::new(io::sink())));
+    let (result, _) = lox::run(&mut interpreter, "1 + 2;\nprint _;\n");
+    rlox::loxerr::set_error_output(previous);
+    assert!(matches!(result.unwrap_err()[..], [LoxError::RuntimeError { line: Some(2), .. }]));
+}
+
+#[test]
+fn loaded_scripts_import_from_their_own_directory() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/nested/app/main.lox");
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let (result, _) = repl::load(&mut interpreter, path).unwrap();
+    assert!(result.is_ok());
+    let value = lox::run_echo(&mut interpreter, "common();").0.unwrap();
+    assert_eq!(repl::show(&value.unwrap()), "\"common\"");
+}
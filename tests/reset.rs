@@ -0,0 +1,89 @@
+
+use rlox::class::NativeClass;
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::loxerr::{self, RuntimeException};
+
+// The exit status and what was printed and reported.
+fn run(interpreter: &mut Interpreter, source: &str) -> (i32, String, String) {
+    let output = Capture::default();
+    let errors = Capture::default();
+    interpreter.set_output(Box::new(output.clone()));
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    let status = lox::exit_code(&lox::run(interpreter, source).0);
+    loxerr::set_error_output(previous);
+    (status, output.text(), errors.text())
+}
+
+fn answer(_: &mut Interpreter, _: &[Value]) -> Result<Value, RuntimeException> {
+    Ok(Value::Number(42.0))
+}
+
+#[test]
+fn reset_forgets_what_lox_code_defined() {
+    let mut interpreter = Interpreter::new();
+    let (status, _, _) = run(&mut interpreter, "var a = 1;\nclass Point {}\n");
+    assert_eq!(status, 0);
+
+    interpreter.reset();
+
+    let (status, _, errors) = run(&mut interpreter, "print a;");
+    assert_eq!(status, 70);
+    assert_eq!(errors, "Undefined variable 'a'.\n[line 1]\n");
+    let (status, _, errors) = run(&mut interpreter, "print Point;");
+    assert_eq!(status, 70);
+    assert!(errors.starts_with("Undefined variable 'Point'."));
+    let (status, output, _) = run(&mut interpreter, "print clock() > 0;");
+    assert_eq!(status, 0);
+    assert_eq!(output, "true\n");
+}
+
+#[test]
+fn reset_keeps_what_the_host_defined() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("answer", 0, answer);
+    interpreter.define_native_class(NativeClass::new("Unit", 0, |_, _| Ok(Box::new(()))));
+
+    interpreter.reset();
+
+    let (status, output, _) = run(&mut interpreter, "print answer();\nprint Unit;");
+    assert_eq!(status, 0);
+    assert_eq!(output, "42\nUnit\n");
+}
+
+#[test]
+fn reset_undoes_redefined_builtins() {
+    let mut interpreter = Interpreter::new();
+    run(&mut interpreter, "fun clock() { return -1; }\n");
+
+    interpreter.reset();
+
+    let (_, output, _) = run(&mut interpreter, "print clock() > 0;");
+    assert_eq!(output, "true\n");
+}
+
+#[test]
+fn code_runs_normally_after_reset() {
+    let mut interpreter = Interpreter::new();
+    run(&mut interpreter, "fun f() { var x = 1; return x; }\nprint f();");
+
+    interpreter.reset();
+
+    let (status, output, _) = run(&mut interpreter, "fun g(a) { var b = a * 2; return b; }\nprint g(2);");
+    assert_eq!(status, 0);
+    assert_eq!(output, "4\n");
+}
+
+#[test]
+fn prelude_sees_restored_builtins_after_reset() {
+    let mut interpreter = Interpreter::new();
+    let (_, output, _) = run(&mut interpreter, "fun push(array, value) { print value; }\nprint toArray(0..2);");
+    assert_eq!(output, "0\n1\n[]\n");
+
+    interpreter.reset();
+
+    let (status, output, _) = run(&mut interpreter, "print toArray(0..2);");
+    assert_eq!(status, 0);
+    assert_eq!(output, "[0, 1]\n");
+}
@@ -0,0 +1,185 @@
+use rlox::expr::{Expr, Value};
+use rlox::interpreter::Interpreter;
+use rlox::lox;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+use rlox::shared::Rc;
+use rlox::stmt::Stmt;
+use rlox::token::{Token, TokenType};
+
+// Rebuilds every node in fresh allocations, as a serializer or an AST
+// transform would, keeping only the expression ids.
+fn rebuild_expr(expr: &Rc<Expr>) -> Rc<Expr> {
+    let exprs = |exprs: &Vec<Rc<Expr>>| exprs.iter().map(rebuild_expr).collect();
+    Rc::new(match &**expr {
+        Expr::Array(elements) => Expr::Array(exprs(elements)),
+        Expr::Assign(id, name, value) => Expr::Assign(id.clone(), name.clone(), rebuild_expr(value)),
+        Expr::AssignArray(targets, equals, value) => {
+            Expr::AssignArray(exprs(targets), equals.clone(), rebuild_expr(value))
+        }
+        Expr::Binary(l, op, r) => Expr::Binary(rebuild_expr(l), op.clone(), rebuild_expr(r)),
+        Expr::Call(callee, paren, args) => {
+            Expr::Call(rebuild_expr(callee), paren.clone(), exprs(args))
+        }
+        Expr::Get(id, object, name) => Expr::Get(id.clone(), rebuild_expr(object), name.clone()),
+        Expr::Grouping(e) => Expr::Grouping(rebuild_expr(e)),
+        Expr::Range(start, op, end) => Expr::Range(rebuild_expr(start), op.clone(), rebuild_expr(end)),
+        Expr::Index(object, bracket, index) => {
+            Expr::Index(rebuild_expr(object), bracket.clone(), rebuild_expr(index))
+        }
+        Expr::SetIndex(object, bracket, index, value) => Expr::SetIndex(
+            rebuild_expr(object),
+            bracket.clone(),
+            rebuild_expr(index),
+            rebuild_expr(value),
+        ),
+        Expr::Tuple(elements) => Expr::Tuple(exprs(elements)),
+        Expr::Is(value, keyword, class) => Expr::Is(rebuild_expr(value), keyword.clone(), rebuild_expr(class)),
+        Expr::Literal(v) => Expr::Literal(v.clone()),
+        Expr::Logical(l, op, r) => Expr::Logical(rebuild_expr(l), op.clone(), rebuild_expr(r)),
+        Expr::Set(object, name, value) => {
+            Expr::Set(rebuild_expr(object), name.clone(), rebuild_expr(value))
+        }
+        Expr::Super(id, keyword, method) => Expr::Super(id.clone(), keyword.clone(), method.clone()),
+        Expr::This(id, keyword) => Expr::This(id.clone(), keyword.clone()),
+        Expr::Unary(op, e) => Expr::Unary(op.clone(), rebuild_expr(e)),
+        Expr::Variable(id, name) => Expr::Variable(id.clone(), name.clone()),
+    })
+}
+
+fn rebuild_stmt(stmt: &Stmt) -> Stmt {
+    let stmts = |stmts: &Vec<Stmt>| stmts.iter().map(rebuild_stmt).collect();
+    match stmt {
+        Stmt::Null => Stmt::Null,
+        Stmt::Block(body) => Stmt::Block(stmts(body)),
+        Stmt::Class(name, superclass, mixins, interfaces, methods) => Stmt::Class(
+            name.clone(),
+            superclass.as_ref().map(rebuild_expr),
+            mixins.iter().map(rebuild_expr).collect(),
+            interfaces.iter().map(rebuild_expr).collect(),
+            stmts(methods),
+        ),
+        Stmt::Expression(first, e) => Stmt::Expression(first.clone(), rebuild_expr(e)),
+        Stmt::Function(name, params, body) => {
+            Stmt::Function(name.clone(), params.clone(), Rc::new(stmts(body)))
+        }
+        Stmt::If(keyword, cond, then, els) => Stmt::If(
+            keyword.clone(),
+            rebuild_expr(cond),
+            Box::new(rebuild_stmt(then)),
+            els.as_ref().map(|els| Box::new(rebuild_stmt(els))),
+        ),
+        Stmt::Interface(name, methods) => Stmt::Interface(name.clone(), methods.clone()),
+        Stmt::Import(keyword, name) => Stmt::Import(keyword.clone(), name.clone()),
+        Stmt::VarArray(names, e) => Stmt::VarArray(names.clone(), rebuild_expr(e)),
+        Stmt::ForIn(keyword, name, iterable, body) => Stmt::ForIn(
+            keyword.clone(),
+            name.clone(),
+            rebuild_expr(iterable),
+            Box::new(rebuild_stmt(body)),
+        ),
+        Stmt::Print(keyword, e) => Stmt::Print(keyword.clone(), rebuild_expr(e)),
+        Stmt::Return(keyword, value) => Stmt::Return(keyword.clone(), value.as_ref().map(rebuild_expr)),
+        Stmt::Var(name, init) => Stmt::Var(name.clone(), init.as_ref().map(rebuild_expr)),
+        Stmt::While(keyword, cond, body) => {
+            Stmt::While(keyword.clone(), rebuild_expr(cond), Box::new(rebuild_stmt(body)))
+        }
+        Stmt::For(keyword, init, cond, incr, body) => Stmt::For(
+            keyword.clone(),
+            init.as_ref().map(|init| Box::new(rebuild_stmt(init))),
+            cond.as_ref().map(rebuild_expr),
+            incr.as_ref().map(rebuild_expr),
+            Box::new(rebuild_stmt(body)),
+        ),
+    }
+}
+
+fn parse(source: &str) -> Vec<Stmt> {
+    let tokens = Scanner::new(source).scan_tokens().clone();
+    Parser::new(&tokens).parse()
+}
+
+fn global(interpreter: &Interpreter, name: &str) -> String {
+    let token = Token::new(TokenType::Identifier, name, 1);
+    match interpreter.globals.borrow().get(&token) {
+        Ok(Value::String(s)) => s.to_string(),
+        Ok(other) => other.to_string(),
+        Err(_) => panic!("{} is undefined", name),
+    }
+}
+
+const PROGRAM: &str = "
+    var result = \"\";
+    var shadowed = \"global\";
+    {
+      var shadowed = \"block\";
+      fun makeCounter() {
+        var i = 0;
+        fun count() { i = i + 1; return i; }
+        return count;
+      }
+      var counter = makeCounter();
+      counter();
+      if (counter() == 2) result = shadowed + \" two\";
+    }
+
+    class Base { name() { return \"base\"; } }
+    class Derived < Base {
+      init() { this.suffix = \"!\"; }
+      name() { return super.name() + this.suffix; }
+    }
+    result = result + \" \" + Derived().name();
+";
+
+#[test]
+fn rebuilt_ast_keeps_resolutions() {
+    let stmts = parse(PROGRAM);
+    let mut interpreter = Interpreter::new();
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve(&stmts);
+    assert!(!resolver.has_error);
+
+    let rebuilt: Vec<Stmt> = stmts.iter().map(rebuild_stmt).collect();
+    drop(stmts);
+
+    assert!(interpreter.interpret(&rebuilt).is_ok());
+    assert_eq!(global(&interpreter, "result"), "block two base!");
+}
+
+#[test]
+fn later_parses_do_not_reuse_resolutions() {
+    let mut interpreter = Interpreter::new();
+    for i in 0..200 {
+        let source = format!(
+            "var result;
+             {{ var a = {i}; var b = a + 1; {{ var c = b + 1; result = c; }} }}",
+            i = i
+        );
+        let stmts = parse(&source);
+        Resolver::new(&mut interpreter).resolve(&stmts);
+        assert!(interpreter.interpret(&stmts).is_ok());
+        assert_eq!(global(&interpreter, "result"), (i + 2).to_string());
+    }
+}
+
+// As at the prompt, where every line's tree is dropped once it has run and
+// the next line's is resolved at different depths and slots.
+#[test]
+fn successive_runs_resolve_their_own_locals() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(std::io::sink()));
+    for i in 0..300 {
+        let source = match i % 3 {
+            0 => format!("var result; {{ var a = {}; result = a; }}", i),
+            1 => format!("var result; {{ var pad = 0; {{ var a = {}; var b = a; result = b; }} }}", i),
+            _ => format!("var result; {{ fun f(x) {{ var y = x; return y; }} result = f({}); }}", i),
+        };
+        let (result, _) = lox::run_echo(&mut interpreter, &source);
+        assert!(matches!(result, Ok(None)));
+        assert_eq!(global(&interpreter, "result"), i.to_string());
+
+        assert!(lox::run(&mut interpreter, "{ var c = result; { result = c + 1; } }").0.is_ok());
+        assert_eq!(global(&interpreter, "result"), (i + 1).to_string());
+    }
+}
@@ -0,0 +1,560 @@
+// Generates random trees the parser could have made, formats them and checks
+// that the source parses back to the same tree and formats the same again.
+// The generator is seeded, so a failure happens again on every run, and it is
+// shrunk to a small tree before it's reported.
+
+use std::io;
+
+use rlox::expr::{Expr, ExprId, Value};
+use rlox::formatter;
+use rlox::lox;
+use rlox::loxerr;
+use rlox::rng::Rng;
+use rlox::shared::Rc;
+use rlox::stmt::Stmt;
+use rlox::token::{Token, TokenType};
+use serde_json::Value as Json;
+use TokenType::*;
+
+const CASES: u64 = 2000;
+
+// Names the parser treats specially in some places but not others are in
+// here too.
+const NAMES: &[&str] = &["a", "b", "x", "_y", "is", "in", "div", "with", "implements", "import"];
+
+const STRINGS: &[&str] = &["", "s", "two words", "\"", "'", "'\"", "\\", "\n", "\t", "\0", "é", "\u{1F600}"];
+
+const NUMBERS: &[f64] = &[0.0, 1.0, 2.5, 10.0, 0.1, 1000000.0, 123456789012.0, 1e21, 1e300, f64::MAX, f64::INFINITY];
+
+fn token(token_type: TokenType, lexeme: &str) -> Token {
+    Token::new(token_type, lexeme, 1)
+}
+
+struct Gen {
+    rng: Rng,
+}
+
+impl Gen {
+    fn below(&mut self, n: usize) -> usize {
+        (self.rng.next_u64() % n as u64) as usize
+    }
+
+    fn chance(&mut self, one_in: usize) -> bool {
+        self.below(one_in) == 0
+    }
+
+    fn name(&mut self) -> Token {
+        token(Identifier, NAMES[self.below(NAMES.len())])
+    }
+
+    fn variable(&mut self) -> Rc<Expr> {
+        Rc::new(Expr::Variable(ExprId::fresh(), self.name()))
+    }
+
+    fn exprs(&mut self, depth: usize, min: usize) -> Vec<Rc<Expr>> {
+        let len = min + self.below(3);
+        (0..len).map(|_| self.expr(depth)).collect()
+    }
+
+    fn leaf(&mut self) -> Rc<Expr> {
+        let expr = match self.below(6) {
+            0 => Expr::Literal(Value::Number(NUMBERS[self.below(NUMBERS.len())])),
+            1 => Expr::Literal(Value::String(Rc::from(STRINGS[self.below(STRINGS.len())]))),
+            2 => Expr::Literal([Value::Nil, Value::Bool(true), Value::Bool(false)][self.below(3)].clone()),
+            3 => Expr::This(ExprId::fresh(), token(This, "this")),
+            4 => Expr::Super(ExprId::fresh(), token(Super, "super"), self.name()),
+            _ => return self.variable(),
+        };
+        Rc::new(expr)
+    }
+
+    // Any expression, without the parentheses precedence may call for.
+    fn expr(&mut self, depth: usize) -> Rc<Expr> {
+        if depth == 0 || self.chance(4) {
+            return self.leaf();
+        }
+        let depth = depth - 1;
+        let expr = match self.below(17) {
+            0 => Expr::Array(self.exprs(depth, 0)),
+            1 => Expr::Assign(ExprId::fresh(), self.name(), self.expr(depth)),
+            2 => {
+                let targets = (0..1 + self.below(2)).map(|_| self.variable()).collect();
+                Expr::AssignArray(targets, token(Equal, "="), self.expr(depth))
+            }
+            3..=5 => {
+                const OPERATORS: &[(TokenType, &str)] = &[
+                    (BangEqual, "!="), (EqualEqual, "=="), (Pipe, "|"), (Caret, "^"), (Ampersand, "&"),
+                    (Greater, ">"), (GreaterEqual, ">="), (Less, "<"), (LessEqual, "<="), (LessLess, "<<"),
+                    (GreaterGreater, ">>"), (Plus, "+"), (Minus, "-"), (Slash, "/"), (Star, "*"), (Div, "div"),
+                ];
+                let (op, lexeme) = OPERATORS[self.below(OPERATORS.len())].clone();
+                Expr::Binary(self.expr(depth), token(op, lexeme), self.expr(depth))
+            }
+            6 => Expr::Call(self.expr(depth), token(RightParen, ")"), self.exprs(depth, 0)),
+            7 => Expr::Get(ExprId::fresh(), self.expr(depth), self.name()),
+            8 => Expr::Grouping(self.expr(depth)),
+            9 => Expr::Index(self.expr(depth), token(LeftBracket, "["), self.expr(depth)),
+            10 => Expr::Is(self.expr(depth), token(Identifier, "is"), self.variable()),
+            11 => {
+                let (op, lexeme) = [(And, "and"), (Or, "or")][self.below(2)].clone();
+                Expr::Logical(self.expr(depth), token(op, lexeme), self.expr(depth))
+            }
+            12 => Expr::Range(self.expr(depth), token(DotDot, ".."), self.expr(depth)),
+            13 => Expr::Set(self.expr(depth), self.name(), self.expr(depth)),
+            14 => Expr::SetIndex(self.expr(depth), token(LeftBracket, "["), self.expr(depth), self.expr(depth)),
+            15 => Expr::Tuple(self.exprs(depth, 2)),
+            _ => {
+                let (op, lexeme) = [(Bang, "!"), (Minus, "-"), (Tilde, "~")][self.below(3)].clone();
+                Expr::Unary(token(op, lexeme), self.expr(depth))
+            }
+        };
+        Rc::new(expr)
+    }
+
+    fn names(&mut self) -> Vec<Token> {
+        (0..self.below(3)).map(|_| self.name()).collect()
+    }
+
+    fn function(&mut self, depth: usize) -> Stmt {
+        Stmt::Function(self.name(), Rc::new(self.names()), Rc::new(self.stmts(depth, true)))
+    }
+
+    fn stmts(&mut self, depth: usize, declarations: bool) -> Vec<Stmt> {
+        (0..self.below(4)).map(|_| self.stmt(depth, declarations)).collect()
+    }
+
+    // Declarations only go where `declarations` is set: in blocks and at the
+    // top, not as the body of an `if` or a loop.
+    fn stmt(&mut self, depth: usize, declarations: bool) -> Stmt {
+        let kinds = if declarations { 16 } else { 10 };
+        let simple = depth == 0 || self.chance(3);
+        let depth = depth.saturating_sub(1);
+        match self.below(kinds) {
+            _ if simple => Stmt::Print(token(Print, "print"), self.expr(3)),
+            0 => Stmt::Block(self.stmts(depth, true)),
+            1 => Stmt::Expression(token(Identifier, "first"), self.expr(3)),
+            2 => {
+                let initializer = match self.below(3) {
+                    0 => None,
+                    1 => Some(Box::new(Stmt::Var(self.name(), self.maybe_expr()))),
+                    _ => Some(Box::new(Stmt::Expression(token(Identifier, "first"), self.expr(2)))),
+                };
+                let condition = self.maybe_expr();
+                let increment = self.maybe_expr();
+                Stmt::For(token(For, "for"), initializer, condition, increment, Box::new(self.stmt(depth, false)))
+            }
+            3 => Stmt::ForIn(token(For, "for"), self.name(), self.expr(2), Box::new(self.stmt(depth, false))),
+            4 => {
+                let else_branch = if self.chance(2) { Some(Box::new(self.stmt(depth, false))) } else { None };
+                Stmt::If(token(If, "if"), self.expr(2), Box::new(self.stmt(depth, false)), else_branch)
+            }
+            5 => Stmt::Return(token(Return, "return"), self.maybe_expr()),
+            6 => Stmt::While(token(While, "while"), self.expr(2), Box::new(self.stmt(depth, false))),
+            7..=9 => Stmt::Print(token(Print, "print"), self.expr(3)),
+            10 => {
+                let superclass = if self.chance(2) { Some(self.variable()) } else { None };
+                let mixins = (0..self.below(2)).map(|_| self.variable()).collect();
+                let interfaces = (0..self.below(2)).map(|_| self.variable()).collect();
+                let methods = (0..self.below(3)).map(|_| self.function(depth)).collect();
+                Stmt::Class(self.name(), superclass, mixins, interfaces, methods)
+            }
+            11 => self.function(depth),
+            12 => Stmt::Import(token(Identifier, "import"), Rc::from(STRINGS[self.below(STRINGS.len())])),
+            13 => {
+                let methods = (0..self.below(3)).map(|_| (self.name(), self.names())).collect();
+                Stmt::Interface(self.name(), methods)
+            }
+            14 => Stmt::Var(self.name(), self.maybe_expr()),
+            _ => Stmt::VarArray(vec![self.name(), self.name()], self.expr(2)),
+        }
+    }
+
+    fn maybe_expr(&mut self) -> Option<Rc<Expr>> {
+        if self.chance(3) {
+            None
+        } else {
+            Some(self.expr(2))
+        }
+    }
+}
+
+// How tightly an expression binds, from assignment at 1 to primary
+// expressions at 15, following the parser's descent.
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Assign(..) | Expr::AssignArray(..) | Expr::Set(..) | Expr::SetIndex(..) => 1,
+        Expr::Logical(_, op, _) if op.token_type == Or => 2,
+        Expr::Logical(..) => 3,
+        Expr::Binary(_, op, _) => match op.token_type {
+            BangEqual | EqualEqual => 4,
+            Pipe => 5,
+            Caret => 6,
+            Ampersand => 7,
+            Greater | GreaterEqual | Less | LessEqual => 8,
+            LessLess | GreaterGreater => 10,
+            Plus | Minus => 11,
+            _ => 12,
+        },
+        Expr::Is(..) => 8,
+        Expr::Range(..) => 9,
+        Expr::Unary(..) => 13,
+        Expr::Call(..) | Expr::Get(..) | Expr::Index(..) => 14,
+        _ => 15,
+    }
+}
+
+// Wraps `expr` in parentheses unless it binds at least as tightly as `min`.
+fn operand(expr: &Rc<Expr>, min: u8) -> Rc<Expr> {
+    let expr = parenthesize(expr);
+    if precedence(&expr) < min {
+        Rc::new(Expr::Grouping(expr))
+    } else {
+        expr
+    }
+}
+
+fn operands(exprs: &[Rc<Expr>]) -> Vec<Rc<Expr>> {
+    exprs.iter().map(|expr| operand(expr, 1)).collect()
+}
+
+// Adds the parentheses the parser would need to make `expr`.
+fn parenthesize(expr: &Rc<Expr>) -> Rc<Expr> {
+    let expr = match &**expr {
+        Expr::Array(elements) => Expr::Array(operands(elements)),
+        Expr::Assign(id, name, value) => Expr::Assign(id.clone(), name.clone(), operand(value, 1)),
+        Expr::AssignArray(targets, equals, value) => Expr::AssignArray(targets.clone(), equals.clone(), operand(value, 1)),
+        Expr::Binary(left, op, right) => {
+            let level = precedence(expr);
+            Expr::Binary(operand(left, level), op.clone(), operand(right, level + 1))
+        }
+        Expr::Logical(left, op, right) => {
+            let level = precedence(expr);
+            Expr::Logical(operand(left, level), op.clone(), operand(right, level + 1))
+        }
+        Expr::Call(callee, paren, args) => Expr::Call(operand(callee, 14), paren.clone(), operands(args)),
+        Expr::Get(id, object, name) => Expr::Get(id.clone(), operand(object, 14), name.clone()),
+        Expr::Grouping(inner) => Expr::Grouping(operand(inner, 1)),
+        Expr::Index(object, bracket, index) => Expr::Index(operand(object, 14), bracket.clone(), operand(index, 1)),
+        Expr::Is(value, keyword, class) => Expr::Is(operand(value, 8), keyword.clone(), operand(class, 9)),
+        Expr::Range(start, op, end) => Expr::Range(operand(start, 10), op.clone(), operand(end, 10)),
+        Expr::Set(object, name, value) => Expr::Set(operand(object, 14), name.clone(), operand(value, 1)),
+        Expr::SetIndex(object, bracket, index, value) => {
+            Expr::SetIndex(operand(object, 14), bracket.clone(), operand(index, 1), operand(value, 1))
+        }
+        Expr::Tuple(elements) => Expr::Tuple(operands(elements)),
+        Expr::Unary(op, right) => Expr::Unary(op.clone(), operand(right, 13)),
+        Expr::Literal(..) | Expr::Super(..) | Expr::This(..) | Expr::Variable(..) => return Rc::clone(expr),
+    };
+    Rc::new(expr)
+}
+
+// Whether an `else` after `stmt` would go to an `if` inside it.
+fn ends_in_if(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::If(_, _, _, None) => true,
+        Stmt::If(_, _, _, Some(body)) | Stmt::For(.., body) | Stmt::ForIn(.., body) | Stmt::While(_, _, body) => {
+            ends_in_if(body)
+        }
+        _ => false,
+    }
+}
+
+fn normalize_stmts(stmts: &[Stmt]) -> Vec<Stmt> {
+    stmts.iter().map(normalize).collect()
+}
+
+fn normalize_body(body: &Stmt) -> Box<Stmt> {
+    Box::new(normalize(body))
+}
+
+// Adds parentheses where precedence needs them and braces where an `else`
+// would otherwise dangle, so the tree is one the parser could have made.
+fn normalize(stmt: &Stmt) -> Stmt {
+    match stmt {
+        Stmt::Block(stmts) => Stmt::Block(normalize_stmts(stmts)),
+        Stmt::Class(name, superclass, mixins, interfaces, methods) => {
+            Stmt::Class(name.clone(), superclass.clone(), mixins.clone(), interfaces.clone(), normalize_stmts(methods))
+        }
+        Stmt::Expression(first, expr) => Stmt::Expression(first.clone(), operand(expr, 1)),
+        Stmt::For(keyword, initializer, condition, increment, body) => Stmt::For(
+            keyword.clone(),
+            initializer.as_ref().map(|initializer| normalize_body(initializer)),
+            condition.as_ref().map(|condition| operand(condition, 1)),
+            increment.as_ref().map(|increment| operand(increment, 1)),
+            normalize_body(body),
+        ),
+        Stmt::ForIn(keyword, name, iterable, body) => {
+            Stmt::ForIn(keyword.clone(), name.clone(), operand(iterable, 1), normalize_body(body))
+        }
+        Stmt::Function(name, params, body) => Stmt::Function(name.clone(), Rc::clone(params), Rc::new(normalize_stmts(body))),
+        Stmt::If(keyword, condition, then_branch, else_branch) => {
+            let mut then_branch = normalize_body(then_branch);
+            if else_branch.is_some() && ends_in_if(&then_branch) {
+                then_branch = Box::new(Stmt::Block(vec![*then_branch]));
+            }
+            Stmt::If(keyword.clone(), operand(condition, 1), then_branch, else_branch.as_ref().map(|body| normalize_body(body)))
+        }
+        Stmt::Print(keyword, expr) => Stmt::Print(keyword.clone(), operand(expr, 1)),
+        Stmt::Return(keyword, value) => Stmt::Return(keyword.clone(), value.as_ref().map(|value| operand(value, 1))),
+        Stmt::Var(name, initializer) => Stmt::Var(name.clone(), initializer.as_ref().map(|value| operand(value, 1))),
+        Stmt::VarArray(names, value) => Stmt::VarArray(names.clone(), operand(value, 1)),
+        Stmt::While(keyword, condition, body) => Stmt::While(keyword.clone(), operand(condition, 1), normalize_body(body)),
+        Stmt::Null | Stmt::Import(..) | Stmt::Interface(..) => clone_stmt(stmt),
+    }
+}
+
+fn clone_stmt(stmt: &Stmt) -> Stmt {
+    match stmt {
+        Stmt::Null => Stmt::Null,
+        Stmt::Import(keyword, name) => Stmt::Import(keyword.clone(), Rc::clone(name)),
+        Stmt::Interface(name, methods) => Stmt::Interface(name.clone(), methods.clone()),
+        _ => normalize(stmt),
+    }
+}
+
+// The tree as JSON without positions, and without the token an expression
+// statement starts with, which the generator doesn't bother to get right.
+fn shape(stmts: &[Stmt]) -> Json {
+    fn strip(value: &mut Json) {
+        match value {
+            Json::Object(map) => {
+                map.remove("line");
+                map.remove("span");
+                if let Some(Json::Array(parts)) = map.get_mut("Expression") {
+                    parts.remove(0);
+                }
+                map.values_mut().for_each(strip);
+            }
+            Json::Array(items) => items.iter_mut().for_each(strip),
+            _ => (),
+        }
+    }
+
+    let mut value = serde_json::to_value(stmts).unwrap();
+    strip(&mut value);
+    value
+}
+
+// Formats the tree and parses it back, returning what went wrong if anything
+// did.
+fn check(stmts: &[Stmt]) -> Result<(), String> {
+    let stmts = normalize_stmts(stmts);
+    let formatted = formatter::format(&stmts);
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let reparsed = lox::parse(&formatted);
+    loxerr::set_error_output(previous);
+    let reparsed = reparsed.ok_or_else(|| format!("doesn't parse:\n{}", formatted))?;
+    if shape(&reparsed) != shape(&stmts) {
+        return Err(format!("parses to another tree:\n{}", formatted));
+    }
+    let reformatted = formatter::format(&reparsed);
+    if reformatted != formatted {
+        return Err(format!("formats differently the second time:\n{}\nthen\n{}", formatted, reformatted));
+    }
+    Ok(())
+}
+
+// The expressions directly inside `expr`, and `expr` rebuilt around others.
+fn children(expr: &Expr) -> Vec<Rc<Expr>> {
+    match expr {
+        Expr::Array(elements) | Expr::Tuple(elements) => elements.clone(),
+        Expr::Assign(_, _, value) | Expr::AssignArray(_, _, value) | Expr::Grouping(value) | Expr::Unary(_, value) => {
+            vec![Rc::clone(value)]
+        }
+        Expr::Binary(left, _, right)
+        | Expr::Logical(left, _, right)
+        | Expr::Index(left, _, right)
+        | Expr::Is(left, _, right)
+        | Expr::Range(left, _, right)
+        | Expr::Set(left, _, right) => vec![Rc::clone(left), Rc::clone(right)],
+        Expr::Call(callee, _, args) => std::iter::once(Rc::clone(callee)).chain(args.iter().cloned()).collect(),
+        Expr::Get(_, object, _) => vec![Rc::clone(object)],
+        Expr::SetIndex(object, _, index, value) => vec![Rc::clone(object), Rc::clone(index), Rc::clone(value)],
+        Expr::Literal(..) | Expr::Super(..) | Expr::This(..) | Expr::Variable(..) => vec![],
+    }
+}
+
+fn rebuild(expr: &Expr, mut parts: Vec<Rc<Expr>>) -> Expr {
+    let mut next = || parts.remove(0);
+    match expr {
+        Expr::Array(elements) => Expr::Array(elements.iter().map(|_| next()).collect()),
+        Expr::Tuple(elements) => Expr::Tuple(elements.iter().map(|_| next()).collect()),
+        Expr::Assign(id, name, _) => Expr::Assign(id.clone(), name.clone(), next()),
+        Expr::AssignArray(targets, equals, _) => Expr::AssignArray(targets.clone(), equals.clone(), next()),
+        Expr::Grouping(_) => Expr::Grouping(next()),
+        Expr::Unary(op, _) => Expr::Unary(op.clone(), next()),
+        Expr::Binary(_, op, _) => Expr::Binary(next(), op.clone(), next()),
+        Expr::Logical(_, op, _) => Expr::Logical(next(), op.clone(), next()),
+        Expr::Index(_, bracket, _) => Expr::Index(next(), bracket.clone(), next()),
+        // The class has to stay a name.
+        Expr::Is(_, keyword, class) => {
+            let value = next();
+            next();
+            Expr::Is(value, keyword.clone(), Rc::clone(class))
+        }
+        Expr::Range(_, op, _) => Expr::Range(next(), op.clone(), next()),
+        Expr::Set(_, name, _) => Expr::Set(next(), name.clone(), next()),
+        Expr::Call(_, paren, args) => Expr::Call(next(), paren.clone(), args.iter().map(|_| next()).collect()),
+        Expr::Get(id, _, name) => Expr::Get(id.clone(), next(), name.clone()),
+        Expr::SetIndex(_, bracket, ..) => Expr::SetIndex(next(), bracket.clone(), next(), next()),
+        Expr::Literal(..) | Expr::Super(..) | Expr::This(..) | Expr::Variable(..) => unreachable!(),
+    }
+}
+
+// Smaller expressions to try in place of `expr`: each of its operands, and
+// itself with one operand made smaller.
+fn shrink_expr(expr: &Rc<Expr>) -> Vec<Rc<Expr>> {
+    let parts = children(expr);
+    let mut smaller: Vec<Rc<Expr>> = match &**expr {
+        Expr::Is(value, ..) => vec![Rc::clone(value)],
+        _ => parts.clone(),
+    };
+    for (idx, part) in parts.iter().enumerate() {
+        for replacement in shrink_expr(part) {
+            let mut parts = parts.clone();
+            parts[idx] = replacement;
+            smaller.push(Rc::new(rebuild(expr, parts)));
+        }
+    }
+    smaller
+}
+
+fn shrink_stmts(stmts: &[Stmt]) -> Vec<Vec<Stmt>> {
+    let mut smaller = Vec::new();
+    for idx in 0..stmts.len() {
+        let mut fewer: Vec<Stmt> = stmts.iter().map(clone_stmt).collect();
+        fewer.remove(idx);
+        smaller.push(fewer);
+    }
+    for (idx, stmt) in stmts.iter().enumerate() {
+        for replacement in shrink_stmt(stmt) {
+            let mut stmts: Vec<Stmt> = stmts.iter().map(clone_stmt).collect();
+            stmts[idx] = replacement;
+            smaller.push(stmts);
+        }
+    }
+    smaller
+}
+
+// Smaller statements to try in place of `stmt`.
+fn shrink_stmt(stmt: &Stmt) -> Vec<Stmt> {
+    let print = |expr: &Rc<Expr>| Stmt::Print(token(Print, "print"), Rc::clone(expr));
+    let mut smaller = Vec::new();
+    match stmt {
+        Stmt::Block(stmts) => {
+            smaller.extend(stmts.iter().map(clone_stmt));
+            smaller.extend(shrink_stmts(stmts).into_iter().map(Stmt::Block));
+        }
+        Stmt::Class(name, superclass, mixins, interfaces, methods) => {
+            if superclass.is_some() || !mixins.is_empty() || !interfaces.is_empty() {
+                smaller.push(Stmt::Class(name.clone(), None, vec![], vec![], methods.iter().map(clone_stmt).collect()));
+            }
+            for methods in shrink_stmts(methods) {
+                if methods.iter().all(|method| matches!(method, Stmt::Function(..))) {
+                    smaller.push(Stmt::Class(name.clone(), superclass.clone(), mixins.clone(), interfaces.clone(), methods));
+                }
+            }
+        }
+        Stmt::Expression(first, expr) => {
+            smaller.extend(shrink_expr(expr).into_iter().map(|expr| Stmt::Expression(first.clone(), expr)));
+        }
+        Stmt::For(keyword, initializer, condition, increment, body) => {
+            smaller.push(clone_stmt(body));
+            smaller.extend(condition.iter().chain(increment).map(print));
+            let parts = |initializer: Option<&Stmt>, condition: Option<&Rc<Expr>>, increment: Option<&Rc<Expr>>, body: &Stmt| {
+                Stmt::For(keyword.clone(), initializer.map(|stmt| Box::new(clone_stmt(stmt))), condition.cloned(), increment.cloned(), Box::new(clone_stmt(body)))
+            };
+            let (initializer, condition, increment) = (initializer.as_deref(), condition.as_ref(), increment.as_ref());
+            if initializer.is_some() {
+                smaller.push(parts(None, condition, increment, body));
+            }
+            if condition.is_some() {
+                smaller.push(parts(initializer, None, increment, body));
+            }
+            if increment.is_some() {
+                smaller.push(parts(initializer, condition, None, body));
+            }
+            for body in shrink_stmt(body) {
+                smaller.push(parts(initializer, condition, increment, &body));
+            }
+        }
+        Stmt::ForIn(keyword, name, iterable, body) => {
+            smaller.push(clone_stmt(body));
+            smaller.push(print(iterable));
+            for body in shrink_stmt(body).into_iter().map(Box::new) {
+                smaller.push(Stmt::ForIn(keyword.clone(), name.clone(), Rc::clone(iterable), body));
+            }
+        }
+        Stmt::Function(name, params, body) => {
+            for body in shrink_stmts(body) {
+                smaller.push(Stmt::Function(name.clone(), Rc::clone(params), Rc::new(body)));
+            }
+        }
+        Stmt::If(keyword, condition, then_branch, else_branch) => {
+            smaller.push(clone_stmt(then_branch));
+            smaller.push(print(condition));
+            if let Some(else_branch) = else_branch {
+                smaller.push(clone_stmt(else_branch));
+                smaller.push(Stmt::If(keyword.clone(), Rc::clone(condition), Box::new(clone_stmt(then_branch)), None));
+            }
+            for then_branch in shrink_stmt(then_branch).into_iter().map(Box::new) {
+                let else_branch = else_branch.as_ref().map(|body| Box::new(clone_stmt(body)));
+                smaller.push(Stmt::If(keyword.clone(), Rc::clone(condition), then_branch, else_branch));
+            }
+            for condition in shrink_expr(condition) {
+                let else_branch = else_branch.as_ref().map(|body| Box::new(clone_stmt(body)));
+                smaller.push(Stmt::If(keyword.clone(), condition, Box::new(clone_stmt(then_branch)), else_branch));
+            }
+        }
+        Stmt::Print(keyword, expr) => {
+            smaller.extend(shrink_expr(expr).into_iter().map(|expr| Stmt::Print(keyword.clone(), expr)));
+        }
+        Stmt::Return(keyword, Some(value)) => {
+            smaller.push(Stmt::Return(keyword.clone(), None));
+            smaller.extend(shrink_expr(value).into_iter().map(|value| Stmt::Return(keyword.clone(), Some(value))));
+        }
+        Stmt::Var(name, Some(value)) => {
+            smaller.push(Stmt::Var(name.clone(), None));
+            smaller.extend(shrink_expr(value).into_iter().map(|value| Stmt::Var(name.clone(), Some(value))));
+        }
+        Stmt::VarArray(names, value) => {
+            smaller.push(print(value));
+            smaller.extend(shrink_expr(value).into_iter().map(|value| Stmt::VarArray(names.clone(), value)));
+        }
+        Stmt::While(keyword, condition, body) => {
+            smaller.push(clone_stmt(body));
+            smaller.push(print(condition));
+            for body in shrink_stmt(body).into_iter().map(Box::new) {
+                smaller.push(Stmt::While(keyword.clone(), Rc::clone(condition), body));
+            }
+        }
+        Stmt::Null | Stmt::Import(..) | Stmt::Interface(..) | Stmt::Return(_, None) | Stmt::Var(_, None) => (),
+    }
+    smaller
+}
+
+// Keeps replacing the failing program with a smaller one that still fails.
+fn shrink(mut stmts: Vec<Stmt>, mut error: String) -> (Vec<Stmt>, String) {
+    'smaller: loop {
+        for candidate in shrink_stmts(&stmts) {
+            if let Err(candidate_error) = check(&candidate) {
+                stmts = candidate;
+                error = candidate_error;
+                continue 'smaller;
+            }
+        }
+        return (stmts, error);
+    }
+}
+
+#[test]
+fn formatted_trees_parse_back() {
+    let mut gen = Gen { rng: Rng::seeded(0) };
+    for case in 0..CASES {
+        let stmts = gen.stmts(3, true);
+        if let Err(error) = check(&stmts) {
+            let (_, error) = shrink(stmts, error);
+            panic!("case {}: formatted source {}", case, error);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::io;
+use std::time::Duration;
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Backend, Capture, Lox, RunStatus};
+use rlox::loxerr::{self, LoxError, Severity, Warnings};
+
+// The errors running `source` ended with, checking they display as they
+// were reported.
+fn errors(interpreter: &mut Interpreter, source: &str) -> Vec<LoxError> {
+    interpreter.set_output(Box::new(io::sink()));
+    let reported = Capture::default();
+    let previous = loxerr::set_error_output(Some(Box::new(reported.clone())));
+    let (result, _) = lox::run(interpreter, source);
+    loxerr::set_error_output(previous);
+    let errors = result.unwrap_err();
+    let shown: String = errors.iter().map(|error| format!("{}\n", error)).collect();
+    assert_eq!(shown, reported.text());
+    errors
+}
+
+fn run(source: &str) -> Vec<LoxError> {
+    errors(&mut Interpreter::new(), source)
+}
+
+#[test]
+fn successful_runs_exit_with_zero() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let result = lox::run(&mut interpreter, "print 1;").0;
+    assert_eq!(result, Ok(0));
+    assert_eq!(lox::exit_code(&result), 0);
+}
+
+#[test]
+fn scan_errors() {
+    let errors = run("var a = 1;\nvar b = @;");
+    assert_eq!(errors[0], LoxError::ScanError { line: 2, message: "Unexpected character.".to_owned() });
+    assert_eq!(errors[0].exit_code(), 65);
+}
+
+#[test]
+fn parse_errors() {
+    let errors = run("var = 1;\nprint 1");
+    assert_eq!(
+        errors,
+        [
+            LoxError::ParseError { line: 1, lexeme: Some("=".to_owned()), message: "Expect variable name.".to_owned() },
+            LoxError::ParseError { line: 2, lexeme: None, message: "Expect ';' after value.".to_owned() },
+        ]
+    );
+    assert_eq!(lox::exit_code(&Err::<i32, _>(errors)), 65);
+}
+
+#[test]
+fn resolve_errors() {
+    let errors = run("print this;");
+    assert!(matches!(&errors[..], [LoxError::ResolveError { line: 1, .. }]));
+    assert_eq!(errors[0].to_string(), "[line 1] Error at 'this': Can't use 'this' outside of a class.");
+}
+
+#[test]
+fn denied_warnings_are_resolve_errors() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_warnings(Warnings::Deny);
+    let errors = errors(&mut interpreter, "{\n  var a = 1;\n  {\n    var a = 2;\n  }\n}");
+    assert!(matches!(&errors[..], [LoxError::ResolveError { line: 4, .. }]));
+}
+
+#[test]
+fn runtime_errors() {
+    let errors = run("print 1;\nprint -\"one\";");
+    assert_eq!(
+        errors,
+        [LoxError::RuntimeError { line: Some(2), message: "Operand must be a number, but got string (\"one\").".to_owned() }]
+    );
+    assert_eq!(errors[0].exit_code(), 70);
+}
+
+#[test]
+fn runs_out_of_fuel() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_fuel(10);
+    let errors = errors(&mut interpreter, "while (true) {}");
+    assert_eq!(errors, [LoxError::RuntimeError { line: None, message: "Fuel exhausted.".to_owned() }]);
+}
+
+#[test]
+fn timeouts() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_timeout(Some(Duration::from_millis(10)));
+    let errors = errors(&mut interpreter, "while (true) {}");
+    assert_eq!(errors, [LoxError::TimedOut]);
+    assert_eq!(errors[0].exit_code(), 75);
+}
+
+#[test]
+fn echoed_lines_fail_the_same_way() {
+    let mut interpreter = Interpreter::new();
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let (result, _) = lox::run_echo(&mut interpreter, "1 +;");
+    loxerr::set_error_output(previous);
+    assert!(matches!(result.unwrap_err()[..], [LoxError::ParseError { line: 1, .. }]));
+}
+
+#[test]
+fn run_source_captures_output_and_diagnostics() {
+    let mut lox = Lox::new();
+    let outcome = lox.run_source("print 1;\n{\n  var a = 1;\n  {\n    var a = 2;\n  }\n}\nprint 2;");
+    assert_eq!(outcome.status, RunStatus::Ok);
+    assert_eq!(outcome.stdout, "1\n2\n");
+    assert_eq!(outcome.diagnostics.len(), 1);
+    assert_eq!(outcome.diagnostics[0].severity, Severity::Warning);
+
+    // Each run gives back only what it printed.
+    assert_eq!(lox.run_source("print 3;").stdout, "3\n");
+}
+
+#[test]
+fn run_source_reports_as_the_command_line_does() {
+    let source = "var a = @;\nprint 1 +;";
+    let mut interpreter = Interpreter::new();
+    let reported = Capture::default();
+    let previous = loxerr::set_error_output(Some(Box::new(reported.clone())));
+    lox::run(&mut interpreter, source).0.unwrap_err();
+    loxerr::set_error_output(previous);
+
+    let outcome = Lox::new().run_source(source);
+    assert_eq!(outcome.status, RunStatus::CompileError);
+    assert_eq!(outcome.status.exit_code(), 65);
+    let shown: String = outcome.diagnostics.iter().map(|diagnostic| format!("{}\n", diagnostic)).collect();
+    assert_eq!(shown, reported.text());
+}
+
+#[test]
+fn run_source_statuses() {
+    let mut lox = Lox::new();
+    assert_eq!(lox.run_source("print nope;").status, RunStatus::RuntimeError);
+    lox.interpreter().set_timeout(Some(Duration::from_millis(10)));
+    let outcome = lox.run_source("while (true) {}");
+    assert_eq!(outcome.status, RunStatus::TimedOut);
+    assert_eq!(outcome.status.exit_code(), 75);
+    assert_eq!(outcome.diagnostics[0].message, "Execution timed out.");
+}
+
+#[test]
+fn run_source_on_the_vm() {
+    let mut lox = Lox::with_interpreter(Interpreter::without_prelude());
+    lox.set_backend(Backend::Vm);
+    let outcome = lox.run_source("var total = 0;\nfor (var i = 0; i < 4; i = i + 1) total = total + i;\nprint total;");
+    assert_eq!(outcome.status, RunStatus::Ok);
+    assert_eq!(outcome.stdout, "6\n");
+}
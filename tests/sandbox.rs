@@ -0,0 +1,90 @@
+use std::path::Path;
+use std::process::Command;
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{Lox, RunStatus};
+use rlox::natives::{Group, Profile};
+
+// What running `source` printed, or the message of the error it stopped with.
+fn run(profile: Profile, source: &str) -> String {
+    let mut lox = Lox::with_interpreter(Interpreter::with_profile(profile));
+    let outcome = lox.run_source(source);
+    match outcome.status {
+        RunStatus::Ok => outcome.stdout,
+        _ => outcome.diagnostics.last().unwrap().message.clone(),
+    }
+}
+
+fn import_common() -> String {
+    let common = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/nested/shared/common.lox");
+    format!("import \"{}\";\nprint common();", common.display())
+}
+
+#[test]
+fn pure_has_no_clock() {
+    assert_eq!(run(Profile::pure(), "print clock;"), "Undefined variable 'clock'.");
+    assert_eq!(run(Profile::full(), "print clock;"), "<native fn>\n");
+}
+
+#[test]
+fn pure_can_not_read_files() {
+    assert!(run(Profile::pure(), &import_common()).starts_with("Can't import '/"));
+    assert!(run(Profile::pure(), &import_common()).ends_with("common.lox' without file access."));
+    assert_eq!(run(Profile::full(), &import_common()), "common\n");
+    // No native reads files either.
+    assert_eq!(run(Profile::pure(), "print readFile;"), "Undefined variable 'readFile'.");
+    assert_eq!(run(Profile::full(), "print readFile;"), "Undefined variable 'readFile'.");
+}
+
+#[test]
+fn pure_keeps_what_only_computes() {
+    let source = "import \"math\";\nprintln(len([1, 2]) + abs(-1) + floor(1.5));";
+    assert_eq!(run(Profile::pure(), source), "4\n");
+}
+
+#[test]
+fn groups_can_be_allowed_one_at_a_time() {
+    let profile = Profile::pure().allow(Group::Time);
+    assert_eq!(run(profile.clone(), "print clock;"), "<native fn>\n");
+    assert!(run(profile, &import_common()).ends_with("without file access."));
+
+    let profile = Profile::full().deny(Group::Time);
+    assert_eq!(run(profile.clone(), "print clock;"), "Undefined variable 'clock'.");
+    assert_eq!(run(profile, &import_common()), "common\n");
+}
+
+#[test]
+fn without_core_there_is_only_what_was_allowed() {
+    let profile = Profile::full().deny(Group::Core).prelude(false);
+    assert_eq!(run(profile.clone(), "print len;"), "Undefined variable 'len'.");
+    assert_eq!(run(profile.clone(), "import \"math\";"), "Unknown module 'math'. No modules are available.");
+    assert_eq!(run(profile, "print clock;"), "<native fn>\n");
+}
+
+#[test]
+fn the_prelude_is_part_of_the_profile() {
+    assert_eq!(run(Profile::pure().prelude(false), "print abs;"), "Undefined variable 'abs'. Did you mean 'add'?");
+    assert_eq!(run(Profile::pure(), "print abs(-2);"), "2\n");
+}
+
+#[test]
+fn reset_keeps_the_profile() {
+    let mut interpreter = Interpreter::with_profile(Profile::pure());
+    interpreter.reset();
+    let mut lox = Lox::with_interpreter(interpreter);
+    assert_eq!(lox.run_source("print clock;").diagnostics[0].message, "Undefined variable 'clock'.");
+}
+
+#[test]
+fn command_line_sandbox() {
+    let script = std::env::temp_dir().join("rlox_sandbox.lox");
+    std::fs::write(&script, "print len(\"abc\");\nprint clock();\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox")).arg("--sandbox").arg(&script).output().unwrap();
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Undefined variable 'clock'.\n[line 2]\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox")).arg(&script).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
@@ -0,0 +1,76 @@
+use std::io;
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::loxerr::{self, DiagnosticFormat};
+use rlox::scanner::Scanner;
+use rlox::token::{Span, Token};
+
+fn tokens(source: &str) -> Vec<Token> {
+    Scanner::new(source).scan_tokens().clone()
+}
+
+// What each token's span covers in `source`.
+fn covered(source: &str) -> Vec<&str> {
+    tokens(source).iter().map(|token| &source[token.span.start..token.span.end]).collect()
+}
+
+#[test]
+fn spans_cover_the_lexemes() {
+    let source = "var answer = 42;";
+    let spans: Vec<Span> = tokens(source).iter().map(|token| token.span).collect();
+    assert_eq!(
+        spans,
+        vec![Span::new(0, 3), Span::new(4, 10), Span::new(11, 12), Span::new(13, 15), Span::new(15, 16), Span::new(16, 16)]
+    );
+}
+
+#[test]
+fn spans_after_multibyte_characters_are_in_bytes() {
+    let source = "print \"héllo → 世界\"; x;";
+    let tokens = tokens(source);
+    assert_eq!(tokens[1].span, Span::new(6, 25));
+    assert_eq!(tokens[3].span, Span::new(27, 28));
+    assert_eq!(covered(source), vec!["print", "\"héllo → 世界\"", ";", "x", ";", ""]);
+}
+
+#[test]
+fn spans_after_multiline_strings() {
+    let source = "var s = \"one\ntwo\nthree\";\nprint s;";
+    let tokens = tokens(source);
+    assert_eq!(tokens[3].span, Span::new(8, 23));
+    assert_eq!(tokens[6].line, 4);
+    assert_eq!(tokens[6].span, Span::new(31, 32));
+    assert_eq!(covered(source)[3..], ["\"one\ntwo\nthree\"", ";", "print", "s", ";", ""]);
+}
+
+#[test]
+fn statements_have_the_span_of_their_token() {
+    let source = "var a = 1;\n{ print a; fun f() {} }";
+    let stmts = lox::parse(source).unwrap();
+    assert_eq!(stmts[0].span(), Some(Span::new(4, 5)));
+    assert_eq!(stmts[1].span(), Some(Span::new(13, 27)));
+}
+
+// The spans of the JSON diagnostics running `source` reports.
+fn diagnostic_spans(source: &str) -> Vec<serde_json::Value> {
+    let errors = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    loxerr::set_diagnostic_format(DiagnosticFormat::Json);
+    assert_ne!(lox::exit_code(&lox::run(&mut interpreter, source).0), 0);
+    loxerr::set_diagnostic_format(DiagnosticFormat::Human);
+    loxerr::set_error_output(previous);
+    let text = errors.text();
+    text.lines().map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["span"].clone()).collect()
+}
+
+#[test]
+fn diagnostics_point_at_bytes() {
+    let spans = diagnostic_spans("print \"ünïcödé\" @;");
+    assert_eq!(spans, vec![serde_json::json!({"start": 20, "end": 21})]);
+
+    let spans = diagnostic_spans("var s = \"a\nb\";\nprint -s;");
+    assert_eq!(spans, vec![serde_json::json!({"start": 21, "end": 22})]);
+}
@@ -0,0 +1,81 @@
+use std::thread;
+
+use rlox::lox::{Backend, Lox, RunStatus};
+
+// Runs `source` on a thread with the default stack, as a host would, and
+// gives back the message it stopped with.
+fn overflow(source: &'static str, backend: Backend) -> String {
+    thread::spawn(move || {
+        let mut lox = Lox::new();
+        lox.set_backend(backend);
+        let outcome = lox.run_source(source);
+        assert_eq!(outcome.status, RunStatus::RuntimeError);
+        outcome.diagnostics.last().unwrap().message.clone()
+    })
+    .join()
+    .unwrap()
+}
+
+#[test]
+fn recursion_stops_before_the_thread_overflows() {
+    for backend in [Backend::Tree, Backend::Vm].iter().copied() {
+        assert_eq!(overflow("fun f() { f(); }\nf();", backend), "Stack overflow.");
+        assert_eq!(overflow("fun f(n) { return 1 + f(n + 1); }\nf(0);", backend), "Stack overflow.");
+    }
+}
+
+#[test]
+fn recursion_through_methods_and_natives_stops_too() {
+    assert_eq!(overflow("class A { m() { this.m(); } }\nA().m();", Backend::Tree), "Stack overflow.");
+    assert_eq!(overflow("fun f(x) { return map([x], f); }\nf(1);", Backend::Tree), "Stack overflow.");
+}
+
+#[test]
+fn the_stack_limit_stops_recursion_without_a_depth_limit() {
+    let message = thread::spawn(|| {
+        let mut lox = Lox::new();
+        lox.interpreter().set_max_call_depth(usize::MAX);
+        let outcome = lox.run_source("fun f(n) { return 1 + f(n + 1); }\nf(0);");
+        outcome.diagnostics.last().unwrap().message.clone()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(message, "Stack overflow.");
+}
+
+#[test]
+fn the_depth_limit_stops_recursion_on_its_own() {
+    let count = "fun count(n) {\n  if (n == 0) return 0;\n  return count(n - 1) + 1;\n}\n";
+    for backend in [Backend::Tree, Backend::Vm].iter().copied() {
+        let mut lox = Lox::new();
+        lox.set_backend(backend);
+        lox.interpreter().set_stack_limit(usize::MAX);
+        lox.interpreter().set_max_call_depth(50);
+        assert_eq!(lox.run_source(&format!("{}print count(49);", count)).stdout, "49\n");
+        let outcome = lox.run_source(&format!("{}print count(50);", count));
+        assert_eq!(outcome.status, RunStatus::RuntimeError);
+        assert_eq!(outcome.diagnostics[0].message, "Stack overflow.");
+    }
+}
+
+// The command line's thread is much bigger than the default one, and so are
+// its limits.
+#[test]
+fn raised_limits_allow_deep_recursion_on_a_bigger_stack() {
+    let output = thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| {
+            let mut lox = Lox::new();
+            lox.interpreter().set_max_call_depth(3000);
+            lox.interpreter().set_stack_limit(32 * 1024 * 1024);
+            let source = "fun count(n) {\n  if (n == 0) return 0;\n  return count(n - 1) + 1;\n}\n\
+                          print count(2500);\n\
+                          // The depth unwinds with the calls, so a second deep call works too.\n\
+                          print count(2500);";
+            lox.run_source(source).stdout
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(output, "2500\n2500\n");
+}
@@ -0,0 +1,91 @@
+
+use rlox::gc;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Backend, Capture};
+
+fn run(interpreter: &mut Interpreter, source: &str) {
+    assert!(lox::run(interpreter, source).0.is_ok());
+}
+
+#[test]
+fn calls_are_counted() {
+    let mut interpreter = Interpreter::new();
+    let before = interpreter.stats().calls;
+    run(&mut interpreter, "fun f() {} for (var i = 0; i < 10; i = i + 1) f();");
+    assert_eq!(interpreter.stats().calls, before + 10);
+
+    // Tail calls count without going deeper.
+    let before = interpreter.stats().calls;
+    run(&mut interpreter, "fun down(n) { if (n > 0) return down(n - 1); } down(100);");
+    let stats = interpreter.stats();
+    assert_eq!(stats.calls, before + 101);
+    assert!(stats.peak_call_depth < 5);
+}
+
+#[test]
+fn bytecode_calls_are_counted_too() {
+    let mut interpreter = Interpreter::new();
+    let source = "fun f(n) { if (n > 0) f(n - 1); } f(9); fun g(n) { if (n > 0) return g(n - 1); } g(9);";
+    assert!(lox::run_with(&mut interpreter, source, Backend::Vm).0.is_ok());
+    let stats = interpreter.stats();
+    assert_eq!(stats.calls, 20);
+    assert_eq!(stats.peak_call_depth, 10);
+}
+
+#[test]
+fn peak_call_depth_is_kept() {
+    let mut interpreter = Interpreter::new();
+    run(&mut interpreter, "fun nest(n) { if (n > 0) nest(n - 1); } nest(20);");
+    assert_eq!(interpreter.stats().peak_call_depth, 21);
+    run(&mut interpreter, "nest(2);");
+    assert_eq!(interpreter.stats().peak_call_depth, 21);
+}
+
+#[test]
+fn globals_do_not_count_builtins() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.stats().globals, 0);
+    run(&mut interpreter, "var a = 1; var b; fun c() {} class D {}");
+    assert_eq!(interpreter.stats().globals, 4);
+
+    interpreter.reset();
+    let stats = interpreter.stats();
+    assert_eq!(stats.globals, 0);
+    assert_eq!(stats.calls, 0);
+    assert_eq!(stats.peak_call_depth, 0);
+}
+
+#[test]
+fn environments_follow_closures() {
+    let mut interpreter = Interpreter::new();
+    let before = interpreter.stats().environments;
+    run(
+        &mut interpreter,
+        "fun make() { var x = 0; fun get() { return x; } return get; }
+         var closures = [];
+         for (var i = 0; i < 20; i = i + 1) push(closures, make());",
+    );
+    assert!(interpreter.stats().environments >= before + 20);
+
+    run(&mut interpreter, "closures = nil;");
+    gc::collect();
+    assert!(interpreter.stats().environments < before + 20);
+}
+
+#[test]
+fn stats_native_returns_a_map() {
+    let output = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    run(
+        &mut interpreter,
+        "var x = 1;
+         var stats = stats();
+         print stats[\"globals\"];
+         print stats[\"calls\"];
+         print stats[\"peakCallDepth\"];
+         print stats[\"environments\"] > 0;",
+    );
+    // `stats` is only defined once it has been called, and the call counts.
+    assert_eq!(output.text(), "1\n1\n1\ntrue\n");
+}
@@ -0,0 +1,79 @@
+use std::process::{Command, Output};
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::loxerr;
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .arg("tests/fixtures/strict.lox")
+        .output()
+        .unwrap()
+}
+
+// The exit status and what was reported, in strict mode.
+fn run_strict(source: &str) -> (i32, String) {
+    let errors = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(Capture::default()));
+    interpreter.set_strict(true);
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    let status = lox::exit_code(&lox::run(&mut interpreter, source).0);
+    loxerr::set_error_output(previous);
+    (status, errors.text())
+}
+
+#[test]
+fn sloppy_code_runs_without_strict() {
+    let output = rlox(&[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 7] Warning at 'n': Local variable shadows a variable in an enclosing scope.\n"
+    );
+}
+
+#[test]
+fn strict_rejects_sloppy_code() {
+    let output = rlox(&["--strict"]);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 2] Error at 'total': Already a global variable with this name.\n\
+         [line 7] Error at 'n': Local variable shadows a variable in an enclosing scope.\n\
+         [line 5] Error at 'doubled': Local variable 'doubled' is never read.\n\
+         [line 13] Error at 'totl': Assignment to undeclared variable 'totl'. Did you mean 'total'?\n"
+    );
+}
+
+#[test]
+fn globals_declared_later_or_built_in_can_be_assigned() {
+    let (status, errors) = run_strict("fun set() { later = 1; clock = nil; } var later;");
+    assert_eq!(errors, "");
+    assert_eq!(status, 0);
+}
+
+#[test]
+fn locals_read_or_named_with_an_underscore_are_fine() {
+    let (status, errors) = run_strict("fun f() { var a = 1; var _b = 2; var c; c = a; print c; } f();");
+    assert_eq!(errors, "");
+    assert_eq!(status, 0);
+}
+
+#[test]
+fn assigning_is_not_reading() {
+    let (status, errors) = run_strict("fun f() { var a; a = 1; } f();");
+    assert_eq!(status, 65);
+    assert_eq!(errors, "[line 1] Error at 'a': Local variable 'a' is never read.\n");
+}
+
+#[test]
+fn imports_turn_off_the_undeclared_check() {
+    let (status, errors) = run_strict("import \"math\"; fun f() { anything = 1; }");
+    assert_eq!(errors, "");
+    assert_eq!(status, 0);
+}
@@ -0,0 +1,49 @@
+use std::time::Instant;
+
+use rlox::interpreter::Interpreter;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+
+// Builds a 1 MB string, then passes it down a recursion 1000 calls deep, 100
+// times over.
+const SOURCE: &str = "
+var s = \"0123456789abcdef\";
+for (var i = 0; i < 16; i = i + 1) s = s + s;
+fun pass(text, n) {
+  if (n == 0) return text;
+  return pass(text, n - 1);
+}
+for (var i = 0; i < 100; i = i + 1) pass(s, 1000);
+";
+
+fn time(label: &str, source: &str) {
+    let tokens = Scanner::new(source).scan_tokens().clone();
+    let stmts = Parser::new(&tokens).parse();
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter).resolve(&stmts);
+
+    let start = Instant::now();
+    assert!(interpreter.interpret(&stmts).is_ok());
+    println!("{}: {:?}", label, start.elapsed());
+}
+
+// Run with `cargo test --release --test string_bench -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn large_string_through_recursion() {
+    time("1 MB string through 100000 calls", SOURCE);
+}
+
+#[test]
+#[ignore]
+fn concatenating_short_pieces() {
+    time(
+        "100000 pieces with +",
+        "var s = \"\"; for (var i = 0; i < 100000; i = i + 1) s = s + \"piece\";",
+    );
+    time(
+        "100000 pieces with a StringBuilder",
+        "var sb = StringBuilder(); for (var i = 0; i < 100000; i = i + 1) sb.append(\"piece\"); var s = sb.toString();",
+    );
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "sync")]
+
+use std::thread;
+
+use rlox::expr::Value;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{Lox, RunStatus};
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn interpreters_and_values_can_be_sent() {
+    assert_send::<Interpreter>();
+    assert_send::<Lox>();
+    assert_send::<Value>();
+}
+
+const SCRIPT: &str = "
+    class Counter {
+      init(start) { this.count = start; }
+      next() { this.count = this.count + 1; return this.count; }
+    }
+    fun makeAdder(n) { fun add(x) { return x + n; } return add; }
+    var counter = Counter(seed);
+    var add = makeAdder(seed);
+    var total = 0;
+    for (var i = 0; i < 1000; i = i + 1) total = add(total) + counter.next();
+    print total;
+";
+
+fn run(seed: u32) -> String {
+    let mut lox = Lox::with_interpreter(Interpreter::new());
+    let outcome = lox.run_source(&format!("var seed = {};\n{}", seed, SCRIPT));
+    assert_eq!(outcome.status, RunStatus::Ok);
+    outcome.stdout
+}
+
+#[test]
+fn interpreters_run_on_their_own_threads() {
+    let expected: Vec<String> = (0..8).map(run).collect();
+
+    // Made here, run there.
+    let handles: Vec<_> = (0..8u32)
+        .map(|seed| {
+            let mut lox = Lox::with_interpreter(Interpreter::new());
+            thread::spawn(move || {
+                let outcome = lox.run_source(&format!("var seed = {};\n{}", seed, SCRIPT));
+                assert_eq!(outcome.status, RunStatus::Ok);
+                outcome.stdout
+            })
+        })
+        .collect();
+    let outputs: Vec<String> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+    assert_eq!(outputs, expected);
+}
+
+#[test]
+fn an_interpreter_keeps_its_state_across_threads() {
+    let mut lox = Lox::with_interpreter(Interpreter::new());
+    lox.run_source("var greeting = \"hello\"; fun greet(name) { return greeting + \" \" + name; }");
+    let outcome = thread::spawn(move || lox.run_source("print greet(\"thread\");")).join().unwrap();
+    assert_eq!(outcome.stdout, "hello thread\n");
+}
+
+#[test]
+fn cycles_are_collected_on_the_thread_that_runs() {
+    let mut lox = Lox::with_interpreter(Interpreter::new());
+    let stats = thread::spawn(move || {
+        let outcome = lox.run_source(
+            "fun makeCounter() { var i = 0; fun count() { i = i + 1; return i; } return count; }
+             for (var j = 0; j < 10000; j = j + 1) makeCounter()();
+             print stats()[\"environments\"] < 2000;",
+        );
+        outcome.stdout
+    });
+    assert_eq!(stats.join().unwrap(), "true\n");
+}
@@ -0,0 +1,64 @@
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use rlox::interpreter::Interpreter;
+use rlox::loxerr::RuntimeException;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+
+fn run(interpreter: &mut Interpreter, source: &str) -> Result<i32, RuntimeException> {
+    let tokens = Scanner::new(source).scan_tokens().clone();
+    let stmts = Parser::new(&tokens).parse();
+    let mut resolver = Resolver::new(interpreter);
+    resolver.resolve(&stmts);
+    assert!(!resolver.has_error);
+    interpreter.interpret(&stmts)
+}
+
+fn assert_times_out(source: &str) {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_timeout(Some(Duration::from_millis(100)));
+
+    let start = Instant::now();
+    let result = run(&mut interpreter, source);
+    let elapsed = start.elapsed();
+
+    assert!(matches!(result, Err(RuntimeException::TimedOut)));
+    assert!(elapsed >= Duration::from_millis(100));
+    assert!(elapsed < Duration::from_secs(2), "took {:?}", elapsed);
+}
+
+#[test]
+fn infinite_loop_times_out() {
+    assert_times_out("while (true) {}");
+    assert_times_out("for (var i = 0; ; i = i + 1) { var j = i * 2; }");
+}
+
+#[test]
+fn infinite_tail_recursion_times_out() {
+    assert_times_out("fun spin(n) { return spin(n + 1); } spin(0);");
+}
+
+#[test]
+fn every_run_gets_the_full_timeout() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_timeout(Some(Duration::from_millis(100)));
+    assert!(matches!(run(&mut interpreter, "while (true) {}"), Err(RuntimeException::TimedOut)));
+    assert!(run(&mut interpreter, "for (var i = 0; i < 1000; i = i + 1) {}").is_ok());
+}
+
+#[test]
+fn cli_exits_with_distinct_status() {
+    let script = std::env::temp_dir().join("rlox_timeout_test.lox");
+    std::fs::write(&script, "var i = 0;\nwhile (true) i = i + 1;\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--timeout", "200ms"])
+        .arg(&script)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(75));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Execution timed out.\n");
+}
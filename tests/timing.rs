@@ -0,0 +1,33 @@
+use rlox::interpreter::Interpreter;
+use rlox::lox;
+use rlox::loxerr::LoxError;
+
+#[test]
+fn reports_counts_and_phases() {
+    let mut interpreter = Interpreter::new();
+    let (result, timings) = lox::run(
+        &mut interpreter,
+        "var total = 0;
+         for (var i = 0; i < 1000; i = i + 1) total = total + i;",
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(timings.statements, 2);
+    assert_eq!(timings.tokens, 28);
+    assert!(timings.run > timings.resolve);
+    assert_eq!(
+        timings.total(),
+        timings.scan + timings.parse + timings.resolve + timings.run
+    );
+}
+
+#[test]
+fn later_phases_are_skipped_after_errors() {
+    let mut interpreter = Interpreter::new();
+    let (result, timings) = lox::run(&mut interpreter, "var a = ;");
+
+    assert!(matches!(result.unwrap_err()[..], [LoxError::ParseError { line: 1, .. }]));
+    assert_eq!(timings.statements, 1);
+    assert_eq!(timings.resolve.as_nanos(), 0);
+    assert_eq!(timings.run.as_nanos(), 0);
+}
@@ -0,0 +1,77 @@
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Backend, Capture};
+
+fn trace(source: &str, backend: Backend) -> (String, String) {
+    let output = Capture::default();
+    let trace = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    interpreter.set_trace(Some(Box::new(trace.clone())));
+    let status = lox::exit_code(&lox::run_with(&mut interpreter, source, backend).0);
+    assert_eq!(status, 0);
+    (output.text(), trace.text())
+}
+
+const PROGRAM: &str = "\
+fun add(a, b) {
+  var sum = a + b;
+  return sum;
+}
+for (var i = 0; i < 2; i = i + 1) {
+  print add(i, 10);
+}
+";
+
+const EXPECTED: &str = "\
+[line 1] fun add(a, b)
+[line 5] for (var i = 0; i < 2; i = i + 1)
+[line 5] var i = 0;
+[line 6] print add(i, 10);
+call <fn add>(0, 10)
+  [line 2] var sum = a + b;
+  [line 3] return sum;
+[line 6] print add(i, 10);
+call <fn add>(1, 10)
+  [line 2] var sum = a + b;
+  [line 3] return sum;
+";
+
+#[test]
+fn traces_statements_and_calls() {
+    let (output, trace) = trace(PROGRAM, Backend::Tree);
+    assert_eq!(output, "10\n11\n");
+    assert_eq!(trace, EXPECTED);
+}
+
+#[test]
+fn tracing_runs_on_the_tree_walker() {
+    let (output, trace) = trace(PROGRAM, Backend::Vm);
+    assert_eq!(output, "10\n11\n");
+    assert_eq!(trace, EXPECTED);
+}
+
+#[test]
+fn tail_calls_stay_at_the_same_depth() {
+    let source = "\
+fun count(n) {
+  if (n == 0) return;
+  return count(n - 1);
+}
+count(1);
+";
+    let (_, trace) = trace(source, Backend::Tree);
+    assert_eq!(
+        trace,
+        "\
+[line 1] fun count(n)
+[line 5] count(1);
+call <fn count>(1)
+  [line 2] if (n == 0) return;
+  [line 3] return count(n - 1);
+  call <fn count>(0)
+  [line 2] if (n == 0) return;
+  [line 2] return;
+"
+    );
+}
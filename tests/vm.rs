@@ -0,0 +1,145 @@
+// Runs every script under `test/` on both backends and checks that they print
+// the same output and errors and exit with the same status.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rlox::clock::MockTime;
+use rlox::interpreter::Interpreter;
+use rlox::rng::Rng;
+use rlox::lox::{self, Backend, Capture};
+use rlox::{compiler, loxerr};
+
+mod common;
+
+use common::collect_scripts;
+
+#[derive(Debug, PartialEq)]
+struct Run {
+    output: String,
+    errors: String,
+    status: i32,
+}
+
+fn run(source: &str, backend: Backend) -> Run {
+    let output = Capture::default();
+    let errors = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    interpreter.set_time_source(Box::new(MockTime::new(1.0)));
+    interpreter.set_rng(Rng::seeded(0));
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    let status = lox::exit_code(&lox::run_with(&mut interpreter, source, backend).0);
+    loxerr::set_error_output(previous);
+    Run { output: output.text(), errors: errors.text(), status }
+}
+
+// Whether the VM runs the script itself rather than handing it to the
+// tree-walker.
+fn compiles(source: &str) -> bool {
+    let previous = loxerr::set_error_output(Some(Box::new(io::sink())));
+    let compiles = lox::parse(source).is_some_and(|stmts| compiler::compile(&stmts).is_ok());
+    loxerr::set_error_output(previous);
+    compiles
+}
+
+#[test]
+fn backends_agree_on_golden_files() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("test");
+    let mut scripts = Vec::new();
+    collect_scripts(&root, &mut scripts);
+    scripts.sort();
+
+    let total = scripts.len();
+    let mut failures = Vec::new();
+    let mut compiled = 0;
+    for path in &scripts {
+        let source = fs::read_to_string(path).unwrap();
+        if compiles(&source) {
+            compiled += 1;
+        }
+        let tree = run(&source, Backend::Tree);
+        let vm = run(&source, Backend::Vm);
+        if tree != vm {
+            let name = path.strip_prefix(&root).unwrap().display();
+            failures.push(format!("{}:\n  tree: {:?}\n  vm:   {:?}", name, tree, vm));
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!("{} of {} scripts differ:\n\n{}", failures.len(), total, failures.join("\n\n"));
+    }
+    // Scripts with classes or closures still go to the tree-walker, but the
+    // rest shouldn't.
+    assert!(compiled >= 40, "only {} of {} scripts compiled", compiled, total);
+}
+
+#[test]
+fn vm_runs_functions_loops_and_recursion() {
+    let source = r#"
+        fun fib(n) {
+          if (n < 2) return n;
+          return fib(n - 1) + fib(n - 2);
+        }
+        var total = 0;
+        for (var i = 0; i < 10; i = i + 1) {
+          total = total + fib(i);
+        }
+        print total;
+        {
+          var a = "a";
+          var b = "b";
+          print a + b;
+        }
+        print !nil and 1 or 2;
+    "#;
+    assert!(compiles(source));
+    let run = run(source, Backend::Vm);
+    assert_eq!(run.output, "88\nab\n1\n");
+    assert_eq!(run.status, 0);
+}
+
+#[test]
+fn vm_tail_calls_run_in_constant_stack() {
+    let source = r#"
+        fun count(n) {
+          if (n == 0) return "done";
+          return count(n - 1);
+        }
+        print count(100000);
+    "#;
+    assert!(compiles(source));
+    assert_eq!(run(source, Backend::Vm).output, "done\n");
+}
+
+#[test]
+fn vm_reports_stack_overflow() {
+    let source = "fun f() { f(); }\nf();";
+    let run = run(source, Backend::Vm);
+    assert_eq!(run.errors, "Stack overflow.\n[line 1]\n");
+    assert_eq!(run.status, 70);
+}
+
+#[test]
+fn vm_uses_fuel() {
+    let errors = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(io::sink()));
+    interpreter.set_fuel(10);
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    let status = lox::exit_code(&lox::run_with(&mut interpreter, "while (true) {}", Backend::Vm).0);
+    loxerr::set_error_output(previous);
+    assert_eq!(errors.text(), "Fuel exhausted.\n");
+    assert_eq!(status, 70);
+}
+
+#[test]
+fn classes_and_closures_fall_back_to_the_tree_walker() {
+    let class = "class A { f() { return 1; } }\nprint A().f();";
+    let closure = "fun outer() { var a = 1; fun inner() { return a; } return inner; }\nprint outer()();";
+    assert!(!compiles(class));
+    assert!(!compiles(closure));
+    assert_eq!(run(class, Backend::Vm).output, "1\n");
+    assert_eq!(run(closure, Backend::Vm).output, "1\n");
+}
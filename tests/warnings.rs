@@ -0,0 +1,52 @@
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Capture};
+use rlox::loxerr::{self, Warnings};
+
+// One warning and no errors.
+const SHADOWING: &str = "\
+{
+  var a = 1;
+  {
+    var a = 2;
+    print a;
+  }
+}
+";
+
+// The exit status, what was printed and what was reported.
+fn run(warnings: Warnings) -> (i32, String, String) {
+    let output = Capture::default();
+    let errors = Capture::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    interpreter.set_warnings(warnings);
+    let previous = loxerr::set_error_output(Some(Box::new(errors.clone())));
+    let status = lox::exit_code(&lox::run(&mut interpreter, SHADOWING).0);
+    loxerr::set_error_output(previous);
+    (status, output.text(), errors.text())
+}
+
+#[test]
+fn warnings_are_reported_but_the_program_runs() {
+    let (status, output, errors) = run(Warnings::Report);
+    assert_eq!(status, 0);
+    assert_eq!(output, "2\n");
+    assert_eq!(errors, "[line 4] Warning at 'a': Local variable shadows a variable in an enclosing scope.\n");
+}
+
+#[test]
+fn werror_turns_warnings_into_errors() {
+    let (status, output, errors) = run(Warnings::Deny);
+    assert_eq!(status, 65);
+    assert_eq!(output, "");
+    assert_eq!(errors, "[line 4] Error at 'a': Local variable shadows a variable in an enclosing scope.\n");
+}
+
+#[test]
+fn warnings_can_be_silenced() {
+    let (status, output, errors) = run(Warnings::Ignore);
+    assert_eq!(status, 0);
+    assert_eq!(output, "2\n");
+    assert_eq!(errors, "");
+}
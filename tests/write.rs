@@ -0,0 +1,69 @@
+use std::io::{self, Write};
+
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Backend};
+use rlox::shared::{Rc, RefCell};
+
+// Keeps what was written, with a "|" wherever the output was flushed.
+#[derive(Clone, Default)]
+struct Sink(Rc<RefCell<Vec<u8>>>);
+
+impl Sink {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().push(b'|');
+        Ok(())
+    }
+}
+
+fn interpreter() -> (Interpreter, Sink) {
+    let sink = Sink::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(sink.clone()));
+    (interpreter, sink)
+}
+
+#[test]
+fn write_and_print_share_the_output() {
+    for backend in [Backend::Tree, Backend::Vm].iter().copied() {
+        let (mut interpreter, sink) = interpreter();
+        let source = "write(\"a\");\nprint \"b\";\nwrite(1);\nwrite(\"\");\nwrite(nil);\nprintln(2);\nwrite(\"c\");";
+        assert!(lox::run_with(&mut interpreter, source, backend).0.is_ok());
+        assert_eq!(sink.text(), "ab\n1nil2\nc|");
+    }
+}
+
+// Besides the one at the end of the run.
+#[test]
+fn only_flush_flushes() {
+    let (mut interpreter, sink) = interpreter();
+    assert!(lox::run(&mut interpreter, "write(\"name? \");\nflush();\nwrite(\"x\");\nprint \"y\";").0.is_ok());
+    assert_eq!(sink.text(), "name? |xy\n|");
+}
+
+#[test]
+fn the_prompt_ends_a_line_write_left_open() {
+    let (mut interpreter, sink) = interpreter();
+    let value = lox::run_echo(&mut interpreter, "write(\"50%\");").0.unwrap();
+    assert_eq!(value.unwrap().to_string(), "nil");
+    assert_eq!(sink.text(), "50%\n|");
+}
+
+#[test]
+fn the_prompt_adds_no_other_newlines() {
+    let (mut interpreter, sink) = interpreter();
+    lox::run_echo(&mut interpreter, "print 1;").0.unwrap();
+    lox::run_echo(&mut interpreter, "write(\"2\n\");").0.unwrap();
+    lox::run_echo(&mut interpreter, "var a = 3;").0.unwrap();
+    assert_eq!(sink.text(), "1\n|2\n||");
+}